@@ -0,0 +1,36 @@
+use crate::services::StorageService;
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Add an image to the asset store
+///
+/// `src` is a file path, data URI or base64 string. Returns the
+/// `asset://<hash>` reference to put into an image block.
+#[tauri::command]
+pub async fn add_asset(
+    src: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<String, String> {
+    info!("Command: add_asset called");
+
+    let storage = storage.lock().await;
+    storage.add_asset(&src).await.map_err(|e| {
+        error!("Failed to add asset: {}", e);
+        String::from(e)
+    })
+}
+
+/// Delete assets nothing refers to anymore, returning how many were deleted
+#[tauri::command]
+pub async fn collect_asset_garbage(
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<usize, String> {
+    info!("Command: collect_asset_garbage called");
+
+    let storage = storage.lock().await;
+    storage.collect_asset_garbage().await.map_err(|e| {
+        error!("Failed to collect asset garbage: {}", e);
+        String::from(e)
+    })
+}
@@ -0,0 +1,102 @@
+use crate::models::Locale;
+use crate::services::batch::{
+    parse_dataset, sanitize_file_name, BatchGenerator, BatchOptions, BatchReport, CombinedOutput,
+    DatasetFormat,
+};
+use crate::services::StorageService;
+use log::{error, info};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::Emitter;
+use tokio::sync::Mutex;
+
+/// Event emitted after each rendered row
+const PROGRESS_EVENT: &str = "batch-progress";
+
+/// Default number of rows rendered in parallel
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Generate one PDF per row of a CSV, JSON or NDJSON dataset
+///
+/// Progress is reported through `batch-progress` events. `locale` overrides
+/// the document locale for this batch.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn generate_batch(
+    app: tauri::AppHandle,
+    document_id: String,
+    dataset_path: String,
+    format: Option<DatasetFormat>,
+    file_name_pattern: Option<String>,
+    output_dir: Option<String>,
+    concurrency: Option<usize>,
+    combine: Option<CombinedOutput>,
+    locale: Option<Locale>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<BatchReport, String> {
+    info!(
+        "Command: generate_batch called for document {} with {}",
+        document_id, dataset_path
+    );
+
+    // Load document
+    let (mut document, assets, fonts) = {
+        let storage = storage.lock().await;
+        let document = storage.load_document(&document_id).await.map_err(|e| {
+            error!("Failed to load document: {}", e);
+            e.to_string()
+        })?;
+        (document, storage.assets().clone(), storage.fonts().clone())
+    };
+    if let Some(locale) = locale {
+        document.metadata.locale = locale;
+    }
+
+    let dataset_path = PathBuf::from(dataset_path);
+    let format = format
+        .or_else(|| DatasetFormat::from_path(&dataset_path))
+        .ok_or("Unknown dataset format, expected CSV, JSON or NDJSON")?;
+    let content = tokio::fs::read_to_string(&dataset_path)
+        .await
+        .map_err(|e| {
+            error!("Failed to read dataset: {}", e);
+            e.to_string()
+        })?;
+    let rows = parse_dataset(&content, format).map_err(|e| {
+        error!("Failed to parse dataset: {}", e);
+        String::from(e)
+    })?;
+
+    let output_dir = match output_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => dirs::home_dir()
+            .ok_or("Failed to get home directory")?
+            .join("Documents")
+            .join("SimpleDoc")
+            .join("exports")
+            .join(sanitize_file_name(&format!(
+                "{}_batch",
+                document.metadata.title.replace(' ', "_")
+            ))),
+    };
+
+    let options = BatchOptions {
+        output_dir,
+        file_name_pattern,
+        concurrency: concurrency.unwrap_or(DEFAULT_CONCURRENCY),
+        combine,
+        assets: Some(assets),
+        fonts: Some(fonts),
+    };
+
+    BatchGenerator::run(document, rows, options, move |progress| {
+        if let Err(e) = app.emit(PROGRESS_EVENT, progress) {
+            error!("Failed to emit batch progress: {}", e);
+        }
+    })
+    .await
+    .map_err(|e| {
+        error!("Batch generation failed: {}", e);
+        String::from(e)
+    })
+}
@@ -1,5 +1,6 @@
-use crate::models::{Block, Document};
-use crate::services::StorageService;
+use crate::models::{AppError, Block, Document, Locale, TableBlockContent, TextBlockContent};
+use crate::services::renderer::layout;
+use crate::services::{formula, rich_text, RichTextFormat, StorageService, TextMeasure};
 use log::{error, info};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -121,7 +122,10 @@ pub async fn reorder_blocks(
     block_ids: Vec<String>,
     storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
 ) -> Result<Document, String> {
-    info!("Command: reorder_blocks called for document {}", document_id);
+    info!(
+        "Command: reorder_blocks called for document {}",
+        document_id
+    );
 
     let storage = storage.lock().await;
 
@@ -220,3 +224,64 @@ pub async fn get_block(
         .ok_or_else(|| format!("Block {} not found", block_id))
 }
 
+/// Compute the formula cells of a table for the editor preview
+///
+/// Works on unsaved content; `data` supplies the variables formulas refer to
+/// and `locale` the number notation of the cells.
+#[tauri::command]
+pub async fn compute_table(
+    mut content: TableBlockContent,
+    data: Option<serde_json::Value>,
+    locale: Option<Locale>,
+) -> Result<TableBlockContent, String> {
+    info!(
+        "Command: compute_table called with {} rows",
+        content.rows.len()
+    );
+
+    let data = data.unwrap_or_default();
+    formula::compute_rows(&mut content.rows, &data, locale.unwrap_or_default()).map_err(|e| {
+        error!("Failed to compute table: {}", e);
+        String::from(AppError::ValidationError(e))
+    })?;
+
+    Ok(content)
+}
+
+/// Replace the text of a text block with imported Markdown or HTML
+///
+/// Works on unsaved content, like `compute_table`; the block keeps its font
+/// defaults and gets the imported paragraphs.
+#[tauri::command]
+pub async fn import_rich_text(
+    mut content: TextBlockContent,
+    source: String,
+    format: RichTextFormat,
+) -> Result<TextBlockContent, String> {
+    info!(
+        "Command: import_rich_text called with {} bytes of {:?}",
+        source.len(),
+        format
+    );
+
+    content.set_paragraphs(rich_text::import(&source, format));
+    Ok(content)
+}
+
+/// Measure the text of blocks as it will be rendered
+///
+/// Returns the required height and overflow of every text block, so the
+/// editor can warn about clipped text or resize the blocks.
+#[tauri::command]
+pub async fn measure_text(
+    blocks: Vec<Block>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<TextMeasure>, String> {
+    info!("Command: measure_text called with {} blocks", blocks.len());
+
+    let fonts = storage.lock().await.fonts().clone();
+    Ok(blocks
+        .iter()
+        .filter_map(|block| layout::measure_block(block, &fonts))
+        .collect())
+}
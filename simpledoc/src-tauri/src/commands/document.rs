@@ -1,20 +1,21 @@
-use crate::models::{Document, DocumentListItem};
+use crate::models::{Document, DocumentListItem, TrashItem};
 use crate::services::StorageService;
 use log::{error, info};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
-/// Save a document to disk
+/// Save a document to disk, keeping a revision with an optional message
 #[tauri::command]
 pub async fn save_document(
     document: Document,
+    message: Option<String>,
     storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
 ) -> Result<String, String> {
     info!("Command: save_document called for {}", document.id);
 
     let storage = storage.lock().await;
     storage
-        .save_document(&document)
+        .save_document_with_message(&document, message.as_deref())
         .await
         .map(|_| document.id.clone())
         .map_err(|e| {
@@ -52,7 +53,7 @@ pub async fn list_documents(
     })
 }
 
-/// Delete a document
+/// Move a document to the trash
 #[tauri::command]
 pub async fn delete_document(
     document_id: String,
@@ -67,6 +68,50 @@ pub async fn delete_document(
     })
 }
 
+/// List the documents in the trash, most recently deleted first
+#[tauri::command]
+pub async fn list_trash(
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<TrashItem>, String> {
+    info!("Command: list_trash called");
+
+    let storage = storage.lock().await;
+    storage.list_trash().await.map_err(|e| {
+        error!("Failed to list trash: {}", e);
+        String::from(e)
+    })
+}
+
+/// Move a document out of the trash
+#[tauri::command]
+pub async fn restore_document(
+    document_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!("Command: restore_document called for {}", document_id);
+
+    let storage = storage.lock().await;
+    storage.restore_document(&document_id).await.map_err(|e| {
+        error!("Failed to restore document: {}", e);
+        String::from(e)
+    })
+}
+
+/// Delete a document in the trash for good
+#[tauri::command]
+pub async fn purge_document(
+    document_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<(), String> {
+    info!("Command: purge_document called for {}", document_id);
+
+    let storage = storage.lock().await;
+    storage.purge_document(&document_id).await.map_err(|e| {
+        error!("Failed to purge document: {}", e);
+        String::from(e)
+    })
+}
+
 /// Create a new empty document
 #[tauri::command]
 pub async fn create_document(
@@ -118,7 +163,7 @@ pub async fn import_document(
 ) -> Result<Document, String> {
     info!("Command: import_document called from {}", import_path);
 
-    let storage = storage.lock().await;
+    let mut storage = storage.lock().await;
     storage
         .import_document(&std::path::PathBuf::from(import_path))
         .await
@@ -135,6 +180,6 @@ pub async fn document_exists(
     storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
 ) -> Result<bool, String> {
     let storage = storage.lock().await;
-    Ok(storage.document_exists(&document_id))
+    Ok(storage.document_exists(&document_id).await)
 }
 
@@ -0,0 +1,36 @@
+use crate::services::{FontInfo, StorageService};
+use log::{error, info};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// List the font faces available for rendering
+///
+/// Includes the standard PDF fonts, system fonts, the user fonts folder
+/// and fonts imported into the asset store.
+#[tauri::command]
+pub async fn list_fonts(
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<FontInfo>, String> {
+    info!("Command: list_fonts called");
+
+    let storage = storage.lock().await;
+    Ok(storage.fonts().list())
+}
+
+/// Import a TTF, OTF or TTC file into the asset store
+///
+/// Returns the faces it added.
+#[tauri::command]
+pub async fn import_font(
+    path: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<FontInfo>, String> {
+    info!("Command: import_font called for {}", path);
+
+    let mut storage = storage.lock().await;
+    storage.import_font(Path::new(&path)).await.map_err(|e| {
+        error!("Failed to import font: {}", e);
+        String::from(e)
+    })
+}
@@ -1,7 +1,12 @@
-use crate::models::Block;
-use crate::services::{PythonService, StorageService};
-use log::{error, info};
-use std::path::PathBuf;
+use crate::models::{Block, Document, Locale, Page};
+use crate::services::diff::{diff_documents, redline_document};
+use crate::services::renderer::layout;
+use crate::services::{
+    AssetStore, DocxRenderer, FontRegistry, PdfRenderer, PythonService, RenderBackend, Renderer, StorageService,
+    TemplateEngine, Validator, VariableUsage,
+};
+use log::{error, info, warn};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -11,64 +16,79 @@ pub struct GeneratePdfResponse {
     pub pdf_path: String,
     pub success: bool,
     pub message: String,
+    /// Problems found before rendering (e.g. blocks inside the margins or
+    /// visibility rules with unknown variables)
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// Response for DOCX generation
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct GenerateDocxResponse {
+    pub docx_path: String,
+    pub success: bool,
+    pub message: String,
+    /// Block styles Word cannot show like the PDF does
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 /// Generate PDF from a document
+///
+/// When `data` is given, `{{ placeholders }}` are resolved against it first.
+/// `locale` overrides the document locale for this generation.
 #[tauri::command]
 pub async fn generate_pdf(
     document_id: String,
     output_path: Option<String>,
+    backend: Option<RenderBackend>,
+    data: Option<serde_json::Value>,
+    locale: Option<Locale>,
     storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
-    python: tauri::State<'_, Arc<Mutex<PythonService>>>,
+    python: tauri::State<'_, Arc<Mutex<Option<PythonService>>>>,
 ) -> Result<GeneratePdfResponse, String> {
     info!("Command: generate_pdf called for document {}", document_id);
 
-    let storage = storage.lock().await;
-
     // Load document
-    let document = storage.load_document(&document_id).await.map_err(|e| {
-        error!("Failed to load document: {}", e);
-        e.to_string()
-    })?;
+    let (mut document, assets, fonts) = {
+        let storage = storage.lock().await;
+        let document = storage.load_document(&document_id).await.map_err(|e| {
+            error!("Failed to load document: {}", e);
+            e.to_string()
+        })?;
+        (document, storage.assets().clone(), storage.fonts().clone())
+    };
+    if let Some(locale) = locale {
+        document.metadata.locale = locale;
+    }
 
-    // Determine output path
-    let output_path = if let Some(path) = output_path {
-        PathBuf::from(path)
-    } else {
-        // Default to Documents/SimpleDoc/exports/
-        let export_dir = dirs::home_dir()
-            .expect("Failed to get home directory")
-            .join("Documents")
-            .join("SimpleDoc")
-            .join("exports");
-
-        // Create directory if it doesn't exist
-        if !export_dir.exists() {
-            std::fs::create_dir_all(&export_dir).map_err(|e| {
-                error!("Failed to create export directory: {}", e);
-                e.to_string()
-            })?;
-        }
+    // Rules are checked before resolving, which drops hidden blocks
+    let mut warnings = Validator::validate_visibility_variables(&document);
+    let mut document = resolve_document(document, data)?;
 
-        let filename = format!("{}.pdf", document.metadata.title.replace(' ', "_"));
-        export_dir.join(filename)
+    // Determine output path
+    let output_path = match output_path {
+        Some(path) => PathBuf::from(path),
+        None => default_output_path(&document.metadata.title, "pdf")?,
     };
 
     info!("PDF will be generated at: {:?}", output_path);
 
-    // Get page dimensions
-    let page = document.pages.first().ok_or("Document has no pages")?;
-    let (width_mm, height_mm) = page.size.dimensions_mm();
+    layout::apply_auto_height(&mut document, &fonts);
+    warnings.extend(Validator::validate_layout(&document, &fonts));
+    for warning in &warnings {
+        warn!("Layout warning: {}", warning);
+    }
 
-    // Generate PDF using Python
-    let python = python.lock().await;
-    let pdf_path = python
-        .generate_pdf(&document.blocks, &output_path, width_mm, height_mm)
-        .await
-        .map_err(|e| {
-            error!("PDF generation failed: {}", e);
-            e.to_string()
-        })?;
+    let pdf_path = render_to_file(
+        document,
+        &output_path,
+        backend.unwrap_or_default(),
+        &assets,
+        fonts,
+        &python,
+    )
+    .await?;
 
     info!("PDF generated successfully at: {:?}", pdf_path);
 
@@ -76,34 +96,57 @@ pub async fn generate_pdf(
         pdf_path: pdf_path.to_string_lossy().to_string(),
         success: true,
         message: "PDF generated successfully".to_string(),
+        warnings,
     })
 }
 
 /// Generate PDF from blocks (without loading from document)
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn generate_pdf_from_blocks(
     blocks: Vec<Block>,
     output_path: String,
     page_width_mm: f64,
     page_height_mm: f64,
-    python: tauri::State<'_, Arc<Mutex<PythonService>>>,
+    backend: Option<RenderBackend>,
+    locale: Option<Locale>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+    python: tauri::State<'_, Arc<Mutex<Option<PythonService>>>>,
 ) -> Result<GeneratePdfResponse, String> {
     info!(
         "Command: generate_pdf_from_blocks called with {} blocks",
         blocks.len()
     );
 
-    let output_path = PathBuf::from(output_path);
+    // Wrap the blocks into a single-page scratch document
+    let mut document = Document::new("Untitled Document".to_string());
+    document.pages[0] = Page::custom(page_width_mm, page_height_mm);
+    document.metadata.locale = locale.unwrap_or_default();
+    for block in blocks {
+        document.add_block(block);
+    }
+    let mut document = resolve_document(document, None)?;
 
-    // Generate PDF using Python
-    let python = python.lock().await;
-    let pdf_path = python
-        .generate_pdf(&blocks, &output_path, page_width_mm, page_height_mm)
-        .await
-        .map_err(|e| {
-            error!("PDF generation failed: {}", e);
-            e.to_string()
-        })?;
+    let (assets, fonts) = {
+        let storage = storage.lock().await;
+        (storage.assets().clone(), storage.fonts().clone())
+    };
+
+    layout::apply_auto_height(&mut document, &fonts);
+    let warnings = Validator::validate_layout(&document, &fonts);
+    for warning in &warnings {
+        warn!("Layout warning: {}", warning);
+    }
+
+    let pdf_path = render_to_file(
+        document,
+        &PathBuf::from(output_path),
+        backend.unwrap_or_default(),
+        &assets,
+        fonts,
+        &python,
+    )
+    .await?;
 
     info!("PDF generated successfully at: {:?}", pdf_path);
 
@@ -111,23 +154,269 @@ pub async fn generate_pdf_from_blocks(
         pdf_path: pdf_path.to_string_lossy().to_string(),
         success: true,
         message: "PDF generated successfully".to_string(),
+        warnings,
+    })
+}
+
+/// Generate a "redline" PDF marking the changes between two revisions
+///
+/// `to_revision` defaults to the current version. Always uses the native
+/// renderer.
+#[tauri::command]
+pub async fn generate_redline_pdf(
+    document_id: String,
+    from_revision: i64,
+    to_revision: Option<i64>,
+    output_path: Option<String>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+    python: tauri::State<'_, Arc<Mutex<Option<PythonService>>>>,
+) -> Result<GeneratePdfResponse, String> {
+    info!(
+        "Command: generate_redline_pdf called for {} from revision {}",
+        document_id, from_revision
+    );
+
+    let (old, new, assets, fonts) = {
+        let storage = storage.lock().await;
+        let old = storage
+            .load_version(&document_id, Some(from_revision))
+            .await;
+        let new = storage.load_version(&document_id, to_revision).await;
+        let (old, new) = old.and_then(|old| new.map(|new| (old, new))).map_err(|e| {
+            error!("Failed to load revision: {}", e);
+            String::from(e)
+        })?;
+        (old, new, storage.assets().clone(), storage.fonts().clone())
+    };
+
+    let diff = diff_documents(&old, &new);
+    let mut warnings = Vec::new();
+    if diff.is_empty() {
+        warnings.push("The revisions are identical".to_string());
+    }
+    let document = redline_document(&old, &new, &diff);
+
+    let output_path = match output_path {
+        Some(path) => PathBuf::from(path),
+        None => default_output_path(&document.metadata.title, "pdf")?,
+    };
+
+    let pdf_path = render_to_file(
+        document,
+        &output_path,
+        RenderBackend::Native,
+        &assets,
+        fonts,
+        &python,
+    )
+    .await?;
+
+    info!("Redline PDF generated at: {:?}", pdf_path);
+
+    Ok(GeneratePdfResponse {
+        pdf_path: pdf_path.to_string_lossy().to_string(),
+        success: true,
+        message: "Redline PDF generated successfully".to_string(),
+        warnings,
+    })
+}
+
+/// Generate an editable Word file from a document
+///
+/// `data` and `locale` are applied like in `generate_pdf`. The warnings name
+/// every block style the DOCX file cannot reproduce.
+#[tauri::command]
+pub async fn generate_docx(
+    document_id: String,
+    output_path: Option<String>,
+    data: Option<serde_json::Value>,
+    locale: Option<Locale>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<GenerateDocxResponse, String> {
+    info!("Command: generate_docx called for document {}", document_id);
+
+    let (mut document, assets) = {
+        let storage = storage.lock().await;
+        let document = storage.load_document(&document_id).await.map_err(|e| {
+            error!("Failed to load document: {}", e);
+            e.to_string()
+        })?;
+        (document, storage.assets().clone())
+    };
+    if let Some(locale) = locale {
+        document.metadata.locale = locale;
+    }
+    let document = resolve_document(document, data)?;
+
+    let output_path = match output_path {
+        Some(path) => PathBuf::from(path),
+        None => default_output_path(&document.metadata.title, "docx")?,
+    };
+
+    // Image decoding and compression are CPU-bound
+    let renderer = DocxRenderer::with_assets(assets);
+    let output = tokio::task::spawn_blocking(move || renderer.export(&document))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| {
+            error!("DOCX generation failed: {}", e);
+            String::from(e)
+        })?;
+    for warning in &output.warnings {
+        warn!("DOCX warning: {}", warning);
+    }
+
+    if let Some(parent) = output_path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    tokio::fs::write(&output_path, output.bytes)
+        .await
+        .map_err(|e| {
+            error!("Failed to write DOCX: {}", e);
+            e.to_string()
+        })?;
+
+    info!("DOCX generated successfully at: {:?}", output_path);
+
+    Ok(GenerateDocxResponse {
+        docx_path: output_path.to_string_lossy().to_string(),
+        success: true,
+        message: "DOCX generated successfully".to_string(),
+        warnings: output.warnings,
     })
 }
 
 /// Check if Python is available
 #[tauri::command]
 pub async fn check_python(
-    python: tauri::State<'_, Arc<Mutex<PythonService>>>,
+    python: tauri::State<'_, Arc<Mutex<Option<PythonService>>>>,
 ) -> Result<String, String> {
     info!("Command: check_python called");
 
     let python = python.lock().await;
+    let python = python
+        .as_ref()
+        .ok_or_else(|| "Python backend is not available".to_string())?;
     python.check_python().await.map_err(|e| {
         error!("Python check failed: {}", e);
         e.to_string()
     })
 }
 
+/// List the template variables used in a document
+#[tauri::command]
+pub async fn list_document_variables(
+    document_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<VariableUsage>, String> {
+    info!(
+        "Command: list_document_variables called for document {}",
+        document_id
+    );
+
+    let storage = storage.lock().await;
+    let document = storage.load_document(&document_id).await.map_err(|e| {
+        error!("Failed to load document: {}", e);
+        e.to_string()
+    })?;
+
+    Ok(TemplateEngine::list_variables(&document))
+}
+
+/// Resolve placeholders against `data`; without data, visibility rules
+/// still apply and placeholders are kept
+fn resolve_document(
+    document: Document,
+    data: Option<serde_json::Value>,
+) -> Result<Document, String> {
+    match data {
+        Some(data) => TemplateEngine::apply(&document, &data),
+        None => TemplateEngine::apply_without_data(&document),
+    }
+    .map_err(|e| {
+        error!("Failed to resolve document: {}", e);
+        String::from(e)
+    })
+}
+
+/// Default output location: Documents/SimpleDoc/exports/<title>.<extension>
+fn default_output_path(title: &str, extension: &str) -> Result<PathBuf, String> {
+    let export_dir = dirs::home_dir()
+        .ok_or("Failed to get home directory")?
+        .join("Documents")
+        .join("SimpleDoc")
+        .join("exports");
+
+    // Create directory if it doesn't exist
+    if !export_dir.exists() {
+        std::fs::create_dir_all(&export_dir).map_err(|e| {
+            error!("Failed to create export directory: {}", e);
+            e.to_string()
+        })?;
+    }
+
+    let filename = format!("{}.{}", title.replace(' ', "_"), extension);
+    Ok(export_dir.join(filename))
+}
+
+/// Render a document with the selected backend and write it to `output_path`
+async fn render_to_file(
+    document: Document,
+    output_path: &Path,
+    backend: RenderBackend,
+    assets: &AssetStore,
+    fonts: FontRegistry,
+    python: &Mutex<Option<PythonService>>,
+) -> Result<PathBuf, String> {
+    match backend {
+        RenderBackend::Native => {
+            // Image decoding and compression are CPU-bound
+            let renderer = PdfRenderer::with_assets(assets.clone()).with_fonts(fonts);
+            let bytes = tokio::task::spawn_blocking(move || renderer.render(&document))
+                .await
+                .map_err(|e| e.to_string())?
+                .map_err(|e| {
+                    error!("PDF generation failed: {}", e);
+                    String::from(e)
+                })?;
+
+            if let Some(parent) = output_path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+            tokio::fs::write(output_path, bytes).await.map_err(|e| {
+                error!("Failed to write PDF: {}", e);
+                e.to_string()
+            })?;
+
+            Ok(output_path.to_path_buf())
+        }
+        RenderBackend::Python => {
+            // The script only understands file paths
+            let mut document = document;
+            assets.resolve_paths(&mut document).map_err(|e| {
+                error!("Failed to resolve assets: {}", e);
+                String::from(e)
+            })?;
+
+            let python = python.lock().await;
+            let python = python
+                .as_ref()
+                .ok_or_else(|| "Python backend is not available".to_string())?;
+            python
+                .generate_pdf(&document, &output_path.to_path_buf())
+                .await
+                .map_err(|e| {
+                    error!("PDF generation failed: {}", e);
+                    e.to_string()
+                })
+        }
+    }
+}
+
 /// Open the generated PDF file
 #[tauri::command]
 pub async fn open_pdf(pdf_path: String) -> Result<(), String> {
@@ -166,4 +455,3 @@ pub async fn open_pdf(pdf_path: String) -> Result<(), String> {
     info!("PDF opened successfully");
     Ok(())
 }
-
@@ -0,0 +1,94 @@
+use crate::models::Document;
+use crate::services::diff::diff_documents;
+use crate::services::{DocumentDiff, RevisionInfo, StorageService};
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// List the saved revisions of a document, newest first
+#[tauri::command]
+pub async fn list_revisions(
+    document_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<RevisionInfo>, String> {
+    info!("Command: list_revisions called for {}", document_id);
+
+    let storage = storage.lock().await;
+    storage.list_revisions(&document_id).await.map_err(|e| {
+        error!("Failed to list revisions: {}", e);
+        String::from(e)
+    })
+}
+
+/// Load a document as it was at a revision, without changing it
+#[tauri::command]
+pub async fn load_revision(
+    document_id: String,
+    revision_id: i64,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!(
+        "Command: load_revision called for {} revision {}",
+        document_id, revision_id
+    );
+
+    let storage = storage.lock().await;
+    storage
+        .load_revision(&document_id, revision_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to load revision: {}", e);
+            String::from(e)
+        })
+}
+
+/// Make a revision the current version of a document
+#[tauri::command]
+pub async fn restore_revision(
+    document_id: String,
+    revision_id: i64,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!(
+        "Command: restore_revision called for {} revision {}",
+        document_id, revision_id
+    );
+
+    let storage = storage.lock().await;
+    storage
+        .restore_revision(&document_id, revision_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to restore revision: {}", e);
+            String::from(e)
+        })
+}
+
+/// Structural diff between two revisions of a document
+///
+/// `to_revision` defaults to the current version.
+#[tauri::command]
+pub async fn diff_revisions(
+    document_id: String,
+    from_revision: i64,
+    to_revision: Option<i64>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<DocumentDiff, String> {
+    info!(
+        "Command: diff_revisions called for {} from revision {}",
+        document_id, from_revision
+    );
+
+    let storage = storage.lock().await;
+    let load = |revision_id| storage.load_version(&document_id, revision_id);
+    let old = load(Some(from_revision)).await.map_err(|e| {
+        error!("Failed to load revision: {}", e);
+        String::from(e)
+    })?;
+    let new = load(to_revision).await.map_err(|e| {
+        error!("Failed to load revision: {}", e);
+        String::from(e)
+    })?;
+
+    Ok(diff_documents(&old, &new))
+}
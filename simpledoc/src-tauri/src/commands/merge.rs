@@ -0,0 +1,60 @@
+use crate::services::merge;
+use crate::services::{MergeResult, MergeSide, StorageService};
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Merge another copy of a document into it
+///
+/// `base_revision` is the revision of `document_id` both copies started
+/// from, e.g. the one exported before the copy was imported. Nothing is
+/// saved; resolve the conflicts and save the merged document.
+#[tauri::command]
+pub async fn merge_documents(
+    document_id: String,
+    other_document_id: String,
+    base_revision: i64,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<MergeResult, String> {
+    info!(
+        "Command: merge_documents called for {} and {} from revision {}",
+        document_id, other_document_id, base_revision
+    );
+
+    let storage = storage.lock().await;
+    let base = storage.load_revision(&document_id, base_revision).await;
+    let ours = storage.load_document(&document_id).await;
+    let theirs = storage.load_document(&other_document_id).await;
+
+    let result = base.and_then(|base| {
+        let (ours, theirs) = (ours?, theirs?);
+        merge::merge_documents(&base, &ours, &theirs)
+    });
+    let result = result.map_err(|e| {
+        error!("Failed to merge documents: {}", e);
+        String::from(e)
+    })?;
+
+    info!("Merged with {} conflicts", result.conflicts.len());
+    Ok(result)
+}
+
+/// Settle one conflict of a merge by taking our or their value
+#[tauri::command]
+pub async fn resolve_merge_conflict(
+    merge: MergeResult,
+    conflict_id: String,
+    side: MergeSide,
+) -> Result<MergeResult, String> {
+    info!(
+        "Command: resolve_merge_conflict called for {} ({:?})",
+        conflict_id, side
+    );
+
+    let mut merge = merge;
+    merge.resolve(&conflict_id, side).map_err(|e| {
+        error!("Failed to resolve merge conflict: {}", e);
+        String::from(e)
+    })?;
+    Ok(merge)
+}
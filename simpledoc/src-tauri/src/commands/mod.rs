@@ -1,8 +1,23 @@
 pub mod document;
 pub mod blocks;
+pub mod pages;
 pub mod generator;
+pub mod batch;
+pub mod search;
+pub mod history;
+pub mod merge;
+pub mod templates;
+pub mod assets;
+pub mod fonts;
 
-pub use document::{save_document, load_document, list_documents, delete_document};
-pub use blocks::{add_block, update_block, delete_block, reorder_blocks};
-pub use generator::generate_pdf;
-
+pub use document::{save_document, load_document, list_documents, delete_document, list_trash, restore_document, purge_document};
+pub use blocks::{add_block, update_block, delete_block, reorder_blocks, compute_table, import_rich_text, measure_text};
+pub use pages::{add_page, duplicate_page, delete_page, reorder_pages, update_page_settings};
+pub use generator::{generate_pdf, generate_docx, list_document_variables, generate_redline_pdf};
+pub use batch::generate_batch;
+pub use search::{search_documents, rebuild_search_index};
+pub use history::{list_revisions, load_revision, restore_revision, diff_revisions};
+pub use merge::{merge_documents, resolve_merge_conflict};
+pub use templates::{save_as_template, list_templates, load_template, delete_template, create_document_from_template, export_template, import_template};
+pub use assets::{add_asset, collect_asset_garbage};
+pub use fonts::{list_fonts, import_font};
@@ -0,0 +1,190 @@
+use crate::models::{Document, Page, PageMargins, PageOrientation, PageSize};
+use crate::services::StorageService;
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Add a new page to a document
+///
+/// Size and orientation default to those of the last page.
+#[tauri::command]
+pub async fn add_page(
+    document_id: String,
+    index: Option<usize>,
+    size: Option<PageSize>,
+    orientation: Option<PageOrientation>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!("Command: add_page called for document {}", document_id);
+
+    let storage = storage.lock().await;
+
+    // Load document
+    let mut document = storage.load_document(&document_id).await.map_err(|e| {
+        error!("Failed to load document: {}", e);
+        e.to_string()
+    })?;
+
+    let template = document.pages.last().cloned().unwrap_or_default();
+    let mut page = Page::new(
+        size.unwrap_or(template.size),
+        orientation.unwrap_or(template.orientation),
+    );
+    page.margins = template.margins;
+
+    document.insert_page(page, index);
+
+    // Save document
+    storage.save_document(&document).await.map_err(|e| {
+        error!("Failed to save document: {}", e);
+        String::from(e)
+    })?;
+
+    Ok(document)
+}
+
+/// Duplicate a page with all of its blocks
+#[tauri::command]
+pub async fn duplicate_page(
+    document_id: String,
+    page_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!(
+        "Command: duplicate_page called for document {} page {}",
+        document_id, page_id
+    );
+
+    let storage = storage.lock().await;
+
+    // Load document
+    let mut document = storage.load_document(&document_id).await.map_err(|e| {
+        error!("Failed to load document: {}", e);
+        e.to_string()
+    })?;
+
+    document.duplicate_page(&page_id).map_err(|e| {
+        error!("Failed to duplicate page: {}", e);
+        e
+    })?;
+
+    // Save document
+    storage.save_document(&document).await.map_err(|e| {
+        error!("Failed to save document: {}", e);
+        String::from(e)
+    })?;
+
+    Ok(document)
+}
+
+/// Delete a page and the blocks placed on it
+#[tauri::command]
+pub async fn delete_page(
+    document_id: String,
+    page_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!(
+        "Command: delete_page called for document {} page {}",
+        document_id, page_id
+    );
+
+    let storage = storage.lock().await;
+
+    // Load document
+    let mut document = storage.load_document(&document_id).await.map_err(|e| {
+        error!("Failed to load document: {}", e);
+        e.to_string()
+    })?;
+
+    document.remove_page(&page_id).map_err(|e| {
+        error!("Failed to delete page: {}", e);
+        e
+    })?;
+
+    // Save document
+    storage.save_document(&document).await.map_err(|e| {
+        error!("Failed to save document: {}", e);
+        String::from(e)
+    })?;
+
+    Ok(document)
+}
+
+/// Reorder the pages of a document
+#[tauri::command]
+pub async fn reorder_pages(
+    document_id: String,
+    page_ids: Vec<String>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!("Command: reorder_pages called for document {}", document_id);
+
+    let storage = storage.lock().await;
+
+    // Load document
+    let mut document = storage.load_document(&document_id).await.map_err(|e| {
+        error!("Failed to load document: {}", e);
+        e.to_string()
+    })?;
+
+    document.reorder_pages(&page_ids).map_err(|e| {
+        error!("Failed to reorder pages: {}", e);
+        e
+    })?;
+
+    // Save document
+    storage.save_document(&document).await.map_err(|e| {
+        error!("Failed to save document: {}", e);
+        String::from(e)
+    })?;
+
+    Ok(document)
+}
+
+/// Change the size, orientation or margins of a page
+#[tauri::command]
+pub async fn update_page_settings(
+    document_id: String,
+    page_id: String,
+    size: Option<PageSize>,
+    orientation: Option<PageOrientation>,
+    margins: Option<PageMargins>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!(
+        "Command: update_page_settings called for document {} page {}",
+        document_id, page_id
+    );
+
+    let storage = storage.lock().await;
+
+    // Load document
+    let mut document = storage.load_document(&document_id).await.map_err(|e| {
+        error!("Failed to load document: {}", e);
+        e.to_string()
+    })?;
+
+    let page = document
+        .get_page_mut(&page_id)
+        .ok_or_else(|| format!("Page {} not found", page_id))?;
+
+    if let Some(size) = size {
+        page.size = size;
+    }
+    if let Some(orientation) = orientation {
+        page.orientation = orientation;
+    }
+    if let Some(margins) = margins {
+        page.margins = margins;
+    }
+    document.touch();
+
+    // Save document
+    storage.save_document(&document).await.map_err(|e| {
+        error!("Failed to save document: {}", e);
+        String::from(e)
+    })?;
+
+    Ok(document)
+}
@@ -0,0 +1,40 @@
+use crate::services::{SearchHit, StorageService};
+use log::{error, info};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Number of hits returned when no limit is given
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// Full-text search across all documents, best match first
+#[tauri::command]
+pub async fn search_documents(
+    query: String,
+    limit: Option<usize>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<SearchHit>, String> {
+    info!("Command: search_documents called with {:?}", query);
+
+    let storage = storage.lock().await;
+    storage
+        .search_documents(&query, limit.unwrap_or(DEFAULT_SEARCH_LIMIT))
+        .await
+        .map_err(|e| {
+            error!("Failed to search documents: {}", e);
+            String::from(e)
+        })
+}
+
+/// Rebuild the search index from the stored documents
+#[tauri::command]
+pub async fn rebuild_search_index(
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<usize, String> {
+    info!("Command: rebuild_search_index called");
+
+    let storage = storage.lock().await;
+    storage.rebuild_search_index().await.map_err(|e| {
+        error!("Failed to rebuild search index: {}", e);
+        String::from(e)
+    })
+}
@@ -0,0 +1,134 @@
+use crate::models::{Document, Template, TemplateListItem};
+use crate::services::{StorageService, TemplateOptions};
+use log::{error, info};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Save a copy of a document in the template library
+#[tauri::command]
+pub async fn save_as_template(
+    document_id: String,
+    options: TemplateOptions,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Template, String> {
+    info!(
+        "Command: save_as_template called for {} as {}",
+        document_id, options.name
+    );
+
+    let storage = storage.lock().await;
+    storage
+        .save_as_template(&document_id, options)
+        .await
+        .map_err(|e| {
+            error!("Failed to save template: {}", e);
+            String::from(e)
+        })
+}
+
+/// List the templates by category and name
+#[tauri::command]
+pub async fn list_templates(
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Vec<TemplateListItem>, String> {
+    info!("Command: list_templates called");
+
+    let storage = storage.lock().await;
+    storage.list_templates().await.map_err(|e| {
+        error!("Failed to list templates: {}", e);
+        String::from(e)
+    })
+}
+
+/// Load a template with its document
+#[tauri::command]
+pub async fn load_template(
+    template_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Template, String> {
+    info!("Command: load_template called for {}", template_id);
+
+    let storage = storage.lock().await;
+    storage.load_template(&template_id).await.map_err(|e| {
+        error!("Failed to load template: {}", e);
+        String::from(e)
+    })
+}
+
+/// Delete a template
+#[tauri::command]
+pub async fn delete_template(
+    template_id: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<(), String> {
+    info!("Command: delete_template called for {}", template_id);
+
+    let storage = storage.lock().await;
+    storage.delete_template(&template_id).await.map_err(|e| {
+        error!("Failed to delete template: {}", e);
+        String::from(e)
+    })
+}
+
+/// Create a new document from a template, with fresh ids for every block
+#[tauri::command]
+pub async fn create_document_from_template(
+    template_id: String,
+    title: Option<String>,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Document, String> {
+    info!(
+        "Command: create_document_from_template called for {}",
+        template_id
+    );
+
+    let storage = storage.lock().await;
+    storage
+        .create_document_from_template(&template_id, title)
+        .await
+        .map_err(|e| {
+            error!("Failed to create document from template: {}", e);
+            String::from(e)
+        })
+}
+
+/// Export a template to a single bundle file
+#[tauri::command]
+pub async fn export_template(
+    template_id: String,
+    export_path: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<(), String> {
+    info!(
+        "Command: export_template called for {} to {}",
+        template_id, export_path
+    );
+
+    let storage = storage.lock().await;
+    storage
+        .export_template(&template_id, &PathBuf::from(export_path))
+        .await
+        .map_err(|e| {
+            error!("Failed to export template: {}", e);
+            String::from(e)
+        })
+}
+
+/// Import a template bundle into the library
+#[tauri::command]
+pub async fn import_template(
+    import_path: String,
+    storage: tauri::State<'_, Arc<Mutex<StorageService>>>,
+) -> Result<Template, String> {
+    info!("Command: import_template called from {}", import_path);
+
+    let mut storage = storage.lock().await;
+    storage
+        .import_template(&PathBuf::from(import_path))
+        .await
+        .map_err(|e| {
+            error!("Failed to import template: {}", e);
+            String::from(e)
+        })
+}
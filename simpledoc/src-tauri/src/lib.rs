@@ -5,7 +5,7 @@ mod services;
 mod utils;
 
 // Re-exports
-use commands::{blocks, document, generator};
+use commands::{assets, batch, blocks, document, fonts, generator, history, merge, pages, search, templates};
 use services::{PythonService, StorageService};
 use utils::init_logger;
 
@@ -20,12 +20,30 @@ pub fn run() {
 
     // Initialize services
     let storage_dir = StorageService::default_storage_dir();
-    let storage_service = StorageService::new(storage_dir)
-        .expect("Failed to initialize storage service");
+    // SIMPLEDOC_STORAGE=json keeps one plain JSON file per document
+    let mut storage_service = match std::env::var("SIMPLEDOC_STORAGE").as_deref() {
+        Ok("json") => StorageService::new(storage_dir),
+        _ => StorageService::open_database(storage_dir),
+    }
+    .expect("Failed to initialize storage service");
+    // SIMPLEDOC_TRASH_DAYS=0 keeps deleted documents until purged by hand
+    if let Some(days) = std::env::var("SIMPLEDOC_TRASH_DAYS").ok().and_then(|d| d.parse().ok()) {
+        storage_service.set_trash_retention((days > 0).then_some(days));
+    }
+    if let Err(e) = storage_service.purge_expired_trash() {
+        log::warn!("Failed to purge expired trash: {}", e);
+    }
+    storage_service.load_system_fonts();
     
+    // Python is only needed for the legacy rendering backend
     let scripts_dir = PythonService::default_scripts_dir();
-    let python_service = PythonService::new(None, scripts_dir)
-        .expect("Failed to initialize Python service");
+    let python_service = match PythonService::new(None, scripts_dir) {
+        Ok(service) => Some(service),
+        Err(e) => {
+            log::warn!("Python backend unavailable: {}", e);
+            None
+        }
+    };
 
     log::info!("Services initialized successfully");
 
@@ -44,6 +62,9 @@ pub fn run() {
             document::load_document,
             document::list_documents,
             document::delete_document,
+            document::list_trash,
+            document::restore_document,
+            document::purge_document,
             document::create_document,
             document::export_document,
             document::import_document,
@@ -55,11 +76,50 @@ pub fn run() {
             blocks::reorder_blocks,
             blocks::update_blocks_bulk,
             blocks::get_block,
+            blocks::compute_table,
+            blocks::import_rich_text,
+            blocks::measure_text,
+            // Page commands
+            pages::add_page,
+            pages::duplicate_page,
+            pages::delete_page,
+            pages::reorder_pages,
+            pages::update_page_settings,
             // Generator commands
             generator::generate_pdf,
             generator::generate_pdf_from_blocks,
             generator::check_python,
             generator::open_pdf,
+            generator::list_document_variables,
+            generator::generate_redline_pdf,
+            generator::generate_docx,
+            // Batch commands
+            batch::generate_batch,
+            // Search commands
+            search::search_documents,
+            search::rebuild_search_index,
+            // History commands
+            history::list_revisions,
+            history::load_revision,
+            history::restore_revision,
+            history::diff_revisions,
+            // Merge commands
+            merge::merge_documents,
+            merge::resolve_merge_conflict,
+            // Template commands
+            templates::save_as_template,
+            templates::list_templates,
+            templates::load_template,
+            templates::delete_template,
+            templates::create_document_from_template,
+            templates::export_template,
+            templates::import_template,
+            // Asset commands
+            assets::add_asset,
+            assets::collect_asset_garbage,
+            // Font commands
+            fonts::list_fonts,
+            fonts::import_font,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -1,31 +1,58 @@
+use crate::models::document::PageRect;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use uuid::Uuid;
 
 /// Types of blocks available in the editor
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum BlockType {
     Text,
     Image,
     Table,
     Spacer,
+    Shape,
+    Line,
 }
 
 /// Position of a block on the canvas (in pixels from top-left)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Position {
     pub x: f64,
     pub y: f64,
 }
 
 /// Size of a block (in pixels)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Size {
     pub width: f64,
     pub height: f64,
 }
 
+/// Rotation and scale of a block around its center, as set in the editor
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Transform {
+    /// Clockwise rotation in degrees
+    pub rotation: f64,
+    #[serde(rename = "scaleX")]
+    pub scale_x: f64,
+    #[serde(rename = "scaleY")]
+    pub scale_y: f64,
+}
+
+impl Transform {
+    /// Whether the transform leaves the block as it is
+    pub fn is_identity(&self) -> bool {
+        self.rotation % 360.0 == 0.0 && self.scale_x == 1.0 && self.scale_y == 1.0
+    }
+}
+
 /// Content for text blocks
+///
+/// The font fields are the defaults for the whole block. `paragraphs`
+/// holds rich text; when it is set, `text` is its plain text so search,
+/// diffs and older renderers keep working.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextBlockContent {
     pub text: String,
@@ -37,9 +64,142 @@ pub struct TextBlockContent {
     pub font_weight: u16,
     pub color: String,
     pub alignment: TextAlignment,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub paragraphs: Vec<Paragraph>,
+    /// Line height as a multiple of the font size
+    #[serde(rename = "lineHeight", default, skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<f64>,
+    /// Break long words at syllables
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub hyphenate: bool,
+    /// Fit the block height to its text when generating
+    #[serde(rename = "autoHeight", default, skip_serializing_if = "is_false")]
+    pub auto_height: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TextBlockContent {
+    /// Paragraphs to lay out: the rich text, or one plain paragraph per line
+    /// of `text`
+    pub fn paragraphs(&self) -> Vec<Paragraph> {
+        if !self.paragraphs.is_empty() {
+            return self.paragraphs.clone();
+        }
+        self.text
+            .split('\n')
+            .map(|line| Paragraph::plain(line.to_string()))
+            .collect()
+    }
+
+    /// Replace the rich text, keeping `text` in sync
+    pub fn set_paragraphs(&mut self, paragraphs: Vec<Paragraph>) {
+        self.text = plain_text(&paragraphs);
+        self.paragraphs = paragraphs;
+    }
+}
+
+/// A paragraph of styled runs, optionally a list item
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Paragraph {
+    pub runs: Vec<TextRun>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list: Option<ListKind>,
+    /// Nesting depth of list items, 0 for the outermost list
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub level: u8,
+    /// Overrides the block alignment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alignment: Option<TextAlignment>,
+    /// Space above the paragraph (in pixels)
+    #[serde(rename = "spaceBefore", default, skip_serializing_if = "is_zero_f64")]
+    pub space_before: f64,
+    /// Space below the paragraph (in pixels)
+    #[serde(rename = "spaceAfter", default, skip_serializing_if = "is_zero_f64")]
+    pub space_after: f64,
+}
+
+impl Paragraph {
+    /// Paragraph of a single unstyled run
+    pub fn plain(text: String) -> Self {
+        Self {
+            runs: vec![TextRun::plain(text)],
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> String {
+        self.runs.iter().map(|run| run.text.as_str()).collect()
+    }
+}
+
+/// Plain text of paragraphs, one line per paragraph
+pub fn plain_text(paragraphs: &[Paragraph]) -> String {
+    paragraphs
+        .iter()
+        .map(Paragraph::text)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListKind {
+    Bullet,
+    Numbered,
+}
+
+/// Text with one style; unset fields inherit from the block
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TextRun {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub strike: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Font size in pixels
+    #[serde(rename = "fontSize", default, skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f64>,
+    /// Target URL of a hyperlink
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(rename = "verticalAlign", default, skip_serializing_if = "Option::is_none")]
+    pub vertical_align: Option<VerticalAlign>,
+}
+
+impl TextRun {
+    pub fn plain(text: String) -> Self {
+        Self {
+            text,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerticalAlign {
+    Superscript,
+    Subscript,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn is_zero(value: &u8) -> bool {
+    *value == 0
+}
+
+fn is_zero_f64(value: &f64) -> bool {
+    *value == 0.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TextAlignment {
     Left,
@@ -51,7 +211,7 @@ pub enum TextAlignment {
 /// Content for image blocks
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageBlockContent {
-    /// Path to file or base64 encoded image
+    /// Asset reference (`asset://<hash>`), path to file or base64 encoded image
     pub src: String,
     pub alt: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -82,9 +242,13 @@ pub struct TableRow {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TableCell {
+    /// Text, or a formula starting with `=` (e.g. `=SUM(col(3))`)
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub styles: Option<CellStyles>,
+    /// Name other formulas use to reference this cell, e.g. `subtotal`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -97,16 +261,117 @@ pub struct CellStyles {
     pub bold: Option<bool>,
 }
 
-/// Union type for block content
+/// Outline of shapes and lines
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Stroke {
+    pub color: String,
+    /// Line width (in pixels)
+    pub width: f64,
+    #[serde(default)]
+    pub dash: StrokeDash,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StrokeDash {
+    #[default]
+    Solid,
+    Dashed,
+    Dotted,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    RoundedRect,
+    Polygon,
+}
+
+/// Content for shape blocks; the shape fills the block box
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShapeBlockContent {
+    pub shape: ShapeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fill: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stroke: Option<Stroke>,
+    /// Corner radius of `roundedRect` (in pixels)
+    #[serde(rename = "cornerRadius", default, skip_serializing_if = "Option::is_none")]
+    pub corner_radius: Option<f64>,
+    /// Corners of `polygon`, relative to the block's top-left corner (in pixels)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub points: Vec<Position>,
+}
+
+/// Content for line blocks
+///
+/// The end points are relative to the block's top-left corner (in pixels),
+/// so moving the block moves the line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineBlockContent {
+    pub start: Position,
+    pub end: Position,
+    pub stroke: Stroke,
+    #[serde(rename = "startArrow", default)]
+    pub start_arrow: bool,
+    #[serde(rename = "endArrow", default)]
+    pub end_arrow: bool,
+}
+
+/// Block content, discriminated by the block's `type`
+///
+/// Serialized as `"type": "text", "content": { ... }` next to the other
+/// block fields.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
+#[serde(tag = "type", content = "content", rename_all = "lowercase")]
 pub enum BlockContent {
     Text(TextBlockContent),
     Image(ImageBlockContent),
     Table(TableBlockContent),
+    Shape(ShapeBlockContent),
+    Line(LineBlockContent),
     Spacer,
 }
 
+impl BlockContent {
+    /// Block type this content belongs to
+    pub fn block_type(&self) -> BlockType {
+        match self {
+            BlockContent::Text(_) => BlockType::Text,
+            BlockContent::Image(_) => BlockType::Image,
+            BlockContent::Table(_) => BlockType::Table,
+            BlockContent::Shape(_) => BlockType::Shape,
+            BlockContent::Line(_) => BlockType::Line,
+            BlockContent::Spacer => BlockType::Spacer,
+        }
+    }
+}
+
+/// Binds a block to an array in the generation data
+///
+/// The block is repeated once per element (tables add rows instead), with
+/// the element available as `item` and its 1-based position as `index`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepeatBinding {
+    /// Path of the array, e.g. `items`
+    pub path: String,
+    /// Vertical gap between repeated blocks (in pixels)
+    #[serde(default)]
+    pub gap: f64,
+    /// Leading table rows kept as a header and repeated on every page
+    #[serde(rename = "headerRows", default = "default_header_rows")]
+    pub header_rows: usize,
+    /// Trailing table rows (e.g. totals) placed once after the repeated rows
+    #[serde(rename = "footerRows", default)]
+    pub footer_rows: usize,
+}
+
+fn default_header_rows() -> usize {
+    1
+}
+
 /// Styles that can be applied to blocks
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockStyles {
@@ -138,20 +403,34 @@ pub struct Padding {
 }
 
 /// Main block structure
+///
+/// Deserialization reads `content` as the variant named by `type` and
+/// reports errors with the block id and field.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Value")]
 pub struct Block {
     pub id: String,
-    #[serde(rename = "type")]
-    pub block_type: BlockType,
+    /// Also writes the block's `type`
+    #[serde(flatten)]
+    pub content: BlockContent,
+    /// Page the block is placed on (empty until assigned by the document)
+    #[serde(rename = "pageId", default)]
+    pub page_id: String,
     pub position: Position,
     pub size: Size,
-    pub content: BlockContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub styles: Option<BlockStyles>,
     #[serde(rename = "zIndex")]
     pub z_index: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub locked: Option<bool>,
+    /// Expression deciding whether the block is rendered, e.g. `amount > 1000`
+    #[serde(rename = "visibleIf", default, skip_serializing_if = "Option::is_none")]
+    pub visible_if: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repeat: Option<RepeatBinding>,
 }
 
 impl Block {
@@ -165,6 +444,10 @@ impl Block {
                 font_weight: 400,
                 color: "#000000".to_string(),
                 alignment: TextAlignment::Left,
+                paragraphs: Vec::new(),
+                line_height: None,
+                hyphenate: false,
+                auto_height: false,
             }),
             BlockType::Image => BlockContent::Image(ImageBlockContent {
                 src: String::new(),
@@ -176,17 +459,59 @@ impl Block {
                 column_widths: vec![],
             }),
             BlockType::Spacer => BlockContent::Spacer,
+            BlockType::Shape => BlockContent::Shape(ShapeBlockContent {
+                shape: ShapeKind::Rectangle,
+                fill: Some("#e5e7eb".to_string()),
+                stroke: None,
+                corner_radius: None,
+                points: vec![],
+            }),
+            BlockType::Line => BlockContent::Line(LineBlockContent {
+                start: Position {
+                    x: 0.0,
+                    y: size.height / 2.0,
+                },
+                end: Position {
+                    x: size.width,
+                    y: size.height / 2.0,
+                },
+                stroke: Stroke {
+                    color: "#000000".to_string(),
+                    width: 1.0,
+                    dash: StrokeDash::Solid,
+                },
+                start_arrow: false,
+                end_arrow: false,
+            }),
         };
 
         Self {
             id: Uuid::new_v4().to_string(),
-            block_type,
+            content,
+            page_id: String::new(),
             position,
             size,
-            content,
+            transform: None,
             styles: None,
             z_index: 0,
             locked: None,
+            visible_if: None,
+            repeat: None,
+        }
+    }
+
+    /// Block type, derived from the content
+    pub fn block_type(&self) -> BlockType {
+        self.content.block_type()
+    }
+
+    /// Bounding rectangle of the block in canvas pixels
+    pub fn bounds(&self) -> PageRect {
+        PageRect {
+            x: self.position.x,
+            y: self.position.y,
+            width: self.size.width,
+            height: self.size.height,
         }
     }
 
@@ -204,3 +529,166 @@ impl Block {
     }
 }
 
+#[cfg(test)]
+impl Block {
+    /// Block of the given type and bounds, for tests
+    pub(crate) fn for_test(
+        block_type: BlockType,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> Self {
+        Self::new(block_type, Position { x, y }, Size { width, height })
+    }
+
+    /// Text block at the origin, 200x40 px, for tests
+    pub(crate) fn text_for_test(text: &str) -> Self {
+        Self::for_test(BlockType::Text, 0.0, 0.0, 200.0, 40.0).with_text(text)
+    }
+
+    /// Same block with its plain text replaced; non-text blocks are unchanged
+    pub(crate) fn with_text(mut self, text: &str) -> Self {
+        if let BlockContent::Text(content) = &mut self.content {
+            content.text = text.to_string();
+        }
+        self
+    }
+}
+
+impl TryFrom<Value> for Block {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, String> {
+        let Value::Object(mut fields) = value else {
+            return Err("Block must be a JSON object".to_string());
+        };
+        let id: String = block_field(&mut fields, "block", "id")?;
+        let block_type: BlockType = block_field(&mut fields, &id, "type")?;
+
+        let content = fields.remove("content").unwrap_or(Value::Null);
+        let content = match block_type {
+            BlockType::Text => content_from(content).map(BlockContent::Text),
+            BlockType::Image => content_from(content).map(BlockContent::Image),
+            BlockType::Table => content_from(content).map(BlockContent::Table),
+            BlockType::Shape => content_from(content).map(BlockContent::Shape),
+            BlockType::Line => content_from(content).map(BlockContent::Line),
+            BlockType::Spacer => Ok(BlockContent::Spacer),
+        }
+        .map_err(|e| {
+            format!(
+                "Block {}: invalid `content` for a {:?} block: {}",
+                id, block_type, e
+            )
+        })?;
+
+        Ok(Self {
+            page_id: block_field::<Option<String>>(&mut fields, &id, "pageId")?.unwrap_or_default(),
+            position: block_field(&mut fields, &id, "position")?,
+            size: block_field(&mut fields, &id, "size")?,
+            transform: block_field(&mut fields, &id, "transform")?,
+            styles: block_field(&mut fields, &id, "styles")?,
+            z_index: block_field(&mut fields, &id, "zIndex")?,
+            locked: block_field(&mut fields, &id, "locked")?,
+            visible_if: block_field(&mut fields, &id, "visibleIf")?,
+            repeat: block_field(&mut fields, &id, "repeat")?,
+            id,
+            content,
+        })
+    }
+}
+
+/// Deserialize one field of a block, naming the block and field on error
+fn block_field<T: DeserializeOwned>(
+    fields: &mut Map<String, Value>,
+    block_id: &str,
+    name: &str,
+) -> Result<T, String> {
+    match fields.remove(name) {
+        Some(value) => T::deserialize(value)
+            .map_err(|e| format!("Block {}: invalid `{}`: {}", block_id, name, e)),
+        // Optional fields accept null
+        None => T::deserialize(Value::Null)
+            .map_err(|_| format!("Block {}: missing `{}`", block_id, name)),
+    }
+}
+
+fn content_from<T: DeserializeOwned>(content: Value) -> Result<T, serde_json::Error> {
+    T::deserialize(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text_block() -> Value {
+        json!({
+            "id": "intro",
+            "type": "text",
+            "position": { "x": 0.0, "y": 0.0 },
+            "size": { "width": 100.0, "height": 20.0 },
+            "zIndex": 0,
+            "content": {
+                "text": "Hello",
+                "fontSize": 16.0,
+                "fontFamily": "Inter",
+                "fontWeight": 400,
+                "color": "#000000",
+                "alignment": "left"
+            }
+        })
+    }
+
+    #[test]
+    fn test_round_trip_keeps_type_and_content() {
+        let block: Block = serde_json::from_value(text_block()).unwrap();
+        assert_eq!(block.block_type(), BlockType::Text);
+
+        let value = serde_json::to_value(&block).unwrap();
+        assert_eq!(value["type"], "text");
+        assert_eq!(value["content"]["text"], "Hello");
+
+        let spacer = Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0);
+        let value = serde_json::to_value(&spacer).unwrap();
+        assert_eq!(value["type"], "spacer");
+        let spacer: Block = serde_json::from_value(value).unwrap();
+        assert_eq!(spacer.block_type(), BlockType::Spacer);
+    }
+
+    #[test]
+    fn test_round_trip_keeps_transform() {
+        let mut value = text_block();
+        value["transform"] = json!({ "rotation": 45.0, "scaleX": 1.5, "scaleY": 1.0 });
+        let block: Block = serde_json::from_value(value).unwrap();
+        let transform = block.transform.as_ref().unwrap();
+        assert_eq!(transform.rotation, 45.0);
+        assert!(!transform.is_identity());
+
+        let value = serde_json::to_value(&block).unwrap();
+        assert_eq!(
+            value["transform"],
+            json!({ "rotation": 45.0, "scaleX": 1.5, "scaleY": 1.0 })
+        );
+    }
+
+    #[test]
+    fn test_errors_name_block_and_field() {
+        let mut value = text_block();
+        value["content"].as_object_mut().unwrap().remove("fontSize");
+        let error = serde_json::from_value::<Block>(value).unwrap_err().to_string();
+        assert!(error.contains("Block intro"), "{}", error);
+        assert!(error.contains("fontSize"), "{}", error);
+
+        // Content of another type is no longer read as whatever matches
+        let mut value = text_block();
+        value["type"] = json!("image");
+        let error = serde_json::from_value::<Block>(value).unwrap_err().to_string();
+        assert!(error.contains("Image block"), "{}", error);
+
+        let mut value = text_block();
+        value.as_object_mut().unwrap().remove("zIndex");
+        let error = serde_json::from_value::<Block>(value).unwrap_err().to_string();
+        assert!(error.contains("missing `zIndex`"), "{}", error);
+    }
+}
@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use uuid::Uuid;
 use crate::models::block::Block;
+use crate::models::locale::Locale;
 
 /// Page size presets
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -15,8 +17,18 @@ pub enum PageSize {
     Custom { width: f64, height: f64 },
 }
 
+/// Canvas pixels per millimeter (the editor works at 96 DPI)
+pub const PX_PER_MM: f64 = 96.0 / 25.4;
+
+/// Schema version written with every document; bump it together with a
+/// new migration in `services::migration`
+pub const SCHEMA_VERSION: u32 = 5;
+
 impl PageSize {
-    /// Get page dimensions in millimeters
+    /// Get the preset dimensions in millimeters, as width x height
+    ///
+    /// This does not know about orientation; use `Page::geometry` for the
+    /// effective page size.
     pub fn dimensions_mm(&self) -> (f64, f64) {
         match self {
             PageSize::A4 => (210.0, 297.0),
@@ -31,8 +43,7 @@ impl PageSize {
     /// Get page dimensions in pixels (assuming 96 DPI)
     pub fn dimensions_px(&self) -> (f64, f64) {
         let (width_mm, height_mm) = self.dimensions_mm();
-        let px_per_mm = 96.0 / 25.4; // 96 DPI to pixels per mm
-        (width_mm * px_per_mm, height_mm * px_per_mm)
+        (width_mm * PX_PER_MM, height_mm * PX_PER_MM)
     }
 }
 
@@ -64,6 +75,57 @@ impl Default for PageMargins {
     }
 }
 
+/// Rectangle measured from the top-left corner of a page
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PageRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PageRect {
+    /// Check whether another rectangle lies completely inside this one
+    pub fn contains(&self, other: &PageRect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+}
+
+/// Effective page dimensions and the content box inside the margins
+///
+/// Values are in millimeters unless converted with `scale`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct PageGeometry {
+    pub width: f64,
+    pub height: f64,
+    #[serde(rename = "contentBox")]
+    pub content_box: PageRect,
+}
+
+impl PageGeometry {
+    /// Convert to another unit (e.g. `PX_PER_MM` for canvas pixels)
+    pub fn scale(&self, factor: f64) -> Self {
+        Self {
+            width: self.width * factor,
+            height: self.height * factor,
+            content_box: PageRect {
+                x: self.content_box.x * factor,
+                y: self.content_box.y * factor,
+                width: self.content_box.width * factor,
+                height: self.content_box.height * factor,
+            },
+        }
+    }
+
+    /// Geometry in canvas pixels, the unit of block positions and sizes
+    pub fn to_px(&self) -> Self {
+        self.scale(PX_PER_MM)
+    }
+}
+
 /// Page configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Page {
@@ -77,14 +139,63 @@ pub struct Page {
 
 impl Default for Page {
     fn default() -> Self {
+        Self::new(PageSize::A4, PageOrientation::Portrait)
+    }
+}
+
+impl Page {
+    /// Create a new page with default margins
+    pub fn new(size: PageSize, orientation: PageOrientation) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
-            size: PageSize::A4,
-            orientation: PageOrientation::Portrait,
+            size,
+            orientation,
             margins: PageMargins::default(),
             background: None,
         }
     }
+
+    /// Create a page of a custom size, oriented by its aspect ratio
+    pub fn custom(width: f64, height: f64) -> Self {
+        let orientation = if width > height {
+            PageOrientation::Landscape
+        } else {
+            PageOrientation::Portrait
+        };
+        Self::new(PageSize::Custom { width, height }, orientation)
+    }
+
+    /// Effective page size for the orientation and the content box inside the margins
+    ///
+    /// This is the single source of page geometry for the editor, validation
+    /// and generation. Custom sizes are used as stored, since portrait is
+    /// also the default orientation; only landscape turns a tall custom page.
+    pub fn geometry(&self) -> PageGeometry {
+        let (preset_width, preset_height) = self.size.dimensions_mm();
+        let (width, height) = match (&self.size, &self.orientation) {
+            (PageSize::Custom { .. }, PageOrientation::Portrait) => (preset_width, preset_height),
+            (_, PageOrientation::Portrait) => (
+                preset_width.min(preset_height),
+                preset_width.max(preset_height),
+            ),
+            (_, PageOrientation::Landscape) => (
+                preset_width.max(preset_height),
+                preset_width.min(preset_height),
+            ),
+        };
+
+        let margins = &self.margins;
+        PageGeometry {
+            width,
+            height,
+            content_box: PageRect {
+                x: margins.left,
+                y: margins.top,
+                width: (width - margins.left - margins.right).max(0.0),
+                height: (height - margins.top - margins.bottom).max(0.0),
+            },
+        }
+    }
 }
 
 /// Document metadata
@@ -101,6 +212,9 @@ pub struct DocumentMetadata {
     pub created_at: DateTime<Utc>,
     #[serde(rename = "updatedAt")]
     pub updated_at: DateTime<Utc>,
+    /// Locale for numbers, currencies and dates in generated output
+    #[serde(default)]
+    pub locale: Locale,
 }
 
 impl Default for DocumentMetadata {
@@ -113,6 +227,7 @@ impl Default for DocumentMetadata {
             tags: None,
             created_at: now,
             updated_at: now,
+            locale: Locale::default(),
         }
     }
 }
@@ -124,8 +239,13 @@ pub struct Document {
     pub metadata: DocumentMetadata,
     pub pages: Vec<Page>,
     pub blocks: Vec<Block>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub version: Option<String>,
+    /// Layout version of the stored JSON, see `services::migration`
+    #[serde(rename = "schemaVersion", default = "current_schema_version")]
+    pub schema_version: u32,
+}
+
+fn current_schema_version() -> u32 {
+    SCHEMA_VERSION
 }
 
 impl Document {
@@ -139,7 +259,7 @@ impl Document {
             metadata,
             pages: vec![Page::default()],
             blocks: vec![],
-            version: Some("1.0.0".to_string()),
+            schema_version: SCHEMA_VERSION,
         }
     }
 
@@ -149,7 +269,14 @@ impl Document {
     }
 
     /// Add a block to the document
-    pub fn add_block(&mut self, block: Block) {
+    ///
+    /// Blocks that are not yet placed on a page go to the first page.
+    pub fn add_block(&mut self, mut block: Block) {
+        if block.page_id.is_empty() {
+            if let Some(page) = self.pages.first() {
+                block.page_id = page.id.clone();
+            }
+        }
         self.blocks.push(block);
         self.touch();
     }
@@ -180,6 +307,140 @@ impl Document {
         self.blocks.iter().find(|b| b.id == block_id)
     }
 
+    /// Get a page by ID
+    pub fn get_page(&self, page_id: &str) -> Option<&Page> {
+        self.pages.iter().find(|p| p.id == page_id)
+    }
+
+    /// Get a mutable page by ID
+    pub fn get_page_mut(&mut self, page_id: &str) -> Option<&mut Page> {
+        self.pages.iter_mut().find(|p| p.id == page_id)
+    }
+
+    /// Iterate over the blocks placed on a page
+    pub fn blocks_on_page<'a>(&'a self, page_id: &'a str) -> impl Iterator<Item = &'a Block> {
+        self.blocks.iter().filter(move |b| b.page_id == page_id)
+    }
+
+    /// Move blocks that reference no existing page onto the first page
+    ///
+    /// Documents saved before blocks were tied to pages have no `pageId`.
+    pub fn assign_orphan_blocks(&mut self) {
+        let Some(first_page) = self.pages.first().map(|p| p.id.clone()) else {
+            return;
+        };
+
+        for block in &mut self.blocks {
+            if !self.pages.iter().any(|p| p.id == block.page_id) {
+                block.page_id = first_page.clone();
+            }
+        }
+    }
+
+    /// Insert a page at the given index (appends when `None` or out of range)
+    pub fn insert_page(&mut self, page: Page, index: Option<usize>) {
+        let index = index.unwrap_or(self.pages.len()).min(self.pages.len());
+        self.pages.insert(index, page);
+        self.touch();
+    }
+
+    /// Duplicate a page and its blocks, inserting the copy right after the original
+    ///
+    /// Returns the ID of the new page.
+    pub fn duplicate_page(&mut self, page_id: &str) -> Result<String, String> {
+        let index = self
+            .pages
+            .iter()
+            .position(|p| p.id == page_id)
+            .ok_or_else(|| format!("Page with id {} not found", page_id))?;
+
+        let mut copy = self.pages[index].clone();
+        copy.id = Uuid::new_v4().to_string();
+
+        let copied_blocks: Vec<Block> = self
+            .blocks_on_page(page_id)
+            .cloned()
+            .map(|mut block| {
+                block.id = Uuid::new_v4().to_string();
+                block.page_id = copy.id.clone();
+                block
+            })
+            .collect();
+
+        let new_id = copy.id.clone();
+        self.pages.insert(index + 1, copy);
+        self.blocks.extend(copied_blocks);
+        self.touch();
+        Ok(new_id)
+    }
+
+    /// Copy of the document with new ids for it, its pages and its blocks
+    pub fn duplicate(&self) -> Document {
+        let mut copy = self.clone();
+        copy.id = Uuid::new_v4().to_string();
+
+        let mut page_ids = HashMap::new();
+        for page in &mut copy.pages {
+            let new_id = Uuid::new_v4().to_string();
+            page_ids.insert(std::mem::replace(&mut page.id, new_id.clone()), new_id);
+        }
+        for block in &mut copy.blocks {
+            block.id = Uuid::new_v4().to_string();
+            if let Some(page_id) = page_ids.get(&block.page_id) {
+                block.page_id = page_id.clone();
+            }
+        }
+        copy
+    }
+
+    /// Remove a page together with all of its blocks
+    pub fn remove_page(&mut self, page_id: &str) -> Result<Page, String> {
+        if self.pages.len() <= 1 {
+            return Err("Document must have at least one page".to_string());
+        }
+
+        let index = self
+            .pages
+            .iter()
+            .position(|p| p.id == page_id)
+            .ok_or_else(|| format!("Page with id {} not found", page_id))?;
+
+        self.blocks.retain(|b| b.page_id != page_id);
+        self.touch();
+        Ok(self.pages.remove(index))
+    }
+
+    /// Reorder pages to match the given list of page IDs
+    ///
+    /// The list is checked as a whole first, so the pages are left untouched
+    /// when it is invalid.
+    pub fn reorder_pages(&mut self, page_ids: &[String]) -> Result<(), String> {
+        if page_ids.len() != self.pages.len() {
+            return Err(format!(
+                "Expected {} page ids, got {}",
+                self.pages.len(),
+                page_ids.len()
+            ));
+        }
+
+        let mut indices = Vec::with_capacity(page_ids.len());
+        for page_id in page_ids {
+            let index = self
+                .pages
+                .iter()
+                .position(|p| &p.id == page_id)
+                .ok_or_else(|| format!("Page with id {} not found", page_id))?;
+            if indices.contains(&index) {
+                return Err(format!("Duplicate page id {}", page_id));
+            }
+            indices.push(index);
+        }
+
+        self.pages = indices.into_iter().map(|index| self.pages[index].clone()).collect();
+        self.touch();
+        Ok(())
+    }
+
     /// Reorder blocks by z-index
     pub fn reorder_blocks(&mut self) {
         self.blocks.sort_by_key(|b| b.z_index);
@@ -194,6 +455,13 @@ impl Document {
 
         for block in &self.blocks {
             block.validate()?;
+
+            if self.get_page(&block.page_id).is_none() {
+                return Err(format!(
+                    "Block {} references unknown page {}",
+                    block.id, block.page_id
+                ));
+            }
         }
 
         Ok(())
@@ -207,6 +475,10 @@ pub struct DocumentListItem {
     pub title: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
     #[serde(rename = "createdAt")]
     pub created_at: DateTime<Utc>,
     #[serde(rename = "updatedAt")]
@@ -223,6 +495,8 @@ impl From<&Document> for DocumentListItem {
             id: doc.id.clone(),
             title: doc.metadata.title.clone(),
             description: doc.metadata.description.clone(),
+            author: doc.metadata.author.clone(),
+            tags: doc.metadata.tags.clone().unwrap_or_default(),
             created_at: doc.metadata.created_at,
             updated_at: doc.metadata.updated_at,
             block_count: doc.blocks.len(),
@@ -231,3 +505,157 @@ impl From<&Document> for DocumentListItem {
     }
 }
 
+/// Document in the trash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    #[serde(flatten)]
+    pub document: DocumentListItem,
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: DateTime<Utc>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::block::BlockType;
+
+    #[test]
+    fn test_add_block_assigns_first_page() {
+        let mut doc = Document::new("Pages".to_string());
+        doc.add_block(Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0));
+
+        assert_eq!(doc.blocks[0].page_id, doc.pages[0].id);
+        assert!(doc.validate().is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_page_copies_blocks() {
+        let mut doc = Document::new("Pages".to_string());
+        doc.add_block(Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0));
+        let original = doc.pages[0].id.clone();
+
+        let copy = doc.duplicate_page(&original).unwrap();
+
+        assert_eq!(doc.pages.len(), 2);
+        assert_eq!(doc.pages[1].id, copy);
+        assert_eq!(doc.blocks_on_page(&copy).count(), 1);
+        assert_ne!(doc.blocks[0].id, doc.blocks[1].id);
+    }
+
+    #[test]
+    fn test_duplicate_assigns_fresh_ids() {
+        let mut doc = Document::new("Original".to_string());
+        doc.add_block(Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0));
+        doc.add_block(Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0));
+
+        let copy = doc.duplicate();
+
+        assert_ne!(copy.id, doc.id);
+        assert_ne!(copy.pages[0].id, doc.pages[0].id);
+        assert_eq!(copy.blocks.len(), 2);
+        for (original, copied) in doc.blocks.iter().zip(&copy.blocks) {
+            assert_ne!(original.id, copied.id);
+            assert_eq!(copied.page_id, copy.pages[0].id);
+        }
+    }
+
+    #[test]
+    fn test_remove_page_removes_blocks() {
+        let mut doc = Document::new("Pages".to_string());
+        let first = doc.pages[0].id.clone();
+        assert!(doc.remove_page(&first).is_err());
+
+        let second = doc.duplicate_page(&first).unwrap();
+        let mut on_second = Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0);
+        on_second.page_id = second.clone();
+        doc.add_block(on_second);
+
+        doc.remove_page(&second).unwrap();
+        assert_eq!(doc.pages.len(), 1);
+        assert!(doc.blocks.is_empty());
+    }
+
+    #[test]
+    fn test_reorder_pages() {
+        let mut doc = Document::new("Pages".to_string());
+        let first = doc.pages[0].id.clone();
+        let second = doc.duplicate_page(&first).unwrap();
+
+        doc.reorder_pages(&[second.clone(), first.clone()]).unwrap();
+        assert_eq!(doc.pages[0].id, second);
+        assert!(doc.reorder_pages(&[first]).is_err());
+    }
+
+    #[test]
+    fn test_failed_reorder_keeps_pages() {
+        let mut doc = Document::new("Pages".to_string());
+        let first = doc.pages[0].id.clone();
+        let second = doc.duplicate_page(&first).unwrap();
+        let third = doc.duplicate_page(&second).unwrap();
+        let original: Vec<String> = doc.pages.iter().map(|p| p.id.clone()).collect();
+
+        assert!(doc
+            .reorder_pages(&[third.clone(), first.clone(), "missing".to_string()])
+            .is_err());
+        assert!(doc.reorder_pages(&[third, first.clone(), first]).is_err());
+
+        let pages: Vec<String> = doc.pages.iter().map(|p| p.id.clone()).collect();
+        assert_eq!(pages, original);
+    }
+
+    #[test]
+    fn test_landscape_geometry() {
+        let page = Page::new(PageSize::A4, PageOrientation::Landscape);
+        let geometry = page.geometry();
+
+        assert_eq!((geometry.width, geometry.height), (297.0, 210.0));
+        assert_eq!(
+            geometry.content_box,
+            PageRect {
+                x: 20.0,
+                y: 20.0,
+                width: 257.0,
+                height: 170.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_custom_geometry_keeps_stored_size() {
+        let page: Page = serde_json::from_value(serde_json::json!({
+            "id": "p1",
+            "size": { "CUSTOM": { "width": 300.0, "height": 200.0 } },
+            "orientation": "portrait",
+            "margins": { "top": 10.0, "right": 10.0, "bottom": 10.0, "left": 10.0 }
+        }))
+        .unwrap();
+        let geometry = page.geometry();
+        assert_eq!((geometry.width, geometry.height), (300.0, 200.0));
+
+        let mut tall = Page::custom(200.0, 300.0);
+        assert_eq!(tall.geometry().height, 300.0);
+        tall.orientation = PageOrientation::Landscape;
+        let geometry = tall.geometry();
+        assert_eq!((geometry.width, geometry.height), (300.0, 200.0));
+    }
+
+    #[test]
+    fn test_geometry_clamps_oversized_margins() {
+        let mut page = Page::new(PageSize::A5, PageOrientation::Portrait);
+        page.margins.left = 100.0;
+        page.margins.right = 100.0;
+
+        assert_eq!(page.geometry().content_box.width, 0.0);
+    }
+
+    #[test]
+    fn test_orphan_blocks_are_assigned() {
+        let mut doc = Document::new("Legacy".to_string());
+        doc.blocks.push(Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0));
+        assert!(doc.validate().is_err());
+
+        doc.assign_orphan_blocks();
+        assert!(doc.validate().is_ok());
+    }
+}
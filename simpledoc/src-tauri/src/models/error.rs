@@ -1,3 +1,4 @@
+use super::document::SCHEMA_VERSION;
 use thiserror::Error;
 
 /// Custom error type for the application
@@ -9,21 +10,39 @@ pub enum AppError {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
 
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+
     #[error("Document not found: {0}")]
     DocumentNotFound(String),
 
     #[error("Block not found: {0}")]
     BlockNotFound(String),
 
+    #[error("Template not found: {0}")]
+    TemplateNotFound(String),
+
+    #[error("Revision not found: {0}")]
+    RevisionNotFound(String),
+
     #[error("Invalid data: {0}")]
     InvalidData(String),
 
     #[error("Python execution error: {0}")]
     PythonError(String),
 
+    #[error("Render error: {0}")]
+    RenderError(String),
+
     #[error("Validation error: {0}")]
     ValidationError(String),
 
+    #[error("Template error: {0}")]
+    TemplateError(String),
+
+    #[error("Unsupported schema version: {0}")]
+    UnsupportedSchemaVersion(u32),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
 }
@@ -37,9 +56,18 @@ impl AppError {
         match self {
             AppError::DocumentNotFound(id) => format!("Document '{}' not found", id),
             AppError::BlockNotFound(id) => format!("Block '{}' not found", id),
+            AppError::TemplateNotFound(id) => format!("Template '{}' not found", id),
+            AppError::RevisionNotFound(id) => format!("Revision '{}' not found", id),
             AppError::InvalidData(msg) => format!("Invalid data: {}", msg),
             AppError::PythonError(msg) => format!("PDF generation failed: {}", msg),
+            AppError::RenderError(msg) => format!("Rendering failed: {}", msg),
             AppError::ValidationError(msg) => format!("Validation error: {}", msg),
+            AppError::TemplateError(msg) => format!("Template error: {}", msg),
+            AppError::UnsupportedSchemaVersion(version) => format!(
+                "The document was saved by a newer version of SimpleDoc (schema {}, supported up to {}). Please update the app.",
+                version, SCHEMA_VERSION
+            ),
+            AppError::Database(e) => format!("Storage error: {}", e),
             _ => "An unexpected error occurred".to_string(),
         }
     }
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// Locale used to format numbers, currencies and dates in a document
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Locale {
+    #[serde(rename = "ru", alias = "ru-RU")]
+    Ru,
+    #[default]
+    #[serde(rename = "en-US", alias = "en")]
+    EnUs,
+    #[serde(rename = "de", alias = "de-DE")]
+    De,
+    #[serde(rename = "fr", alias = "fr-FR")]
+    Fr,
+}
@@ -1,8 +1,20 @@
 pub mod block;
 pub mod document;
 pub mod error;
+pub mod locale;
+pub mod template;
 
-pub use block::{Block, BlockContent, BlockStyles, BlockType, ImageBlockContent, TableBlockContent, TextBlockContent};
-pub use document::{Document, DocumentListItem, Page, PageOrientation, PageSize};
+pub use block::{
+    plain_text, Block, BlockContent, BlockStyles, BlockType, BorderStyle, CellStyles, ImageBlockContent,
+    ImageFit, LineBlockContent, ListKind, Paragraph, Position, RepeatBinding, ShapeBlockContent, ShapeKind,
+    Size, Stroke, StrokeDash, TableBlockContent, TableCell, TableRow, TextAlignment, TextBlockContent,
+    TextRun, Transform, VerticalAlign,
+};
+pub use document::{
+    Document, DocumentListItem, Page, PageGeometry, PageMargins, PageOrientation, PageRect,
+    PageSize, TrashItem, PX_PER_MM, SCHEMA_VERSION,
+};
 pub use error::{AppError, Result};
+pub use locale::Locale;
+pub use template::{Template, TemplateListItem, TemplateVariable};
 
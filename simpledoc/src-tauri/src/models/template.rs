@@ -0,0 +1,68 @@
+use super::Document;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Variable a template expects in its data
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TemplateVariable {
+    /// Dotted path, as used in `{{ placeholders }}`
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Reusable document layout, stored apart from documents
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Template {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Preview image as a data URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub variables: Vec<TemplateVariable>,
+    /// Sample data for previews and unfilled variables
+    #[serde(rename = "defaultData", default)]
+    pub default_data: Value,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: DateTime<Utc>,
+    pub document: Document,
+}
+
+/// Template summary for the library view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateListItem {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    #[serde(rename = "variableCount")]
+    pub variable_count: usize,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<&Template> for TemplateListItem {
+    fn from(template: &Template) -> Self {
+        Self {
+            id: template.id.clone(),
+            name: template.name.clone(),
+            category: template.category.clone(),
+            description: template.description.clone(),
+            thumbnail: template.thumbnail.clone(),
+            variable_count: template.variables.len(),
+            updated_at: template.updated_at,
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use crate::models::{AppError, BlockContent, Document, Result};
+use crate::services::renderer::pdf::load_image_source;
+use image::ImageFormat;
+use log::{debug, info};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Scheme of references into the asset store
+pub const ASSET_SCHEME: &str = "asset://";
+
+/// Length of a hex encoded SHA-256 hash
+const HASH_LEN: usize = 64;
+
+/// Content-addressed store of binary assets such as images
+///
+/// Every asset is a file named by the SHA-256 of its bytes, so adding the
+/// same content twice keeps one copy. Documents refer to assets as
+/// `asset://<hash>`.
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    dir: PathBuf,
+}
+
+impl AssetStore {
+    /// Open the store, creating its directory
+    pub fn open(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Store bytes and return their `asset://` reference
+    pub fn add(&self, data: &[u8]) -> Result<String> {
+        let hash = format!("{:x}", Sha256::digest(data));
+        let path = self.dir.join(&hash);
+
+        if path.exists() {
+            debug!("Asset {} already stored", hash);
+        } else {
+            // Atomic write, so a crash never leaves a truncated asset
+            let temp_path = path.with_extension("tmp");
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+            drop(file);
+            fs::rename(&temp_path, &path)?;
+            debug!("Stored asset {} ({} bytes)", hash, data.len());
+        }
+
+        Ok(format!("{}{}", ASSET_SCHEME, hash))
+    }
+
+    /// Store an image given as a file path, data URI or base64 string
+    ///
+    /// Fails with `InvalidData` unless the content is a PNG or JPEG image,
+    /// the formats the renderers can embed.
+    pub fn add_image(&self, src: &str) -> Result<String> {
+        if asset_hash(src).is_some() {
+            self.path(src)?;
+            return Ok(src.to_string());
+        }
+
+        let data = load_image_source(src, None)?;
+        if !matches!(
+            image::guess_format(&data),
+            Ok(ImageFormat::Png | ImageFormat::Jpeg)
+        ) {
+            return Err(AppError::InvalidData(
+                "Unsupported image format, use PNG or JPEG".to_string(),
+            ));
+        }
+        self.add(&data)
+    }
+
+    /// Store a TrueType or OpenType font file (or collection)
+    ///
+    /// Fails with `InvalidData` if no face of the data can be read.
+    pub fn add_font(&self, data: &[u8]) -> Result<String> {
+        let faces = ttf_parser::fonts_in_collection(data).unwrap_or(1);
+        if (0..faces).all(|index| ttf_parser::Face::parse(data, index).is_err()) {
+            return Err(AppError::InvalidData("Unsupported font format".to_string()));
+        }
+        self.add(data)
+    }
+
+    /// File of an `asset://` reference, failing if it is not stored
+    pub fn path(&self, uri: &str) -> Result<PathBuf> {
+        let hash = asset_hash(uri)
+            .ok_or_else(|| AppError::InvalidData(format!("Invalid asset reference: {}", uri)))?;
+        let path = self.dir.join(hash);
+        if !path.exists() {
+            return Err(AppError::InvalidData(format!("Asset not found: {}", uri)));
+        }
+        Ok(path)
+    }
+
+    /// Contents of an `asset://` reference
+    pub fn read(&self, uri: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.path(uri)?)?)
+    }
+
+    /// Point `asset://` image sources at their files, for renderers that
+    /// only understand paths
+    pub fn resolve_paths(&self, document: &mut Document) -> Result<()> {
+        for block in &mut document.blocks {
+            if let BlockContent::Image(image) = &mut block.content {
+                if asset_hash(&image.src).is_some() {
+                    image.src = self.path(&image.src)?.to_string_lossy().to_string();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hashes of all stored assets
+    pub fn list(&self) -> Result<Vec<String>> {
+        let mut hashes = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if is_hash(&name) {
+                hashes.push(name);
+            }
+        }
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    /// Delete assets whose hash is not in `referenced`
+    ///
+    /// Assets modified within `grace` are kept, since they may have been
+    /// added to a document that is not saved yet. Returns the deleted hashes.
+    pub fn collect_garbage(
+        &self,
+        referenced: &HashSet<String>,
+        grace: Duration,
+    ) -> Result<Vec<String>> {
+        let now = SystemTime::now();
+        let mut deleted = Vec::new();
+
+        for hash in self.list()? {
+            if referenced.contains(&hash) {
+                continue;
+            }
+            let path = self.dir.join(&hash);
+            let age = fs::metadata(&path)?
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age < grace {
+                continue;
+            }
+
+            fs::remove_file(&path)?;
+            deleted.push(hash);
+        }
+
+        info!(
+            "Asset garbage collection deleted {} assets from {:?}",
+            deleted.len(),
+            self.dir
+        );
+        Ok(deleted)
+    }
+}
+
+/// Hash of an `asset://` reference, `None` for any other source
+pub fn asset_hash(uri: &str) -> Option<&str> {
+    uri.strip_prefix(ASSET_SCHEME).filter(|hash| is_hash(hash))
+}
+
+/// Add the hashes of all `asset://` references in `text` to `hashes`
+///
+/// Works on any serialized form (document JSON, revision blobs), so new
+/// places that hold references need no changes here.
+pub fn collect_references(text: &str, hashes: &mut HashSet<String>) {
+    for (start, _) in text.match_indices(ASSET_SCHEME) {
+        let rest = &text[start + ASSET_SCHEME.len()..];
+        if let Some(hash) = rest.get(..HASH_LEN).filter(|hash| is_hash(hash)) {
+            hashes.insert(hash.to_string());
+        }
+    }
+}
+
+fn is_hash(value: &str) -> bool {
+    value.len() == HASH_LEN
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use tempfile::TempDir;
+
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    #[test]
+    fn test_add_deduplicates_by_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let logo = temp_dir.path().join("logo.png");
+        fs::write(&logo, BASE64.decode(PNG).unwrap()).unwrap();
+
+        let from_file = store.add_image(&logo.to_string_lossy()).unwrap();
+        let from_data_uri = store
+            .add_image(&format!("data:image/png;base64,{}", PNG))
+            .unwrap();
+
+        assert!(from_file.starts_with(ASSET_SCHEME));
+        assert_eq!(from_file, from_data_uri);
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.read(&from_file).unwrap(), BASE64.decode(PNG).unwrap());
+        assert_eq!(store.add_image(&from_file).unwrap(), from_file);
+
+        assert!(store.add_image(&BASE64.encode(b"not an image")).is_err());
+        let gif = b"GIF89a\x01\x00\x01\x00\x00\x00\x00;";
+        assert!(matches!(
+            store.add_image(&BASE64.encode(gif)),
+            Err(AppError::InvalidData(_))
+        ));
+        assert!(store.read("asset://../../etc/passwd").is_err());
+    }
+
+    #[test]
+    fn test_collect_garbage_keeps_referenced_and_recent() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let kept = store.add(b"kept").unwrap();
+        let dropped = store.add(b"dropped").unwrap();
+
+        let mut referenced = HashSet::new();
+        collect_references(&format!(r#"{{"src":"{}"}}"#, kept), &mut referenced);
+        assert_eq!(referenced.len(), 1);
+
+        let deleted = store
+            .collect_garbage(&referenced, Duration::from_secs(3600))
+            .unwrap();
+        assert!(deleted.is_empty());
+
+        let deleted = store.collect_garbage(&referenced, Duration::ZERO).unwrap();
+        assert_eq!(deleted, vec![asset_hash(&dropped).unwrap().to_string()]);
+        assert!(store.path(&kept).is_ok());
+        assert!(store.path(&dropped).is_err());
+    }
+}
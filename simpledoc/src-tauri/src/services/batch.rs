@@ -0,0 +1,556 @@
+use crate::models::{AppError, Document, Locale, Page, Result};
+use crate::services::assets::AssetStore;
+use crate::services::font_registry::FontRegistry;
+use crate::services::renderer::{PdfRenderer, Renderer};
+use crate::services::template_engine::TemplateEngine;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use uuid::Uuid;
+
+/// Format of a mail-merge dataset
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatasetFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+impl DatasetFormat {
+    /// Guess the format from a file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "csv" => Some(DatasetFormat::Csv),
+            "json" => Some(DatasetFormat::Json),
+            "ndjson" | "jsonl" => Some(DatasetFormat::Ndjson),
+            _ => None,
+        }
+    }
+}
+
+/// Extra output built from all successful rows
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CombinedOutput {
+    /// One PDF with the pages of every row
+    Pdf,
+    /// ZIP archive of the individual PDFs
+    Zip,
+}
+
+/// Options for a batch run
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub output_dir: PathBuf,
+    /// File name pattern, e.g. `{{client.name}}_{{date}}.pdf`
+    pub file_name_pattern: Option<String>,
+    /// Maximum number of rows rendered at the same time
+    pub concurrency: usize,
+    pub combine: Option<CombinedOutput>,
+    /// Store used to resolve `asset://` images
+    pub assets: Option<AssetStore>,
+    /// Registry used to resolve and embed fonts
+    pub fonts: Option<FontRegistry>,
+}
+
+/// Progress event sent after every finished row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchProgress {
+    pub completed: usize,
+    pub total: usize,
+    /// 1-based row number in the dataset
+    pub row: usize,
+    pub success: bool,
+}
+
+/// Outcome of a single row
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchRowResult {
+    /// 1-based row number in the dataset
+    pub row: usize,
+    pub pdf_path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Report of a whole batch run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchReport {
+    pub output_dir: String,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub rows: Vec<BatchRowResult>,
+    pub combined_path: Option<String>,
+}
+
+/// Parse a dataset into one JSON object per row
+///
+/// CSV headers with dots (`client.name`) become nested objects, so the same
+/// placeholders work for every format.
+pub fn parse_dataset(content: &str, format: DatasetFormat) -> Result<Vec<Value>> {
+    match format {
+        DatasetFormat::Csv => parse_csv(content),
+        DatasetFormat::Json => match serde_json::from_str(content)? {
+            Value::Array(rows) => Ok(rows),
+            _ => Err(AppError::InvalidData(
+                "JSON dataset must be an array of objects".to_string(),
+            )),
+        },
+        DatasetFormat::Ndjson => content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(|e| AppError::InvalidData(format!("Line {}: {}", i + 1, e)))
+            })
+            .collect(),
+    }
+}
+
+fn parse_csv(content: &str) -> Result<Vec<Value>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| AppError::InvalidData(format!("Invalid CSV header: {}", e)))?
+        .clone();
+
+    reader
+        .records()
+        .map(|record| {
+            let record =
+                record.map_err(|e| AppError::InvalidData(format!("Invalid CSV row: {}", e)))?;
+            let mut row = Value::Object(Map::new());
+            for (header, field) in headers.iter().zip(record.iter()) {
+                insert_path(&mut row, header.trim(), Value::String(field.to_string()));
+            }
+            Ok(row)
+        })
+        .collect()
+}
+
+/// Insert a value at a dotted path, creating intermediate objects
+fn insert_path(target: &mut Value, path: &str, value: Value) {
+    let mut current = target;
+    let mut keys = path.split('.').peekable();
+
+    while let Some(key) = keys.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let Value::Object(map) = current else {
+            unreachable!()
+        };
+        if keys.peek().is_none() {
+            map.insert(key.to_string(), value);
+            return;
+        }
+        current = map
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Build the PDF file name for a row from the pattern
+pub fn render_file_name(pattern: &str, data: &Value, locale: Locale) -> Result<String> {
+    let name = TemplateEngine::render(pattern, data, locale)
+        .map_err(|errors| AppError::TemplateError(errors.join("; ")))?;
+    let name = sanitize_file_name(&name);
+
+    if name.is_empty() {
+        return Err(AppError::TemplateError(
+            "File name pattern produced an empty name".to_string(),
+        ));
+    }
+
+    if name.to_lowercase().ends_with(".pdf") {
+        Ok(name)
+    } else {
+        Ok(format!("{}.pdf", name))
+    }
+}
+
+/// Replace characters that are not allowed in file names
+pub fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim()
+        .trim_matches('.')
+        .to_string()
+}
+
+/// Append `_2`, `_3`, ... before the extension until the name is not taken yet
+///
+/// Names are compared case-insensitively, as on Windows and macOS.
+fn unique_file_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let (stem, extension) = match name.rfind('.') {
+        Some(index) if index > 0 => name.split_at(index),
+        _ => (name.as_str(), ""),
+    };
+    let mut counter = 2;
+
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = format!("{}_{}{}", stem, counter, extension);
+        counter += 1;
+    }
+
+    candidate
+}
+
+/// Concatenate the pages of several documents into one
+///
+/// Pages and blocks get fresh ids so they cannot collide.
+pub fn merge_documents(title: &str, documents: Vec<Document>) -> Document {
+    let mut merged = Document::new(title.to_string());
+    merged.pages.clear();
+
+    for document in documents {
+        let mut page_ids = HashMap::new();
+        for mut page in document.pages {
+            let id = Uuid::new_v4().to_string();
+            page_ids.insert(std::mem::replace(&mut page.id, id.clone()), id);
+            merged.pages.push(page);
+        }
+        for mut block in document.blocks {
+            block.id = Uuid::new_v4().to_string();
+            if let Some(page_id) = page_ids.get(&block.page_id) {
+                block.page_id = page_id.clone();
+            }
+            merged.blocks.push(block);
+        }
+    }
+
+    if merged.pages.is_empty() {
+        merged.pages.push(Page::default());
+    }
+
+    merged
+}
+
+/// Renders one PDF per dataset row
+pub struct BatchGenerator;
+
+impl BatchGenerator {
+    /// Render every row; failed rows are reported instead of aborting the batch
+    pub async fn run<F>(
+        document: Document,
+        rows: Vec<Value>,
+        options: BatchOptions,
+        on_progress: F,
+    ) -> Result<BatchReport>
+    where
+        F: Fn(BatchProgress) + Send + Sync + 'static,
+    {
+        tokio::fs::create_dir_all(&options.output_dir).await?;
+
+        let total = rows.len();
+        let title = sanitize_file_name(&document.metadata.title.replace(' ', "_"));
+        let document = Arc::new(document);
+        let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+        let mut renderer = match &options.assets {
+            Some(assets) => PdfRenderer::with_assets(assets.clone()),
+            None => PdfRenderer::new(),
+        };
+        if let Some(fonts) = &options.fonts {
+            renderer = renderer.with_fonts(fonts.clone());
+        }
+        let mut results: Vec<Option<BatchRowResult>> = vec![None; total];
+        // Resolved documents are only needed for a combined PDF
+        let keep_resolved = options.combine == Some(CombinedOutput::Pdf);
+        let mut resolved: Vec<Option<Document>> = vec![None; total];
+        let mut used_names = HashSet::new();
+        let mut tasks = JoinSet::new();
+
+        info!("Starting batch of {} rows for {}", total, document.id);
+
+        for (index, data) in rows.into_iter().enumerate() {
+            let file_name = match &options.file_name_pattern {
+                Some(pattern) => render_file_name(pattern, &data, document.metadata.locale),
+                None => Ok(format!("{}_{}.pdf", title, index + 1)),
+            };
+            let file_name = match file_name {
+                Ok(name) => unique_file_name(name, &mut used_names),
+                Err(e) => {
+                    results[index] = Some(BatchRowResult {
+                        row: index + 1,
+                        pdf_path: None,
+                        error: Some(e.to_string()),
+                    });
+                    continue;
+                }
+            };
+
+            let path = options.output_dir.join(file_name);
+            let document = Arc::clone(&document);
+            let semaphore = Arc::clone(&semaphore);
+            let renderer = renderer.clone();
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await;
+                let result =
+                    tokio::task::spawn_blocking(move || -> Result<(PathBuf, Option<Document>)> {
+                        let resolved = TemplateEngine::apply(&document, &data)?;
+                        let bytes = renderer.render(&resolved)?;
+                        std::fs::write(&path, bytes)?;
+                        Ok((path, keep_resolved.then_some(resolved)))
+                    })
+                    .await
+                    .unwrap_or_else(|e| Err(AppError::RenderError(e.to_string())));
+                (index, result)
+            });
+        }
+
+        // Rows rejected before rendering still count towards progress
+        let mut completed = 0;
+        for result in results.iter().flatten() {
+            completed += 1;
+            on_progress(BatchProgress {
+                completed,
+                total,
+                row: result.row,
+                success: false,
+            });
+        }
+
+        while let Some(joined) = tasks.join_next().await {
+            let (index, result) = joined.map_err(|e| AppError::Unknown(e.to_string()))?;
+            let row = match result {
+                Ok((path, document)) => {
+                    resolved[index] = document;
+                    BatchRowResult {
+                        row: index + 1,
+                        pdf_path: Some(path.to_string_lossy().to_string()),
+                        error: None,
+                    }
+                }
+                Err(e) => {
+                    error!("Batch row {} failed: {}", index + 1, e);
+                    BatchRowResult {
+                        row: index + 1,
+                        pdf_path: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+
+            completed += 1;
+            on_progress(BatchProgress {
+                completed,
+                total,
+                row: row.row,
+                success: row.error.is_none(),
+            });
+            results[index] = Some(row);
+        }
+
+        let rows: Vec<BatchRowResult> = results.into_iter().flatten().collect();
+        let succeeded = rows.iter().filter(|r| r.error.is_none()).count();
+
+        let combined_path = match options.combine {
+            Some(_) if succeeded == 0 => None,
+            Some(CombinedOutput::Pdf) => {
+                let name = unique_file_name(format!("{}_combined.pdf", title), &mut used_names);
+                let path = options.output_dir.join(name);
+                let merged = merge_documents(
+                    &document.metadata.title,
+                    resolved.into_iter().flatten().collect(),
+                );
+                let bytes = tokio::task::spawn_blocking(move || renderer.render(&merged))
+                    .await
+                    .map_err(|e| AppError::RenderError(e.to_string()))??;
+                tokio::fs::write(&path, bytes).await?;
+                Some(path)
+            }
+            Some(CombinedOutput::Zip) => {
+                let name = unique_file_name(format!("{}.zip", title), &mut used_names);
+                let path = options.output_dir.join(name);
+                let files: Vec<PathBuf> = rows
+                    .iter()
+                    .filter_map(|r| r.pdf_path.as_ref().map(PathBuf::from))
+                    .collect();
+                let zip_path = path.clone();
+                tokio::task::spawn_blocking(move || write_zip(&zip_path, &files))
+                    .await
+                    .map_err(|e| AppError::Unknown(e.to_string()))??;
+                Some(path)
+            }
+            None => None,
+        };
+
+        info!("Batch finished: {} of {} rows succeeded", succeeded, total);
+
+        Ok(BatchReport {
+            output_dir: options.output_dir.to_string_lossy().to_string(),
+            total,
+            succeeded,
+            failed: total - succeeded,
+            rows,
+            combined_path: combined_path.map(|p| p.to_string_lossy().to_string()),
+        })
+    }
+}
+
+/// Pack files into a ZIP archive under their file names
+fn write_zip(path: &Path, files: &[PathBuf]) -> Result<()> {
+    let zip_error = |e: zip::result::ZipError| AppError::Io(std::io::Error::other(e));
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default();
+
+    for file in files {
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        zip.start_file(name, options).map_err(zip_error)?;
+        zip.write_all(&std::fs::read(file)?)?;
+    }
+
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Block;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    fn template_document() -> Document {
+        let mut document = Document::new("Proposal".to_string());
+        document.add_block(Block::text_for_test("Dear {{ client.name }}"));
+        document
+    }
+
+    #[test]
+    fn test_parse_datasets() {
+        let rows = parse_dataset(
+            "client.name,amount\nACME,100\nGlobex,200\n",
+            DatasetFormat::Csv,
+        )
+        .unwrap();
+        assert_eq!(
+            rows[1],
+            json!({ "client": { "name": "Globex" }, "amount": "200" })
+        );
+
+        let rows = parse_dataset("{\"a\":1}\n\n{\"a\":2}\n", DatasetFormat::Ndjson).unwrap();
+        assert_eq!(rows.len(), 2);
+
+        assert!(parse_dataset("{\"a\":1}", DatasetFormat::Json).is_err());
+        assert_eq!(
+            DatasetFormat::from_path(Path::new("clients.JSONL")),
+            Some(DatasetFormat::Ndjson)
+        );
+    }
+
+    #[test]
+    fn test_render_file_name() {
+        let data = json!({ "client": { "name": "ACME/EU" }, "date": "2024-05-01" });
+        assert_eq!(
+            render_file_name("{{client.name}}_{{date}}", &data, Locale::EnUs).unwrap(),
+            "ACME_EU_2024-05-01.pdf"
+        );
+        assert!(render_file_name("{{missing}}", &data, Locale::EnUs).is_err());
+
+        let mut used = HashSet::new();
+        assert_eq!(unique_file_name("a.pdf".to_string(), &mut used), "a.pdf");
+        assert_eq!(unique_file_name("a.pdf".to_string(), &mut used), "a_2.pdf");
+        assert_eq!(unique_file_name("A.PDF".to_string(), &mut used), "A_3.PDF");
+    }
+
+    #[test]
+    fn test_merge_documents() {
+        let merged = merge_documents("All", vec![template_document(), template_document()]);
+        assert_eq!(merged.pages.len(), 2);
+        assert_eq!(merged.blocks.len(), 2);
+        assert_ne!(merged.blocks[0].page_id, merged.blocks[1].page_id);
+        assert!(merged.validate().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run_batch_reports_failed_rows() {
+        let temp_dir = TempDir::new().unwrap();
+        let rows = vec![
+            json!({ "client": { "name": "ACME" } }),
+            json!({ "client": {} }),
+            json!({ "client": { "name": "Globex" } }),
+        ];
+        let progress = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&progress);
+
+        let report = BatchGenerator::run(
+            template_document(),
+            rows,
+            BatchOptions {
+                output_dir: temp_dir.path().to_path_buf(),
+                file_name_pattern: Some("{{ client.name | default:'unknown' }}".to_string()),
+                concurrency: 2,
+                combine: Some(CombinedOutput::Zip),
+                assets: None,
+                fonts: None,
+            },
+            move |_| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.failed, 1);
+        assert!(report.rows[1]
+            .error
+            .as_ref()
+            .unwrap()
+            .contains("client.name"));
+        assert!(temp_dir.path().join("ACME.pdf").exists());
+        assert!(Path::new(report.combined_path.as_ref().unwrap()).exists());
+        assert_eq!(progress.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_combined_pdf_does_not_overwrite_rows() {
+        let temp_dir = TempDir::new().unwrap();
+        let rows = vec![json!({ "client": { "name": "ACME" } })];
+
+        let report = BatchGenerator::run(
+            template_document(),
+            rows,
+            BatchOptions {
+                output_dir: temp_dir.path().to_path_buf(),
+                file_name_pattern: Some("Proposal_combined.PDF".to_string()),
+                concurrency: 1,
+                combine: Some(CombinedOutput::Pdf),
+                assets: None,
+                fonts: None,
+            },
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        let row_path = report.rows[0].pdf_path.clone().unwrap();
+        let combined_path = report.combined_path.unwrap();
+        assert!(row_path.ends_with("Proposal_combined.PDF"));
+        assert!(combined_path.ends_with("Proposal_combined_2.pdf"));
+        assert!(Path::new(&row_path).exists());
+        assert!(Path::new(&combined_path).exists());
+    }
+}
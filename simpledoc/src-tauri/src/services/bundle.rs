@@ -0,0 +1,464 @@
+use crate::models::{AppError, BlockContent, Document, Result, Template};
+use crate::services::assets::AssetStore;
+use crate::services::font_registry::{font_extension, FontRegistry};
+use crate::services::migration;
+use crate::services::renderer::pdf::load_image_source;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// File extension of document bundles
+pub const BUNDLE_EXTENSION: &str = "sdoc";
+
+/// Marks an archive as a document bundle
+const BUNDLE_FORMAT: &str = "simpledoc-bundle";
+
+/// Bumped whenever the bundle layout changes
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DOCUMENT_FILE: &str = "document.json";
+const TEMPLATE_FILE: &str = "template.json";
+const ASSETS_DIR: &str = "assets";
+
+/// Kind of a bundled file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AssetKind {
+    Image,
+    Font,
+}
+
+/// Bundled asset with its checksum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Path inside the archive, as referenced from `document.json`
+    pub path: String,
+    pub kind: AssetKind,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// `manifest.json` of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(rename = "documentId")]
+    pub document_id: String,
+    pub title: String,
+    /// Checksum of `document.json`
+    #[serde(rename = "documentSha256")]
+    pub document_sha256: String,
+    /// Checksum of `template.json`, present in template bundles
+    #[serde(
+        rename = "templateSha256",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub template_sha256: Option<String>,
+    pub assets: Vec<ManifestEntry>,
+}
+
+/// Whether a file is a bundle (a ZIP archive) rather than bare JSON
+pub fn is_bundle(path: &Path) -> Result<bool> {
+    let mut magic = [0u8; 4];
+    let read = fs::File::open(path)?.read(&mut magic)?;
+    Ok(read == magic.len() && magic == *b"PK\x03\x04")
+}
+
+/// Write a document and every image it references to a bundle
+///
+/// Image sources are rewritten to their path inside the archive. Remote
+/// (`http(s)://`) images are left as they are; `asset://` references are
+/// read from `store`. Fonts of text blocks that are imported or in the user
+/// fonts folder are bundled too; system fonts are expected to be installed.
+pub fn write_bundle(
+    document: &Document,
+    path: &Path,
+    store: &AssetStore,
+    fonts: &FontRegistry,
+) -> Result<()> {
+    write_archive(document, None, path, store, fonts)
+}
+
+/// Write a template to a bundle
+///
+/// The document and its assets are bundled as by `write_bundle`; the other
+/// template settings go to `template.json`.
+pub fn write_template_bundle(
+    template: &Template,
+    path: &Path,
+    store: &AssetStore,
+    fonts: &FontRegistry,
+) -> Result<()> {
+    let mut settings = serde_json::to_value(template)?;
+    if let Value::Object(map) = &mut settings {
+        map.remove("document");
+    }
+    write_archive(&template.document, Some(&settings), path, store, fonts)
+}
+
+fn write_archive(
+    document: &Document,
+    template: Option<&Value>,
+    path: &Path,
+    store: &AssetStore,
+    fonts: &FontRegistry,
+) -> Result<()> {
+    let mut document = document.clone();
+    let mut assets: Vec<(ManifestEntry, Vec<u8>)> = Vec::new();
+    let mut bundled: HashMap<String, String> = HashMap::new();
+
+    for block in &mut document.blocks {
+        let BlockContent::Image(image) = &mut block.content else {
+            continue;
+        };
+        if image.src.is_empty() || is_remote(&image.src) {
+            continue;
+        }
+        if let Some(bundled_path) = bundled.get(&image.src) {
+            image.src = bundled_path.clone();
+            continue;
+        }
+
+        let data = load_image_source(&image.src, Some(store))
+            .map_err(|_| AppError::InvalidData(format!("Image not found: {}", image.src)))?;
+        let sha256 = sha256_hex(&data);
+        let extension = image::guess_format(&data)
+            .ok()
+            .and_then(|format| format.extensions_str().first().copied())
+            .unwrap_or("bin");
+        let bundled_path = format!("{}/{}.{}", ASSETS_DIR, sha256, extension);
+
+        if !assets.iter().any(|(entry, _)| entry.path == bundled_path) {
+            let entry = ManifestEntry {
+                path: bundled_path.clone(),
+                kind: AssetKind::Image,
+                sha256,
+                size: data.len() as u64,
+            };
+            assets.push((entry, data));
+        }
+        let src = std::mem::replace(&mut image.src, bundled_path.clone());
+        bundled.insert(src, bundled_path);
+    }
+
+    for data in fonts.portable_files(text_styles(&document)) {
+        let sha256 = sha256_hex(&data);
+        let entry = ManifestEntry {
+            path: format!("{}/{}.{}", ASSETS_DIR, sha256, font_extension(&data)),
+            kind: AssetKind::Font,
+            sha256,
+            size: data.len() as u64,
+        };
+        assets.push((entry, data));
+    }
+
+    let json = serde_json::to_vec_pretty(&document)?;
+    let template = template.map(serde_json::to_vec_pretty).transpose()?;
+    let manifest = Manifest {
+        format: BUNDLE_FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        created_at: Utc::now(),
+        document_id: document.id.clone(),
+        title: document.metadata.title.clone(),
+        document_sha256: sha256_hex(&json),
+        template_sha256: template.as_deref().map(sha256_hex),
+        assets: assets.iter().map(|(entry, _)| entry.clone()).collect(),
+    };
+
+    let zip_error = |e: zip::result::ZipError| AppError::Io(std::io::Error::other(e));
+    let mut zip = ZipWriter::new(fs::File::create(path)?);
+    let options = SimpleFileOptions::default();
+    // Images are compressed already, fonts are not
+    let stored = options.compression_method(CompressionMethod::Stored);
+
+    zip.start_file(MANIFEST_FILE, options).map_err(zip_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.start_file(DOCUMENT_FILE, options).map_err(zip_error)?;
+    zip.write_all(&json)?;
+    if let Some(template) = &template {
+        zip.start_file(TEMPLATE_FILE, options).map_err(zip_error)?;
+        zip.write_all(template)?;
+    }
+    for (entry, data) in &assets {
+        let options = match entry.kind {
+            AssetKind::Image => stored,
+            AssetKind::Font => options,
+        };
+        zip.start_file(entry.path.as_str(), options)
+            .map_err(zip_error)?;
+        zip.write_all(data)?;
+    }
+    zip.finish().map_err(zip_error)?;
+
+    info!(
+        "Bundled document {} with {} assets into {:?}",
+        document.id,
+        assets.len(),
+        path
+    );
+    Ok(())
+}
+
+/// Read a bundle, adding its assets to `store`
+///
+/// Every file is checked against the manifest checksums, and image sources
+/// are relinked to `asset://` references.
+pub fn read_bundle(path: &Path, store: &AssetStore) -> Result<Document> {
+    let (mut archive, manifest) = open_bundle(path)?;
+    read_document(&mut archive, &manifest, store)
+}
+
+/// Read a template bundle, adding its assets to `store`
+///
+/// Returns the template settings with the relinked document under `document`.
+pub fn read_template_bundle(path: &Path, store: &AssetStore) -> Result<Value> {
+    let (mut archive, manifest) = open_bundle(path)?;
+    let not_template = || AppError::InvalidData(format!("{:?} is not a template bundle", path));
+    let sha256 = manifest
+        .template_sha256
+        .as_deref()
+        .ok_or_else(not_template)?;
+
+    let json = read_entry(&mut archive, TEMPLATE_FILE)?;
+    verify(TEMPLATE_FILE, &json, sha256, None)?;
+    let mut template: Value = serde_json::from_slice(&json)?;
+    let document = read_document(&mut archive, &manifest, store)?;
+    template
+        .as_object_mut()
+        .ok_or_else(not_template)?
+        .insert("document".to_string(), serde_json::to_value(document)?);
+    Ok(template)
+}
+
+/// Open a bundle and check its manifest
+fn open_bundle(path: &Path) -> Result<(ZipArchive<fs::File>, Manifest)> {
+    let mut archive = ZipArchive::new(fs::File::open(path)?)
+        .map_err(|e| AppError::InvalidData(format!("Invalid bundle: {}", e)))?;
+
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_FILE)?)?;
+    if manifest.format != BUNDLE_FORMAT {
+        return Err(AppError::InvalidData(format!(
+            "{:?} is not a document bundle",
+            path
+        )));
+    }
+    if manifest.version > FORMAT_VERSION {
+        return Err(AppError::InvalidData(format!(
+            "Bundle format version {} is newer than supported version {}",
+            manifest.version, FORMAT_VERSION
+        )));
+    }
+    Ok((archive, manifest))
+}
+
+/// Read the document of an open bundle, relinking its images to `store`
+fn read_document(
+    archive: &mut ZipArchive<fs::File>,
+    manifest: &Manifest,
+    store: &AssetStore,
+) -> Result<Document> {
+    let json = read_entry(archive, DOCUMENT_FILE)?;
+    verify(DOCUMENT_FILE, &json, &manifest.document_sha256, None)?;
+    let mut document = migration::document_from_value(serde_json::from_slice(&json)?)?;
+
+    let mut local: HashMap<&str, String> = HashMap::new();
+    for entry in &manifest.assets {
+        let data = read_entry(archive, &entry.path)?;
+        verify(&entry.path, &data, &entry.sha256, Some(entry.size))?;
+        local.insert(&entry.path, store.add(&data)?);
+    }
+
+    for block in &mut document.blocks {
+        if let BlockContent::Image(image) = &mut block.content {
+            if let Some(local_path) = local.get(image.src.as_str()) {
+                image.src = local_path.clone();
+            }
+        }
+    }
+    Ok(document)
+}
+
+/// Family, weight and style of all text in the document
+fn text_styles(document: &Document) -> Vec<(&str, u16, bool)> {
+    let mut styles = Vec::new();
+    for block in &document.blocks {
+        let BlockContent::Text(text) = &block.content else {
+            continue;
+        };
+        styles.push((text.font_family.as_str(), text.font_weight, false));
+        for run in text.paragraphs.iter().flat_map(|p| p.runs.iter()) {
+            let weight = if run.bold { 700 } else { text.font_weight };
+            styles.push((text.font_family.as_str(), weight, run.italic));
+        }
+    }
+    styles.sort();
+    styles.dedup();
+    styles
+}
+
+fn is_remote(src: &str) -> bool {
+    src.starts_with("http://") || src.starts_with("https://")
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>> {
+    let mut file = archive
+        .by_name(name)
+        .map_err(|_| AppError::InvalidData(format!("Bundle is missing {}", name)))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Check a bundled file against its manifest entry
+fn verify(name: &str, data: &[u8], sha256: &str, size: Option<u64>) -> Result<()> {
+    if sha256_hex(data) != sha256 || size.is_some_and(|size| size != data.len() as u64) {
+        return Err(AppError::InvalidData(format!(
+            "Checksum mismatch for {} in bundle",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Block, BlockType, Position, Size};
+    use crate::services::assets::ASSET_SCHEME;
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use tempfile::TempDir;
+
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn image_document(srcs: &[String]) -> Document {
+        let mut document = Document::new("Brochure".to_string());
+        for src in srcs {
+            let mut block = Block::new(
+                BlockType::Image,
+                Position { x: 0.0, y: 0.0 },
+                Size {
+                    width: 50.0,
+                    height: 50.0,
+                },
+            );
+            if let BlockContent::Image(image) = &mut block.content {
+                image.src = src.clone();
+            }
+            document.add_block(block);
+        }
+        document
+    }
+
+    fn image_src(document: &Document, index: usize) -> &str {
+        match &document.blocks[index].content {
+            BlockContent::Image(image) => &image.src,
+            _ => panic!("not an image block"),
+        }
+    }
+
+    #[test]
+    fn test_bundle_round_trip_relinks_assets() {
+        let temp_dir = TempDir::new().unwrap();
+        let logo = temp_dir.path().join("logo.png");
+        fs::write(&logo, BASE64.decode(PNG).unwrap()).unwrap();
+
+        let document = image_document(&[
+            logo.to_string_lossy().to_string(),
+            format!("data:image/png;base64,{}", PNG),
+            "https://example.com/banner.png".to_string(),
+        ]);
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let bundle = temp_dir.path().join("brochure.sdoc");
+        write_bundle(&document, &bundle, &assets, &FontRegistry::new()).unwrap();
+        assert!(is_bundle(&bundle).unwrap());
+
+        // The file travels; the original image does not
+        fs::remove_file(&logo).unwrap();
+
+        let imported = read_bundle(&bundle, &assets).unwrap();
+        assert_eq!(imported.metadata.title, "Brochure");
+
+        // Both local images have the same bytes, so they share one asset
+        let local = image_src(&imported, 0);
+        assert_eq!(local, image_src(&imported, 1));
+        assert!(local.starts_with(ASSET_SCHEME));
+        assert_eq!(assets.read(local).unwrap(), BASE64.decode(PNG).unwrap());
+        assert_eq!(assets.list().unwrap().len(), 1);
+
+        // Stored assets bundle again
+        let rebundled = temp_dir.path().join("copy.sdoc");
+        write_bundle(&imported, &rebundled, &assets, &FontRegistry::new()).unwrap();
+        assert_eq!(read_bundle(&rebundled, &assets).unwrap().blocks.len(), 3);
+        assert_eq!(image_src(&imported, 2), "https://example.com/banner.png");
+    }
+
+    #[test]
+    fn test_missing_image_fails_export() {
+        let temp_dir = TempDir::new().unwrap();
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let document = image_document(&["/nonexistent/logo.png".to_string()]);
+        let broken = temp_dir.path().join("broken.sdoc");
+        assert!(write_bundle(&document, &broken, &assets, &FontRegistry::new()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_tampered_and_newer_bundles() {
+        let temp_dir = TempDir::new().unwrap();
+        let write = |name: &str, manifest: &Manifest, document: &[u8]| {
+            let path = temp_dir.path().join(name);
+            let mut zip = ZipWriter::new(fs::File::create(&path).unwrap());
+            let options = SimpleFileOptions::default();
+            zip.start_file(MANIFEST_FILE, options).unwrap();
+            zip.write_all(&serde_json::to_vec(manifest).unwrap())
+                .unwrap();
+            zip.start_file(DOCUMENT_FILE, options).unwrap();
+            zip.write_all(document).unwrap();
+            zip.finish().unwrap();
+            path
+        };
+
+        let json = serde_json::to_vec(&Document::new("Offer".to_string())).unwrap();
+        let mut manifest = Manifest {
+            format: BUNDLE_FORMAT.to_string(),
+            version: FORMAT_VERSION,
+            created_at: Utc::now(),
+            document_id: "offer".to_string(),
+            title: "Offer".to_string(),
+            document_sha256: sha256_hex(&json),
+            template_sha256: None,
+            assets: Vec::new(),
+        };
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+
+        let valid = write("valid.sdoc", &manifest, &json);
+        assert!(read_bundle(&valid, &assets).is_ok());
+
+        let tampered = write("tampered.sdoc", &manifest, b"{}");
+        assert!(read_bundle(&tampered, &assets).is_err());
+
+        manifest.version = FORMAT_VERSION + 1;
+        let newer = write("newer.sdoc", &manifest, &json);
+        assert!(read_bundle(&newer, &assets)
+            .unwrap_err()
+            .to_string()
+            .contains("newer"));
+    }
+}
@@ -0,0 +1,792 @@
+use crate::models::{
+    Block, BlockContent, BlockStyles, BlockType, BorderStyle, Document, Page, Position, Size,
+    TableRow, TextBlockContent,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::{ChangeTag, TextDiff};
+use std::collections::{BTreeSet, HashMap};
+use uuid::Uuid;
+
+/// Distance (in pixels) below which positions and sizes count as unchanged
+const EPSILON: f64 = 0.01;
+
+/// Redline colors
+const ADDED_COLOR: &str = "#2e7d32";
+const REMOVED_COLOR: &str = "#c62828";
+const MOVED_COLOR: &str = "#1565c0";
+const CHANGED_COLOR: &str = "#ef6c00";
+
+/// Font size and spacing of the change summary pages (in pixels)
+const SUMMARY_FONT_SIZE: f64 = 12.0;
+const SUMMARY_GAP: f64 = 8.0;
+
+/// Kind of a word-level change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WordOp {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// Run of text with the same change kind
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WordChange {
+    pub op: WordOp,
+    pub text: String,
+}
+
+/// Word-level changes of one table cell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CellChange {
+    pub row: usize,
+    pub column: usize,
+    pub words: Vec<WordChange>,
+}
+
+/// One change of a block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BlockChange {
+    Added,
+    Removed,
+    /// New position, or a different page
+    Moved {
+        from: Position,
+        to: Position,
+        #[serde(rename = "fromPageId", skip_serializing_if = "Option::is_none")]
+        from_page_id: Option<String>,
+    },
+    Resized {
+        from: Size,
+        to: Size,
+    },
+    /// Block styles, text formatting or cell styles
+    Restyled {
+        fields: Vec<String>,
+    },
+    /// Word-level diff of `TextBlockContent.text`
+    Text {
+        words: Vec<WordChange>,
+    },
+    /// Word-level diffs of the table cells that changed
+    Cells {
+        cells: Vec<CellChange>,
+    },
+    /// Other properties, e.g. image source, column widths or visibility rule
+    Changed {
+        fields: Vec<String>,
+    },
+}
+
+/// All changes of one block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockDiff {
+    #[serde(rename = "blockId")]
+    pub block_id: String,
+    /// Page in the new document, or the old one for removed blocks
+    #[serde(rename = "pageId")]
+    pub page_id: String,
+    pub changes: Vec<BlockChange>,
+}
+
+/// One change of a page
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PageChange {
+    Added {
+        #[serde(rename = "pageId")]
+        page_id: String,
+        index: usize,
+    },
+    Removed {
+        #[serde(rename = "pageId")]
+        page_id: String,
+        index: usize,
+    },
+    Reordered {
+        #[serde(rename = "pageId")]
+        page_id: String,
+        from: usize,
+        to: usize,
+    },
+    /// Size, orientation, margins or background
+    Changed {
+        #[serde(rename = "pageId")]
+        page_id: String,
+        fields: Vec<String>,
+    },
+}
+
+/// Structural difference between two versions of a document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocumentDiff {
+    /// Changed metadata fields, e.g. `title`
+    pub metadata: Vec<String>,
+    pub pages: Vec<PageChange>,
+    pub blocks: Vec<BlockDiff>,
+}
+
+impl DocumentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.pages.is_empty() && self.blocks.is_empty()
+    }
+}
+
+/// Compare two versions of a document, matching pages and blocks by id
+pub fn diff_documents(old: &Document, new: &Document) -> DocumentDiff {
+    let mut metadata = changed_fields(&old.metadata, &new.metadata, "");
+    metadata.retain(|field| field != "updatedAt");
+
+    DocumentDiff {
+        metadata,
+        pages: diff_pages(&old.pages, &new.pages),
+        blocks: diff_blocks(&old.blocks, &new.blocks),
+    }
+}
+
+fn diff_pages(old: &[Page], new: &[Page]) -> Vec<PageChange> {
+    let old_index: HashMap<&str, usize> = old
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.id.as_str(), i))
+        .collect();
+    let new_index: HashMap<&str, usize> = new
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.id.as_str(), i))
+        .collect();
+
+    let mut changes = Vec::new();
+    for (index, page) in old.iter().enumerate() {
+        if !new_index.contains_key(page.id.as_str()) {
+            changes.push(PageChange::Removed {
+                page_id: page.id.clone(),
+                index,
+            });
+        }
+    }
+
+    // Order among the pages both versions share, so insertions alone are not moves
+    let kept_old: Vec<&str> = old
+        .iter()
+        .map(|p| p.id.as_str())
+        .filter(|id| new_index.contains_key(id))
+        .collect();
+    let kept_new: Vec<&str> = new
+        .iter()
+        .map(|p| p.id.as_str())
+        .filter(|id| old_index.contains_key(id))
+        .collect();
+
+    for (index, page) in new.iter().enumerate() {
+        let Some(&from) = old_index.get(page.id.as_str()) else {
+            changes.push(PageChange::Added {
+                page_id: page.id.clone(),
+                index,
+            });
+            continue;
+        };
+
+        let moved = kept_old.iter().position(|id| *id == page.id)
+            != kept_new.iter().position(|id| *id == page.id);
+        if moved {
+            changes.push(PageChange::Reordered {
+                page_id: page.id.clone(),
+                from,
+                to: index,
+            });
+        }
+
+        let fields = changed_fields(&old[from], page, "");
+        if !fields.is_empty() {
+            changes.push(PageChange::Changed {
+                page_id: page.id.clone(),
+                fields,
+            });
+        }
+    }
+
+    changes
+}
+
+fn diff_blocks(old: &[Block], new: &[Block]) -> Vec<BlockDiff> {
+    let old_by_id: HashMap<&str, &Block> = old.iter().map(|b| (b.id.as_str(), b)).collect();
+    let new_ids: BTreeSet<&str> = new.iter().map(|b| b.id.as_str()).collect();
+
+    let mut diffs = Vec::new();
+    for block in new {
+        let changes = match old_by_id.get(block.id.as_str()) {
+            Some(previous) => diff_block(previous, block),
+            None => vec![BlockChange::Added],
+        };
+        if !changes.is_empty() {
+            diffs.push(BlockDiff {
+                block_id: block.id.clone(),
+                page_id: block.page_id.clone(),
+                changes,
+            });
+        }
+    }
+
+    for block in old.iter().filter(|b| !new_ids.contains(b.id.as_str())) {
+        diffs.push(BlockDiff {
+            block_id: block.id.clone(),
+            page_id: block.page_id.clone(),
+            changes: vec![BlockChange::Removed],
+        });
+    }
+
+    diffs
+}
+
+fn diff_block(old: &Block, new: &Block) -> Vec<BlockChange> {
+    let mut changes = Vec::new();
+
+    let moved = (old.position.x - new.position.x).abs() > EPSILON
+        || (old.position.y - new.position.y).abs() > EPSILON;
+    if moved || old.page_id != new.page_id {
+        changes.push(BlockChange::Moved {
+            from: old.position.clone(),
+            to: new.position.clone(),
+            from_page_id: (old.page_id != new.page_id).then(|| old.page_id.clone()),
+        });
+    }
+
+    if (old.size.width - new.size.width).abs() > EPSILON
+        || (old.size.height - new.size.height).abs() > EPSILON
+    {
+        changes.push(BlockChange::Resized {
+            from: old.size.clone(),
+            to: new.size.clone(),
+        });
+    }
+
+    let mut restyled = changed_fields(&old.styles, &new.styles, "styles");
+    let mut fields = Vec::new();
+    if old.block_type() != new.block_type() {
+        fields.push("type".to_string());
+    }
+    for (name, old_value, new_value) in [
+        ("transform", json(&old.transform), json(&new.transform)),
+        ("zIndex", json(&old.z_index), json(&new.z_index)),
+        ("locked", json(&old.locked), json(&new.locked)),
+        ("visibleIf", json(&old.visible_if), json(&new.visible_if)),
+        ("repeat", json(&old.repeat), json(&new.repeat)),
+    ] {
+        if old_value != new_value {
+            fields.push(name.to_string());
+        }
+    }
+
+    match (&old.content, &new.content) {
+        (BlockContent::Text(before), BlockContent::Text(after)) => {
+            restyled.extend(text_style_changes(before, after));
+            if before.text != after.text {
+                changes.push(BlockChange::Text {
+                    words: diff_words(&before.text, &after.text),
+                });
+            }
+        }
+        (BlockContent::Table(before), BlockContent::Table(after)) => {
+            let (cells, cell_styles) = diff_cells(&before.rows, &after.rows);
+            restyled.extend(cell_styles);
+            if !cells.is_empty() {
+                changes.push(BlockChange::Cells { cells });
+            }
+            if json(&before.column_widths) != json(&after.column_widths) {
+                fields.push("columnWidths".to_string());
+            }
+        }
+        (before, after) => fields.extend(changed_fields(before, after, "content")),
+    }
+
+    if !restyled.is_empty() {
+        changes.push(BlockChange::Restyled { fields: restyled });
+    }
+    if !fields.is_empty() {
+        changes.push(BlockChange::Changed { fields });
+    }
+    changes
+}
+
+/// Changed text styles; rich text counts as restyled when its formatting
+/// changed, while text edits show up in the word diff
+fn text_style_changes(old: &TextBlockContent, new: &TextBlockContent) -> Vec<String> {
+    let strip = |content: &TextBlockContent| {
+        let mut content = content.clone();
+        content.text.clear();
+        for run in content
+            .paragraphs
+            .iter_mut()
+            .flat_map(|p| p.runs.iter_mut())
+        {
+            run.text.clear();
+        }
+        json(&content)
+    };
+    changed_fields(&strip(old), &strip(new), "")
+}
+
+/// Word diffs of changed cells and the names of changed cell styles
+fn diff_cells(old: &[TableRow], new: &[TableRow]) -> (Vec<CellChange>, Vec<String>) {
+    let mut cells = Vec::new();
+    let mut styles = Vec::new();
+
+    for row in 0..old.len().max(new.len()) {
+        let old_cells = old.get(row).map(|r| r.cells.as_slice()).unwrap_or_default();
+        let new_cells = new.get(row).map(|r| r.cells.as_slice()).unwrap_or_default();
+
+        for column in 0..old_cells.len().max(new_cells.len()) {
+            let before = old_cells.get(column);
+            let after = new_cells.get(column);
+            let old_text = before.map_or("", |c| c.content.as_str());
+            let new_text = after.map_or("", |c| c.content.as_str());
+            if old_text != new_text {
+                cells.push(CellChange {
+                    row,
+                    column,
+                    words: diff_words(old_text, new_text),
+                });
+            }
+
+            let prefix = format!("cells[{}][{}]", row, column);
+            styles.extend(changed_fields(
+                &before.and_then(|c| c.styles.as_ref()),
+                &after.and_then(|c| c.styles.as_ref()),
+                &prefix,
+            ));
+        }
+    }
+
+    (cells, styles)
+}
+
+/// Word-level diff
+///
+/// Neighbouring changed words are merged into one deletion and one
+/// insertion, so `bare metal` → `managed Kubernetes` reads as one edit.
+pub fn diff_words(old: &str, new: &str) -> Vec<WordChange> {
+    let diff = TextDiff::from_words(old, new);
+    let changes: Vec<_> = diff.iter_all_changes().collect();
+
+    let mut words: Vec<WordChange> = Vec::new();
+    let mut deleted = String::new();
+    let mut inserted = String::new();
+    let push = |words: &mut Vec<WordChange>, op: WordOp, text: &str| {
+        if text.is_empty() {
+            return;
+        }
+        match words.last_mut() {
+            Some(last) if last.op == op => last.text.push_str(text),
+            _ => words.push(WordChange {
+                op,
+                text: text.to_string(),
+            }),
+        }
+    };
+
+    for (index, change) in changes.iter().enumerate() {
+        let value = change.value();
+        let is_gap = change.tag() == ChangeTag::Equal && value.trim().is_empty();
+        let inside_edit = is_gap
+            && (!deleted.is_empty() || !inserted.is_empty())
+            && changes[index + 1..]
+                .iter()
+                .find(|c| !(c.tag() == ChangeTag::Equal && c.value().trim().is_empty()))
+                .is_some_and(|c| c.tag() != ChangeTag::Equal);
+
+        match change.tag() {
+            ChangeTag::Delete => deleted.push_str(value),
+            ChangeTag::Insert => inserted.push_str(value),
+            ChangeTag::Equal if inside_edit => {
+                deleted.push_str(value);
+                inserted.push_str(value);
+            }
+            ChangeTag::Equal => {
+                push(&mut words, WordOp::Delete, &std::mem::take(&mut deleted));
+                push(&mut words, WordOp::Insert, &std::mem::take(&mut inserted));
+                push(&mut words, WordOp::Equal, value);
+            }
+        }
+    }
+    push(&mut words, WordOp::Delete, &deleted);
+    push(&mut words, WordOp::Insert, &inserted);
+    words
+}
+
+/// Text with deletions as `[-old-]` and insertions as `{+new+}`
+pub fn format_words(words: &[WordChange]) -> String {
+    words
+        .iter()
+        .map(|word| match word.op {
+            WordOp::Equal => word.text.clone(),
+            WordOp::Delete => format!("[-{}-]", word.text),
+            WordOp::Insert => format!("{{+{}+}}", word.text),
+        })
+        .collect()
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Value {
+    serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Names of the top-level JSON fields that differ, prefixed with `prefix`
+fn changed_fields<T: Serialize + ?Sized>(old: &T, new: &T, prefix: &str) -> Vec<String> {
+    let name = |key: &str| {
+        if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        }
+    };
+
+    match (json(old), json(new)) {
+        (a, b) if a == b => Vec::new(),
+        (Value::Object(a), Value::Object(b)) => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            keys.into_iter()
+                .filter(|key| a.get(*key) != b.get(*key))
+                .map(|key| name(key))
+                .collect()
+        }
+        (Value::Object(map), Value::Null) | (Value::Null, Value::Object(map)) => {
+            map.keys().map(|key| name(key)).collect()
+        }
+        _ if prefix.is_empty() => vec!["value".to_string()],
+        _ => vec![prefix.to_string()],
+    }
+}
+
+/// Build a document showing the diff on top of the new version
+///
+/// Added blocks are outlined green, removed ones are drawn faded in red at
+/// their old place, moved ones get a blue outline of where they were and
+/// otherwise changed ones an orange outline. Pages listing the text changes
+/// and page changes are appended.
+pub fn redline_document(old: &Document, new: &Document, diff: &DocumentDiff) -> Document {
+    let mut redline = new.clone();
+    redline.metadata.title = format!("Redline: {}", new.metadata.title);
+    let page_ids: BTreeSet<String> = redline.pages.iter().map(|p| p.id.clone()).collect();
+    let old_blocks: HashMap<&str, &Block> = old.blocks.iter().map(|b| (b.id.as_str(), b)).collect();
+
+    let mut summary = Vec::new();
+    for change in &diff.pages {
+        summary.push(match change {
+            PageChange::Added { index, .. } => format!("Page {} added", index + 1),
+            PageChange::Removed { index, .. } => format!("Page {} removed", index + 1),
+            PageChange::Reordered { from, to, .. } => {
+                format!("Page {} moved to position {}", from + 1, to + 1)
+            }
+            PageChange::Changed { page_id, fields } => format!(
+                "Page {} changed: {}",
+                page_number(&redline, page_id),
+                fields.join(", ")
+            ),
+        });
+    }
+
+    for block_diff in &diff.blocks {
+        let label = format!("Page {}", page_number(&redline, &block_diff.page_id));
+        for change in &block_diff.changes {
+            match change {
+                BlockChange::Added => {
+                    outline(&mut redline, &block_diff.block_id, ADDED_COLOR, "solid");
+                    summary.push(format!("{}: block added", label));
+                }
+                BlockChange::Removed => {
+                    let Some(block) = old_blocks.get(block_diff.block_id.as_str()) else {
+                        continue;
+                    };
+                    if page_ids.contains(&block.page_id) {
+                        let mut ghost = (*block).clone();
+                        mark(&mut ghost, REMOVED_COLOR, "dashed");
+                        if let Some(styles) = ghost.styles.as_mut() {
+                            styles.opacity = Some(0.4);
+                        }
+                        redline.blocks.push(ghost);
+                    }
+                    summary.push(format!("{}: block removed", label));
+                }
+                BlockChange::Moved {
+                    from, from_page_id, ..
+                } => {
+                    let Some(block) = old_blocks.get(block_diff.block_id.as_str()) else {
+                        continue;
+                    };
+                    let page_id = from_page_id.clone().unwrap_or(block.page_id.clone());
+                    if page_ids.contains(&page_id) {
+                        let mut ghost =
+                            Block::new(BlockType::Spacer, from.clone(), block.size.clone());
+                        ghost.page_id = page_id;
+                        mark(&mut ghost, MOVED_COLOR, "dotted");
+                        redline.blocks.push(ghost);
+                    }
+                    outline(&mut redline, &block_diff.block_id, MOVED_COLOR, "solid");
+                }
+                BlockChange::Text { words } => {
+                    outline(&mut redline, &block_diff.block_id, CHANGED_COLOR, "solid");
+                    summary.push(format!("{}: {}", label, format_words(words)));
+                }
+                BlockChange::Cells { cells } => {
+                    outline(&mut redline, &block_diff.block_id, CHANGED_COLOR, "solid");
+                    for cell in cells {
+                        summary.push(format!(
+                            "{}, row {}, column {}: {}",
+                            label,
+                            cell.row + 1,
+                            cell.column + 1,
+                            format_words(&cell.words)
+                        ));
+                    }
+                }
+                BlockChange::Resized { .. }
+                | BlockChange::Restyled { .. }
+                | BlockChange::Changed { .. } => {
+                    outline(&mut redline, &block_diff.block_id, CHANGED_COLOR, "solid");
+                }
+            }
+        }
+    }
+
+    append_summary(&mut redline, &summary);
+    redline
+}
+
+fn page_number(document: &Document, page_id: &str) -> String {
+    document
+        .pages
+        .iter()
+        .position(|p| p.id == page_id)
+        .map_or_else(|| "(removed)".to_string(), |i| (i + 1).to_string())
+}
+
+fn outline(document: &mut Document, block_id: &str, color: &str, style: &str) {
+    if let Some(block) = document.blocks.iter_mut().find(|b| b.id == block_id) {
+        mark(block, color, style);
+    }
+}
+
+fn mark(block: &mut Block, color: &str, style: &str) {
+    let styles = block.styles.get_or_insert(BlockStyles {
+        background: None,
+        border: None,
+        padding: None,
+        shadow: None,
+        opacity: None,
+    });
+    styles.border = Some(BorderStyle {
+        width: 2.0,
+        color: color.to_string(),
+        style: style.to_string(),
+    });
+}
+
+/// Append pages listing the changes, one text block per entry
+fn append_summary(document: &mut Document, entries: &[String]) {
+    if entries.is_empty() {
+        return;
+    }
+    let template = document.pages.last().cloned().unwrap_or_default();
+    let content_box = template.geometry().to_px().content_box;
+    let line_height = SUMMARY_FONT_SIZE * 1.2;
+    // Rough width of a Helvetica character
+    let chars_per_line = (content_box.width / (SUMMARY_FONT_SIZE * 0.5)).max(1.0);
+
+    let mut page_id = String::new();
+    let mut y = f64::INFINITY;
+    let heading = "Changes".to_string();
+    for (index, entry) in std::iter::once(&heading).chain(entries).enumerate() {
+        let lines = entry
+            .lines()
+            .map(|line| {
+                (line.chars().count() as f64 / chars_per_line)
+                    .ceil()
+                    .max(1.0)
+            })
+            .sum::<f64>();
+        let height = lines * line_height + 4.0;
+
+        if y + height > content_box.y + content_box.height {
+            let mut page = template.clone();
+            page.id = Uuid::new_v4().to_string();
+            page.background = None;
+            page_id = page.id.clone();
+            document.pages.push(page);
+            y = content_box.y;
+        }
+
+        let mut block = Block::new(
+            BlockType::Text,
+            Position {
+                x: content_box.x,
+                y,
+            },
+            Size {
+                width: content_box.width,
+                height,
+            },
+        );
+        block.page_id = page_id.clone();
+        if let BlockContent::Text(text) = &mut block.content {
+            text.text = entry.clone();
+            text.font_family = "Helvetica".to_string();
+            text.font_size = if index == 0 {
+                SUMMARY_FONT_SIZE * 1.5
+            } else {
+                SUMMARY_FONT_SIZE
+            };
+            text.font_weight = if index == 0 { 700 } else { 400 };
+        }
+        document.blocks.push(block);
+        y += height + SUMMARY_GAP;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{TableBlockContent, TableCell};
+
+    fn table_block(cells: &[&str]) -> Block {
+        let mut block = Block::for_test(BlockType::Table, 10.0, 100.0, 200.0, 40.0);
+        block.content = BlockContent::Table(TableBlockContent {
+            rows: vec![TableRow {
+                cells: cells
+                    .iter()
+                    .map(|c| TableCell {
+                        content: c.to_string(),
+                        styles: None,
+                        name: None,
+                    })
+                    .collect(),
+            }],
+            column_widths: vec![100.0, 100.0],
+        });
+        block
+    }
+
+    #[test]
+    fn test_diff_words() {
+        let words = diff_words("Hosting on bare metal", "Hosting on managed Kubernetes");
+        assert_eq!(
+            format_words(&words),
+            "Hosting on [-bare metal-]{+managed Kubernetes+}"
+        );
+        assert!(diff_words("same", "same")
+            .iter()
+            .all(|w| w.op == WordOp::Equal));
+    }
+
+    #[test]
+    fn test_diff_documents() {
+        let mut old = Document::new("Offer".to_string());
+        old.add_block(Block::text_for_test("Price is 100 EUR"));
+        old.add_block(table_block(&["Setup", "100"]));
+        old.add_block(Block::text_for_test("Old footer"));
+        let mut new = old.clone();
+
+        new.metadata.title = "Offer v2".to_string();
+        new.blocks[0].position.y = 30.0;
+        if let BlockContent::Text(text) = &mut new.blocks[0].content {
+            text.text = "Price is 120 EUR".to_string();
+            text.color = "#ff0000".to_string();
+        }
+        if let BlockContent::Table(table) = &mut new.blocks[1].content {
+            table.rows[0].cells[1].content = "150".to_string();
+        }
+        new.blocks[1].size.width = 300.0;
+        let removed = new.blocks.remove(2);
+        new.add_block(Block::text_for_test("New footer"));
+        new.pages[0].margins.top = 30.0;
+
+        let diff = diff_documents(&old, &new);
+        assert_eq!(diff.metadata, vec!["title"]);
+        assert_eq!(
+            diff.pages,
+            vec![PageChange::Changed {
+                page_id: new.pages[0].id.clone(),
+                fields: vec!["margins".to_string()],
+            }]
+        );
+
+        let changes = &diff.blocks[0].changes;
+        assert!(matches!(changes[0], BlockChange::Moved { .. }));
+        assert!(
+            matches!(&changes[1], BlockChange::Text { words } if format_words(words) == "Price is [-100-]{+120+} EUR")
+        );
+        assert_eq!(
+            changes[2],
+            BlockChange::Restyled {
+                fields: vec!["color".to_string()]
+            }
+        );
+
+        let changes = &diff.blocks[1].changes;
+        assert!(matches!(changes[0], BlockChange::Resized { .. }));
+        assert!(
+            matches!(&changes[1], BlockChange::Cells { cells } if cells.len() == 1 && cells[0].column == 1)
+        );
+
+        assert_eq!(diff.blocks[2].changes, vec![BlockChange::Added]);
+        assert_eq!(diff.blocks[3].block_id, removed.id);
+        assert_eq!(diff.blocks[3].changes, vec![BlockChange::Removed]);
+
+        assert!(diff_documents(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_page_reordering() {
+        let mut old = Document::new("Offer".to_string());
+        old.pages.push(Page::default());
+        old.pages.push(Page::default());
+        let mut new = old.clone();
+        new.pages.swap(1, 2);
+        new.pages.insert(0, Page::default());
+
+        let diff = diff_documents(&old, &new);
+        assert!(matches!(diff.pages[0], PageChange::Added { index: 0, .. }));
+        assert_eq!(
+            diff.pages
+                .iter()
+                .filter(|c| matches!(c, PageChange::Reordered { .. }))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_redline_document() {
+        let mut old = Document::new("Offer".to_string());
+        old.add_block(Block::text_for_test("Price is 100 EUR"));
+        old.add_block(Block::text_for_test("Removed"));
+        let mut new = old.clone();
+        new.blocks.remove(1);
+        if let BlockContent::Text(text) = &mut new.blocks[0].content {
+            text.text = "Price is 120 EUR".to_string();
+        }
+
+        let diff = diff_documents(&old, &new);
+        let redline = redline_document(&old, &new, &diff);
+
+        assert_eq!(redline.metadata.title, "Redline: Offer");
+        assert_eq!(redline.pages.len(), 2);
+        let border = |block: &Block| block.styles.as_ref().unwrap().border.clone().unwrap();
+        assert_eq!(border(&redline.blocks[0]).color, CHANGED_COLOR);
+        assert_eq!(border(&redline.blocks[1]).color, REMOVED_COLOR);
+
+        let summary: Vec<String> = redline
+            .blocks
+            .iter()
+            .filter(|b| b.page_id == redline.pages[1].id)
+            .filter_map(|b| match &b.content {
+                BlockContent::Text(text) => Some(text.text.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(summary[0], "Changes");
+        assert!(summary.contains(&"Page 1: Price is [-100-]{+120+} EUR".to_string()));
+        assert!(summary.contains(&"Page 1: block removed".to_string()));
+    }
+}
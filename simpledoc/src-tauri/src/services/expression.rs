@@ -0,0 +1,368 @@
+use crate::services::template_engine::{is_valid_path, lookup};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// Comparison operators
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// Parsed `visible_if` expression, e.g. `amount > 1000 && exists(discount)`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(Value),
+    Variable(String),
+    Exists(String),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Compare(Box<Expression>, CompareOp, Box<Expression>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Ident(String),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl Expression {
+    /// Parse an expression
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Err("Expression is empty".to_string());
+        }
+
+        let mut parser = Parser { tokens, pos: 0 };
+        let expression = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(token) => Err(format!("Unexpected {:?} in expression", token)),
+        }
+    }
+
+    /// Evaluate against the data context; missing variables are `null`
+    pub fn evaluate(&self, data: &Value) -> Value {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::Variable(path) => lookup(data, path).cloned().unwrap_or(Value::Null),
+            Expression::Exists(path) => {
+                Value::Bool(lookup(data, path).is_some_and(|v| !v.is_null()))
+            }
+            Expression::Not(inner) => Value::Bool(!is_truthy(&inner.evaluate(data))),
+            Expression::And(left, right) => Value::Bool(left.is_true(data) && right.is_true(data)),
+            Expression::Or(left, right) => Value::Bool(left.is_true(data) || right.is_true(data)),
+            Expression::Compare(left, op, right) => {
+                Value::Bool(compare(&left.evaluate(data), *op, &right.evaluate(data)))
+            }
+        }
+    }
+
+    /// Evaluate and convert the result to a boolean
+    pub fn is_true(&self, data: &Value) -> bool {
+        is_truthy(&self.evaluate(data))
+    }
+
+    /// Variable paths referenced by the expression
+    pub fn variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+
+    fn collect_variables(&self, variables: &mut Vec<String>) {
+        match self {
+            Expression::Literal(_) => {}
+            Expression::Variable(path) | Expression::Exists(path) => {
+                if !variables.contains(path) {
+                    variables.push(path.clone());
+                }
+            }
+            Expression::Not(inner) => inner.collect_variables(variables),
+            Expression::And(left, right)
+            | Expression::Or(left, right)
+            | Expression::Compare(left, _, right) => {
+                left.collect_variables(variables);
+                right.collect_variables(variables);
+            }
+        }
+    }
+}
+
+/// `null`, `false`, `0`, empty strings and empty collections are false
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+/// Numeric strings compare as numbers, since CSV datasets only contain text
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn compare(left: &Value, op: CompareOp, right: &Value) -> bool {
+    let ordering = match (as_number(left), as_number(right)) {
+        (Some(l), Some(r)) => l.partial_cmp(&r),
+        _ => match (left, right) {
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            _ if left == right => Some(Ordering::Equal),
+            _ => None,
+        },
+    };
+
+    match (op, ordering) {
+        (CompareOp::Eq, ordering) => ordering == Some(Ordering::Equal),
+        (CompareOp::Ne, ordering) => ordering != Some(Ordering::Equal),
+        (_, None) => false,
+        (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+        (CompareOp::Ge, Some(o)) => o != Ordering::Less,
+        (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+        (CompareOp::Le, Some(o)) => o != Ordering::Greater,
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        let (token, length) = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => (Token::LParen, 1),
+            (')', _) => (Token::RParen, 1),
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::Op(CompareOp::Eq), 2),
+            ('!', Some('=')) => (Token::Op(CompareOp::Ne), 2),
+            ('>', Some('=')) => (Token::Op(CompareOp::Ge), 2),
+            ('<', Some('=')) => (Token::Op(CompareOp::Le), 2),
+            ('>', _) => (Token::Op(CompareOp::Gt), 1),
+            ('<', _) => (Token::Op(CompareOp::Lt), 1),
+            ('!', _) => (Token::Not, 1),
+            ('"' | '\'', _) => {
+                let end = chars[i + 1..]
+                    .iter()
+                    .position(|&q| q == c)
+                    .ok_or("Unterminated string in expression")?;
+                let text: String = chars[i + 1..i + 1 + end].iter().collect();
+                (Token::Str(text), end + 2)
+            }
+            (c, next)
+                if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) =>
+            {
+                let length = 1 + chars[i + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit() || **c == '.')
+                    .count();
+                let text: String = chars[i..i + length].iter().collect();
+                let number = text
+                    .parse()
+                    .map_err(|_| format!("Invalid number in expression: {}", text))?;
+                (Token::Number(number), length)
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                let length = chars[i..]
+                    .iter()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '[' | ']' | '-'))
+                    .count();
+                let word: String = chars[i..i + length].iter().collect();
+                let token = match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    _ => Token::Ident(word),
+                };
+                (token, length)
+            }
+            (c, _) => return Err(format!("Unexpected character '{}' in expression", c)),
+        };
+
+        tokens.push(token);
+        i += length;
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser: or > and > not > comparison > primary
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => Err(format!("Expected {:?}, found {:?}", expected, token)),
+            None => Err(format!("Expected {:?} at end of expression", expected)),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            left = Expression::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, String> {
+        let mut left = self.parse_not()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            left = Expression::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, String> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, String> {
+        let left = self.parse_primary()?;
+        if let Some(Token::Op(op)) = self.peek().cloned() {
+            self.pos += 1;
+            let right = self.parse_primary()?;
+            return Ok(Expression::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, String> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expression::Literal(
+                serde_json::Number::from_f64(n)
+                    .map(Value::Number)
+                    .unwrap_or(Value::Null),
+            )),
+            Some(Token::Str(s)) => Ok(Expression::Literal(Value::String(s))),
+            Some(Token::LParen) => {
+                let inner = self.parse_or()?;
+                self.expect(Token::RParen)?;
+                Ok(inner)
+            }
+            Some(Token::Ident(word)) => match word.as_str() {
+                "true" => Ok(Expression::Literal(Value::Bool(true))),
+                "false" => Ok(Expression::Literal(Value::Bool(false))),
+                "null" => Ok(Expression::Literal(Value::Null)),
+                "exists" => {
+                    self.expect(Token::LParen)?;
+                    let path = match self.next() {
+                        Some(Token::Ident(path)) => path,
+                        _ => return Err("exists() expects a variable name".to_string()),
+                    };
+                    self.expect(Token::RParen)?;
+                    Ok(Expression::Exists(checked_path(path)?))
+                }
+                _ => Ok(Expression::Variable(checked_path(word)?)),
+            },
+            Some(token) => Err(format!("Unexpected {:?} in expression", token)),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+}
+
+fn checked_path(path: String) -> Result<String, String> {
+    if is_valid_path(&path) {
+        Ok(path)
+    } else {
+        Err(format!("Invalid variable name: {}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(source: &str, data: &Value) -> bool {
+        Expression::parse(source).unwrap().is_true(data)
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let data = json!({ "amount": 1500, "text_amount": "900", "status": "paid" });
+        assert!(eval("amount > 1000", &data));
+        assert!(!eval("text_amount > 1000", &data));
+        assert!(eval("amount >= 1500 && amount <= 1500", &data));
+        assert!(eval("status == 'paid'", &data));
+        assert!(eval("status != \"draft\"", &data));
+        assert!(!eval("missing > 0", &data));
+    }
+
+    #[test]
+    fn test_boolean_logic_and_exists() {
+        let data = json!({ "discount": 10, "client": { "vip": false }, "note": null });
+        assert!(eval("exists(discount) and not client.vip", &data));
+        assert!(!eval("exists(note) || exists(client.name)", &data));
+        assert!(eval("!(discount < 5 || client.vip)", &data));
+        assert!(eval("exists(client) && discount", &data));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Expression::parse("").is_err());
+        assert!(Expression::parse("amount >").is_err());
+        assert!(Expression::parse("(amount > 1").is_err());
+        assert!(Expression::parse("amount = 1").is_err());
+        assert!(Expression::parse("'open").is_err());
+        assert!(Expression::parse("client..name").is_err());
+        assert!(Expression::parse("exists('x')").is_err());
+    }
+
+    #[test]
+    fn test_variables() {
+        let expression = Expression::parse("a > 1 && (exists(b.c) || a < 5)").unwrap();
+        assert_eq!(
+            expression.variables(),
+            vec!["a".to_string(), "b.c".to_string()]
+        );
+    }
+}
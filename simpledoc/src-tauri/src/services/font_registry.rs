@@ -0,0 +1,441 @@
+use crate::models::Result;
+use crate::services::assets::{self, AssetStore};
+use crate::services::renderer::fonts::{Font, FontFace, Script, StandardFont};
+use fontdb::{Database, Family, Query, Source, Stretch, Style, Weight, ID};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Families tried, in order, for Cyrillic text the chosen font cannot show
+const CYRILLIC_FALLBACK: &[&str] = &[
+    "PT Sans",
+    "Noto Sans",
+    "Arial",
+    "Segoe UI",
+    "DejaVu Sans",
+    "Liberation Sans",
+];
+
+/// Families tried, in order, for Chinese, Japanese and Korean text
+const CJK_FALLBACK: &[&str] = &[
+    "Noto Sans CJK SC",
+    "Noto Sans SC",
+    "Source Han Sans SC",
+    "Microsoft YaHei",
+    "PingFang SC",
+    "Hiragino Sans GB",
+    "WenQuanYi Micro Hei",
+    "Noto Sans CJK JP",
+    "Yu Gothic",
+    "Malgun Gothic",
+];
+
+/// Families tried for any other character
+const GENERAL_FALLBACK: &[&str] = &[
+    "Noto Sans",
+    "DejaVu Sans",
+    "Arial Unicode MS",
+    "Segoe UI Symbol",
+];
+
+/// Public domain Tuffy Regular, with Latin, Greek and Cyrillic glyphs
+#[cfg(test)]
+pub(crate) const TEST_FONT: &[u8] = include_bytes!("../../tests/fixtures/fonts/Tuffy.ttf");
+
+/// Where a font comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FontSource {
+    /// Standard PDF font, always available
+    Builtin,
+    System,
+    /// Per-user fonts folder
+    User,
+    /// Imported into the asset store
+    Asset,
+}
+
+/// A font face available for rendering
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FontInfo {
+    pub family: String,
+    pub weight: u16,
+    pub italic: bool,
+    pub monospace: bool,
+    pub source: FontSource,
+    /// `asset://` reference of imported fonts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+}
+
+/// Fonts installed on the machine, in the user's fonts folder and imported
+/// into the asset store
+///
+/// Resolves a block's family and weight to a concrete face. Families that
+/// are not installed fall back to the standard PDF fonts, and characters a
+/// face cannot show are taken from fallback families for their script.
+/// Clones share the loaded faces, so a clone per render is cheap.
+#[derive(Debug, Clone, Default)]
+pub struct FontRegistry {
+    db: Arc<Database>,
+    user_dir: Option<PathBuf>,
+    /// `asset://` reference of every imported face
+    assets: Arc<HashMap<ID, String>>,
+    /// Faces loaded for layout, by face id
+    loaded: Arc<Mutex<HashMap<ID, Arc<FontFace>>>>,
+    /// Installed fallback faces per script, weight and style
+    fallbacks: Arc<Mutex<HashMap<FallbackKey, Vec<ID>>>>,
+}
+
+/// Script, weight and italic flag of a fallback chain
+type FallbackKey = (Script, u16, bool);
+
+impl FontRegistry {
+    /// Registry with only the standard PDF fonts
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the fonts of a per-user folder and the fonts in the asset store
+    pub fn open(user_dir: PathBuf, assets: &AssetStore) -> Result<Self> {
+        fs::create_dir_all(&user_dir)?;
+        let mut registry = Self::new();
+        Arc::make_mut(&mut registry.db).load_fonts_dir(&user_dir);
+        registry.user_dir = Some(user_dir);
+        registry.load_assets(assets)?;
+        Ok(registry)
+    }
+
+    /// Add the fonts installed on the system
+    pub fn load_system_fonts(&mut self) {
+        let before = self.db.len();
+        Arc::make_mut(&mut self.db).load_system_fonts();
+        self.changed();
+        info!("Loaded {} system font faces", self.db.len() - before);
+    }
+
+    /// Add fonts from the asset store that are not loaded yet
+    ///
+    /// Assets are recognized as fonts by their file signature.
+    pub fn load_assets(&mut self, assets: &AssetStore) -> Result<()> {
+        let known: BTreeSet<String> = self.assets.values().cloned().collect();
+        for hash in assets.list()? {
+            let uri = format!("{}{}", assets::ASSET_SCHEME, hash);
+            if known.contains(&uri) || !is_font_file(&assets.path(&uri)?)? {
+                continue;
+            }
+            self.load_asset(&uri, assets.read(&uri)?);
+        }
+        Ok(())
+    }
+
+    /// Store a TTF, OTF or TTC file in the asset store and load its faces
+    pub fn import(&mut self, data: &[u8], assets: &AssetStore) -> Result<Vec<FontInfo>> {
+        let uri = assets.add_font(data)?;
+        // Importing the same file again keeps the loaded faces
+        let mut ids: Vec<ID> = self
+            .assets
+            .iter()
+            .filter(|(_, asset)| **asset == uri)
+            .map(|(id, _)| *id)
+            .collect();
+        if ids.is_empty() {
+            ids = self.load_asset(&uri, data.to_vec());
+        }
+        ids.sort();
+
+        info!("Imported font {} with {} faces", uri, ids.len());
+        Ok(ids.into_iter().filter_map(|id| self.info(id)).collect())
+    }
+
+    fn load_asset(&mut self, uri: &str, data: Vec<u8>) -> Vec<ID> {
+        let ids = Arc::make_mut(&mut self.db).load_font_source(Source::Binary(Arc::new(data)));
+        let assets = Arc::make_mut(&mut self.assets);
+        for id in &ids {
+            assets.insert(*id, uri.to_string());
+        }
+        self.changed();
+        ids.to_vec()
+    }
+
+    /// Forget cached lookups after faces were added
+    fn changed(&mut self) {
+        self.fallbacks = Arc::default();
+    }
+
+    /// All available faces, the standard fonts included, sorted by family
+    pub fn list(&self) -> Vec<FontInfo> {
+        let mut fonts: Vec<FontInfo> = self
+            .db
+            .faces()
+            .filter_map(|face| self.info(face.id))
+            .collect();
+        for (family, monospace) in [("Helvetica", false), ("Courier", true)] {
+            for (weight, italic) in [(400, false), (700, false), (400, true), (700, true)] {
+                fonts.push(FontInfo {
+                    family: family.to_string(),
+                    weight,
+                    italic,
+                    monospace,
+                    source: FontSource::Builtin,
+                    asset: None,
+                });
+            }
+        }
+
+        fonts.sort_by(|a, b| {
+            (&a.family, a.weight, a.italic, a.source as u8).cmp(&(
+                &b.family,
+                b.weight,
+                b.italic,
+                b.source as u8,
+            ))
+        });
+        fonts.dedup_by(|a, b| (&a.family, a.weight, a.italic) == (&b.family, b.weight, b.italic));
+        fonts
+    }
+
+    fn info(&self, id: ID) -> Option<FontInfo> {
+        let face = self.db.face(id)?;
+        Some(FontInfo {
+            family: face.families.first()?.0.clone(),
+            weight: face.weight.0,
+            italic: face.style != Style::Normal,
+            monospace: face.monospaced,
+            source: self.source(id),
+            asset: self.assets.get(&id).cloned(),
+        })
+    }
+
+    fn source(&self, id: ID) -> FontSource {
+        if self.assets.contains_key(&id) {
+            return FontSource::Asset;
+        }
+        let path = match self.db.face(id).map(|face| &face.source) {
+            Some(Source::File(path)) | Some(Source::SharedFile(path, _)) => path,
+            _ => return FontSource::System,
+        };
+        match &self.user_dir {
+            Some(user_dir) if path.starts_with(user_dir) => FontSource::User,
+            _ => FontSource::System,
+        }
+    }
+
+    /// Concrete font for a CSS-like family, weight and style
+    ///
+    /// Family names match case-insensitively; the closest weight and style
+    /// of the family are picked. Unknown families get a standard font.
+    pub fn resolve(&self, family: &str, weight: u16, italic: bool) -> Font {
+        self.query(family, weight, italic)
+            .and_then(|id| self.face(id))
+            .map(Font::Embedded)
+            .unwrap_or_else(|| Font::Standard(StandardFont::resolve_style(family, weight, italic)))
+    }
+
+    /// Font to show a character that `font` has no glyph for
+    ///
+    /// The families for the character's script are tried first, then the
+    /// fonts of the user folder and the asset store, closest style first.
+    pub fn fallback(&self, font: &Font, c: char, weight: u16, italic: bool) -> Option<Font> {
+        if font.has_char(c) {
+            return None;
+        }
+
+        let script = Script::of(c);
+        let candidates = {
+            let mut fallbacks = self.fallbacks.lock().unwrap_or_else(|e| e.into_inner());
+            fallbacks
+                .entry((script, weight, italic))
+                .or_insert_with(|| {
+                    let families = match script {
+                        Script::Cyrillic => CYRILLIC_FALLBACK,
+                        Script::Cjk => CJK_FALLBACK,
+                        Script::Latin | Script::Other => GENERAL_FALLBACK,
+                    };
+                    let mut ids: Vec<ID> = families
+                        .iter()
+                        .filter_map(|family| self.query(family, weight, italic))
+                        .collect();
+                    let mut added: Vec<(bool, u16, ID)> = self
+                        .db
+                        .faces()
+                        .filter(|face| self.source(face.id) != FontSource::System)
+                        .filter(|face| !ids.contains(&face.id))
+                        .map(|face| {
+                            let style_differs = (face.style != Style::Normal) != italic;
+                            (style_differs, face.weight.0.abs_diff(weight), face.id)
+                        })
+                        .collect();
+                    added.sort();
+                    ids.extend(added.into_iter().map(|(_, _, id)| id));
+                    ids
+                })
+                .clone()
+        };
+
+        candidates
+            .into_iter()
+            .filter_map(|id| self.face(id))
+            .find(|face| face.glyph(c).is_some())
+            .map(Font::Embedded)
+    }
+
+    fn query(&self, family: &str, weight: u16, italic: bool) -> Option<ID> {
+        // fontdb compares family names exactly
+        let name = self
+            .db
+            .faces()
+            .flat_map(|face| face.families.iter())
+            .find(|(name, _)| name.eq_ignore_ascii_case(family))?
+            .0
+            .clone();
+
+        self.db.query(&Query {
+            families: &[Family::Name(&name)],
+            weight: Weight(weight),
+            stretch: Stretch::Normal,
+            style: if italic { Style::Italic } else { Style::Normal },
+        })
+    }
+
+    /// Loaded face for layout, reading the font file on first use
+    fn face(&self, id: ID) -> Option<Arc<FontFace>> {
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(face) = loaded.get(&id) {
+            return Some(Arc::clone(face));
+        }
+
+        let info = self.db.face(id)?;
+        let data: Arc<dyn AsRef<[u8]> + Send + Sync> = match &info.source {
+            Source::Binary(data) | Source::SharedFile(_, data) => Arc::clone(data),
+            Source::File(path) => match fs::read(path) {
+                Ok(data) => Arc::new(data),
+                Err(e) => {
+                    warn!("Failed to read font {:?}: {}", path, e);
+                    return None;
+                }
+            },
+        };
+        let face = FontFace::parse(
+            loaded.len(),
+            info.post_script_name.clone(),
+            data,
+            info.index,
+        )?;
+        let face = Arc::new(face);
+        loaded.insert(id, Arc::clone(&face));
+        Some(face)
+    }
+
+    /// Font files a document needs on another machine: faces from the user
+    /// folder or the asset store that `styles` resolve to
+    ///
+    /// `styles` are family, weight and italic as used by text blocks.
+    pub fn portable_files<'a>(
+        &self,
+        styles: impl IntoIterator<Item = (&'a str, u16, bool)>,
+    ) -> Vec<Vec<u8>> {
+        let mut sources = BTreeSet::new();
+        let mut files = Vec::new();
+
+        for (family, weight, italic) in styles {
+            let Some(id) = self.query(family, weight, italic) else {
+                continue;
+            };
+            if self.source(id) == FontSource::System {
+                continue;
+            }
+            let Some(info) = self.db.face(id) else {
+                continue;
+            };
+            // Collections hold several faces, but travel as one file
+            let key = match &info.source {
+                Source::Binary(data) => format!("{:p}", Arc::as_ptr(data)),
+                Source::File(path) | Source::SharedFile(path, _) => path.display().to_string(),
+            };
+            if !sources.insert(key) {
+                continue;
+            }
+            if let Some(data) = self.db.with_face_data(id, |data, _| data.to_vec()) {
+                files.push(data);
+            }
+        }
+
+        files
+    }
+}
+
+/// File extension matching the signature of a font file
+pub fn font_extension(data: &[u8]) -> &'static str {
+    match data.get(..4) {
+        Some(b"OTTO") => "otf",
+        Some(b"ttcf") => "ttc",
+        _ => "ttf",
+    }
+}
+
+/// Whether the file starts with a TrueType, OpenType or collection signature
+fn is_font_file(path: &Path) -> Result<bool> {
+    let mut signature = [0u8; 4];
+    let read = fs::File::open(path)?.read(&mut signature)?;
+    Ok(read == signature.len()
+        && matches!(
+            &signature,
+            b"\x00\x01\x00\x00" | b"OTTO" | b"true" | b"ttcf"
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_unknown_family_uses_standard_font() {
+        let registry = FontRegistry::new();
+        assert_eq!(
+            registry.resolve("Inter", 700, false),
+            Font::Standard(StandardFont::HelveticaBold)
+        );
+        assert!(registry
+            .fallback(&Font::Standard(StandardFont::Helvetica), 'Ж', 400, false)
+            .is_none());
+        assert_eq!(registry.list().len(), 8);
+    }
+
+    #[test]
+    fn test_import_resolves_and_falls_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let mut registry = FontRegistry::open(temp_dir.path().join("fonts"), &assets).unwrap();
+
+        assert!(registry.import(b"not a font", &assets).is_err());
+        let imported = registry.import(TEST_FONT, &assets).unwrap();
+        assert_eq!(imported.len(), 1);
+        assert_eq!(imported[0].family, "Tuffy");
+        assert_eq!(imported[0].source, FontSource::Asset);
+        assert_eq!(registry.import(TEST_FONT, &assets).unwrap(), imported);
+
+        let font = registry.resolve("TUFFY", 400, false);
+        assert!(matches!(font, Font::Embedded(_)));
+        assert!(font.has_char('Ж'));
+
+        // Imported fonts show what the standard fonts cannot
+        let helvetica = Font::Standard(StandardFont::Helvetica);
+        assert_eq!(registry.fallback(&helvetica, 'Ж', 700, false), Some(font));
+        assert_eq!(registry.fallback(&helvetica, 'A', 400, false), None);
+        assert_eq!(registry.fallback(&helvetica, '中', 400, false), None);
+
+        // Imported fonts are found again after a restart
+        let reopened = FontRegistry::open(temp_dir.path().join("fonts"), &assets).unwrap();
+        assert_eq!(reopened.list(), registry.list());
+        assert_eq!(
+            reopened.portable_files([("tuffy", 400, false)]),
+            vec![TEST_FONT.to_vec()]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use crate::models::Locale;
+use chrono::{Datelike, NaiveDate};
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::str::FromStr;
+
+/// No-break space, used where locales separate with a space
+const NBSP: char = '\u{a0}';
+
+/// Length of a formatted date
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateStyle {
+    /// Numeric, e.g. `05.03.2024` or `03/05/2024`
+    Short,
+    /// With the month name, e.g. `5 марта 2024 г.` or `March 5, 2024`
+    Long,
+}
+
+/// Formats numbers, currencies, percentages and dates for a locale
+#[derive(Debug, Clone, Copy)]
+pub struct Formatter {
+    locale: Locale,
+}
+
+impl Formatter {
+    pub fn new(locale: Locale) -> Self {
+        Self { locale }
+    }
+
+    fn decimal_separator(&self) -> char {
+        match self.locale {
+            Locale::EnUs => '.',
+            Locale::Ru | Locale::De | Locale::Fr => ',',
+        }
+    }
+
+    fn group_separator(&self) -> char {
+        match self.locale {
+            Locale::EnUs => ',',
+            Locale::De => '.',
+            Locale::Ru | Locale::Fr => NBSP,
+        }
+    }
+
+    /// Format a number with grouped thousands
+    ///
+    /// `decimals` rounds half away from zero and pads with zeros; `None`
+    /// keeps the scale of the value.
+    pub fn format_number(&self, value: Decimal, decimals: Option<u32>) -> String {
+        let mut value = match decimals {
+            Some(decimals) => {
+                let mut rounded =
+                    value.round_dp_with_strategy(decimals, RoundingStrategy::MidpointAwayFromZero);
+                rounded.rescale(decimals);
+                rounded
+            }
+            None => value,
+        };
+        let negative = value.is_sign_negative() && !value.is_zero();
+        value.set_sign_positive(true);
+
+        let text = value.to_string();
+        let (integer, fraction) = match text.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (text.as_str(), None),
+        };
+
+        let mut formatted = String::new();
+        if negative {
+            formatted.push('-');
+        }
+        for (i, digit) in integer.chars().enumerate() {
+            if i > 0 && (integer.len() - i) % 3 == 0 {
+                formatted.push(self.group_separator());
+            }
+            formatted.push(digit);
+        }
+        if let Some(fraction) = fraction {
+            formatted.push(self.decimal_separator());
+            formatted.push_str(fraction);
+        }
+        formatted
+    }
+
+    /// Format an amount with two decimals and the symbol of an ISO currency code
+    pub fn format_currency(&self, value: Decimal, code: &str) -> String {
+        let number = self.format_number(value, Some(2));
+        let code = code.to_uppercase();
+        let symbol = match code.as_str() {
+            "USD" => "$",
+            "EUR" => "€",
+            "RUB" => "₽",
+            "GBP" => "£",
+            other => other,
+        };
+
+        match self.locale {
+            Locale::EnUs if symbol.chars().count() == 1 => match number.strip_prefix('-') {
+                Some(amount) => format!("-{}{}", symbol, amount),
+                None => format!("{}{}", symbol, number),
+            },
+            Locale::EnUs => format!("{}{}{}", symbol, NBSP, number),
+            Locale::Ru | Locale::De | Locale::Fr => format!("{}{}{}", number, NBSP, symbol),
+        }
+    }
+
+    /// Format a fraction as a percentage, e.g. `0.2` as `20%` or `20 %`
+    pub fn format_percent(&self, value: Decimal, decimals: u32) -> String {
+        let number = self.format_number(value * Decimal::ONE_HUNDRED, Some(decimals));
+        match self.locale {
+            Locale::EnUs => format!("{}%", number),
+            Locale::Ru | Locale::De | Locale::Fr => format!("{}{}%", number, NBSP),
+        }
+    }
+
+    /// Format a date
+    pub fn format_date(&self, date: NaiveDate, style: DateStyle) -> String {
+        let (day, month, year) = (date.day(), date.month(), date.year());
+
+        match (style, self.locale) {
+            (DateStyle::Short, Locale::EnUs) => format!("{:02}/{:02}/{}", month, day, year),
+            (DateStyle::Short, Locale::Fr) => format!("{:02}/{:02}/{}", day, month, year),
+            (DateStyle::Short, Locale::Ru | Locale::De) => {
+                format!("{:02}.{:02}.{}", day, month, year)
+            }
+            (DateStyle::Long, Locale::EnUs) => {
+                format!("{} {}, {}", self.month_name(month), day, year)
+            }
+            (DateStyle::Long, Locale::Ru) => {
+                format!("{} {} {} г.", day, self.month_name(month), year)
+            }
+            (DateStyle::Long, Locale::De) => {
+                format!("{}. {} {}", day, self.month_name(month), year)
+            }
+            (DateStyle::Long, Locale::Fr) => format!("{} {} {}", day, self.month_name(month), year),
+        }
+    }
+
+    /// Month name as used in a long date (genitive in Russian)
+    fn month_name(&self, month: u32) -> &'static str {
+        const RU: [&str; 12] = [
+            "января",
+            "февраля",
+            "марта",
+            "апреля",
+            "мая",
+            "июня",
+            "июля",
+            "августа",
+            "сентября",
+            "октября",
+            "ноября",
+            "декабря",
+        ];
+        const EN: [&str; 12] = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+        const DE: [&str; 12] = [
+            "Januar",
+            "Februar",
+            "März",
+            "April",
+            "Mai",
+            "Juni",
+            "Juli",
+            "August",
+            "September",
+            "Oktober",
+            "November",
+            "Dezember",
+        ];
+        const FR: [&str; 12] = [
+            "janvier",
+            "février",
+            "mars",
+            "avril",
+            "mai",
+            "juin",
+            "juillet",
+            "août",
+            "septembre",
+            "octobre",
+            "novembre",
+            "décembre",
+        ];
+
+        let names = match self.locale {
+            Locale::Ru => &RU,
+            Locale::EnUs => &EN,
+            Locale::De => &DE,
+            Locale::Fr => &FR,
+        };
+        names[(month as usize).clamp(1, 12) - 1]
+    }
+
+    /// Parse a number written by a person or taken from a dataset
+    ///
+    /// When both `.` and `,` appear, the last one is the decimal separator.
+    /// A single separator is decimal if it is the locale's one, or if it is
+    /// not followed by exactly three digits (`100.10` in a German document).
+    pub fn parse_number(&self, text: &str) -> Option<Decimal> {
+        let cleaned: String = text
+            .trim()
+            .chars()
+            .filter(|c| {
+                !c.is_whitespace()
+                    && !matches!(c, '\u{a0}' | '\u{202f}' | '$' | '€' | '₽' | '£' | '%')
+            })
+            .collect();
+        if cleaned.is_empty() {
+            return None;
+        }
+
+        let separators: Vec<(usize, char)> = cleaned
+            .char_indices()
+            .filter(|(_, c)| matches!(c, '.' | ','))
+            .collect();
+        let decimal = match separators.as_slice() {
+            [] => None,
+            [.., (_, last)] if separators.iter().any(|(_, c)| c != last) => Some(*last),
+            [(index, separator)] => {
+                let digits_after = cleaned.len() - index - 1;
+                (*separator == self.decimal_separator() || digits_after != 3).then_some(*separator)
+            }
+            _ => None,
+        };
+
+        let normalized: String = cleaned
+            .chars()
+            .filter_map(|c| match c {
+                '.' | ',' if Some(c) == decimal => Some('.'),
+                '.' | ',' => None,
+                c => Some(c),
+            })
+            .collect();
+        Decimal::from_str(&normalized).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(text: &str) -> Decimal {
+        Decimal::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_format_number() {
+        let cases = [
+            (Locale::Ru, "1234567.891", Some(2), "1\u{a0}234\u{a0}567,89"),
+            (Locale::EnUs, "1234567.891", Some(2), "1,234,567.89"),
+            (Locale::De, "1234567.891", Some(2), "1.234.567,89"),
+            (Locale::Fr, "1234567.891", Some(2), "1\u{a0}234\u{a0}567,89"),
+            (Locale::EnUs, "-1234.5", None, "-1,234.5"),
+            (Locale::De, "0.125", Some(2), "0,13"),
+            (Locale::Ru, "999", Some(0), "999"),
+            (Locale::EnUs, "-0.001", Some(2), "0.00"),
+        ];
+
+        for (locale, value, decimals, expected) in cases {
+            let formatted = Formatter::new(locale).format_number(dec(value), decimals);
+            assert_eq!(formatted, expected, "{:?} {}", locale, value);
+        }
+    }
+
+    #[test]
+    fn test_format_currency() {
+        let cases = [
+            (
+                Locale::Ru,
+                "1234567.89",
+                "RUB",
+                "1\u{a0}234\u{a0}567,89\u{a0}₽",
+            ),
+            (Locale::De, "1234567.89", "EUR", "1.234.567,89\u{a0}€"),
+            (Locale::Fr, "1234.5", "eur", "1\u{a0}234,50\u{a0}€"),
+            (Locale::EnUs, "1234.5", "USD", "$1,234.50"),
+            (Locale::EnUs, "-20", "USD", "-$20.00"),
+            (Locale::EnUs, "20", "CHF", "CHF\u{a0}20.00"),
+            (Locale::De, "20", "CHF", "20,00\u{a0}CHF"),
+        ];
+
+        for (locale, value, code, expected) in cases {
+            let formatted = Formatter::new(locale).format_currency(dec(value), code);
+            assert_eq!(formatted, expected, "{:?} {}", locale, value);
+        }
+    }
+
+    #[test]
+    fn test_format_percent() {
+        let cases = [
+            (Locale::Ru, 0, "20\u{a0}%"),
+            (Locale::EnUs, 0, "20%"),
+            (Locale::De, 1, "20,0\u{a0}%"),
+            (Locale::Fr, 0, "20\u{a0}%"),
+        ];
+
+        for (locale, decimals, expected) in cases {
+            let formatted = Formatter::new(locale).format_percent(dec("0.2"), decimals);
+            assert_eq!(formatted, expected, "{:?}", locale);
+        }
+    }
+
+    #[test]
+    fn test_format_date() {
+        let date = NaiveDate::from_ymd_opt(2024, 3, 5).unwrap();
+        let cases = [
+            (Locale::Ru, "05.03.2024", "5 марта 2024 г."),
+            (Locale::EnUs, "03/05/2024", "March 5, 2024"),
+            (Locale::De, "05.03.2024", "5. März 2024"),
+            (Locale::Fr, "05/03/2024", "5 mars 2024"),
+        ];
+
+        for (locale, short, long) in cases {
+            let formatter = Formatter::new(locale);
+            assert_eq!(formatter.format_date(date, DateStyle::Short), short);
+            assert_eq!(formatter.format_date(date, DateStyle::Long), long);
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        let cases = [
+            (Locale::Ru, "1 234 567,89", Some("1234567.89")),
+            (Locale::Ru, "1234.50", Some("1234.50")),
+            (Locale::EnUs, "1,234,567.89", Some("1234567.89")),
+            (Locale::EnUs, "1,234", Some("1234")),
+            (Locale::De, "1.234.567,89 €", Some("1234567.89")),
+            (Locale::De, "100.10", Some("100.10")),
+            (Locale::De, "1.234", Some("1234")),
+            (Locale::Fr, "1\u{202f}234,5", Some("1234.5")),
+            (Locale::EnUs, "Price", None),
+        ];
+
+        for (locale, text, expected) in cases {
+            let parsed = Formatter::new(locale).parse_number(text);
+            assert_eq!(parsed, expected.map(dec), "{:?} {}", locale, text);
+        }
+    }
+}
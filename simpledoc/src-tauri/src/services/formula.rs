@@ -0,0 +1,645 @@
+use crate::models::{BlockContent, Document, Locale, TableRow};
+use crate::services::formatter::Formatter;
+use crate::services::template_engine::lookup;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Parsed formula expression
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(Decimal),
+    /// Cell name or data variable
+    Name(String),
+    Call(String, Vec<Expr>),
+    Negate(Box<Expr>),
+    Binary(Box<Expr>, char, Box<Expr>),
+}
+
+/// Position of a cell as (row, column), both 0-based
+type CellRef = (usize, usize);
+
+/// Result of a sub-expression: a number or the cells of a range
+#[derive(Debug, Clone)]
+enum Operand {
+    Number(Decimal),
+    List(Vec<Decimal>),
+}
+
+/// Error marking a formula that cannot be computed without generation data
+const NEEDS_DATA: &str = "Formula depends on generation data";
+
+/// Whether cell content is a formula (starts with `=`)
+pub fn is_formula(content: &str) -> bool {
+    content.trim_start().starts_with('=')
+}
+
+/// Compute every formula cell of a table in place
+///
+/// Identifiers refer to named cells first and to data variables otherwise.
+/// `col(n)` covers the numeric cells of column `n` in the rows above.
+/// Cells are read and results written in the notation of `locale`.
+pub fn compute_rows(rows: &mut [TableRow], data: &Value, locale: Locale) -> Result<(), String> {
+    let formatter = Formatter::new(locale);
+    let values = Calculator::new(rows, data, formatter, true).evaluate_all()?;
+    write_values(rows, values, formatter);
+    Ok(())
+}
+
+/// Compute the formula cells that do not depend on generation data
+///
+/// Formulas reading a data variable, or a cell that still holds a
+/// placeholder, keep their source text; all others are computed as usual.
+pub fn compute_rows_without_data(rows: &mut [TableRow], locale: Locale) -> Result<(), String> {
+    let formatter = Formatter::new(locale);
+    let mut calculator = Calculator::new(rows, &Value::Null, formatter, true);
+    calculator.without_data = true;
+    let values = calculator.evaluate_all()?;
+    write_values(rows, values, formatter);
+    Ok(())
+}
+
+fn write_values(rows: &mut [TableRow], values: Vec<(CellRef, Decimal)>, formatter: Formatter) {
+    for ((row, column), value) in values {
+        rows[row].cells[column].content = formatter.format_number(value, None);
+    }
+}
+
+/// Check formula syntax and circular references without data
+pub fn check_rows(rows: &[TableRow]) -> Result<(), String> {
+    Calculator::new(rows, &Value::Null, Formatter::new(Locale::default()), false)
+        .evaluate_all()
+        .map(|_| ())
+}
+
+/// Compute the formulas of every table in a document
+///
+/// Without data, formulas that depend on it are left as they are.
+pub fn compute_document(document: &mut Document, data: Option<&Value>) -> Result<(), String> {
+    let locale = document.metadata.locale;
+    for block in &mut document.blocks {
+        if let BlockContent::Table(table) = &mut block.content {
+            match data {
+                Some(data) => compute_rows(&mut table.rows, data, locale),
+                None => compute_rows_without_data(&mut table.rows, locale),
+            }
+            .map_err(|e| format!("{} (block {})", e, block.id))?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum CellState {
+    InProgress,
+    Done(Decimal),
+    NeedsData,
+}
+
+/// Evaluates the formulas of one table with memoization and cycle detection
+struct Calculator<'a> {
+    rows: &'a [TableRow],
+    data: &'a Value,
+    formatter: Formatter,
+    names: HashMap<String, CellRef>,
+    /// Missing data and non-numeric cells are errors, not zero
+    strict: bool,
+    /// Data variables and placeholder cells are unknown, not errors
+    without_data: bool,
+    states: HashMap<CellRef, CellState>,
+}
+
+impl<'a> Calculator<'a> {
+    fn new(rows: &'a [TableRow], data: &'a Value, formatter: Formatter, strict: bool) -> Self {
+        let mut names = HashMap::new();
+        for (r, row) in rows.iter().enumerate() {
+            for (c, cell) in row.cells.iter().enumerate() {
+                if let Some(name) = cell.name.as_ref().filter(|n| !n.is_empty()) {
+                    names.insert(name.clone(), (r, c));
+                }
+            }
+        }
+
+        Self {
+            rows,
+            data,
+            formatter,
+            names,
+            strict,
+            without_data: false,
+            states: HashMap::new(),
+        }
+    }
+
+    fn evaluate_all(mut self) -> Result<Vec<(CellRef, Decimal)>, String> {
+        let mut values = Vec::new();
+        for (r, row) in self.rows.iter().enumerate() {
+            for (c, cell) in row.cells.iter().enumerate() {
+                if is_formula(&cell.content) {
+                    match self.cell_value(r, c) {
+                        Ok(value) => values.push(((r, c), value)),
+                        Err(e) if self.without_data && e == NEEDS_DATA => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Numeric value of a cell, evaluating its formula if needed
+    fn cell_value(&mut self, r: usize, c: usize) -> Result<Decimal, String> {
+        let label = cell_label(r, c);
+        let content = self
+            .rows
+            .get(r)
+            .and_then(|row| row.cells.get(c))
+            .map(|cell| cell.content.as_str())
+            .ok_or_else(|| format!("Cell {} does not exist", label))?;
+
+        if !is_formula(content) {
+            if self.is_placeholder(content) {
+                return Err(NEEDS_DATA.to_string());
+            }
+            return match self.formatter.parse_number(content) {
+                Some(value) => Ok(value),
+                None if !self.strict => Ok(Decimal::ZERO),
+                None => Err(format!("Cell {} is not a number", label)),
+            };
+        }
+
+        match self.states.get(&(r, c)) {
+            Some(CellState::Done(value)) => return Ok(*value),
+            Some(CellState::InProgress) => {
+                return Err(format!("Circular reference in cell {}", label))
+            }
+            Some(CellState::NeedsData) => return Err(NEEDS_DATA.to_string()),
+            None => {}
+        }
+
+        self.states.insert((r, c), CellState::InProgress);
+        let source = content.trim_start()[1..].to_string();
+        let expr = parse(&source).map_err(|e| format!("Cell {}: {}", label, e))?;
+        let value = match self.evaluate(&expr, r) {
+            Err(e) if e == NEEDS_DATA => {
+                self.states.insert((r, c), CellState::NeedsData);
+                return Err(e);
+            }
+            result => result?,
+        };
+        let value = match value {
+            Operand::Number(value) => value,
+            Operand::List(_) => {
+                return Err(format!(
+                    "Cell {}: a range must be wrapped in SUM, MIN, ...",
+                    label
+                ))
+            }
+        };
+        self.states.insert((r, c), CellState::Done(value));
+        Ok(value)
+    }
+
+    fn evaluate(&mut self, expr: &Expr, row: usize) -> Result<Operand, String> {
+        match expr {
+            Expr::Number(value) => Ok(Operand::Number(*value)),
+            Expr::Name(name) => self.name_value(name).map(Operand::Number),
+            Expr::Negate(inner) => Ok(Operand::Number(-self.number(inner, row)?)),
+            Expr::Binary(left, op, right) => {
+                let (left, right) = (self.number(left, row)?, self.number(right, row)?);
+                let result = match op {
+                    '+' => left.checked_add(right),
+                    '-' => left.checked_sub(right),
+                    '*' => left.checked_mul(right),
+                    '/' if right.is_zero() => {
+                        return if self.strict {
+                            Err("Division by zero".to_string())
+                        } else {
+                            Ok(Operand::Number(Decimal::ZERO))
+                        }
+                    }
+                    // Division picks its own scale; drop the trailing zeros
+                    _ => left.checked_div(right).map(|v| v.normalize()),
+                };
+                result
+                    .map(Operand::Number)
+                    .ok_or_else(|| "Arithmetic overflow".to_string())
+            }
+            Expr::Call(name, args) => self.call(name, args, row),
+        }
+    }
+
+    fn number(&mut self, expr: &Expr, row: usize) -> Result<Decimal, String> {
+        match self.evaluate(expr, row)? {
+            Operand::Number(value) => Ok(value),
+            Operand::List(_) => Err("A range cannot be used as a number".to_string()),
+        }
+    }
+
+    fn name_value(&mut self, name: &str) -> Result<Decimal, String> {
+        if let Some(&(r, c)) = self.names.get(name) {
+            return self.cell_value(r, c);
+        }
+
+        let value = lookup(self.data, name).and_then(|value| match value {
+            Value::Number(n) => {
+                let text = n.to_string();
+                Decimal::from_str(&text)
+                    .or_else(|_| Decimal::from_scientific(&text))
+                    .ok()
+            }
+            Value::String(s) => self.formatter.parse_number(s),
+            _ => None,
+        });
+        match value {
+            Some(value) => Ok(value),
+            None if self.without_data => Err(NEEDS_DATA.to_string()),
+            None if !self.strict => Ok(Decimal::ZERO),
+            None => Err(format!("Unknown name or non-numeric variable: {}", name)),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], row: usize) -> Result<Operand, String> {
+        let function = name.to_uppercase();
+        match function.as_str() {
+            "COL" => {
+                let column = self.index_argument(&function, args, 0, row)?;
+                // Only the rows above, so totals never include themselves
+                let mut values = Vec::new();
+                for r in 0..row {
+                    let is_numeric = self.rows[r].cells.get(column).is_some_and(|cell| {
+                        is_formula(&cell.content)
+                            || self.is_placeholder(&cell.content)
+                            || self.formatter.parse_number(&cell.content).is_some()
+                    });
+                    if is_numeric {
+                        values.push(self.cell_value(r, column)?);
+                    }
+                }
+                Ok(Operand::List(values))
+            }
+            "CELL" => {
+                expect_args(&function, args, 2)?;
+                let r = self.index_argument(&function, args, 0, row)?;
+                let c = self.index_argument(&function, args, 1, row)?;
+                self.cell_value(r, c).map(Operand::Number)
+            }
+            "SUM" | "MIN" | "MAX" | "AVG" | "COUNT" => {
+                let mut values = Vec::new();
+                for arg in args {
+                    match self.evaluate(arg, row)? {
+                        Operand::Number(value) => values.push(value),
+                        Operand::List(list) => values.extend(list),
+                    }
+                }
+                aggregate(&function, &values).map(Operand::Number)
+            }
+            "ROUND" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err("ROUND expects 1 or 2 arguments".to_string());
+                }
+                let value = self.number(&args[0], row)?;
+                let places = match args.get(1) {
+                    Some(arg) => self.number(arg, row)?,
+                    None => Decimal::ZERO,
+                };
+                let places = places
+                    .to_u32()
+                    .filter(|p| *p <= 28 && places.fract().is_zero())
+                    .ok_or("ROUND expects 0 to 28 decimal places")?;
+                Ok(Operand::Number(value.round_dp_with_strategy(
+                    places,
+                    RoundingStrategy::MidpointAwayFromZero,
+                )))
+            }
+            "ABS" => {
+                expect_args(&function, args, 1)?;
+                Ok(Operand::Number(self.number(&args[0], row)?.abs()))
+            }
+            _ => Err(format!("Unknown function: {}", name)),
+        }
+    }
+
+    /// Whether a cell holds a placeholder left unresolved without data
+    fn is_placeholder(&self, content: &str) -> bool {
+        self.without_data && content.contains("{{")
+    }
+
+    /// 1-based row or column argument converted to an index
+    fn index_argument(
+        &mut self,
+        function: &str,
+        args: &[Expr],
+        position: usize,
+        row: usize,
+    ) -> Result<usize, String> {
+        if function == "COL" {
+            expect_args(function, args, 1)?;
+        }
+        let value = self.number(&args[position], row)?;
+        if !value.fract().is_zero() || value < Decimal::ONE {
+            return Err(format!("{} expects positive whole numbers", function));
+        }
+        value
+            .to_usize()
+            .map(|index| index - 1)
+            .ok_or_else(|| format!("{} index is too large", function))
+    }
+}
+
+fn expect_args(function: &str, args: &[Expr], count: usize) -> Result<(), String> {
+    if args.len() == count {
+        Ok(())
+    } else {
+        Err(format!("{} expects {} argument(s)", function, count))
+    }
+}
+
+fn aggregate(function: &str, values: &[Decimal]) -> Result<Decimal, String> {
+    let overflow = || "Arithmetic overflow".to_string();
+    match function {
+        "SUM" => values
+            .iter()
+            .try_fold(Decimal::ZERO, |sum, v| sum.checked_add(*v))
+            .ok_or_else(overflow),
+        "COUNT" => Ok(Decimal::from(values.len())),
+        "AVG" if values.is_empty() => Ok(Decimal::ZERO),
+        "AVG" => aggregate("SUM", values)?
+            .checked_div(Decimal::from(values.len()))
+            .map(|v| v.normalize())
+            .ok_or_else(overflow),
+        "MIN" => Ok(values.iter().copied().min().unwrap_or_default()),
+        _ => Ok(values.iter().copied().max().unwrap_or_default()),
+    }
+}
+
+/// Spreadsheet-style label of a cell, e.g. `C2`
+fn cell_label(row: usize, column: usize) -> String {
+    let mut letters = String::new();
+    let mut n = column + 1;
+    while n > 0 {
+        letters.insert(0, (b'A' + ((n - 1) % 26) as u8) as char);
+        n = (n - 1) / 26;
+    }
+    format!("{}{}", letters, row + 1)
+}
+
+/// Parse a formula without the leading `=`
+fn parse(source: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+    };
+    let expr = parser.expression()?;
+    parser.skip_whitespace();
+    match parser.peek() {
+        None => Ok(expr),
+        Some(c) => Err(format!("Unexpected '{}' in formula", c)),
+    }
+}
+
+/// Recursive descent parser: sum > product > unary > primary
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expression(&mut self) -> Result<Expr, String> {
+        let mut left = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(Box::new(left), op, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(left);
+            };
+            left = Expr::Binary(Box::new(left), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.expression()?;
+                if !self.eat(')') {
+                    return Err("Missing ')' in formula".to_string());
+                }
+                Ok(inner)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let text: String = self.chars[start..self.pos].iter().collect();
+                Decimal::from_str(&text)
+                    .map(Expr::Number)
+                    .map_err(|_| format!("Invalid number in formula: {}", text))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '[' | ']'))
+                {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+
+                if !self.eat('(') {
+                    return Ok(Expr::Name(name));
+                }
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expression()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        if !self.eat(',') && !self.eat(';') {
+                            return Err(format!("Expected ',' or ')' in {}()", name));
+                        }
+                    }
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Some(c) => Err(format!("Unexpected '{}' in formula", c)),
+            None => Err("Unexpected end of formula".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::TableCell;
+    use serde_json::json;
+
+    fn table(rows: &[&[(&str, Option<&str>)]]) -> Vec<TableRow> {
+        rows.iter()
+            .map(|cells| TableRow {
+                cells: cells
+                    .iter()
+                    .map(|(content, name)| TableCell {
+                        content: content.to_string(),
+                        styles: None,
+                        name: name.map(str::to_string),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn contents(rows: &[TableRow]) -> Vec<Vec<&str>> {
+        rows.iter()
+            .map(|r| r.cells.iter().map(|c| c.content.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_totals_with_decimal_arithmetic() {
+        let mut rows = table(&[
+            &[("Item", None), ("Price", None)],
+            &[("A", None), ("0.10", None)],
+            &[("B", None), ("0.20", None)],
+            &[("Subtotal", None), ("=SUM(col(2))", Some("subtotal"))],
+            &[
+                ("VAT", None),
+                ("=ROUND(subtotal * vat_rate, 2)", Some("vat")),
+            ],
+            &[("Total", None), ("=subtotal + vat - discount", None)],
+        ]);
+
+        compute_rows(
+            &mut rows,
+            &json!({ "vat_rate": 0.2, "discount": "0.01" }),
+            Locale::EnUs,
+        )
+        .unwrap();
+        let values = contents(&rows);
+        // f64 would give 0.30000000000000004
+        assert_eq!(values[3][1], "0.30");
+        assert_eq!(values[4][1], "0.06");
+        assert_eq!(values[5][1], "0.35");
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut rows = table(&[
+            &[("2", None), ("-3.5", None)],
+            &[
+                ("=MAX(col(1), 10) + MIN(4, 6)", None),
+                ("=ROUND(ABS(cell(1, 2)))", None),
+            ],
+            &[
+                ("=AVG(1, 2) * COUNT(col(2))", None),
+                ("=2.5 / (1 + 1)", None),
+            ],
+        ]);
+
+        compute_rows(&mut rows, &Value::Null, Locale::EnUs).unwrap();
+        let values = contents(&rows);
+        assert_eq!(values[1], vec!["14", "4"]);
+        assert_eq!(values[2], vec!["3.0", "1.25"]);
+    }
+
+    #[test]
+    fn test_locale_notation() {
+        let mut rows = table(&[
+            &[("1 200,50", None)],
+            &[("99,5", None)],
+            &[("=SUM(col(1)) * rate", None)],
+        ]);
+
+        compute_rows(&mut rows, &json!({ "rate": "1,5" }), Locale::Ru).unwrap();
+        assert_eq!(contents(&rows)[2][0], "1\u{a0}950,000");
+    }
+
+    #[test]
+    fn test_errors() {
+        let circular = table(&[&[("=b + 1", Some("a"))], &[("=a * 2", Some("b"))]]);
+        assert!(check_rows(&circular)
+            .unwrap_err()
+            .contains("Circular reference"));
+
+        let invalid = table(&[&[("=SUM(1,", None)]]);
+        assert!(check_rows(&invalid).is_err());
+
+        let unknown = table(&[&[("=price * 2", None)]]);
+        assert!(check_rows(&unknown).is_ok());
+        assert!(compute_rows(&mut unknown.clone(), &json!({}), Locale::EnUs).is_err());
+
+        let mut division = table(&[&[("=1 / 0", None)]]);
+        assert!(compute_rows(&mut division, &Value::Null, Locale::EnUs).is_err());
+    }
+
+    #[test]
+    fn test_compute_without_data() {
+        let mut rows = table(&[
+            &[("2", None), ("{{ price }}", None)],
+            &[("3", None), ("4", None)],
+            &[("=SUM(col(1))", None), ("=SUM(col(2))", None)],
+            &[
+                ("=ROUND(price * qty, 2)", Some("line")),
+                ("=line - 1", None),
+            ],
+            &[("=cell(3, 1) * 2", None)],
+        ]);
+
+        compute_rows_without_data(&mut rows, Locale::EnUs).unwrap();
+        let values = contents(&rows);
+        assert_eq!(values[2], vec!["5", "=SUM(col(2))"]);
+        assert_eq!(values[3], vec!["=ROUND(price * qty, 2)", "=line - 1"]);
+        assert_eq!(values[4], vec!["10"]);
+
+        let mut division = table(&[&[("=1 / 0", None)]]);
+        assert!(compute_rows_without_data(&mut division, Locale::EnUs).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+use crate::models::{AppError, Document, Result};
+use crate::services::{assets, migration};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use log::{debug, info};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// Bumped whenever `SCHEMA` changes
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS revisions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        document_id TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        author TEXT,
+        message TEXT,
+        title TEXT NOT NULL,
+        skeleton TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_revisions_document
+        ON revisions (document_id, created_at DESC);
+
+    CREATE TABLE IF NOT EXISTS revision_blocks (
+        revision_id INTEGER NOT NULL REFERENCES revisions (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        hash TEXT NOT NULL,
+        PRIMARY KEY (revision_id, position)
+    );
+    CREATE INDEX IF NOT EXISTS idx_revision_blocks_hash ON revision_blocks (hash);
+
+    CREATE TABLE IF NOT EXISTS blobs (
+        hash TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );
+";
+
+/// Summary of a stored revision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionInfo {
+    pub id: i64,
+    #[serde(rename = "documentId")]
+    pub document_id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    pub title: String,
+    #[serde(rename = "blockCount")]
+    pub block_count: usize,
+}
+
+/// Which revisions survive pruning
+///
+/// Everything younger than `keep_all` is kept, then the newest revision of
+/// each day until `keep_daily`, then the newest of each week. Weekly
+/// revisions older than `keep_weekly` are dropped; `None` keeps them forever.
+/// The newest revision of a document is never dropped.
+#[derive(Debug, Clone, Copy)]
+pub struct RetentionPolicy {
+    pub keep_all: Duration,
+    pub keep_daily: Duration,
+    pub keep_weekly: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            keep_all: Duration::hours(24),
+            keep_daily: Duration::days(30),
+            keep_weekly: None,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Ids of the revisions to drop, given `(id, created_at)` pairs
+    pub fn expired(&self, revisions: &[(i64, DateTime<Utc>)], now: DateTime<Utc>) -> Vec<i64> {
+        let mut sorted = revisions.to_vec();
+        sorted.sort_by_key(|(_, created_at)| std::cmp::Reverse(*created_at));
+
+        let mut days = HashSet::new();
+        let mut weeks = HashSet::new();
+        let mut expired = Vec::new();
+
+        for (index, (id, created_at)) in sorted.into_iter().enumerate() {
+            let age = now - created_at;
+            let keep = if index == 0 || age <= self.keep_all {
+                true
+            } else if age <= self.keep_daily {
+                days.insert(created_at.date_naive())
+            } else if self.keep_weekly.is_none_or(|limit| age <= limit) {
+                let week = created_at.iso_week();
+                weeks.insert((week.year(), week.week()))
+            } else {
+                false
+            };
+            if !keep {
+                expired.push(id);
+            }
+        }
+        expired
+    }
+}
+
+/// Keeps a revision of every saved document
+///
+/// Blocks are stored once per distinct payload, so a revision only costs
+/// the blocks that changed plus the page and metadata skeleton.
+pub struct RevisionStore {
+    conn: Mutex<Connection>,
+    retention: RetentionPolicy,
+}
+
+impl RevisionStore {
+    /// Open or create the history database at `path`
+    pub fn open(path: &Path, retention: RetentionPolicy) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(AppError::InvalidData(format!(
+                "History schema version {} is newer than supported version {}",
+                version, SCHEMA_VERSION
+            )));
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            retention,
+        })
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Store a revision of the document and prune its history
+    pub fn record(
+        &self,
+        document: &Document,
+        author: Option<&str>,
+        message: Option<&str>,
+    ) -> Result<i64> {
+        let mut skeleton = document.clone();
+        let blocks = std::mem::take(&mut skeleton.blocks);
+        let skeleton = serde_json::to_string(&skeleton)?;
+
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO revisions (document_id, created_at, author, message, title, skeleton)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                document.id,
+                Utc::now(),
+                author,
+                message,
+                document.metadata.title,
+                skeleton
+            ],
+        )?;
+        let revision_id = tx.last_insert_rowid();
+
+        for (position, block) in blocks.iter().enumerate() {
+            let data = serde_json::to_string(block)?;
+            let hash = format!("{:x}", Sha256::digest(data.as_bytes()));
+            tx.execute(
+                "INSERT OR IGNORE INTO blobs (hash, data) VALUES (?1, ?2)",
+                params![hash, data],
+            )?;
+            tx.execute(
+                "INSERT INTO revision_blocks (revision_id, position, hash) VALUES (?1, ?2, ?3)",
+                params![revision_id, position as i64, hash],
+            )?;
+        }
+        tx.commit()?;
+        drop(conn);
+
+        debug!(
+            "Recorded revision {} of document {}",
+            revision_id, document.id
+        );
+        self.prune(&document.id, Utc::now())?;
+        Ok(revision_id)
+    }
+
+    /// Revisions of a document, newest first
+    pub fn list(&self, document_id: &str) -> Result<Vec<RevisionInfo>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT r.id, r.document_id, r.created_at, r.author, r.message, r.title,
+                    (SELECT COUNT(*) FROM revision_blocks WHERE revision_id = r.id)
+             FROM revisions r
+             WHERE r.document_id = ?1
+             ORDER BY r.created_at DESC, r.id DESC",
+        )?;
+        let rows = statement.query_map([document_id], |row| {
+            Ok(RevisionInfo {
+                id: row.get(0)?,
+                document_id: row.get(1)?,
+                created_at: row.get(2)?,
+                author: row.get(3)?,
+                message: row.get(4)?,
+                title: row.get(5)?,
+                block_count: row.get::<_, i64>(6)? as usize,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Rebuild the document as it was at a revision
+    pub fn load(&self, document_id: &str, revision_id: i64) -> Result<Document> {
+        let conn = self.conn();
+        let skeleton: Option<String> = conn
+            .query_row(
+                "SELECT skeleton FROM revisions WHERE id = ?1 AND document_id = ?2",
+                params![revision_id, document_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let skeleton = skeleton.ok_or_else(|| {
+            AppError::RevisionNotFound(format!("{} of document {}", revision_id, document_id))
+        })?;
+        // Revisions keep the schema they were recorded with
+        let mut document: Value = serde_json::from_str(&skeleton)?;
+        let mut blocks = Vec::new();
+
+        let mut statement = conn.prepare(
+            "SELECT b.data FROM revision_blocks rb
+             JOIN blobs b ON b.hash = rb.hash
+             WHERE rb.revision_id = ?1
+             ORDER BY rb.position",
+        )?;
+        let rows = statement.query_map([revision_id], |row| row.get::<_, String>(0))?;
+        for data in rows {
+            blocks.push(serde_json::from_str::<Value>(&data?)?);
+        }
+        document["blocks"] = Value::Array(blocks);
+        migration::document_from_value(document)
+    }
+
+    /// Drop revisions outside the retention policy and unreferenced blocks
+    pub fn prune(&self, document_id: &str, now: DateTime<Utc>) -> Result<usize> {
+        let conn = self.conn();
+        let revisions = {
+            let mut statement =
+                conn.prepare("SELECT id, created_at FROM revisions WHERE document_id = ?1")?;
+            let rows = statement.query_map([document_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<Vec<_>>>()?
+        };
+
+        let expired = self.retention.expired(&revisions, now);
+        if expired.is_empty() {
+            return Ok(0);
+        }
+
+        let tx = conn.unchecked_transaction()?;
+        for id in &expired {
+            tx.execute("DELETE FROM revisions WHERE id = ?1", [id])?;
+        }
+        delete_unreferenced_blobs(&tx)?;
+        tx.commit()?;
+
+        info!(
+            "Pruned {} revisions of document {}",
+            expired.len(),
+            document_id
+        );
+        Ok(expired.len())
+    }
+
+    /// Drop every revision of a document, e.g. once it is purged
+    pub fn forget(&self, document_id: &str) -> Result<usize> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        let deleted = tx.execute(
+            "DELETE FROM revisions WHERE document_id = ?1",
+            [document_id],
+        )?;
+        delete_unreferenced_blobs(&tx)?;
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Add the hashes of all assets referenced by any revision to `hashes`
+    pub fn collect_asset_references(&self, hashes: &mut HashSet<String>) -> Result<()> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT skeleton FROM revisions UNION ALL SELECT data FROM blobs")?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        for text in rows {
+            assets::collect_references(&text?, hashes);
+        }
+        Ok(())
+    }
+}
+
+/// Drop block contents no revision refers to anymore
+fn delete_unreferenced_blobs(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM blobs
+         WHERE NOT EXISTS (SELECT 1 FROM revision_blocks WHERE hash = blobs.hash)",
+        [],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Block, BlockType};
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn blob_count(store: &RevisionStore) -> i64 {
+        store
+            .conn()
+            .query_row("SELECT COUNT(*) FROM blobs", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_record_and_load_revisions() {
+        let temp_dir = TempDir::new().unwrap();
+        let store =
+            RevisionStore::open(&temp_dir.path().join("history.db"), Default::default()).unwrap();
+
+        let mut document = Document::new("Offer".to_string());
+        document.add_block(Block::for_test(BlockType::Text, 0.0, 0.0, 100.0, 20.0));
+        document.add_block(Block::for_test(BlockType::Text, 0.0, 40.0, 100.0, 20.0));
+        let first = store.record(&document, Some("anna"), None).unwrap();
+
+        // Only the changed block adds a payload
+        document.metadata.title = "Offer v2".to_string();
+        document.blocks[1].position.y = 80.0;
+        let second = store
+            .record(&document, Some("anna"), Some("Moved"))
+            .unwrap();
+        assert_eq!(blob_count(&store), 3);
+
+        let revisions = store.list(&document.id).unwrap();
+        assert_eq!(
+            revisions.iter().map(|r| r.id).collect::<Vec<_>>(),
+            vec![second, first]
+        );
+        assert_eq!(revisions[0].message.as_deref(), Some("Moved"));
+        assert_eq!(revisions[0].block_count, 2);
+
+        let old = store.load(&document.id, first).unwrap();
+        assert_eq!(old.metadata.title, "Offer");
+        assert_eq!(old.blocks.len(), 2);
+        assert_eq!(old.blocks[1].position.y, 40.0);
+        assert_eq!(old.blocks[1].id, document.blocks[1].id);
+
+        assert!(matches!(
+            store.load("other", first),
+            Err(AppError::RevisionNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let now = Utc.with_ymd_and_hms(2024, 6, 30, 12, 0, 0).unwrap();
+        let at = |hours: i64| now - Duration::hours(hours);
+        let revisions = vec![
+            (1, at(1)),
+            (2, at(20)),
+            // Same day, two days ago: only the newest stays
+            (3, at(48)),
+            (4, at(50)),
+            // Same ISO week, ten weeks ago
+            (5, at(24 * 70)),
+            (6, at(24 * 71)),
+        ];
+
+        let policy = RetentionPolicy::default();
+        assert_eq!(policy.expired(&revisions, now), vec![4, 6]);
+
+        let policy = RetentionPolicy {
+            keep_weekly: Some(Duration::days(60)),
+            ..Default::default()
+        };
+        assert_eq!(policy.expired(&revisions, now), vec![4, 5, 6]);
+
+        // The newest revision survives any policy
+        assert!(policy.expired(&[(7, at(24 * 365))], now).is_empty());
+    }
+
+    #[test]
+    fn test_prune_removes_unreferenced_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = RetentionPolicy {
+            keep_all: Duration::zero(),
+            keep_daily: Duration::zero(),
+            keep_weekly: Some(Duration::zero()),
+        };
+        let store = RevisionStore::open(&temp_dir.path().join("history.db"), policy).unwrap();
+
+        let mut document = Document::new("Offer".to_string());
+        document.add_block(Block::for_test(BlockType::Text, 0.0, 0.0, 100.0, 20.0));
+        store.record(&document, None, None).unwrap();
+        document.blocks[0].position.y = 10.0;
+        store.record(&document, None, None).unwrap();
+
+        assert_eq!(store.list(&document.id).unwrap().len(), 1);
+        assert_eq!(blob_count(&store), 1);
+
+        assert_eq!(store.forget(&document.id).unwrap(), 1);
+        assert!(store.list(&document.id).unwrap().is_empty());
+        assert_eq!(blob_count(&store), 0);
+    }
+}
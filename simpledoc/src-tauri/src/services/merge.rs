@@ -0,0 +1,659 @@
+use crate::models::{plain_text, AppError, Document, Paragraph, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// Fields that are merged as a whole: moving a block sets x and y together
+const ATOMIC_FIELDS: &[&str] = &["position", "size", "transform"];
+
+/// Side of a merge
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeSide {
+    Ours,
+    Theirs,
+}
+
+/// Part of the document a conflict belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictScope {
+    Metadata,
+    Page,
+    Block,
+}
+
+/// Property changed differently on both sides
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MergeConflict {
+    pub id: String,
+    pub scope: ConflictScope,
+    /// Page or block id; not set for metadata
+    #[serde(rename = "targetId", skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+    /// Dotted path of the property, e.g. `content.text`; not set when the
+    /// whole page or block was removed on one side and changed on the other
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Value in each version; `None` where the property or block is missing
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// Merged document with the conflicts left to resolve
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeResult {
+    /// Conflicting properties hold our value until resolved
+    pub document: Document,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+    /// Settle a conflict by taking one side's value
+    pub fn resolve(&mut self, conflict_id: &str, side: MergeSide) -> Result<()> {
+        let index = self
+            .conflicts
+            .iter()
+            .position(|c| c.id == conflict_id)
+            .ok_or_else(|| AppError::InvalidData(format!("Unknown conflict {}", conflict_id)))?;
+        let conflict = self.conflicts.remove(index);
+        if side == MergeSide::Ours {
+            return Ok(());
+        }
+
+        let mut document = serde_json::to_value(&self.document)?;
+        let target = match conflict.scope {
+            ConflictScope::Metadata => &mut document["metadata"],
+            ConflictScope::Page | ConflictScope::Block => {
+                let key = if conflict.scope == ConflictScope::Page {
+                    "pages"
+                } else {
+                    "blocks"
+                };
+                let target_id = conflict.target_id.as_deref().unwrap_or_default();
+                let Some(items) = document[key].as_array_mut() else {
+                    return Err(AppError::InvalidData(format!("Document has no {}", key)));
+                };
+                let index = items.iter().position(|item| item_id(item) == target_id);
+
+                match (index, &conflict.field, &conflict.theirs) {
+                    (Some(index), Some(_), _) => &mut items[index],
+                    (Some(index), None, Some(item)) => {
+                        items[index] = item.clone();
+                        return self.reload(document);
+                    }
+                    (Some(_), None, None) if conflict.scope == ConflictScope::Page => {
+                        // Their side removed the page, so its blocks go too
+                        self.document
+                            .remove_page(target_id)
+                            .map_err(AppError::ValidationError)?;
+                        return Ok(());
+                    }
+                    (Some(index), None, None) => {
+                        items.remove(index);
+                        return self.reload(document);
+                    }
+                    (None, None, Some(item)) => {
+                        items.push(item.clone());
+                        return self.reload(document);
+                    }
+                    (None, _, _) => {
+                        return Err(AppError::InvalidData(format!(
+                            "{} not found in merged document",
+                            target_id
+                        )))
+                    }
+                }
+            }
+        };
+
+        if let Some(field) = &conflict.field {
+            set_path(target, field, conflict.theirs);
+        }
+        self.reload(document)
+    }
+
+    fn reload(&mut self, mut document: Value) -> Result<()> {
+        if let Some(blocks) = document["blocks"].as_array_mut() {
+            blocks.iter_mut().for_each(sync_rich_text);
+        }
+        self.document = serde_json::from_value(document)?;
+        Ok(())
+    }
+}
+
+/// Three-way merge of two versions of a document that share `base`
+///
+/// Pages and blocks are matched by id and merged property by property, so
+/// edits to different properties of the same block combine. A property
+/// changed differently on both sides is a conflict and keeps our value.
+/// Page and block order follows the side that reordered, or ours when both
+/// did.
+pub fn merge_documents(base: &Document, ours: &Document, theirs: &Document) -> Result<MergeResult> {
+    let [base_json, ours_json, theirs_json] = [base, ours, theirs].map(|document| {
+        let mut value = serde_json::to_value(document).unwrap_or_default();
+        // Every save bumps it, so it would always conflict
+        if let Some(metadata) = value["metadata"].as_object_mut() {
+            metadata.remove("updatedAt");
+        }
+        if let Some(blocks) = value["blocks"].as_array_mut() {
+            blocks.iter_mut().for_each(strip_rich_text);
+        }
+        value
+    });
+    let mut conflicts = Vec::new();
+
+    let mut raw = Vec::new();
+    let metadata = merge_value(
+        "",
+        Some(&base_json["metadata"]),
+        Some(&ours_json["metadata"]),
+        Some(&theirs_json["metadata"]),
+        &mut raw,
+    );
+    conflicts.extend(
+        raw.into_iter()
+            .map(|raw| conflict(ConflictScope::Metadata, None, raw)),
+    );
+
+    let items = |json: &Value, key: &str| json[key].as_array().cloned().unwrap_or_default();
+    let [base_pages, ours_pages, theirs_pages] =
+        [&base_json, &ours_json, &theirs_json].map(|json| items(json, "pages"));
+    let mut pages = merge_items(
+        ConflictScope::Page,
+        &base_pages,
+        &ours_pages,
+        &theirs_pages,
+        &mut conflicts,
+    );
+    let mut blocks = merge_items(
+        ConflictScope::Block,
+        &items(&base_json, "blocks"),
+        &items(&ours_json, "blocks"),
+        &items(&theirs_json, "blocks"),
+        &mut conflicts,
+    );
+
+    // A page removed on one side may still hold blocks added on the other
+    for block in &blocks {
+        let page_id = block["pageId"].as_str().unwrap_or_default();
+        if pages.iter().any(|page| item_id(page) == page_id) {
+            continue;
+        }
+        let find = |pages: &[Value]| pages.iter().find(|page| item_id(page) == page_id).cloned();
+        let (ours_page, theirs_page) = (find(&ours_pages), find(&theirs_pages));
+        let Some(page) = ours_page.clone().or_else(|| theirs_page.clone()) else {
+            continue;
+        };
+        let index = ours_pages
+            .iter()
+            .chain(&theirs_pages)
+            .position(|p| item_id(p) == page_id)
+            .unwrap_or(pages.len())
+            .min(pages.len());
+        pages.insert(index, page);
+        conflicts.push(conflict(
+            ConflictScope::Page,
+            Some(page_id),
+            (String::new(), [find(&base_pages), ours_page, theirs_page]),
+        ));
+    }
+
+    blocks.iter_mut().for_each(sync_rich_text);
+    let mut merged = ours_json.clone();
+    merged["metadata"] = metadata.unwrap_or_default();
+    merged["metadata"]["updatedAt"] = serde_json::to_value(ours.metadata.updated_at)?;
+    merged["pages"] = Value::Array(pages);
+    merged["blocks"] = Value::Array(blocks);
+
+    let mut document: Document = serde_json::from_value(merged)?;
+    document.touch();
+    Ok(MergeResult {
+        document,
+        conflicts,
+    })
+}
+
+/// Rich text blocks derive `text` from their paragraphs, so only the
+/// paragraphs are merged
+fn strip_rich_text(block: &mut Value) {
+    if let Some(content) = block.get_mut("content").and_then(Value::as_object_mut) {
+        if content.contains_key("paragraphs") {
+            content.remove("text");
+        }
+    }
+}
+
+/// Restore the `text` of rich text blocks from their merged paragraphs
+fn sync_rich_text(block: &mut Value) {
+    let Some(content) = block.get_mut("content").and_then(Value::as_object_mut) else {
+        return;
+    };
+    let Some(paragraphs) = content.get("paragraphs") else {
+        return;
+    };
+    if let Ok(paragraphs) = serde_json::from_value::<Vec<Paragraph>>(paragraphs.clone()) {
+        content.insert("text".to_string(), Value::String(plain_text(&paragraphs)));
+    }
+}
+
+/// Conflicting property: path and the base, ours and theirs values
+type RawConflict = (String, [Option<Value>; 3]);
+
+fn conflict(scope: ConflictScope, target_id: Option<&str>, raw: RawConflict) -> MergeConflict {
+    let (path, [base, ours, theirs]) = raw;
+    MergeConflict {
+        id: Uuid::new_v4().to_string(),
+        scope,
+        target_id: target_id.map(str::to_string),
+        field: (!path.is_empty()).then_some(path),
+        base,
+        ours,
+        theirs,
+    }
+}
+
+fn item_id(item: &Value) -> &str {
+    item["id"].as_str().unwrap_or_default()
+}
+
+/// Merge lists of pages or blocks by id
+fn merge_items(
+    scope: ConflictScope,
+    base: &[Value],
+    ours: &[Value],
+    theirs: &[Value],
+    conflicts: &mut Vec<MergeConflict>,
+) -> Vec<Value> {
+    let ids = |items: &[Value]| -> Vec<String> {
+        items.iter().map(|item| item_id(item).to_string()).collect()
+    };
+    let find = |items: &'_ [Value], id: &str| -> Option<Value> {
+        items.iter().find(|item| item_id(item) == id).cloned()
+    };
+
+    let mut merged = Vec::new();
+    for id in merge_order(&ids(base), &ids(ours), &ids(theirs)) {
+        let mut raw = Vec::new();
+        let item = merge_value(
+            "",
+            find(base, &id).as_ref(),
+            find(ours, &id).as_ref(),
+            find(theirs, &id).as_ref(),
+            &mut raw,
+        );
+        conflicts.extend(raw.into_iter().map(|raw| conflict(scope, Some(&id), raw)));
+        merged.extend(item);
+    }
+    merged
+}
+
+/// Order of the ids on both sides
+///
+/// Starts from the side that reordered the shared ids (ours when neither or
+/// both did) and inserts the other side's new ids after their predecessor.
+fn merge_order(base: &[String], ours: &[String], theirs: &[String]) -> Vec<String> {
+    let shared = |list: &[String]| -> Vec<String> {
+        list.iter()
+            .filter(|id| base.contains(id) && ours.contains(id) && theirs.contains(id))
+            .cloned()
+            .collect()
+    };
+    let (mut order, other) = if shared(ours) == shared(base) && shared(theirs) != shared(base) {
+        (theirs.to_vec(), ours)
+    } else {
+        (ours.to_vec(), theirs)
+    };
+
+    for (i, id) in other.iter().enumerate() {
+        if order.contains(id) {
+            continue;
+        }
+        let index = other[..i]
+            .iter()
+            .rev()
+            .find_map(|previous| order.iter().position(|o| o == previous))
+            .map_or(0, |index| index + 1);
+        order.insert(index, id.clone());
+    }
+    order
+}
+
+/// Merge one value; `None` means missing (or removed)
+///
+/// Objects are merged key by key and arrays of unchanged length item by
+/// item, so conflicts are reported for the innermost property.
+fn merge_value(
+    path: &str,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<RawConflict>,
+) -> Option<Value> {
+    if ours == theirs || base == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    let name = path.rsplit('.').next().unwrap_or(path);
+
+    if !ATOMIC_FIELDS.contains(&name) {
+        match (base, ours, theirs) {
+            (base, Some(Value::Object(ours)), Some(Value::Object(theirs)))
+                if base.is_none_or(Value::is_object) =>
+            {
+                let empty = Map::new();
+                let base = base.and_then(Value::as_object).unwrap_or(&empty);
+                let mut keys: Vec<&String> = ours.keys().collect();
+                for key in theirs.keys().chain(base.keys()) {
+                    if !keys.contains(&key) {
+                        keys.push(key);
+                    }
+                }
+
+                let mut merged = Map::new();
+                for key in keys {
+                    let value = merge_value(
+                        &join(key),
+                        base.get(key),
+                        ours.get(key),
+                        theirs.get(key),
+                        conflicts,
+                    );
+                    if let Some(value) = value {
+                        merged.insert(key.clone(), value);
+                    }
+                }
+                return Some(Value::Object(merged));
+            }
+            (Some(Value::Array(base)), Some(Value::Array(ours)), Some(Value::Array(theirs)))
+                if base.len() == ours.len() && ours.len() == theirs.len() =>
+            {
+                let merged = (0..ours.len())
+                    .map(|i| {
+                        merge_value(
+                            &join(&i.to_string()),
+                            Some(&base[i]),
+                            Some(&ours[i]),
+                            Some(&theirs[i]),
+                            conflicts,
+                        )
+                        .unwrap_or_default()
+                    })
+                    .collect();
+                return Some(Value::Array(merged));
+            }
+            _ => {}
+        }
+    }
+
+    conflicts.push((
+        path.to_string(),
+        [base.cloned(), ours.cloned(), theirs.cloned()],
+    ));
+    ours.cloned()
+}
+
+/// Set (or with `None` remove) the value at a dotted path
+fn set_path(root: &mut Value, path: &str, value: Option<Value>) {
+    let mut segments: Vec<&str> = path.split('.').collect();
+    let Some(last) = segments.pop() else {
+        return;
+    };
+
+    let mut current = root;
+    for segment in segments {
+        current = match current {
+            Value::Array(items) => {
+                match segment.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                    Some(item) => item,
+                    None => return,
+                }
+            }
+            _ => {
+                if !current.is_object() {
+                    *current = Value::Object(Map::new());
+                }
+                current
+                    .as_object_mut()
+                    .map(|map| {
+                        map.entry(segment)
+                            .or_insert_with(|| Value::Object(Map::new()))
+                    })
+                    .expect("value is an object")
+            }
+        };
+    }
+
+    match (current, value) {
+        (Value::Array(items), value) => {
+            if let Some(item) = last.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                *item = value.unwrap_or_default();
+            }
+        }
+        (Value::Object(map), Some(value)) => {
+            map.insert(last.to_string(), value);
+        }
+        (Value::Object(map), None) => {
+            map.remove(last);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Block, BlockContent, BlockType, Page, Position, TextRun};
+
+    fn text_block(document: &mut Document, text: &str, y: f64) -> String {
+        let block = Block::for_test(BlockType::Text, 10.0, y, 200.0, 20.0).with_text(text);
+        let id = block.id.clone();
+        document.add_block(block);
+        id
+    }
+
+    fn text_of(document: &Document, block_id: &str) -> String {
+        match &document.get_block(block_id).unwrap().content {
+            BlockContent::Text(content) => content.text.clone(),
+            _ => panic!("not a text block"),
+        }
+    }
+
+    fn edit_text(document: &mut Document, block_id: &str, text: &str) {
+        let mut block = document.get_block(block_id).unwrap().clone();
+        if let BlockContent::Text(content) = &mut block.content {
+            content.text = text.to_string();
+        }
+        document.update_block(block).unwrap();
+    }
+
+    #[test]
+    fn test_merge_order() {
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let base = ids(&["a", "b", "c"]);
+
+        assert_eq!(
+            merge_order(
+                &base,
+                &ids(&["a", "x", "b", "c"]),
+                &ids(&["a", "b", "c", "y"])
+            ),
+            ids(&["a", "x", "b", "c", "y"])
+        );
+        assert_eq!(
+            merge_order(&base, &ids(&["b", "a", "c"]), &ids(&["c", "b", "a"])),
+            ids(&["b", "a", "c"])
+        );
+        assert_eq!(
+            merge_order(&base, &ids(&["a", "b", "c", "x"]), &ids(&["c", "b", "a"])),
+            ids(&["c", "x", "b", "a"])
+        );
+    }
+
+    #[test]
+    fn test_non_conflicting_changes_merge() {
+        let mut base = Document::new("Proposal".to_string());
+        let intro = text_block(&mut base, "Introduction", 10.0);
+        let prices = text_block(&mut base, "Prices", 50.0);
+        let legal = text_block(&mut base, "Legal", 90.0);
+
+        let mut ours = base.clone();
+        edit_text(&mut ours, &intro, "Introduction for ACME");
+        ours.metadata.title = "Proposal for ACME".to_string();
+
+        let mut theirs = base.clone();
+        let mut block = theirs.get_block(&prices).unwrap().clone();
+        block.position = Position { x: 10.0, y: 300.0 };
+        theirs.update_block(block).unwrap();
+        theirs.remove_block(&legal);
+        let added = text_block(&mut theirs, "Timeline", 120.0);
+        theirs.metadata.tags = Some(vec!["sales".to_string()]);
+
+        let result = merge_documents(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+
+        let document = &result.document;
+        assert_eq!(document.id, ours.id);
+        assert_eq!(document.metadata.title, "Proposal for ACME");
+        assert_eq!(document.metadata.tags, Some(vec!["sales".to_string()]));
+        assert_eq!(text_of(document, &intro), "Introduction for ACME");
+        assert_eq!(document.get_block(&prices).unwrap().position.y, 300.0);
+        assert!(document.get_block(&legal).is_none());
+        assert_eq!(text_of(document, &added), "Timeline");
+    }
+
+    #[test]
+    fn test_conflicts_are_reported_per_field() {
+        let mut base = Document::new("Proposal".to_string());
+        let intro = text_block(&mut base, "Introduction", 10.0);
+
+        let mut ours = base.clone();
+        edit_text(&mut ours, &intro, "Our introduction");
+
+        let mut theirs = base.clone();
+        edit_text(&mut theirs, &intro, "Their introduction");
+        let mut block = theirs.get_block(&intro).unwrap().clone();
+        if let BlockContent::Text(content) = &mut block.content {
+            content.color = "#ff0000".to_string();
+        }
+        theirs.update_block(block).unwrap();
+
+        let mut result = merge_documents(&base, &ours, &theirs).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        let conflict = result.conflicts[0].clone();
+        assert_eq!(conflict.scope, ConflictScope::Block);
+        assert_eq!(conflict.target_id.as_deref(), Some(intro.as_str()));
+        assert_eq!(conflict.field.as_deref(), Some("content.text"));
+        assert_eq!(conflict.theirs, Some(Value::from("Their introduction")));
+
+        // Our text wins until resolved; their color merges cleanly
+        assert_eq!(text_of(&result.document, &intro), "Our introduction");
+        match &result.document.get_block(&intro).unwrap().content {
+            BlockContent::Text(content) => assert_eq!(content.color, "#ff0000"),
+            _ => unreachable!(),
+        }
+
+        result.resolve(&conflict.id, MergeSide::Theirs).unwrap();
+        assert!(result.conflicts.is_empty());
+        assert_eq!(text_of(&result.document, &intro), "Their introduction");
+        assert!(result.resolve(&conflict.id, MergeSide::Ours).is_err());
+    }
+
+    #[test]
+    fn test_removed_and_changed_conflicts() {
+        let mut base = Document::new("Proposal".to_string());
+        base.insert_page(Page::default(), None);
+        let second_page = base.pages[1].id.clone();
+        let intro = text_block(&mut base, "Introduction", 10.0);
+
+        let mut ours = base.clone();
+        ours.remove_block(&intro);
+        let mut block = Block::for_test(BlockType::Spacer, 0.0, 0.0, 10.0, 10.0);
+        block.page_id = second_page.clone();
+        let spacer = block.id.clone();
+        ours.add_block(block);
+
+        let mut theirs = base.clone();
+        edit_text(&mut theirs, &intro, "Their introduction");
+        theirs.remove_page(&second_page).unwrap();
+
+        let mut result = merge_documents(&base, &ours, &theirs).unwrap();
+        assert_eq!(result.conflicts.len(), 2);
+        assert!(result.document.get_block(&intro).is_none());
+        assert!(result.document.get_page(&second_page).is_some());
+
+        let block_conflict = result
+            .conflicts
+            .iter()
+            .find(|c| c.scope == ConflictScope::Block)
+            .unwrap()
+            .clone();
+        assert_eq!(block_conflict.field, None);
+        assert_eq!(block_conflict.ours, None);
+        result
+            .resolve(&block_conflict.id, MergeSide::Theirs)
+            .unwrap();
+        assert_eq!(text_of(&result.document, &intro), "Their introduction");
+
+        let page_conflict = result.conflicts[0].clone();
+        assert_eq!(page_conflict.scope, ConflictScope::Page);
+        result
+            .resolve(&page_conflict.id, MergeSide::Theirs)
+            .unwrap();
+        assert!(result.document.get_page(&second_page).is_none());
+        assert!(result.document.get_block(&spacer).is_none());
+    }
+
+    #[test]
+    fn test_rich_text_merges_by_paragraph() {
+        let mut base = Document::new("Offer".to_string());
+        let intro = text_block(&mut base, "", 10.0);
+        let set_paragraphs = |document: &mut Document, first: TextRun, second: &str| {
+            let mut block = document.get_block(&intro).unwrap().clone();
+            if let BlockContent::Text(content) = &mut block.content {
+                content.set_paragraphs(vec![
+                    Paragraph {
+                        runs: vec![first],
+                        ..Default::default()
+                    },
+                    Paragraph::plain(second.to_string()),
+                ]);
+            }
+            document.update_block(block).unwrap();
+        };
+        set_paragraphs(&mut base, TextRun::plain("Price".to_string()), "Terms");
+
+        let mut ours = base.clone();
+        let bold = TextRun {
+            bold: true,
+            ..TextRun::plain("Price".to_string())
+        };
+        set_paragraphs(&mut ours, bold, "Terms");
+        let mut theirs = base.clone();
+        set_paragraphs(
+            &mut theirs,
+            TextRun::plain("Price".to_string()),
+            "New terms",
+        );
+
+        let result = merge_documents(&base, &ours, &theirs).unwrap();
+        assert!(result.conflicts.is_empty());
+        assert_eq!(text_of(&result.document, &intro), "Price\nNew terms");
+        let BlockContent::Text(content) = &result.document.get_block(&intro).unwrap().content
+        else {
+            panic!("not a text block");
+        };
+        assert!(content.paragraphs[0].runs[0].bold);
+    }
+}
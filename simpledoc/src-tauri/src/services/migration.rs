@@ -0,0 +1,361 @@
+use crate::models::{AppError, Document, Result, SCHEMA_VERSION};
+use log::info;
+use serde_json::{json, Map, Value};
+
+/// Upgrades document JSON by one schema version
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades schema version `n + 1` to `n + 2`
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize - 1] =
+    [v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+/// Schema version of document JSON
+///
+/// Documents written before versioning have no `schemaVersion` and count as
+/// version 1.
+pub fn schema_version(value: &Value) -> Result<u32> {
+    match value.get("schemaVersion") {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .filter(|v| *v >= 1)
+            .map(|v| v as u32)
+            .ok_or_else(|| AppError::InvalidData(format!("Invalid schema version: {}", version))),
+    }
+}
+
+/// Upgrade document JSON to the current schema in place
+///
+/// Returns the version it had. Fails with `UnsupportedSchemaVersion` for
+/// documents from a newer release, which must not be read or overwritten.
+pub fn migrate(value: &mut Value) -> Result<u32> {
+    let from = schema_version(value)?;
+    if from > SCHEMA_VERSION {
+        return Err(AppError::UnsupportedSchemaVersion(from));
+    }
+    let object = value
+        .as_object_mut()
+        .ok_or_else(|| AppError::InvalidData("Document is not a JSON object".to_string()))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(from as usize - 1) {
+        migration(object);
+        object.insert("schemaVersion".to_string(), json!(index + 2));
+    }
+    if from < SCHEMA_VERSION {
+        info!(
+            "Migrated document {} from schema {} to {}",
+            object.get("id").and_then(Value::as_str).unwrap_or("?"),
+            from,
+            SCHEMA_VERSION
+        );
+    }
+    Ok(from)
+}
+
+/// Parse document JSON of any supported schema version
+pub fn parse_document(json: &str) -> Result<Document> {
+    document_from_value(serde_json::from_str(json)?)
+}
+
+/// Deserialize document JSON of any supported schema version
+pub fn document_from_value(mut value: Value) -> Result<Document> {
+    migrate(&mut value)?;
+    let mut document: Document = serde_json::from_value(value)?;
+    document.assign_orphan_blocks();
+    Ok(document)
+}
+
+/// Version 2 replaces the free-form `version` string with `schemaVersion`
+/// and accepts the layout the editor used to save:
+///
+/// - blocks nested in `pages[].blocks` move to `blocks` with a `pageId`
+/// - a top-level `title` moves into `metadata`
+/// - image `objectFit` becomes `fit`
+/// - tables with a `cells` grid become `rows` of cells
+fn v1_to_v2(document: &mut Map<String, Value>) {
+    document.remove("version");
+
+    if let Some(title) = document.remove("title") {
+        if let Some(metadata) = document.get_mut("metadata").and_then(Value::as_object_mut) {
+            metadata.entry("title").or_insert(title);
+        }
+    }
+
+    let mut nested = Vec::new();
+    if let Some(pages) = document.get_mut("pages").and_then(Value::as_array_mut) {
+        for page in pages.iter_mut().filter_map(Value::as_object_mut) {
+            let page_id = page.get("id").cloned().unwrap_or(Value::Null);
+            let Some(Value::Array(blocks)) = page.remove("blocks") else {
+                continue;
+            };
+            for mut block in blocks {
+                if let Some(block) = block.as_object_mut() {
+                    block.entry("pageId").or_insert_with(|| page_id.clone());
+                }
+                nested.push(block);
+            }
+        }
+    }
+    let blocks = document
+        .entry("blocks")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if let Some(blocks) = blocks.as_array_mut() {
+        blocks.extend(nested);
+        for block in blocks.iter_mut().filter_map(Value::as_object_mut) {
+            if let Some(content) = block.get_mut("content").and_then(Value::as_object_mut) {
+                migrate_v1_content(content);
+            }
+        }
+    }
+}
+
+fn migrate_v1_content(content: &mut Map<String, Value>) {
+    if let Some(fit) = content.remove("objectFit") {
+        content.entry("fit").or_insert(fit);
+    }
+
+    let Some(Value::Array(grid)) = content.remove("cells") else {
+        return;
+    };
+    let rows: Vec<Value> = grid
+        .into_iter()
+        .map(|row| {
+            let cells: Vec<Value> = row
+                .as_array()
+                .map(|cells| cells.iter().map(v1_cell).collect())
+                .unwrap_or_default();
+            json!({ "cells": cells })
+        })
+        .collect();
+    content.insert("rows".to_string(), Value::Array(rows));
+    content
+        .entry("columnWidths")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    for key in [
+        "cols",
+        "rowHeights",
+        "borderWidth",
+        "borderColor",
+        "cellPadding",
+    ] {
+        content.remove(key);
+    }
+}
+
+/// Editor table cell (`backgroundColor`, `textColor`, `fontWeight`) to `TableCell`
+fn v1_cell(cell: &Value) -> Value {
+    let mut styles = Map::new();
+    if let Some(background) = cell.get("backgroundColor") {
+        styles.insert("background".to_string(), background.clone());
+    }
+    if let Some(color) = cell.get("textColor") {
+        styles.insert("color".to_string(), color.clone());
+    }
+    if let Some(weight) = cell.get("fontWeight").and_then(Value::as_u64) {
+        styles.insert("bold".to_string(), json!(weight >= 600));
+    }
+
+    let mut migrated = Map::new();
+    migrated.insert(
+        "content".to_string(),
+        cell.get("content").cloned().unwrap_or_else(|| json!("")),
+    );
+    if !styles.is_empty() {
+        migrated.insert("styles".to_string(), Value::Object(styles));
+    }
+    Value::Object(migrated)
+}
+
+/// Version 3 reads block content by the block's `type` instead of taking
+/// the first content shape that fits
+///
+/// Blocks whose `type` disagreed with their content were loaded as the
+/// content's shape, so `type` is corrected to match what older releases
+/// rendered. Spacers lose their `null` content.
+fn v2_to_v3(document: &mut Map<String, Value>) {
+    let Some(blocks) = document.get_mut("blocks").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for block in blocks.iter_mut().filter_map(Value::as_object_mut) {
+        let Some(block_type) = untagged_content_type(block.get("content")) else {
+            continue;
+        };
+        if block.get("type").and_then(Value::as_str) != Some(block_type) {
+            info!(
+                "Block {} had content of a {} block",
+                block.get("id").and_then(Value::as_str).unwrap_or("?"),
+                block_type
+            );
+            block.insert("type".to_string(), json!(block_type));
+        }
+        if block_type == "spacer" {
+            block.remove("content");
+        }
+    }
+}
+
+/// Version 4 adds rich text `paragraphs` to text blocks
+///
+/// Existing documents need no changes; the version only keeps older
+/// releases from loading rich text and dropping it on save.
+fn v3_to_v4(_document: &mut Map<String, Value>) {}
+
+/// Version 5 keeps the editor's block `transform` (rotation and scale)
+///
+/// Earlier versions dropped it when loading, so there is nothing to
+/// convert; the version keeps older releases from discarding it on save.
+fn v4_to_v5(_document: &mut Map<String, Value>) {}
+
+/// Block type the untagged content format resolved to, trying variants in
+/// declaration order
+fn untagged_content_type(content: Option<&Value>) -> Option<&'static str> {
+    let content = match content {
+        None | Some(Value::Null) => return Some("spacer"),
+        Some(Value::Object(content)) => content,
+        Some(_) => return None,
+    };
+    let has = |keys: &[&str]| keys.iter().all(|key| content.contains_key(*key));
+
+    [
+        (
+            "text",
+            &[
+                "text",
+                "fontSize",
+                "fontFamily",
+                "fontWeight",
+                "color",
+                "alignment",
+            ][..],
+        ),
+        ("image", &["src", "alt"][..]),
+        ("table", &["rows", "columnWidths"][..]),
+        ("shape", &["shape"][..]),
+        ("line", &["start", "end", "stroke"][..]),
+    ]
+    .into_iter()
+    .find(|(_, keys)| has(keys))
+    .map(|(block_type, _)| block_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BlockContent;
+
+    #[test]
+    fn test_migrates_legacy_document() {
+        let legacy = json!({
+            "id": "doc",
+            "title": "Invoice",
+            "metadata": {
+                "createdAt": "2024-01-01T00:00:00Z",
+                "updatedAt": "2024-01-01T00:00:00Z"
+            },
+            "pages": [{
+                "id": "p1",
+                "size": "A4",
+                "orientation": "portrait",
+                "margins": { "top": 20.0, "right": 20.0, "bottom": 20.0, "left": 20.0 },
+                "blocks": [{
+                    "id": "table",
+                    "type": "table",
+                    "position": { "x": 0.0, "y": 0.0 },
+                    "size": { "width": 100.0, "height": 40.0 },
+                    "transform": { "rotation": 90.0, "scaleX": 1.0, "scaleY": 1.0 },
+                    "zIndex": 0,
+                    "content": {
+                        "rows": 1,
+                        "cols": 2,
+                        "cells": [[
+                            { "content": "Item", "fontWeight": 700 },
+                            { "content": "Price", "backgroundColor": "#eeeeee" }
+                        ]],
+                        "columnWidths": [50.0, 50.0],
+                        "borderWidth": 1
+                    }
+                }]
+            }],
+            "version": "1.0.0"
+        });
+
+        let document = document_from_value(legacy).unwrap();
+        assert_eq!(document.schema_version, SCHEMA_VERSION);
+        assert_eq!(document.metadata.title, "Invoice");
+        assert_eq!(document.blocks.len(), 1);
+        assert_eq!(document.blocks[0].page_id, "p1");
+        assert_eq!(
+            document.blocks[0].transform.as_ref().unwrap().rotation,
+            90.0
+        );
+        let BlockContent::Table(table) = &document.blocks[0].content else {
+            panic!("not a table");
+        };
+        assert_eq!(table.rows[0].cells[1].content, "Price");
+        assert_eq!(
+            table.rows[0].cells[0].styles.as_ref().unwrap().bold,
+            Some(true)
+        );
+    }
+
+    #[test]
+    fn test_retypes_blocks_by_content() {
+        let mut value = serde_json::to_value(Document::new("Offer".to_string())).unwrap();
+        value["schemaVersion"] = json!(2);
+        value["blocks"] = json!([
+            {
+                "id": "caption",
+                "type": "image",
+                "position": { "x": 0.0, "y": 0.0 },
+                "size": { "width": 100.0, "height": 20.0 },
+                "zIndex": 0,
+                "content": {
+                    "text": "Caption",
+                    "fontSize": 12.0,
+                    "fontFamily": "Inter",
+                    "fontWeight": 400,
+                    "color": "#000000",
+                    "alignment": "left"
+                }
+            },
+            {
+                "id": "gap",
+                "type": "spacer",
+                "position": { "x": 0.0, "y": 20.0 },
+                "size": { "width": 100.0, "height": 20.0 },
+                "zIndex": 0,
+                "content": null
+            }
+        ]);
+
+        let document = document_from_value(value).unwrap();
+        assert_eq!(
+            document.blocks[0].block_type(),
+            crate::models::BlockType::Text
+        );
+        assert!(matches!(document.blocks[1].content, BlockContent::Spacer));
+    }
+
+    #[test]
+    fn test_current_document_is_unchanged() {
+        let document = Document::new("Offer".to_string());
+        let mut value = serde_json::to_value(&document).unwrap();
+        let before = value.clone();
+
+        assert_eq!(migrate(&mut value).unwrap(), SCHEMA_VERSION);
+        assert_eq!(value, before);
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let mut value = serde_json::to_value(Document::new("Offer".to_string())).unwrap();
+        value["schemaVersion"] = json!(SCHEMA_VERSION + 1);
+
+        assert!(matches!(
+            migrate(&mut value),
+            Err(AppError::UnsupportedSchemaVersion(v)) if v == SCHEMA_VERSION + 1
+        ));
+        value["schemaVersion"] = json!("2");
+        assert!(migrate(&mut value).is_err());
+    }
+}
@@ -1,8 +1,40 @@
 pub mod storage;
+pub mod store;
 pub mod python;
 pub mod validator;
+pub mod renderer;
+pub mod template_engine;
+pub mod template_library;
+pub mod expression;
+pub mod repeater;
+pub mod formula;
+pub mod formatter;
+pub mod batch;
+pub mod search;
+pub mod history;
+pub mod diff;
+pub mod bundle;
+pub mod assets;
+pub mod migration;
+pub mod merge;
+pub mod rich_text;
+pub mod font_registry;
 
 pub use storage::StorageService;
+pub use store::{DocumentStore, JsonStore, SqliteStore};
 pub use python::PythonService;
 pub use validator::Validator;
+pub use renderer::{DocxRenderer, PdfRenderer, RenderBackend, Renderer, TextMeasure};
+pub use template_engine::{TemplateEngine, VariableUsage};
+pub use template_library::TemplateOptions;
+pub use expression::Expression;
+pub use batch::{BatchGenerator, BatchReport};
+pub use formatter::{DateStyle, Formatter};
+pub use search::{SearchField, SearchHit};
+pub use history::{RetentionPolicy, RevisionInfo, RevisionStore};
+pub use diff::DocumentDiff;
+pub use merge::{MergeResult, MergeSide};
+pub use assets::AssetStore;
+pub use rich_text::RichTextFormat;
+pub use font_registry::{FontInfo, FontRegistry};
 
@@ -1,4 +1,4 @@
-use crate::models::{AppError, Block, Result};
+use crate::models::{AppError, Document, Result};
 use log::{debug, error, info};
 use std::path::PathBuf;
 use std::process::Stdio;
@@ -147,22 +147,39 @@ impl PythonService {
         })
     }
 
-    /// Generate PDF from blocks using Python
+    /// Generate PDF from a document using Python
     pub async fn generate_pdf(
         &self,
-        blocks: &[Block],
+        document: &Document,
         output_path: &PathBuf,
-        page_width_mm: f64,
-        page_height_mm: f64,
     ) -> Result<PathBuf> {
-        info!("Generating PDF with {} blocks", blocks.len());
+        info!(
+            "Generating PDF with {} pages and {} blocks",
+            document.pages.len(),
+            document.blocks.len()
+        );
+
+        // One entry per page, each with its own size and blocks
+        let pages: Vec<serde_json::Value> = document
+            .pages
+            .iter()
+            .map(|page| {
+                let geometry = page.geometry();
+                let blocks: Vec<_> = document.blocks_on_page(&page.id).collect();
+                serde_json::json!({
+                    "page_width_mm": geometry.width,
+                    "page_height_mm": geometry.height,
+                    "margins_mm": page.margins,
+                    "content_box_mm": geometry.content_box,
+                    "blocks": blocks,
+                })
+            })
+            .collect();
 
         // Prepare data for Python script
         let data = serde_json::json!({
-            "blocks": blocks,
+            "pages": pages,
             "output_path": output_path.to_string_lossy(),
-            "page_width_mm": page_width_mm,
-            "page_height_mm": page_height_mm,
         });
 
         // Execute PDF generation script
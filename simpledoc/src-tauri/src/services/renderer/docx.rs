@@ -0,0 +1,1498 @@
+use super::layout::LIST_INDENT_PX;
+use super::pdf::{
+    column_widths, fit_size, load_image_source, parse_color, parse_shadow, Rgba, LINE_HEIGHT,
+    PT_PER_PX, TABLE_CELL_PADDING_PX, TABLE_FONT_SIZE_PX,
+};
+use super::Renderer;
+use crate::models::{
+    AppError, Block, BlockContent, BlockStyles, Document, ImageBlockContent, ImageFit,
+    LineBlockContent, ListKind, Locale, Page, PageOrientation, PageRect, Result, ShapeBlockContent,
+    ShapeKind, StrokeDash, TableBlockContent, TextAlignment, TextBlockContent, TextRun,
+    VerticalAlign,
+};
+use crate::services::assets::AssetStore;
+use chrono::SecondsFormat;
+use image::{ImageFormat, ImageReader};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// English Metric Units per canvas pixel, the unit of DrawingML
+const EMU_PER_PX: f64 = 9525.0;
+
+/// Twentieths of a point per canvas pixel, the unit of WordprocessingML
+const TWIPS_PER_PX: f64 = 15.0;
+
+/// Twentieths of a point per millimeter
+const TWIPS_PER_MM: f64 = 1440.0 / 25.4;
+
+/// Font of table cells, as in the PDF
+const TABLE_FONT: &str = "Helvetica";
+
+/// Numbering instance shared by all bullet lists
+const BULLET_LIST_ID: usize = 1;
+
+/// Properties of the paragraphs holding anchors and separating tables, which
+/// must not take up visible space
+const HIDDEN_PARAGRAPH: &str = "<w:spacing w:before=\"0\" w:after=\"0\" w:line=\"20\" w:lineRule=\"exact\"/><w:rPr><w:sz w:val=\"2\"/><w:szCs w:val=\"2\"/></w:rPr>";
+
+const NO_LINE: &str = "<a:ln><a:noFill/></a:ln>";
+
+const NS_W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const NS_R: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const NS_WP: &str = "http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing";
+const NS_A: &str = "http://schemas.openxmlformats.org/drawingml/2006/main";
+const NS_PIC: &str = "http://schemas.openxmlformats.org/drawingml/2006/picture";
+const NS_WPS: &str = "http://schemas.microsoft.com/office/word/2010/wordprocessingShape";
+
+const REL_STYLES: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles";
+const REL_SETTINGS: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/settings";
+const REL_NUMBERING: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering";
+const REL_IMAGE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+const REL_HYPERLINK: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Default Extension="png" ContentType="image/png"/><Default Extension="jpeg" ContentType="image/jpeg"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/><Override PartName="/word/settings.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.settings+xml"/><Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/><Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/></Types>"#;
+
+const PACKAGE_RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/></Relationships>"#;
+
+/// A generated Word file and the styles it could not reproduce
+#[derive(Debug, Clone)]
+pub struct DocxOutput {
+    pub bytes: Vec<u8>,
+    /// One message per block style Word cannot show like the PDF does
+    pub warnings: Vec<String>,
+}
+
+/// Native DOCX renderer
+///
+/// Every page becomes a Word section of the same size and margins. Blocks
+/// keep their absolute positions as objects anchored to the page: text
+/// blocks become text boxes, shapes and lines drawing shapes, images
+/// pictures and tables floating Word tables, so the file stays editable.
+#[derive(Debug, Default, Clone)]
+pub struct DocxRenderer {
+    assets: Option<AssetStore>,
+}
+
+impl DocxRenderer {
+    /// Renderer that resolves `asset://` image sources from a store
+    pub fn with_assets(assets: AssetStore) -> Self {
+        Self {
+            assets: Some(assets),
+        }
+    }
+
+    /// Render the whole document, collecting the styles Word cannot show
+    pub fn export(&self, document: &Document) -> Result<DocxOutput> {
+        if document.pages.is_empty() {
+            return Err(AppError::RenderError("Document has no pages".to_string()));
+        }
+
+        info!(
+            "Exporting document {} with {} pages and {} blocks to DOCX",
+            document.id,
+            document.pages.len(),
+            document.blocks.len()
+        );
+
+        let mut builder = DocxBuilder::new(self.assets.as_ref());
+        let mut body = String::new();
+        for (index, page) in document.pages.iter().enumerate() {
+            let last = index + 1 == document.pages.len();
+            builder.write_page(&mut body, document, page, last)?;
+        }
+
+        builder.finish(document, &body)
+    }
+}
+
+impl Renderer for DocxRenderer {
+    fn render(&self, document: &Document) -> Result<Vec<u8>> {
+        self.export(document).map(|output| output.bytes)
+    }
+}
+
+/// Picture embedded in the package
+#[derive(Debug, Clone)]
+struct ImagePart {
+    rel_id: String,
+    width: u32,
+    height: u32,
+}
+
+/// Relationship of the main document part
+struct Relationship {
+    id: String,
+    kind: &'static str,
+    target: String,
+    external: bool,
+}
+
+/// Package assembly: relationships, media and numbering shared by all pages
+struct DocxBuilder<'a> {
+    assets: Option<&'a AssetStore>,
+    relationships: Vec<Relationship>,
+    /// File names under `word/media` and their contents
+    media: Vec<(String, Vec<u8>)>,
+    images: HashMap<String, ImagePart>,
+    bullets: bool,
+    /// Number of numbered lists, each restarting at 1
+    numbered_lists: usize,
+    hyphenate: bool,
+    /// Last id given to a drawing object
+    last_id: u32,
+    warnings: Vec<String>,
+}
+
+impl<'a> DocxBuilder<'a> {
+    fn new(assets: Option<&'a AssetStore>) -> Self {
+        let mut builder = Self {
+            assets,
+            relationships: Vec::new(),
+            media: Vec::new(),
+            images: HashMap::new(),
+            bullets: false,
+            numbered_lists: 0,
+            hyphenate: false,
+            last_id: 0,
+            warnings: Vec::new(),
+        };
+        builder.add_relationship(REL_STYLES, "styles.xml".to_string(), false);
+        builder.add_relationship(REL_SETTINGS, "settings.xml".to_string(), false);
+        builder
+    }
+
+    fn add_relationship(&mut self, kind: &'static str, target: String, external: bool) -> String {
+        if let Some(existing) = self
+            .relationships
+            .iter()
+            .find(|r| r.kind == kind && r.target == target)
+        {
+            return existing.id.clone();
+        }
+        let id = format!("rId{}", self.relationships.len() + 1);
+        self.relationships.push(Relationship {
+            id: id.clone(),
+            kind,
+            target,
+            external,
+        });
+        id
+    }
+
+    fn next_id(&mut self) -> u32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn warn(&mut self, block: &Block, message: &str) {
+        let warning = format!("Block {}: {}", block.id, message);
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Write one page as a section: its floating tables, then a paragraph
+    /// anchoring every other block
+    fn write_page(
+        &mut self,
+        body: &mut String,
+        document: &Document,
+        page: &Page,
+        last: bool,
+    ) -> Result<()> {
+        let geometry = page.geometry().to_px();
+        let mut drawings = String::new();
+
+        if let Some(color) = page.background.as_deref().and_then(parse_color) {
+            let frame = PageRect {
+                x: 0.0,
+                y: 0.0,
+                width: geometry.width,
+                height: geometry.height,
+            };
+            let style = format!("{}{}", solid_fill(color, 1.0), NO_LINE);
+            let shape = drawing_shape(frame, &preset("rect"), &style, "", None);
+            let id = self.next_id();
+            drawings.push_str(&anchor(id, frame, 1, true, "", NS_WPS, &shape));
+        }
+
+        let mut blocks: Vec<&Block> = document.blocks_on_page(&page.id).collect();
+        blocks.sort_by_key(|b| b.z_index);
+
+        // Tables are part of the text layer, so anything stacked below an
+        // overlapping table has to go behind the text
+        let tables: Vec<(usize, PageRect)> = blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| matches!(b.content, BlockContent::Table(_)))
+            .map(|(index, b)| (index, b.bounds()))
+            .collect();
+
+        for (index, block) in blocks.iter().enumerate() {
+            debug!("Exporting block {} ({:?})", block.id, block.block_type());
+            let behind = tables.iter().any(|(table, bounds)| {
+                *table > index && intersect(bounds, &block.bounds()).is_some()
+            });
+            // Two stacking slots per block, the lower one for its background box
+            let z = (index as u32 + 1) * 2;
+            self.write_block(body, &mut drawings, block, z, behind)?;
+        }
+
+        let section = section_properties(page);
+        let _ = write!(
+            body,
+            "<w:p><w:pPr>{}{}</w:pPr>{}</w:p>",
+            HIDDEN_PARAGRAPH,
+            if last { "" } else { section.as_str() },
+            drawings
+        );
+        if last {
+            body.push_str(&section);
+        }
+        Ok(())
+    }
+
+    fn write_block(
+        &mut self,
+        body: &mut String,
+        drawings: &mut String,
+        block: &Block,
+        z: u32,
+        behind: bool,
+    ) -> Result<()> {
+        if block.transform.as_ref().is_some_and(|t| !t.is_identity()) {
+            self.warn(block, "rotation and scale are not exported");
+        }
+        let styles = block.styles.as_ref();
+        let opacity = styles
+            .and_then(|s| s.opacity)
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0);
+        let frame = block.bounds();
+        let content_frame = match styles.and_then(|s| s.padding.as_ref()) {
+            Some(p) => PageRect {
+                x: frame.x + p.left,
+                y: frame.y + p.top,
+                width: (frame.width - p.left - p.right).max(0.0),
+                height: (frame.height - p.top - p.bottom).max(0.0),
+            },
+            None => frame,
+        };
+
+        match &block.content {
+            BlockContent::Text(text) => {
+                let shape = self.text_box(block, text, opacity);
+                let id = self.next_id();
+                drawings.push_str(&anchor(id, frame, z, behind, "", NS_WPS, &shape));
+            }
+            BlockContent::Table(table) => {
+                let table = self.table(block, table, content_frame, opacity);
+                if !table.is_empty() {
+                    body.push_str(&table);
+                    // Adjacent tables would be merged into one
+                    let _ = write!(body, "<w:p><w:pPr>{}</w:pPr></w:p>", HIDDEN_PARAGRAPH);
+                }
+            }
+            content => {
+                if let Some(styles) = styles.filter(|s| has_box(s)) {
+                    let style = box_style(Some(styles), opacity);
+                    let shape = drawing_shape(frame, &preset("rect"), &style, "", None);
+                    let id = self.next_id();
+                    drawings.push_str(&anchor(id, frame, z - 1, behind, "", NS_WPS, &shape));
+                }
+                let drawing = match content {
+                    BlockContent::Image(image) => {
+                        self.picture(image, content_frame, z, behind, opacity)?
+                    }
+                    BlockContent::Shape(shape) => {
+                        let shape = shape_graphic(shape, frame, opacity);
+                        let id = self.next_id();
+                        anchor(id, frame, z, behind, "", NS_WPS, &shape)
+                    }
+                    BlockContent::Line(line) => match line_graphic(block, line, opacity) {
+                        Some((frame, shape)) => {
+                            let id = self.next_id();
+                            anchor(id, frame, z, behind, "", NS_WPS, &shape)
+                        }
+                        None => String::new(),
+                    },
+                    _ => String::new(),
+                };
+                drawings.push_str(&drawing);
+            }
+        }
+        Ok(())
+    }
+
+    /// Text box with the block's background, border, shadow and padding
+    fn text_box(&mut self, block: &Block, text: &TextBlockContent, opacity: f64) -> String {
+        if opacity < 1.0 && !text.text.trim().is_empty() {
+            self.warn(
+                block,
+                "opacity applies to the background only, text stays opaque",
+            );
+        }
+
+        let (left, top, right, bottom) = block
+            .styles
+            .as_ref()
+            .and_then(|s| s.padding.as_ref())
+            .map_or((0.0, 0.0, 0.0, 0.0), |p| (p.left, p.top, p.right, p.bottom));
+        let body_properties = format!(
+            "<wps:bodyPr rot=\"0\" vert=\"horz\" wrap=\"square\" lIns=\"{}\" tIns=\"{}\" rIns=\"{}\" bIns=\"{}\" anchor=\"t\" anchorCtr=\"0\">{}</wps:bodyPr>",
+            emu(left),
+            emu(top),
+            emu(right),
+            emu(bottom),
+            if text.auto_height {
+                "<a:spAutoFit/>"
+            } else {
+                "<a:noAutofit/>"
+            }
+        );
+
+        let paragraphs = self.text_paragraphs(block, text);
+        let style = box_style(block.styles.as_ref(), opacity);
+        drawing_shape(
+            block.bounds(),
+            &preset("rect"),
+            &style,
+            "",
+            Some((&paragraphs, &body_properties)),
+        )
+    }
+
+    fn text_paragraphs(&mut self, block: &Block, text: &TextBlockContent) -> String {
+        if !matches!(text.font_weight, 400 | 700) {
+            let shown = if text.font_weight >= 600 {
+                "bold"
+            } else {
+                "regular"
+            };
+            self.warn(
+                block,
+                &format!("font weight {} is exported as {}", text.font_weight, shown),
+            );
+        }
+        self.hyphenate |= text.hyphenate;
+
+        let line = (text.line_height.unwrap_or(LINE_HEIGHT as f64) * 240.0).round() as i64;
+        let mark = self.run_properties(block, text, &TextRun::default());
+        // Numbering of the current numbered list, restarted after other paragraphs
+        let mut numbered: Option<usize> = None;
+        let mut xml = String::new();
+
+        for paragraph in text.paragraphs() {
+            let list = match paragraph.list {
+                None => {
+                    numbered = None;
+                    None
+                }
+                Some(ListKind::Bullet) => {
+                    self.bullets = true;
+                    Some(BULLET_LIST_ID)
+                }
+                Some(ListKind::Numbered) => Some(*numbered.get_or_insert_with(|| {
+                    self.numbered_lists += 1;
+                    BULLET_LIST_ID + self.numbered_lists
+                })),
+            };
+
+            xml.push_str("<w:p><w:pPr>");
+            if let Some(list) = list {
+                let _ = write!(
+                    xml,
+                    "<w:numPr><w:ilvl w:val=\"{}\"/><w:numId w:val=\"{}\"/></w:numPr>",
+                    paragraph.level.min(8),
+                    list
+                );
+            }
+            if !text.hyphenate {
+                xml.push_str("<w:suppressAutoHyphens/>");
+            }
+            let _ = write!(
+                xml,
+                "<w:spacing w:before=\"{}\" w:after=\"{}\" w:line=\"{}\" w:lineRule=\"auto\"/><w:jc w:val=\"{}\"/>{}</w:pPr>",
+                twips(paragraph.space_before),
+                twips(paragraph.space_after),
+                line,
+                justification(paragraph.alignment.unwrap_or(text.alignment)),
+                mark
+            );
+
+            for run in paragraph.runs.iter().filter(|run| !run.text.is_empty()) {
+                let properties = self.run_properties(block, text, run);
+                let run_xml = format!("<w:r>{}{}</w:r>", properties, text_with_breaks(&run.text));
+                match &run.link {
+                    Some(link) => {
+                        let id = self.add_relationship(REL_HYPERLINK, link.clone(), true);
+                        let _ = write!(
+                            xml,
+                            "<w:hyperlink r:id=\"{}\">{}</w:hyperlink>",
+                            id, run_xml
+                        );
+                    }
+                    None => xml.push_str(&run_xml),
+                }
+            }
+            xml.push_str("</w:p>");
+        }
+
+        xml
+    }
+
+    /// Run properties of a text run; unset fields come from the block
+    fn run_properties(&mut self, block: &Block, text: &TextBlockContent, run: &TextRun) -> String {
+        let font = escape(&text.font_family);
+        let mut xml = format!(
+            "<w:rPr><w:rFonts w:ascii=\"{0}\" w:hAnsi=\"{0}\" w:eastAsia=\"{0}\" w:cs=\"{0}\"/>",
+            font
+        );
+        if run.bold || text.font_weight >= 600 {
+            xml.push_str("<w:b/><w:bCs/>");
+        }
+        if run.italic {
+            xml.push_str("<w:i/><w:iCs/>");
+        }
+        if run.strike {
+            xml.push_str("<w:strike/>");
+        }
+        if let Some(color) = parse_color(run.color.as_deref().unwrap_or(&text.color)) {
+            if color.a < 1.0 {
+                self.warn(block, "transparent text color is exported opaque");
+            }
+            let _ = write!(xml, "<w:color w:val=\"{}\"/>", hex(color));
+        }
+        let size = half_points(run.font_size.unwrap_or(text.font_size));
+        let _ = write!(xml, "<w:sz w:val=\"{0}\"/><w:szCs w:val=\"{0}\"/>", size);
+        if run.underline {
+            xml.push_str("<w:u w:val=\"single\"/>");
+        }
+        match run.vertical_align {
+            Some(VerticalAlign::Superscript) => {
+                xml.push_str("<w:vertAlign w:val=\"superscript\"/>")
+            }
+            Some(VerticalAlign::Subscript) => xml.push_str("<w:vertAlign w:val=\"subscript\"/>"),
+            None => {}
+        }
+        xml.push_str("</w:rPr>");
+        xml
+    }
+
+    /// Floating table positioned on the page, with the PDF's grid and fonts
+    fn table(
+        &mut self,
+        block: &Block,
+        table: &TableBlockContent,
+        frame: PageRect,
+        opacity: f64,
+    ) -> String {
+        let columns = table.rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+        if table.rows.is_empty() || columns == 0 {
+            return String::new();
+        }
+
+        let styles = block.styles.as_ref();
+        if styles.and_then(|s| s.shadow.as_ref()).is_some() {
+            self.warn(block, "tables cannot have a shadow");
+        }
+        if opacity < 1.0 {
+            self.warn(block, "tables cannot be transparent");
+        }
+        if styles.is_some_and(|s| s.padding.is_some() && has_box(s)) {
+            self.warn(
+                block,
+                "background and border are drawn around the table, not the padded block",
+            );
+        }
+
+        let grid = "w:val=\"single\" w:sz=\"6\" w:space=\"0\" w:color=\"000000\"".to_string();
+        let outer = styles
+            .and_then(|s| s.border.as_ref())
+            .filter(|b| b.width > 0.0 && b.style != "none")
+            .and_then(|b| {
+                let color = parse_color(&b.color)?;
+                let style = match b.style.as_str() {
+                    "dashed" => "dashed",
+                    "dotted" => "dotted",
+                    _ => "single",
+                };
+                // Border widths are given in eighths of a point
+                let size = (b.width * PT_PER_PX as f64 * 8.0).round().clamp(2.0, 96.0);
+                Some(format!(
+                    "w:val=\"{}\" w:sz=\"{}\" w:space=\"0\" w:color=\"{}\"",
+                    style,
+                    size,
+                    hex(color)
+                ))
+            })
+            .unwrap_or_else(|| grid.clone());
+        let shading = styles
+            .and_then(|s| s.background.as_deref())
+            .and_then(parse_color)
+            .map(|color| {
+                format!(
+                    "<w:shd w:val=\"clear\" w:color=\"auto\" w:fill=\"{}\"/>",
+                    hex(color)
+                )
+            })
+            .unwrap_or_default();
+        let padding = (TABLE_CELL_PADDING_PX as f64 * TWIPS_PER_PX).round();
+
+        let mut xml = format!(
+            "<w:tbl><w:tblPr><w:tblpPr w:leftFromText=\"0\" w:rightFromText=\"0\" w:topFromText=\"0\" w:bottomFromText=\"0\" w:vertAnchor=\"page\" w:horzAnchor=\"page\" w:tblpX=\"{}\" w:tblpY=\"{}\"/><w:tblOverlap w:val=\"overlap\"/><w:tblW w:w=\"{}\" w:type=\"dxa\"/><w:tblBorders><w:top {3}/><w:left {3}/><w:bottom {3}/><w:right {3}/><w:insideH {4}/><w:insideV {4}/></w:tblBorders>{5}<w:tblLayout w:type=\"fixed\"/><w:tblCellMar><w:top w:w=\"{6}\" w:type=\"dxa\"/><w:left w:w=\"{6}\" w:type=\"dxa\"/><w:bottom w:w=\"{6}\" w:type=\"dxa\"/><w:right w:w=\"{6}\" w:type=\"dxa\"/></w:tblCellMar></w:tblPr><w:tblGrid>",
+            twips(frame.x),
+            twips(frame.y),
+            twips(frame.width),
+            outer,
+            grid,
+            shading,
+            padding
+        );
+        let widths = column_widths(table, columns, frame.width as f32);
+        for width in &widths {
+            let _ = write!(xml, "<w:gridCol w:w=\"{}\"/>", twips(*width as f64));
+        }
+        xml.push_str("</w:tblGrid>");
+
+        // Rows grow with their text instead of clipping it like the PDF
+        let row_height = twips(frame.height / table.rows.len() as f64);
+        for row in &table.rows {
+            let _ = write!(
+                xml,
+                "<w:tr><w:trPr><w:trHeight w:val=\"{}\" w:hRule=\"atLeast\"/></w:trPr>",
+                row_height
+            );
+            for (index, width) in widths.iter().enumerate() {
+                let cell = row.cells.get(index);
+                let styles = cell.and_then(|c| c.styles.as_ref());
+                let _ = write!(
+                    xml,
+                    "<w:tc><w:tcPr><w:tcW w:w=\"{}\" w:type=\"dxa\"/>",
+                    twips(*width as f64)
+                );
+                if let Some(color) = styles
+                    .and_then(|s| s.background.as_deref())
+                    .and_then(parse_color)
+                {
+                    let _ = write!(
+                        xml,
+                        "<w:shd w:val=\"clear\" w:color=\"auto\" w:fill=\"{}\"/>",
+                        hex(color)
+                    );
+                }
+
+                let color = styles
+                    .and_then(|s| s.color.as_deref())
+                    .and_then(parse_color)
+                    .unwrap_or(Rgba::BLACK);
+                let size = half_points(TABLE_FONT_SIZE_PX as f64);
+                let properties = format!(
+                    "<w:rPr><w:rFonts w:ascii=\"{0}\" w:hAnsi=\"{0}\" w:eastAsia=\"{0}\" w:cs=\"{0}\"/>{1}<w:color w:val=\"{2}\"/><w:sz w:val=\"{3}\"/><w:szCs w:val=\"{3}\"/></w:rPr>",
+                    TABLE_FONT,
+                    if styles.and_then(|s| s.bold).unwrap_or(false) {
+                        "<w:b/><w:bCs/>"
+                    } else {
+                        ""
+                    },
+                    hex(color),
+                    size
+                );
+                let _ = write!(
+                    xml,
+                    "</w:tcPr><w:p><w:pPr><w:spacing w:before=\"0\" w:after=\"0\" w:line=\"{}\" w:lineRule=\"auto\"/>{}</w:pPr>",
+                    (LINE_HEIGHT as f64 * 240.0).round(),
+                    properties
+                );
+                if let Some(cell) = cell.filter(|c| !c.content.is_empty()) {
+                    let _ = write!(
+                        xml,
+                        "<w:r>{}{}</w:r>",
+                        properties,
+                        text_with_breaks(&cell.content)
+                    );
+                }
+                xml.push_str("</w:p></w:tc>");
+            }
+            xml.push_str("</w:tr>");
+        }
+        xml.push_str("</w:tbl>");
+        xml
+    }
+
+    /// Picture placed according to its fit; parts outside the block are cropped
+    fn picture(
+        &mut self,
+        image: &ImageBlockContent,
+        frame: PageRect,
+        z: u32,
+        behind: bool,
+        opacity: f64,
+    ) -> Result<String> {
+        // An image block without a source yet has nothing to export
+        if image.src.trim().is_empty() {
+            return Ok(String::new());
+        }
+
+        let part = self.image_part(&image.src)?;
+        let fit = image.fit.as_ref().unwrap_or(&ImageFit::Contain);
+        let (width, height) = fit_size(
+            fit,
+            part.width as f32,
+            part.height as f32,
+            frame.width as f32,
+            frame.height as f32,
+        );
+        let (width, height) = (width as f64, height as f64);
+        let placed = PageRect {
+            x: frame.x + (frame.width - width) / 2.0,
+            y: frame.y + (frame.height - height) / 2.0,
+            width,
+            height,
+        };
+        let Some(visible) = intersect(&frame, &placed) else {
+            return Ok(String::new());
+        };
+
+        // Cropping is given in thousandths of a percent of the picture
+        let crop = |offset: f64, size: f64| (offset / size * 100_000.0).round() as i64;
+        let (left, top, right, bottom) = (
+            crop(visible.x - placed.x, placed.width),
+            crop(visible.y - placed.y, placed.height),
+            crop(
+                placed.x + placed.width - visible.x - visible.width,
+                placed.width,
+            ),
+            crop(
+                placed.y + placed.height - visible.y - visible.height,
+                placed.height,
+            ),
+        );
+        let source_rect = if left > 0 || top > 0 || right > 0 || bottom > 0 {
+            format!(
+                "<a:srcRect l=\"{}\" t=\"{}\" r=\"{}\" b=\"{}\"/>",
+                left, top, right, bottom
+            )
+        } else {
+            String::new()
+        };
+        let alpha = if opacity < 1.0 {
+            format!("<a:alphaModFix amt=\"{}\"/>", (opacity * 100_000.0).round())
+        } else {
+            String::new()
+        };
+
+        let id = self.next_id();
+        let picture = format!(
+            "<pic:pic><pic:nvPicPr><pic:cNvPr id=\"{0}\" name=\"Picture {0}\" descr=\"{1}\"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed=\"{2}\">{3}</a:blip>{4}<a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x=\"0\" y=\"0\"/><a:ext cx=\"{5}\" cy=\"{6}\"/></a:xfrm>{7}</pic:spPr></pic:pic>",
+            id,
+            escape(&image.alt),
+            part.rel_id,
+            alpha,
+            source_rect,
+            emu(visible.width),
+            emu(visible.height),
+            preset("rect")
+        );
+        Ok(anchor(id, visible, z, behind, &image.alt, NS_PIC, &picture))
+    }
+
+    /// Add an image to the package once per source, returning the cached part
+    fn image_part(&mut self, src: &str) -> Result<ImagePart> {
+        if let Some(part) = self.images.get(src) {
+            return Ok(part.clone());
+        }
+
+        let bytes = load_image_source(src, self.assets)?;
+        let extension = match image::guess_format(&bytes) {
+            Ok(ImageFormat::Png) => "png",
+            Ok(ImageFormat::Jpeg) => "jpeg",
+            _ => {
+                return Err(AppError::RenderError(
+                    "Unsupported image format".to_string(),
+                ))
+            }
+        };
+        let (width, height) = ImageReader::new(Cursor::new(&bytes))
+            .with_guessed_format()?
+            .into_dimensions()
+            .map_err(|e| AppError::RenderError(format!("Failed to decode image: {}", e)))?;
+
+        let name = format!("image{}.{}", self.media.len() + 1, extension);
+        let part = ImagePart {
+            rel_id: self.add_relationship(REL_IMAGE, format!("media/{}", name), false),
+            width,
+            height,
+        };
+        self.media.push((name, bytes));
+        self.images.insert(src.to_string(), part.clone());
+        Ok(part)
+    }
+
+    /// Zip the package parts, returning the file and the warnings
+    fn finish(mut self, document: &Document, body: &str) -> Result<DocxOutput> {
+        let lists = self.bullets || self.numbered_lists > 0;
+        if lists {
+            self.add_relationship(REL_NUMBERING, "numbering.xml".to_string(), false);
+        }
+
+        let mut relationships = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">",
+        );
+        for relationship in &self.relationships {
+            let _ = write!(
+                relationships,
+                "<Relationship Id=\"{}\" Type=\"{}\" Target=\"{}\"{}/>",
+                relationship.id,
+                relationship.kind,
+                escape(&relationship.target),
+                if relationship.external {
+                    " TargetMode=\"External\""
+                } else {
+                    ""
+                }
+            );
+        }
+        relationships.push_str("</Relationships>");
+
+        let document_xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:document xmlns:w=\"{}\" xmlns:r=\"{}\" xmlns:wp=\"{}\" xmlns:a=\"{}\" xmlns:pic=\"{}\" xmlns:wps=\"{}\"><w:body>{}</w:body></w:document>",
+            NS_W, NS_R, NS_WP, NS_A, NS_PIC, NS_WPS, body
+        );
+
+        let zip_error = |e: zip::result::ZipError| AppError::Io(std::io::Error::other(e));
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        // Pictures are compressed already
+        let stored = options.compression_method(CompressionMethod::Stored);
+
+        let mut parts = vec![
+            ("[Content_Types].xml".to_string(), CONTENT_TYPES.to_string()),
+            ("_rels/.rels".to_string(), PACKAGE_RELATIONSHIPS.to_string()),
+            ("docProps/core.xml".to_string(), core_properties(document)),
+            ("word/document.xml".to_string(), document_xml),
+            ("word/_rels/document.xml.rels".to_string(), relationships),
+            (
+                "word/styles.xml".to_string(),
+                styles(document.metadata.locale),
+            ),
+            ("word/settings.xml".to_string(), settings(self.hyphenate)),
+        ];
+        if lists {
+            parts.push((
+                "word/numbering.xml".to_string(),
+                numbering(self.numbered_lists),
+            ));
+        }
+        for (name, xml) in parts {
+            zip.start_file(name, options).map_err(zip_error)?;
+            zip.write_all(xml.as_bytes())?;
+        }
+        for (name, data) in &self.media {
+            zip.start_file(format!("word/media/{}", name), stored)
+                .map_err(zip_error)?;
+            zip.write_all(data)?;
+        }
+        let bytes = zip.finish().map_err(zip_error)?.into_inner();
+
+        Ok(DocxOutput {
+            bytes,
+            warnings: self.warnings,
+        })
+    }
+}
+
+/// Drawing anchored to the page at a position in canvas pixels
+///
+/// `z` orders the drawings like `zIndex`; `behind` puts it behind the text
+/// layer.
+fn anchor(
+    id: u32,
+    frame: PageRect,
+    z: u32,
+    behind: bool,
+    description: &str,
+    uri: &str,
+    graphic: &str,
+) -> String {
+    format!(
+        "<w:r><w:drawing><wp:anchor distT=\"0\" distB=\"0\" distL=\"0\" distR=\"0\" simplePos=\"0\" relativeHeight=\"{}\" behindDoc=\"{}\" locked=\"0\" layoutInCell=\"1\" allowOverlap=\"1\"><wp:simplePos x=\"0\" y=\"0\"/><wp:positionH relativeFrom=\"page\"><wp:posOffset>{}</wp:posOffset></wp:positionH><wp:positionV relativeFrom=\"page\"><wp:posOffset>{}</wp:posOffset></wp:positionV><wp:extent cx=\"{}\" cy=\"{}\"/><wp:effectExtent l=\"0\" t=\"0\" r=\"0\" b=\"0\"/><wp:wrapNone/><wp:docPr id=\"{}\" name=\"Object {}\" descr=\"{}\"/><wp:cNvGraphicFramePr/><a:graphic><a:graphicData uri=\"{}\">{}</a:graphicData></a:graphic></wp:anchor></w:drawing></w:r>",
+        z,
+        if behind { 1 } else { 0 },
+        emu(frame.x),
+        emu(frame.y),
+        emu(frame.width),
+        emu(frame.height),
+        id,
+        id,
+        escape(description),
+        uri,
+        graphic
+    )
+}
+
+/// Drawing shape filling a frame, with text content and body properties for
+/// text boxes
+fn drawing_shape(
+    frame: PageRect,
+    geometry: &str,
+    style: &str,
+    transform: &str,
+    text: Option<(&str, &str)>,
+) -> String {
+    let (text_box, body_properties) = match text {
+        Some((content, body_properties)) => (
+            format!(
+                "<wps:txbx><w:txbxContent>{}</w:txbxContent></wps:txbx>",
+                // A text box needs at least one paragraph
+                if content.is_empty() {
+                    "<w:p/>"
+                } else {
+                    content
+                }
+            ),
+            body_properties.to_string(),
+        ),
+        None => (String::new(), "<wps:bodyPr/>".to_string()),
+    };
+    format!(
+        "<wps:wsp><wps:cNvSpPr{}/><wps:spPr><a:xfrm{}><a:off x=\"0\" y=\"0\"/><a:ext cx=\"{}\" cy=\"{}\"/></a:xfrm>{}{}</wps:spPr>{}{}</wps:wsp>",
+        if text.is_some() { " txBox=\"1\"" } else { "" },
+        transform,
+        emu(frame.width),
+        emu(frame.height),
+        geometry,
+        style,
+        text_box,
+        body_properties
+    )
+}
+
+fn shape_graphic(shape: &ShapeBlockContent, frame: PageRect, opacity: f64) -> String {
+    let geometry = match shape.shape {
+        ShapeKind::Rectangle => preset("rect"),
+        ShapeKind::Ellipse => preset("ellipse"),
+        ShapeKind::RoundedRect => {
+            // The corner radius as a share of the shorter side, at most half
+            let shorter = frame.width.min(frame.height);
+            let adjust = if shorter > 0.0 {
+                (shape.corner_radius.unwrap_or(0.0) / shorter * 100_000.0).clamp(0.0, 50_000.0)
+            } else {
+                0.0
+            };
+            format!(
+                "<a:prstGeom prst=\"roundRect\"><a:avLst><a:gd name=\"adj\" fmla=\"val {}\"/></a:avLst></a:prstGeom>",
+                adjust.round()
+            )
+        }
+        ShapeKind::Polygon => {
+            let mut path = String::new();
+            for (index, point) in shape.points.iter().enumerate() {
+                let _ = write!(
+                    path,
+                    "<a:{0}><a:pt x=\"{1}\" y=\"{2}\"/></a:{0}>",
+                    if index == 0 { "moveTo" } else { "lnTo" },
+                    emu(point.x),
+                    emu(point.y)
+                );
+            }
+            format!(
+                "<a:custGeom><a:avLst/><a:gdLst/><a:ahLst/><a:cxnLst/><a:rect l=\"0\" t=\"0\" r=\"r\" b=\"b\"/><a:pathLst><a:path w=\"{}\" h=\"{}\">{}<a:close/></a:path></a:pathLst></a:custGeom>",
+                emu(frame.width),
+                emu(frame.height),
+                path
+            )
+        }
+    };
+
+    let fill = shape.fill.as_deref().and_then(parse_color).map_or_else(
+        || "<a:noFill/>".to_string(),
+        |color| solid_fill(color, opacity),
+    );
+    let line = shape
+        .stroke
+        .as_ref()
+        .filter(|s| s.width > 0.0)
+        .and_then(|s| {
+            Some(outline(
+                parse_color(&s.color)?,
+                s.width,
+                s.dash,
+                opacity,
+                (false, false),
+            ))
+        })
+        .unwrap_or_else(|| NO_LINE.to_string());
+    drawing_shape(frame, &geometry, &(fill + &line), "", None)
+}
+
+/// Line shape spanning the line's end points, flipped to match its direction
+fn line_graphic(
+    block: &Block,
+    line: &LineBlockContent,
+    opacity: f64,
+) -> Option<(PageRect, String)> {
+    let color = parse_color(&line.stroke.color)?;
+    let (start, end) = (&line.start, &line.end);
+    let frame = PageRect {
+        x: block.position.x + start.x.min(end.x),
+        y: block.position.y + start.y.min(end.y),
+        width: (end.x - start.x).abs(),
+        height: (end.y - start.y).abs(),
+    };
+    if line.stroke.width <= 0.0 || (frame.width == 0.0 && frame.height == 0.0) {
+        return None;
+    }
+
+    let transform = format!(
+        "{}{}",
+        if start.x > end.x { " flipH=\"1\"" } else { "" },
+        if start.y > end.y { " flipV=\"1\"" } else { "" }
+    );
+    let style = format!(
+        "<a:noFill/>{}",
+        outline(
+            color,
+            line.stroke.width,
+            line.stroke.dash,
+            opacity,
+            (line.start_arrow, line.end_arrow)
+        )
+    );
+    let shape = drawing_shape(frame, &preset("line"), &style, &transform, None);
+    Some((frame, shape))
+}
+
+/// Whether a block draws a box of its own around the content
+fn has_box(styles: &BlockStyles) -> bool {
+    styles.background.is_some() || styles.border.is_some() || styles.shadow.is_some()
+}
+
+/// Fill, outline and shadow of a block's box
+fn box_style(styles: Option<&BlockStyles>, opacity: f64) -> String {
+    let fill = styles
+        .and_then(|s| s.background.as_deref())
+        .and_then(parse_color)
+        .map_or_else(
+            || "<a:noFill/>".to_string(),
+            |color| solid_fill(color, opacity),
+        );
+    let line = styles
+        .and_then(|s| s.border.as_ref())
+        .filter(|b| b.width > 0.0 && b.style != "none")
+        .and_then(|b| {
+            let dash = match b.style.as_str() {
+                "dashed" => StrokeDash::Dashed,
+                "dotted" => StrokeDash::Dotted,
+                _ => StrokeDash::Solid,
+            };
+            Some(outline(
+                parse_color(&b.color)?,
+                b.width,
+                dash,
+                opacity,
+                (false, false),
+            ))
+        })
+        .unwrap_or_else(|| NO_LINE.to_string());
+    let effects = styles
+        .and_then(|s| s.shadow.as_deref())
+        .and_then(parse_shadow)
+        .map(|(dx, dy, color)| {
+            let (dx, dy) = (dx as f64, dy as f64);
+            // Directions are clockwise from the x axis in 60000ths of a degree
+            let direction = dy.atan2(dx).to_degrees().rem_euclid(360.0);
+            format!(
+                "<a:effectLst><a:outerShdw dist=\"{}\" dir=\"{}\" algn=\"tl\" rotWithShape=\"0\">{}</a:outerShdw></a:effectLst>",
+                emu(dx.hypot(dy)),
+                (direction * 60_000.0).round(),
+                color_element(color, opacity)
+            )
+        })
+        .unwrap_or_default();
+    fill + &line + &effects
+}
+
+fn preset(geometry: &str) -> String {
+    format!("<a:prstGeom prst=\"{}\"><a:avLst/></a:prstGeom>", geometry)
+}
+
+fn solid_fill(color: Rgba, opacity: f64) -> String {
+    format!(
+        "<a:solidFill>{}</a:solidFill>",
+        color_element(color, opacity)
+    )
+}
+
+/// Outline of a shape or line; `arrows` marks the start and end point
+fn outline(
+    color: Rgba,
+    width: f64,
+    dash: StrokeDash,
+    opacity: f64,
+    arrows: (bool, bool),
+) -> String {
+    let dash = match dash {
+        StrokeDash::Solid => "",
+        StrokeDash::Dashed => "<a:prstDash val=\"dash\"/>",
+        StrokeDash::Dotted => "<a:prstDash val=\"sysDot\"/>",
+    };
+    format!(
+        "<a:ln w=\"{}\">{}{}{}{}</a:ln>",
+        emu(width),
+        solid_fill(color, opacity),
+        dash,
+        if arrows.0 {
+            "<a:headEnd type=\"triangle\"/>"
+        } else {
+            ""
+        },
+        if arrows.1 {
+            "<a:tailEnd type=\"triangle\"/>"
+        } else {
+            ""
+        }
+    )
+}
+
+/// DrawingML color with the color's alpha and the block opacity
+fn color_element(color: Rgba, opacity: f64) -> String {
+    let alpha = color.a as f64 * opacity;
+    if alpha < 1.0 {
+        format!(
+            "<a:srgbClr val=\"{}\"><a:alpha val=\"{}\"/></a:srgbClr>",
+            hex(color),
+            (alpha * 100_000.0).round()
+        )
+    } else {
+        format!("<a:srgbClr val=\"{}\"/>", hex(color))
+    }
+}
+
+fn hex(color: Rgba) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "{:02X}{:02X}{:02X}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
+
+fn justification(alignment: TextAlignment) -> &'static str {
+    match alignment {
+        TextAlignment::Left => "left",
+        TextAlignment::Center => "center",
+        TextAlignment::Right => "right",
+        TextAlignment::Justify => "both",
+    }
+}
+
+/// Overlap of two rectangles, if they overlap
+fn intersect(a: &PageRect, b: &PageRect) -> Option<PageRect> {
+    let x = a.x.max(b.x);
+    let y = a.y.max(b.y);
+    let right = (a.x + a.width).min(b.x + b.width);
+    let bottom = (a.y + a.height).min(b.y + b.height);
+    (right > x && bottom > y).then_some(PageRect {
+        x,
+        y,
+        width: right - x,
+        height: bottom - y,
+    })
+}
+
+fn emu(px: f64) -> i64 {
+    (px * EMU_PER_PX).round() as i64
+}
+
+fn twips(px: f64) -> i64 {
+    (px * TWIPS_PER_PX).round() as i64
+}
+
+/// Font size in half-points
+fn half_points(px: f64) -> i64 {
+    ((px * PT_PER_PX as f64 * 2.0).round() as i64).max(1)
+}
+
+/// Size, orientation and margins of the section for a page
+fn section_properties(page: &Page) -> String {
+    let geometry = page.geometry();
+    let mm = |value: f64| (value * TWIPS_PER_MM).round() as i64;
+    let margins = &page.margins;
+    format!(
+        "<w:sectPr><w:pgSz w:w=\"{}\" w:h=\"{}\"{}/><w:pgMar w:top=\"{}\" w:right=\"{}\" w:bottom=\"{}\" w:left=\"{}\" w:header=\"0\" w:footer=\"0\" w:gutter=\"0\"/></w:sectPr>",
+        mm(geometry.width),
+        mm(geometry.height),
+        match page.orientation {
+            PageOrientation::Landscape => " w:orient=\"landscape\"",
+            PageOrientation::Portrait => "",
+        },
+        mm(margins.top),
+        mm(margins.right),
+        mm(margins.bottom),
+        mm(margins.left)
+    )
+}
+
+/// Bullet list and numbered list definitions with the PDF's indentation
+fn numbering(numbered_lists: usize) -> String {
+    let levels = |format: &str, text: &dyn Fn(usize) -> String| {
+        (0..9)
+            .map(|level| {
+                let indent = (level as f64 + 1.0) * LIST_INDENT_PX as f64;
+                format!(
+                    "<w:lvl w:ilvl=\"{}\"><w:start w:val=\"1\"/><w:numFmt w:val=\"{}\"/><w:lvlText w:val=\"{}\"/><w:lvlJc w:val=\"left\"/><w:pPr><w:ind w:left=\"{}\" w:hanging=\"{}\"/></w:pPr></w:lvl>",
+                    level,
+                    format,
+                    text(level),
+                    twips(indent),
+                    twips(LIST_INDENT_PX as f64 * 0.75)
+                )
+            })
+            .collect::<String>()
+    };
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:numbering xmlns:w=\"{}\"><w:abstractNum w:abstractNumId=\"0\">{}</w:abstractNum><w:abstractNum w:abstractNumId=\"1\">{}</w:abstractNum><w:num w:numId=\"{}\"><w:abstractNumId w:val=\"0\"/></w:num>",
+        NS_W,
+        levels("bullet", &|_| "•".to_string()),
+        levels("decimal", &|level| format!("%{}.", level + 1)),
+        BULLET_LIST_ID
+    );
+    for list in 1..=numbered_lists {
+        let _ = write!(
+            xml,
+            "<w:num w:numId=\"{}\"><w:abstractNumId w:val=\"1\"/><w:lvlOverride w:ilvl=\"0\"><w:startOverride w:val=\"1\"/></w:lvlOverride></w:num>",
+            BULLET_LIST_ID + list
+        );
+    }
+    xml.push_str("</w:numbering>");
+    xml
+}
+
+/// Default paragraph style without spacing, in the document's language
+fn styles(locale: Locale) -> String {
+    let language = match locale {
+        Locale::Ru => "ru-RU",
+        Locale::EnUs => "en-US",
+        Locale::De => "de-DE",
+        Locale::Fr => "fr-FR",
+    };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:styles xmlns:w=\"{}\"><w:docDefaults><w:rPrDefault><w:rPr><w:lang w:val=\"{}\"/></w:rPr></w:rPrDefault><w:pPrDefault><w:pPr><w:spacing w:after=\"0\" w:line=\"240\" w:lineRule=\"auto\"/></w:pPr></w:pPrDefault></w:docDefaults><w:style w:type=\"paragraph\" w:default=\"1\" w:styleId=\"Normal\"><w:name w:val=\"Normal\"/><w:qFormat/></w:style></w:styles>",
+        NS_W, language
+    )
+}
+
+/// Settings; paragraphs of blocks that do not hyphenate suppress it
+fn settings(hyphenate: bool) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<w:settings xmlns:w=\"{}\"><w:defaultTabStop w:val=\"720\"/>{}<w:compat><w:compatSetting w:name=\"compatibilityMode\" w:uri=\"http://schemas.microsoft.com/office/word\" w:val=\"15\"/></w:compat></w:settings>",
+        NS_W,
+        if hyphenate { "<w:autoHyphenation/>" } else { "" }
+    )
+}
+
+fn core_properties(document: &Document) -> String {
+    let metadata = &document.metadata;
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n<cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:dcterms=\"http://purl.org/dc/terms/\" xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\"><dc:title>{}</dc:title>",
+        escape(&metadata.title)
+    );
+    if let Some(author) = &metadata.author {
+        let _ = write!(xml, "<dc:creator>{}</dc:creator>", escape(author));
+    }
+    if let Some(description) = &metadata.description {
+        let _ = write!(
+            xml,
+            "<dc:description>{}</dc:description>",
+            escape(description)
+        );
+    }
+    if let Some(tags) = metadata.tags.as_ref().filter(|t| !t.is_empty()) {
+        let _ = write!(
+            xml,
+            "<cp:keywords>{}</cp:keywords>",
+            escape(&tags.join(", "))
+        );
+    }
+    let _ = write!(
+        xml,
+        "<dcterms:created xsi:type=\"dcterms:W3CDTF\">{}</dcterms:created><dcterms:modified xsi:type=\"dcterms:W3CDTF\">{}</dcterms:modified></cp:coreProperties>",
+        metadata.created_at.to_rfc3339_opts(SecondsFormat::Secs, true),
+        metadata.updated_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    );
+    xml
+}
+
+/// Run content for text with hard line breaks, which `w:t` renders as spaces
+fn text_with_breaks(text: &str) -> String {
+    text.split('\n')
+        .map(|line| format!("<w:t xml:space=\"preserve\">{}</w:t>", escape(line)))
+        .collect::<Vec<_>>()
+        .join("<w:br/>")
+}
+
+/// Escape text for XML, dropping control characters XML cannot contain
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlockType, CellStyles, Position, Stroke, TableCell, TableRow, Transform};
+    use crate::services::rich_text;
+    use std::io::Read;
+    use zip::ZipArchive;
+
+    fn part(bytes: &[u8], name: &str) -> Option<String> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut file = archive.by_name(name).ok()?;
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+        Some(content)
+    }
+
+    #[test]
+    fn test_export_all_block_types() {
+        let mut document = Document::new("Export Test".to_string());
+        document.pages[0].background = Some("#fafafa".to_string());
+
+        let mut text = Block::for_test(BlockType::Text, 40.0, 40.0, 300.0, 100.0);
+        if let BlockContent::Text(content) = &mut text.content {
+            content.hyphenate = true;
+            content.set_paragraphs(rich_text::from_markdown(
+                "Total **120** EUR ^1^ see [terms](https://example.com?a=1&b=2)\n\n1. one\n2. two\n\n- dot",
+            ));
+        }
+        document.add_block(text);
+
+        let mut table = Block::for_test(BlockType::Table, 40.0, 200.0, 300.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![
+                TableRow {
+                    cells: vec![
+                        TableCell {
+                            content: "Item".to_string(),
+                            styles: Some(CellStyles {
+                                background: Some("#eeeeee".to_string()),
+                                color: None,
+                                bold: Some(true),
+                            }),
+                            name: None,
+                        },
+                        TableCell {
+                            content: "Price".to_string(),
+                            styles: None,
+                            name: None,
+                        },
+                    ],
+                },
+                TableRow {
+                    cells: vec![TableCell {
+                        content: "Total <net>".to_string(),
+                        styles: None,
+                        name: None,
+                    }],
+                },
+            ],
+            column_widths: vec![200.0, 100.0],
+        });
+        document.add_block(table);
+
+        // 1x1 transparent PNG, cropped to the block by `cover`
+        let mut image = Block::for_test(BlockType::Image, 40.0, 300.0, 100.0, 50.0);
+        image.content = BlockContent::Image(ImageBlockContent {
+            src: "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==".to_string(),
+            alt: "Logo".to_string(),
+            fit: Some(ImageFit::Cover),
+        });
+        document.add_block(image);
+
+        for kind in [ShapeKind::RoundedRect, ShapeKind::Polygon] {
+            let mut shape = Block::for_test(BlockType::Shape, 40.0, 450.0, 80.0, 40.0);
+            if let BlockContent::Shape(content) = &mut shape.content {
+                content.shape = kind;
+                content.corner_radius = Some(8.0);
+                content.stroke = Some(Stroke {
+                    color: "rgba(0, 0, 255, 0.5)".to_string(),
+                    width: 2.0,
+                    dash: StrokeDash::Dashed,
+                });
+                content.points = vec![
+                    Position { x: 40.0, y: 0.0 },
+                    Position { x: 80.0, y: 40.0 },
+                    Position { x: 0.0, y: 40.0 },
+                ];
+            }
+            document.add_block(shape);
+        }
+
+        let mut line = Block::for_test(BlockType::Line, 40.0, 520.0, 200.0, 40.0);
+        if let BlockContent::Line(content) = &mut line.content {
+            content.start = Position { x: 200.0, y: 0.0 };
+            content.end = Position { x: 0.0, y: 40.0 };
+            content.end_arrow = true;
+        }
+        document.add_block(line);
+
+        let first = document.pages[0].id.clone();
+        let second = document.duplicate_page(&first).unwrap();
+        document.get_page_mut(&second).unwrap().orientation = PageOrientation::Landscape;
+
+        let output = DocxRenderer::default().export(&document).unwrap();
+        assert!(output.warnings.is_empty(), "{:?}", output.warnings);
+
+        let xml = part(&output.bytes, "word/document.xml").unwrap();
+        assert_eq!(xml.matches("<w:sectPr>").count(), 2);
+        assert!(xml.contains("w:orient=\"landscape\""));
+        assert!(xml.contains("<wps:txbx>"));
+        assert!(xml.contains("<w:vertAlign w:val=\"superscript\"/>"));
+        assert!(xml.contains("<w:tblpPr"));
+        assert!(xml.contains("Total &lt;net&gt;"));
+        assert!(xml.contains("<pic:pic>"));
+        assert!(xml.contains("<a:srcRect l=\"0\" t=\"25000\" r=\"0\" b=\"25000\"/>"));
+        assert!(xml.contains("prst=\"roundRect\""));
+        assert!(xml.contains("<a:custGeom>"));
+        assert!(xml.contains("<a:xfrm flipH=\"1\">"));
+        assert!(xml.contains("<a:tailEnd type=\"triangle\"/>"));
+
+        let relationships = part(&output.bytes, "word/_rels/document.xml.rels").unwrap();
+        assert!(relationships
+            .contains("Target=\"https://example.com?a=1&amp;b=2\" TargetMode=\"External\""));
+        let archive = ZipArchive::new(Cursor::new(&output.bytes)).unwrap();
+        assert!(archive
+            .file_names()
+            .any(|name| name == "word/media/image1.png"));
+        let numbering = part(&output.bytes, "word/numbering.xml").unwrap();
+        assert!(numbering.contains("<w:startOverride w:val=\"1\"/>"));
+        assert!(part(&output.bytes, "word/settings.xml")
+            .unwrap()
+            .contains("<w:autoHyphenation/>"));
+    }
+
+    #[test]
+    fn test_export_warns_about_lost_styles() {
+        let mut document = Document::new("Warnings".to_string());
+
+        let mut text = Block::for_test(BlockType::Text, 40.0, 40.0, 300.0, 100.0);
+        if let BlockContent::Text(content) = &mut text.content {
+            content.text = "Faded".to_string();
+            content.font_weight = 300;
+        }
+        text.styles = Some(BlockStyles {
+            background: Some("#ffeeee".to_string()),
+            border: None,
+            padding: None,
+            shadow: None,
+            opacity: Some(0.5),
+        });
+        text.transform = Some(Transform {
+            rotation: 15.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+        });
+        let text_id = text.id.clone();
+        document.add_block(text);
+
+        let mut table = Block::for_test(BlockType::Table, 40.0, 200.0, 300.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![TableRow {
+                cells: vec![TableCell {
+                    content: "Item".to_string(),
+                    styles: None,
+                    name: None,
+                }],
+            }],
+            column_widths: vec![],
+        });
+        table.styles = Some(BlockStyles {
+            background: None,
+            border: None,
+            padding: None,
+            shadow: Some("2px 2px 4px #000000".to_string()),
+            opacity: None,
+        });
+        let table_id = table.id.clone();
+        document.add_block(table);
+
+        let warnings = DocxRenderer::default().export(&document).unwrap().warnings;
+        assert_eq!(
+            warnings,
+            vec![
+                format!("Block {}: rotation and scale are not exported", text_id),
+                format!(
+                    "Block {}: opacity applies to the background only, text stays opaque",
+                    text_id
+                ),
+                format!("Block {}: font weight 300 is exported as regular", text_id),
+                format!("Block {}: tables cannot have a shadow", table_id),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_line_breaks() {
+        let mut document = Document::new("Breaks".to_string());
+        let mut text = Block::for_test(BlockType::Text, 40.0, 40.0, 300.0, 100.0);
+        if let BlockContent::Text(content) = &mut text.content {
+            content.set_paragraphs(rich_text::from_html("<p>a<br>b</p>"));
+        }
+        document.add_block(text);
+
+        let output = DocxRenderer::default().export(&document).unwrap();
+        let xml = part(&output.bytes, "word/document.xml").unwrap();
+        assert!(xml.contains(
+            "<w:t xml:space=\"preserve\">a</w:t><w:br/><w:t xml:space=\"preserve\">b</w:t>"
+        ));
+    }
+
+    #[test]
+    fn test_missing_image_fails() {
+        let mut document = Document::new("Broken".to_string());
+        let mut image = Block::for_test(BlockType::Image, 0.0, 0.0, 50.0, 50.0);
+        image.content = BlockContent::Image(ImageBlockContent {
+            src: "/nonexistent/logo.png".to_string(),
+            alt: String::new(),
+            fit: None,
+        });
+        document.add_block(image);
+
+        assert!(DocxRenderer::default().export(&document).is_err());
+    }
+
+    #[test]
+    fn test_image_without_source_is_skipped() {
+        let mut document = Document::new("Draft".to_string());
+        document.add_block(Block::for_test(BlockType::Image, 0.0, 0.0, 50.0, 50.0));
+
+        let output = DocxRenderer::default().export(&document).unwrap();
+        let xml = part(&output.bytes, "word/document.xml").unwrap();
+        assert!(!xml.contains("<pic:pic"));
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use ttf_parser::{Face, GlyphId};
+
+/// Standard PDF fonts available to every viewer without embedding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StandardFont {
+    Helvetica,
+    HelveticaBold,
+    Courier,
+    CourierBold,
+    HelveticaOblique,
+    HelveticaBoldOblique,
+    CourierOblique,
+    CourierBoldOblique,
+}
+
+/// Helvetica advance widths for ASCII 32..=126 (1/1000 em)
+#[rustfmt::skip]
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, // '0'..'?'
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778, // '@'..'O'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556, // 'P'..'_'
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556, // '`'..'o'
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, // 'p'..'~'
+];
+
+/// Helvetica-Bold advance widths for ASCII 32..=126 (1/1000 em)
+#[rustfmt::skip]
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, // ' '..'/'
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, // '0'..'?'
+    975, 722, 722, 722, 722, 667, 611, 778, 722, 278, 556, 722, 611, 833, 722, 778, // '@'..'O'
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 333, 278, 333, 584, 556, // 'P'..'_'
+    333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556, 278, 889, 611, 611, // '`'..'o'
+    611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584, // 'p'..'~'
+];
+
+/// Courier is monospaced
+const COURIER_WIDTH: u16 = 600;
+
+impl StandardFont {
+    /// Pick the closest standard font for a CSS-like family name and weight
+    pub fn resolve(family: &str, weight: u16) -> Self {
+        Self::resolve_style(family, weight, false)
+    }
+
+    /// Like `resolve`, optionally picking the oblique variant
+    pub fn resolve_style(family: &str, weight: u16, italic: bool) -> Self {
+        let family = family.to_lowercase();
+        let monospace = ["mono", "courier", "consolas", "menlo"]
+            .iter()
+            .any(|name| family.contains(name));
+        let bold = weight >= 600;
+
+        match (monospace, bold, italic) {
+            (true, true, false) => StandardFont::CourierBold,
+            (true, false, false) => StandardFont::Courier,
+            (false, true, false) => StandardFont::HelveticaBold,
+            (false, false, false) => StandardFont::Helvetica,
+            (true, true, true) => StandardFont::CourierBoldOblique,
+            (true, false, true) => StandardFont::CourierOblique,
+            (false, true, true) => StandardFont::HelveticaBoldOblique,
+            (false, false, true) => StandardFont::HelveticaOblique,
+        }
+    }
+
+    fn is_courier(self) -> bool {
+        matches!(
+            self,
+            StandardFont::Courier
+                | StandardFont::CourierBold
+                | StandardFont::CourierOblique
+                | StandardFont::CourierBoldOblique
+        )
+    }
+
+    /// PostScript name written to the PDF font dictionary
+    pub fn base_font(self) -> &'static str {
+        match self {
+            StandardFont::Helvetica => "Helvetica",
+            StandardFont::HelveticaBold => "Helvetica-Bold",
+            StandardFont::Courier => "Courier",
+            StandardFont::CourierBold => "Courier-Bold",
+            StandardFont::HelveticaOblique => "Helvetica-Oblique",
+            StandardFont::HelveticaBoldOblique => "Helvetica-BoldOblique",
+            StandardFont::CourierOblique => "Courier-Oblique",
+            StandardFont::CourierBoldOblique => "Courier-BoldOblique",
+        }
+    }
+
+    /// Name used to reference the font from page resources
+    pub fn resource_name(self) -> &'static str {
+        match self {
+            StandardFont::Helvetica => "F1",
+            StandardFont::HelveticaBold => "F2",
+            StandardFont::Courier => "F3",
+            StandardFont::CourierBold => "F4",
+            StandardFont::HelveticaOblique => "F5",
+            StandardFont::HelveticaBoldOblique => "F6",
+            StandardFont::CourierOblique => "F7",
+            StandardFont::CourierBoldOblique => "F8",
+        }
+    }
+
+    /// Distance from the baseline to the top of capitals (1/1000 em)
+    pub fn ascent(self) -> f32 {
+        if self.is_courier() {
+            629.0
+        } else {
+            718.0
+        }
+    }
+
+    /// Advance width of a single character (1/1000 em)
+    pub fn char_width(self, c: char) -> u16 {
+        if self.is_courier() {
+            return COURIER_WIDTH;
+        }
+
+        // Oblique variants share the metrics of their upright fonts
+        let table = match self {
+            StandardFont::HelveticaBold | StandardFont::HelveticaBoldOblique => {
+                &HELVETICA_BOLD_WIDTHS
+            }
+            _ => &HELVETICA_WIDTHS,
+        };
+
+        match c {
+            ' '..='~' => table[c as usize - 32],
+            '\u{a0}' => table[0],
+            '—' | '…' | '‰' => 1000,
+            '•' => 350,
+            '‘' | '’' | '‚' => 222,
+            '“' | '”' | '„' => 333,
+            _ => 556,
+        }
+    }
+
+    /// Width of a string in points at the given font size
+    pub fn text_width(self, text: &str, font_size: f32) -> f32 {
+        let units: u32 = text.chars().map(|c| self.char_width(c) as u32).sum();
+        units as f32 * font_size / 1000.0
+    }
+}
+
+/// Encode text as WinAnsi bytes for the standard fonts
+///
+/// Characters outside the WinAnsi character set are replaced with `?`.
+pub fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| win_ansi_byte(c).unwrap_or(b'?'))
+        .collect()
+}
+
+/// WinAnsi code of a character, `None` if the standard fonts cannot show it
+fn win_ansi_byte(c: char) -> Option<u8> {
+    Some(match c {
+        ' '..='~' => c as u8,
+        '\u{a0}'..='\u{ff}' => c as u32 as u8,
+        '€' => 0x80,
+        '‚' => 0x82,
+        '„' => 0x84,
+        '…' => 0x85,
+        '‰' => 0x89,
+        '‘' => 0x91,
+        '’' => 0x92,
+        '“' => 0x93,
+        '”' => 0x94,
+        '•' => 0x95,
+        '–' => 0x96,
+        '—' => 0x97,
+        '™' => 0x99,
+        _ => return None,
+    })
+}
+
+/// Writing system of a character, for picking fallback fonts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    Latin,
+    Cyrillic,
+    /// Chinese, Japanese and Korean
+    Cjk,
+    Other,
+}
+
+impl Script {
+    pub fn of(c: char) -> Self {
+        match c as u32 {
+            0x0000..=0x024f | 0x1e00..=0x1eff => Script::Latin,
+            0x0400..=0x052f | 0x1c80..=0x1c8f | 0x2de0..=0x2dff | 0xa640..=0xa69f => {
+                Script::Cyrillic
+            }
+            0x1100..=0x11ff
+            | 0x2e80..=0x2fdf
+            | 0x3000..=0x30ff
+            | 0x3130..=0x318f
+            | 0x31f0..=0x31ff
+            | 0x3400..=0x4dbf
+            | 0x4e00..=0x9fff
+            | 0xac00..=0xd7af
+            | 0xf900..=0xfaff
+            | 0xff00..=0xffef
+            | 0x20000..=0x2ffff => Script::Cjk,
+            _ => Script::Other,
+        }
+    }
+}
+
+/// Face of a TrueType or OpenType font file, loaded for layout and embedding
+pub struct FontFace {
+    /// Unique within its registry; names the PDF font resource
+    pub id: usize,
+    pub post_script_name: String,
+    data: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    index: u32,
+    pub units_per_em: f32,
+    pub ascender: f32,
+    pub descender: f32,
+    pub cap_height: f32,
+    /// Bounding box of all glyphs (font units)
+    pub bbox: [f32; 4],
+    pub italic_angle: f32,
+    pub monospaced: bool,
+    /// Outlines are CFF rather than TrueType
+    pub cff: bool,
+    /// Glyph id and advance of every character looked up so far
+    glyphs: Mutex<HashMap<char, Option<(u16, u16)>>>,
+}
+
+impl FontFace {
+    /// Read the metrics of face `index` of a font file
+    pub fn parse(
+        id: usize,
+        post_script_name: String,
+        data: Arc<dyn AsRef<[u8]> + Send + Sync>,
+        index: u32,
+    ) -> Option<Self> {
+        let face = Face::parse((*data).as_ref(), index).ok()?;
+        let bbox = face.global_bounding_box();
+        let metrics = Self {
+            id,
+            post_script_name,
+            units_per_em: face.units_per_em() as f32,
+            ascender: face.ascender() as f32,
+            descender: face.descender() as f32,
+            cap_height: face.capital_height().unwrap_or(face.ascender()) as f32,
+            bbox: [
+                bbox.x_min as f32,
+                bbox.y_min as f32,
+                bbox.x_max as f32,
+                bbox.y_max as f32,
+            ],
+            italic_angle: face.italic_angle(),
+            monospaced: face.is_monospaced(),
+            cff: face.tables().cff.is_some() || face.tables().cff2.is_some(),
+            glyphs: Mutex::new(HashMap::new()),
+            data: Arc::clone(&data),
+            index,
+        };
+        Some(metrics)
+    }
+
+    /// Raw font file, possibly a collection
+    pub fn data(&self) -> &[u8] {
+        (*self.data).as_ref()
+    }
+
+    /// Index of the face within a font collection
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    fn face(&self) -> Option<Face<'_>> {
+        Face::parse(self.data(), self.index).ok()
+    }
+
+    /// Glyph id and advance (font units) of a character the font has
+    pub fn glyph(&self, c: char) -> Option<(u16, u16)> {
+        let mut glyphs = self.glyphs.lock().unwrap_or_else(|e| e.into_inner());
+        *glyphs.entry(c).or_insert_with(|| {
+            let face = self.face()?;
+            let id = face.glyph_index(c)?;
+            Some((id.0, face.glyph_hor_advance(id).unwrap_or(0)))
+        })
+    }
+
+    /// Advance of a glyph (font units)
+    pub fn advance(&self, glyph: u16) -> u16 {
+        self.face()
+            .and_then(|face| face.glyph_hor_advance(GlyphId(glyph)))
+            .unwrap_or(0)
+    }
+
+    /// Scale font units to 1/1000 em, the unit of PDF font metrics
+    pub fn to_pdf_units(&self, value: f32) -> f32 {
+        value * 1000.0 / self.units_per_em
+    }
+}
+
+impl fmt::Debug for FontFace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FontFace")
+            .field("id", &self.id)
+            .field("post_script_name", &self.post_script_name)
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// Font of a piece of text: a standard font or an embedded font file
+#[derive(Debug, Clone)]
+pub enum Font {
+    Standard(StandardFont),
+    Embedded(Arc<FontFace>),
+}
+
+impl PartialEq for Font {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Font::Standard(a), Font::Standard(b)) => a == b,
+            (Font::Embedded(a), Font::Embedded(b)) => a.id == b.id,
+            _ => false,
+        }
+    }
+}
+
+impl Font {
+    /// Name used to reference the font from page resources
+    pub fn resource_name(&self) -> String {
+        match self {
+            Font::Standard(font) => font.resource_name().to_string(),
+            Font::Embedded(face) => format!("E{}", face.id),
+        }
+    }
+
+    /// Distance from the baseline to the top of capitals (1/1000 em)
+    pub fn ascent(&self) -> f32 {
+        match self {
+            Font::Standard(font) => font.ascent(),
+            Font::Embedded(face) => face.to_pdf_units(face.ascender),
+        }
+    }
+
+    /// Whether the font has a glyph for the character
+    pub fn has_char(&self, c: char) -> bool {
+        match self {
+            Font::Standard(_) => win_ansi_byte(c).is_some(),
+            Font::Embedded(face) => face.glyph(c).is_some(),
+        }
+    }
+
+    /// Width of a string in points at the given font size
+    pub fn text_width(&self, text: &str, font_size: f32) -> f32 {
+        match self {
+            Font::Standard(font) => font.text_width(text, font_size),
+            Font::Embedded(face) => {
+                let units: f32 = text
+                    .chars()
+                    .map(|c| match face.glyph(c) {
+                        Some((_, advance)) => advance as f32,
+                        // Missing characters are shown as the .notdef glyph
+                        None => face.advance(0) as f32,
+                    })
+                    .sum();
+                units * font_size / face.units_per_em
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_font() {
+        assert_eq!(StandardFont::resolve("Inter", 400), StandardFont::Helvetica);
+        assert_eq!(
+            StandardFont::resolve("Inter", 700),
+            StandardFont::HelveticaBold
+        );
+        assert_eq!(
+            StandardFont::resolve("JetBrains Mono", 400),
+            StandardFont::Courier
+        );
+        assert_eq!(
+            StandardFont::resolve_style("Inter", 700, true),
+            StandardFont::HelveticaBoldOblique
+        );
+    }
+
+    #[test]
+    fn test_text_width() {
+        // "Hi" = 722 + 222 units
+        let width = StandardFont::Helvetica.text_width("Hi", 10.0);
+        assert!((width - 9.44).abs() < 0.001);
+        assert_eq!(StandardFont::Courier.text_width("abc", 10.0), 18.0);
+    }
+
+    #[test]
+    fn test_encode_win_ansi() {
+        assert_eq!(encode_win_ansi("A€é"), vec![b'A', 0x80, 0xe9]);
+        assert_eq!(encode_win_ansi("Я"), vec![b'?']);
+        assert!(!Font::Standard(StandardFont::Helvetica).has_char('Я'));
+    }
+
+    #[test]
+    fn test_script() {
+        assert_eq!(Script::of('é'), Script::Latin);
+        assert_eq!(Script::of('Ж'), Script::Cyrillic);
+        assert_eq!(Script::of('漢'), Script::Cjk);
+        assert_eq!(Script::of('한'), Script::Cjk);
+        assert_eq!(Script::of('א'), Script::Other);
+    }
+}
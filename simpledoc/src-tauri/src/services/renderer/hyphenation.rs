@@ -0,0 +1,92 @@
+/// Vowels of the Latin and Cyrillic alphabets, lowercase
+const VOWELS: &str = "aeiouyäöüàáâèéêìíîòóôùúûаеёиоуыэюя";
+
+/// Letters that never start a syllable
+const NO_ONSET: &str = "ьъй";
+
+/// Minimum number of letters kept before a break
+const MIN_PREFIX: usize = 2;
+
+/// Minimum number of letters moved after a break
+const MIN_SUFFIX: usize = 3;
+
+/// Character positions where a word may be hyphenated
+///
+/// A language-independent approximation of syllable boundaries for Latin and
+/// Cyrillic words: a consonant cluster before a vowel is split after its
+/// first consonant, a single consonant goes with the following vowel.
+/// Leading and trailing punctuation is kept; words with other characters
+/// (digits, mixed scripts with symbols) are not hyphenated.
+pub fn break_points(word: &str) -> Vec<usize> {
+    let chars: Vec<char> = word.chars().collect();
+    let start = chars
+        .iter()
+        .position(|c| c.is_alphabetic())
+        .unwrap_or(chars.len());
+    let end = chars
+        .iter()
+        .rposition(|c| c.is_alphabetic())
+        .map_or(start, |i| i + 1);
+    let core = &chars[start..end];
+    if core.len() < MIN_PREFIX + MIN_SUFFIX || !core.iter().all(|c| c.is_alphabetic()) {
+        return Vec::new();
+    }
+
+    let vowel = |c: char| c.to_lowercase().all(|c| VOWELS.contains(c));
+    let mut points = Vec::new();
+
+    for v in 1..core.len() {
+        if !vowel(core[v]) || vowel(core[v - 1]) {
+            continue;
+        }
+        let mut cluster = v - 1;
+        while cluster > 0 && !vowel(core[cluster - 1]) {
+            cluster -= 1;
+        }
+        if cluster == 0 {
+            // No vowel before the cluster, so no syllable to split off
+            continue;
+        }
+
+        let mut point = if v - cluster >= 2 {
+            cluster + 1
+        } else {
+            cluster
+        };
+        while point < v && core[point].to_lowercase().all(|c| NO_ONSET.contains(c)) {
+            point += 1;
+        }
+        if point >= MIN_PREFIX && core.len() - point >= MIN_SUFFIX {
+            points.push(start + point);
+        }
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hyphenate(word: &str) -> String {
+        let chars: Vec<char> = word.chars().collect();
+        let mut result = String::new();
+        for (i, c) in chars.iter().enumerate() {
+            if break_points(word).contains(&i) {
+                result.push('-');
+            }
+            result.push(*c);
+        }
+        result
+    }
+
+    #[test]
+    fn test_break_points() {
+        assert_eq!(hyphenate("contract"), "con-tract");
+        assert_eq!(hyphenate("documentation,"), "do-cu-men-ta-tion,");
+        assert_eq!(hyphenate("документация"), "до-ку-мен-та-ция");
+        assert_eq!(hyphenate("подъезд"), "подъ-езд");
+        assert_eq!(hyphenate("short"), "short");
+        assert_eq!(hyphenate("R2D2R2D2"), "R2D2R2D2");
+    }
+}
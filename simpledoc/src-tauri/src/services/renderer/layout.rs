@@ -0,0 +1,750 @@
+use super::fonts::{Font, Script};
+use super::hyphenation;
+use super::pdf::{parse_color, Rgba, LINE_HEIGHT, PT_PER_PX};
+use crate::models::{
+    Block, BlockContent, Document, ListKind, Paragraph, TextAlignment, TextBlockContent, TextRun,
+    VerticalAlign,
+};
+use crate::services::font_registry::FontRegistry;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+/// Indentation per list level (in pixels)
+pub(crate) const LIST_INDENT_PX: f32 = 24.0;
+
+/// Size of superscript and subscript text relative to the run
+const SCRIPT_SCALE: f32 = 0.7;
+
+/// Invisible break opportunity that shows a hyphen when used
+const SOFT_HYPHEN: char = '\u{ad}';
+
+/// Text may exceed the block by this much before it counts as clipped (in pixels)
+const OVERFLOW_TOLERANCE_PX: f64 = 0.5;
+
+/// Resolved style of a piece of text
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunStyle {
+    pub font: Font,
+    /// Font size in points
+    pub size: f32,
+    /// Baseline shift in points (positive is up)
+    pub rise: f32,
+    pub color: Rgba,
+    pub underline: bool,
+    pub strike: bool,
+    pub link: Option<String>,
+}
+
+impl RunStyle {
+    fn width(&self, text: &str) -> f32 {
+        self.font.text_width(text, self.size)
+    }
+}
+
+/// Text in one style, positioned on its line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub text: String,
+    /// Offset from the left edge of the text box (in points)
+    pub x: f32,
+    pub width: f32,
+    pub style: RunStyle,
+}
+
+/// A laid out line of mixed runs
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine {
+    /// List marker and text fragments
+    pub fragments: Vec<Fragment>,
+    /// Distance from the top of the text box to the baseline (in points)
+    pub baseline: f32,
+    /// Line height (in points)
+    pub height: f32,
+}
+
+/// Paragraphs broken into lines for a given width
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub lines: Vec<LayoutLine>,
+    /// Height of all lines and paragraph spacing (in points)
+    pub height: f32,
+}
+
+/// Space the text of a block needs at its current width
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextMeasure {
+    #[serde(rename = "blockId")]
+    pub block_id: String,
+    /// Block height that fits all lines, padding included (in pixels)
+    #[serde(rename = "requiredHeight")]
+    pub required_height: f64,
+    #[serde(rename = "lineCount")]
+    pub line_count: usize,
+    /// The text does not fit the current block height and gets clipped
+    pub overflow: bool,
+}
+
+/// Part of a word after which the word may be broken
+#[derive(Default)]
+struct Syllable {
+    pieces: Vec<(String, RunStyle)>,
+    /// Breaking after it shows a hyphen (soft hyphen or hyphenation point)
+    hyphen: bool,
+}
+
+impl Syllable {
+    fn push(&mut self, c: char, style: &RunStyle) {
+        match self.pieces.last_mut() {
+            Some((text, last)) if last == style => text.push(c),
+            _ => self.pieces.push((c.to_string(), style.clone())),
+        }
+    }
+
+    fn width(&self) -> f32 {
+        self.pieces
+            .iter()
+            .map(|(text, style)| style.width(text))
+            .sum()
+    }
+
+    /// Width of the hyphen shown when breaking after the syllable
+    fn hyphen_width(&self) -> f32 {
+        match self.pieces.last() {
+            Some((_, style)) if self.hyphen => style.width("-"),
+            _ => 0.0,
+        }
+    }
+
+    fn text(&self) -> String {
+        self.pieces.iter().map(|(text, _)| text.as_str()).collect()
+    }
+}
+
+/// Text up to the next whitespace, possibly spanning runs
+type Word = Vec<Syllable>;
+
+fn word_width(word: &[Syllable]) -> f32 {
+    word.iter().map(Syllable::width).sum()
+}
+
+enum Token {
+    Word(Word),
+    Space(RunStyle),
+    /// Line break inside a paragraph
+    Break,
+}
+
+/// Line being filled: fragments relative to the line start
+#[derive(Default)]
+struct PendingLine {
+    fragments: Vec<Fragment>,
+    width: f32,
+    /// Whitespace to insert before the next word
+    space: Option<RunStyle>,
+}
+
+impl PendingLine {
+    fn push(&mut self, text: &str, style: &RunStyle) {
+        let width = style.width(text);
+        self.fragments.push(Fragment {
+            text: text.to_string(),
+            x: self.width,
+            width,
+            style: style.clone(),
+        });
+        self.width += width;
+    }
+
+    fn space_width(&self) -> f32 {
+        self.space.as_ref().map_or(0.0, |space| space.width(" "))
+    }
+
+    /// Append syllables, ending with a hyphen if the word is broken there
+    fn push_syllables(&mut self, syllables: &[Syllable], broken: bool) {
+        if let Some(space) = self.space.take() {
+            self.push(" ", &space);
+        }
+        for (text, style) in syllables.iter().flat_map(|s| s.pieces.iter()) {
+            self.push(text, style);
+        }
+        if let Some(last) = syllables.last().filter(|_| broken) {
+            if let Some((_, style)) = last.pieces.last().filter(|_| last.hyphen) {
+                self.push("-", style);
+            }
+        }
+    }
+}
+
+/// Break the block's paragraphs into lines that fit into `max_width` points
+pub fn layout_text(content: &TextBlockContent, max_width: f32, fonts: &FontRegistry) -> TextLayout {
+    let base = RunStyle {
+        font: fonts.resolve(&content.font_family, content.font_weight, false),
+        size: content.font_size as f32 * PT_PER_PX,
+        rise: 0.0,
+        color: parse_color(&content.color).unwrap_or(Rgba::BLACK),
+        underline: false,
+        strike: false,
+        link: None,
+    };
+    let mut layout = TextLayout {
+        lines: Vec::new(),
+        height: 0.0,
+    };
+    let line_height = content
+        .line_height
+        .map_or(LINE_HEIGHT, |height| height as f32);
+    // Counters of numbered lists, one per level
+    let mut counters: Vec<usize> = Vec::new();
+
+    for paragraph in content.paragraphs() {
+        let indent = match paragraph.list {
+            Some(_) => (paragraph.level as f32 + 1.0) * LIST_INDENT_PX * PT_PER_PX,
+            None => 0.0,
+        };
+        let marker = list_marker(&paragraph, &mut counters);
+        let alignment = paragraph.alignment.unwrap_or(content.alignment);
+
+        layout.height += paragraph.space_before as f32 * PT_PER_PX;
+        let lines = break_lines(
+            &paragraph,
+            content,
+            &base,
+            fonts,
+            (max_width - indent).max(0.0),
+        );
+        let count = lines.len();
+
+        for (index, (line, forced_break)) in lines.into_iter().enumerate() {
+            let last = index + 1 == count || forced_break;
+            let mut fragments = align(line, alignment, (max_width - indent).max(0.0), last);
+            for fragment in &mut fragments {
+                fragment.x += indent;
+            }
+            if index == 0 {
+                if let Some(marker) = &marker {
+                    fragments.insert(
+                        0,
+                        Fragment {
+                            text: marker.clone(),
+                            x: indent - LIST_INDENT_PX * PT_PER_PX * 0.75,
+                            width: base.width(marker),
+                            style: base.clone(),
+                        },
+                    );
+                }
+            }
+            layout.push_line(fragments, &base, line_height);
+        }
+        layout.height += paragraph.space_after as f32 * PT_PER_PX;
+    }
+
+    layout
+}
+
+/// Lay out a text block the way it is rendered and compare with its height
+///
+/// Returns `None` for blocks other than text.
+pub fn measure_block(block: &Block, fonts: &FontRegistry) -> Option<TextMeasure> {
+    let BlockContent::Text(text) = &block.content else {
+        return None;
+    };
+    let (vertical, horizontal) = match block.styles.as_ref().and_then(|s| s.padding.as_ref()) {
+        Some(p) => (p.top + p.bottom, p.left + p.right),
+        None => (0.0, 0.0),
+    };
+
+    let width = ((block.size.width - horizontal).max(0.0) as f32) * PT_PER_PX;
+    let layout = layout_text(text, width, fonts);
+    let required_height = (layout.height / PT_PER_PX) as f64 + vertical;
+
+    Some(TextMeasure {
+        block_id: block.id.clone(),
+        required_height,
+        line_count: layout.lines.len(),
+        overflow: required_height > block.size.height + OVERFLOW_TOLERANCE_PX,
+    })
+}
+
+/// Resize text blocks with `autoHeight` to the height of their text
+pub fn apply_auto_height(document: &mut Document, fonts: &FontRegistry) {
+    for block in &mut document.blocks {
+        if !is_auto_height(block) {
+            continue;
+        }
+        if let Some(measure) = measure_block(block, fonts) {
+            block.size.height = measure.required_height;
+        }
+    }
+}
+
+/// The document with auto height applied, copied only if it has such blocks
+pub fn with_auto_height<'a>(document: &'a Document, fonts: &FontRegistry) -> Cow<'a, Document> {
+    if !document.blocks.iter().any(is_auto_height) {
+        return Cow::Borrowed(document);
+    }
+    let mut document = document.clone();
+    apply_auto_height(&mut document, fonts);
+    Cow::Owned(document)
+}
+
+/// Text block whose height follows its text
+pub fn is_auto_height(block: &Block) -> bool {
+    matches!(&block.content, BlockContent::Text(text) if text.auto_height)
+}
+
+impl TextLayout {
+    fn push_line(&mut self, fragments: Vec<Fragment>, base: &RunStyle, line_height: f32) {
+        // Line height follows the largest run; empty lines use the block font
+        let size = fragments
+            .iter()
+            .map(|f| f.style.size)
+            .fold(None, |max: Option<f32>, size| {
+                Some(max.map_or(size, |m| m.max(size)))
+            })
+            .unwrap_or(base.size);
+        let ascent = fragments
+            .iter()
+            .map(|f| f.style.font.ascent() * f.style.size / 1000.0 + f.style.rise)
+            .fold(base.font.ascent() * size / 1000.0, f32::max);
+        let height = size * line_height;
+
+        self.lines.push(LayoutLine {
+            fragments: merge_fragments(fragments),
+            baseline: self.height + (height - size) / 2.0 + ascent,
+            height,
+        });
+        self.height += height;
+    }
+}
+
+/// Bullet or number of a list item, advancing the numbering
+fn list_marker(paragraph: &Paragraph, counters: &mut Vec<usize>) -> Option<String> {
+    let Some(kind) = paragraph.list else {
+        counters.clear();
+        return None;
+    };
+    let level = paragraph.level as usize;
+    counters.resize(level + 1, 0);
+
+    match kind {
+        ListKind::Bullet => {
+            counters[level] = 0;
+            Some("•".to_string())
+        }
+        ListKind::Numbered => {
+            counters[level] += 1;
+            Some(format!("{}.", counters[level]))
+        }
+    }
+}
+
+fn run_style(
+    run: &TextRun,
+    content: &TextBlockContent,
+    base: &RunStyle,
+    fonts: &FontRegistry,
+) -> RunStyle {
+    let weight = run_weight(run, content);
+    let mut size = run
+        .font_size
+        .map(|size| size as f32 * PT_PER_PX)
+        .unwrap_or(base.size);
+    let rise = match run.vertical_align {
+        Some(VerticalAlign::Superscript) => size * 0.35,
+        Some(VerticalAlign::Subscript) => -size * 0.15,
+        None => 0.0,
+    };
+    if run.vertical_align.is_some() {
+        size *= SCRIPT_SCALE;
+    }
+
+    RunStyle {
+        font: fonts.resolve(&content.font_family, weight, run.italic),
+        size,
+        rise,
+        color: run
+            .color
+            .as_deref()
+            .and_then(parse_color)
+            .unwrap_or(base.color),
+        underline: run.underline,
+        strike: run.strike,
+        link: run.link.clone(),
+    }
+}
+
+fn run_weight(run: &TextRun, content: &TextBlockContent) -> u16 {
+    if run.bold {
+        700
+    } else {
+        content.font_weight
+    }
+}
+
+/// Split runs into words, whitespace and line breaks
+///
+/// Words end only at whitespace, so a style change inside a word (`**12**,50`)
+/// never allows a break. Inside words, breaks are allowed after hyphens, at
+/// soft hyphens, after CJK characters and, if the block hyphenates, at
+/// syllable boundaries. Characters the run's font lacks get a fallback font.
+fn tokenize(
+    paragraph: &Paragraph,
+    content: &TextBlockContent,
+    base: &RunStyle,
+    fonts: &FontRegistry,
+) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = Word::new();
+    let mut syllable = Syllable::default();
+
+    for run in &paragraph.runs {
+        let style = run_style(run, content, base, fonts);
+        let weight = run_weight(run, content);
+
+        for c in run.text.chars() {
+            if c == '\n' || (c.is_whitespace() && c != '\u{a0}') {
+                end_word(&mut tokens, &mut word, &mut syllable, content.hyphenate);
+                tokens.push(if c == '\n' {
+                    Token::Break
+                } else {
+                    Token::Space(style.clone())
+                });
+            } else if c == SOFT_HYPHEN {
+                if !syllable.pieces.is_empty() {
+                    syllable.hyphen = true;
+                    word.push(std::mem::take(&mut syllable));
+                }
+            } else {
+                match fonts.fallback(&style.font, c, weight, run.italic) {
+                    Some(font) => syllable.push(
+                        c,
+                        &RunStyle {
+                            font,
+                            ..style.clone()
+                        },
+                    ),
+                    None => syllable.push(c, &style),
+                }
+                let breaks_after = match c {
+                    '-' => syllable.text().chars().count() > 1,
+                    _ => Script::of(c) == Script::Cjk,
+                };
+                if breaks_after {
+                    word.push(std::mem::take(&mut syllable));
+                }
+            }
+        }
+    }
+    end_word(&mut tokens, &mut word, &mut syllable, content.hyphenate);
+
+    tokens
+}
+
+fn end_word(tokens: &mut Vec<Token>, word: &mut Word, syllable: &mut Syllable, hyphenate: bool) {
+    if !syllable.pieces.is_empty() {
+        word.push(std::mem::take(syllable));
+    }
+    if word.is_empty() {
+        return;
+    }
+    let mut word = std::mem::take(word);
+    if hyphenate {
+        word = word.into_iter().flat_map(split_syllable).collect();
+    }
+    tokens.push(Token::Word(word));
+}
+
+/// Split a syllable at its hyphenation points
+fn split_syllable(syllable: Syllable) -> Vec<Syllable> {
+    let points = hyphenation::break_points(&syllable.text());
+    if points.is_empty() {
+        return vec![syllable];
+    }
+
+    let mut parts = vec![Syllable::default()];
+    let mut index = 0;
+    for (text, style) in &syllable.pieces {
+        for c in text.chars() {
+            if points.contains(&index) {
+                parts.last_mut().unwrap().hyphen = true;
+                parts.push(Syllable::default());
+            }
+            parts.last_mut().unwrap().push(c, style);
+            index += 1;
+        }
+    }
+    parts.last_mut().unwrap().hyphen = syllable.hyphen;
+    parts
+}
+
+/// Greedy line breaking; each line is flagged if it ends at a forced break
+fn break_lines(
+    paragraph: &Paragraph,
+    content: &TextBlockContent,
+    base: &RunStyle,
+    fonts: &FontRegistry,
+    max_width: f32,
+) -> Vec<(PendingLine, bool)> {
+    let mut lines = Vec::new();
+    let mut line = PendingLine::default();
+
+    for token in tokenize(paragraph, content, base, fonts) {
+        match token {
+            Token::Space(style) => {
+                // Consecutive and leading whitespace collapses
+                if !line.fragments.is_empty() && line.space.is_none() {
+                    line.space = Some(style);
+                }
+            }
+            Token::Break => lines.push((std::mem::take(&mut line), true)),
+            Token::Word(word) => place_word(word, &mut line, &mut lines, max_width),
+        }
+    }
+    line.space = None;
+    lines.push((line, false));
+
+    lines
+}
+
+/// Put a word on the line, breaking it between syllables where it does not fit
+fn place_word(
+    mut word: Word,
+    line: &mut PendingLine,
+    lines: &mut Vec<(PendingLine, bool)>,
+    max_width: f32,
+) {
+    loop {
+        let used = line.width + line.space_width();
+        if used + word_width(&word) <= max_width {
+            line.push_syllables(&word, false);
+            return;
+        }
+
+        // Longest part of the word that still fits, hyphen included
+        let fits = (1..word.len())
+            .rev()
+            .find(|&k| used + word_width(&word[..k]) + word[k - 1].hyphen_width() <= max_width);
+        if let Some(k) = fits {
+            let rest = word.split_off(k);
+            line.push_syllables(&word, true);
+            lines.push((std::mem::take(line), false));
+            word = rest;
+            continue;
+        }
+        if !line.fragments.is_empty() {
+            lines.push((std::mem::take(line), false));
+            continue;
+        }
+
+        // Syllables wider than the box are broken at character boundaries
+        let first = word.remove(0);
+        for (text, style) in &first.pieces {
+            for c in text.chars() {
+                let width = style.width(&c.to_string());
+                if line.width + width > max_width && !line.fragments.is_empty() {
+                    lines.push((std::mem::take(line), false));
+                }
+                line.push(&c.to_string(), style);
+            }
+        }
+        if word.is_empty() {
+            return;
+        }
+    }
+}
+
+/// Position fragments for the alignment within `width`
+fn align(line: PendingLine, alignment: TextAlignment, width: f32, last: bool) -> Vec<Fragment> {
+    let mut fragments = line.fragments;
+    let free = (width - line.width).max(0.0);
+
+    match alignment {
+        TextAlignment::Left => {}
+        TextAlignment::Center => fragments.iter_mut().for_each(|f| f.x += free / 2.0),
+        TextAlignment::Right => fragments.iter_mut().for_each(|f| f.x += free),
+        TextAlignment::Justify => {
+            let gaps = fragments.iter().filter(|f| f.text == " ").count();
+            if !last && gaps > 0 {
+                let extra = free / gaps as f32;
+                let mut shift = 0.0;
+                for fragment in &mut fragments {
+                    fragment.x += shift;
+                    if fragment.text == " " {
+                        fragment.width += extra;
+                        shift += extra;
+                    }
+                }
+            }
+        }
+    }
+
+    fragments
+}
+
+/// Join neighbouring fragments of the same style into one text run
+fn merge_fragments(fragments: Vec<Fragment>) -> Vec<Fragment> {
+    let mut merged: Vec<Fragment> = Vec::new();
+
+    for fragment in fragments {
+        if let Some(last) = merged.last_mut() {
+            let adjacent = (last.x + last.width - fragment.x).abs() < 0.01;
+            let natural = last.style.width(&last.text);
+            // Justified gaps are wider than the space glyph and stay separate
+            if adjacent && last.style == fragment.style && (natural - last.width).abs() < 0.01 {
+                last.text.push_str(&fragment.text);
+                last.width += fragment.width;
+                continue;
+            }
+        }
+        merged.push(fragment);
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::BlockType;
+    use crate::services::renderer::fonts::StandardFont;
+
+    fn content(paragraphs: Vec<Paragraph>) -> TextBlockContent {
+        let mut content = TextBlockContent {
+            text: String::new(),
+            font_size: 16.0,
+            font_family: "JetBrains Mono".to_string(),
+            font_weight: 400,
+            color: "#000000".to_string(),
+            alignment: TextAlignment::Left,
+            paragraphs: Vec::new(),
+            line_height: None,
+            hyphenate: false,
+            auto_height: false,
+        };
+        content.set_paragraphs(paragraphs);
+        content
+    }
+
+    fn run(text: &str, bold: bool) -> TextRun {
+        TextRun {
+            bold,
+            ..TextRun::plain(text.to_string())
+        }
+    }
+
+    fn line_text(line: &LayoutLine) -> String {
+        line.fragments.iter().map(|f| f.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_breaks_mixed_runs_at_whitespace() {
+        // Courier 12pt: 7.2pt per character, so 10 characters per 75pt line
+        let paragraph = Paragraph {
+            runs: vec![
+                run("Price ", false),
+                run("120", true),
+                run(",50 EUR net", false),
+            ],
+            ..Paragraph::plain(String::new())
+        };
+        let layout = layout_text(&content(vec![paragraph]), 75.0, &FontRegistry::new());
+
+        let lines: Vec<String> = layout.lines.iter().map(line_text).collect();
+        assert_eq!(lines, vec!["Price", "120,50 EUR", "net"]);
+        // The bold price stays its own fragment, glued to the following run
+        let second = &layout.lines[1].fragments;
+        assert_eq!(second[0].text, "120");
+        assert_eq!(
+            second[0].style.font,
+            Font::Standard(StandardFont::CourierBold)
+        );
+        assert!((second[1].x - 3.0 * 7.2).abs() < 0.01);
+        assert!((layout.height - 3.0 * 12.0 * LINE_HEIGHT).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_list_markers_and_spacing() {
+        let item = |text: &str, list: ListKind| Paragraph {
+            list: Some(list),
+            ..Paragraph::plain(text.to_string())
+        };
+        let mut heading = Paragraph::plain("Terms".to_string());
+        heading.space_after = 8.0;
+        let layout = layout_text(
+            &content(vec![
+                heading,
+                item("one", ListKind::Numbered),
+                item("two", ListKind::Numbered),
+                item("dot", ListKind::Bullet),
+            ]),
+            300.0,
+            &FontRegistry::new(),
+        );
+
+        let markers: Vec<&str> = layout.lines[1..]
+            .iter()
+            .map(|line| line.fragments[0].text.as_str())
+            .collect();
+        assert_eq!(markers, vec!["1.", "2.", "•"]);
+        assert!(layout.lines[1].fragments[1].x > layout.lines[1].fragments[0].x);
+        assert!((layout.height - (4.0 * 12.0 * LINE_HEIGHT + 6.0)).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_hyphenates_long_words() {
+        // 10 characters per line, so "documentation" must be broken
+        let mut text = content(vec![Paragraph::plain("the documentation".to_string())]);
+        let lines = |text: &TextBlockContent| -> Vec<String> {
+            layout_text(text, 75.0, &FontRegistry::new())
+                .lines
+                .iter()
+                .map(line_text)
+                .collect()
+        };
+        assert_eq!(lines(&text), vec!["the", "documentat", "ion"]);
+
+        text.hyphenate = true;
+        assert_eq!(lines(&text), vec!["the docu-", "mentation"]);
+
+        text.hyphenate = false;
+        text.set_paragraphs(vec![Paragraph::plain(
+            "the docu\u{ad}mentation".to_string(),
+        )]);
+        assert_eq!(lines(&text), vec!["the docu-", "mentation"]);
+    }
+
+    #[test]
+    fn test_measure_and_auto_height() {
+        let mut block = Block::for_test(BlockType::Text, 0.0, 0.0, 100.0, 20.0);
+        let BlockContent::Text(text) = &mut block.content else {
+            unreachable!();
+        };
+        *text = content(vec![Paragraph::plain(
+            "one two three four five".to_string(),
+        )]);
+        text.line_height = Some(1.5);
+
+        // 16px Courier is 9.6px per character, so 10 characters per line
+        let measure = measure_block(&block, &FontRegistry::new()).unwrap();
+        assert_eq!(measure.line_count, 3);
+        assert!((measure.required_height - 3.0 * 16.0 * 1.5).abs() < 0.01);
+        assert!(measure.overflow);
+
+        let mut document = Document::new("Letter".to_string());
+        document.add_block(block);
+        assert!(matches!(
+            with_auto_height(&document, &FontRegistry::new()),
+            Cow::Borrowed(_)
+        ));
+        if let BlockContent::Text(text) = &mut document.blocks[0].content {
+            text.auto_height = true;
+        }
+        let sized = with_auto_height(&document, &FontRegistry::new());
+        assert!((sized.blocks[0].size.height - 72.0).abs() < 0.01);
+        assert!(
+            !measure_block(&sized.blocks[0], &FontRegistry::new())
+                .unwrap()
+                .overflow
+        );
+    }
+}
@@ -0,0 +1,29 @@
+pub mod docx;
+pub mod fonts;
+pub mod hyphenation;
+pub mod layout;
+pub mod pdf;
+
+pub use docx::DocxRenderer;
+pub use layout::TextMeasure;
+pub use pdf::PdfRenderer;
+
+use crate::models::{Document, Result};
+use serde::{Deserialize, Serialize};
+
+/// Backend used to generate output files
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderBackend {
+    /// In-process Rust renderer
+    #[default]
+    Native,
+    /// Legacy reportlab script run through `PythonService`
+    Python,
+}
+
+/// Turns a document into an encoded output file
+pub trait Renderer {
+    /// Render the whole document and return the file contents
+    fn render(&self, document: &Document) -> Result<Vec<u8>>;
+}
@@ -0,0 +1,1698 @@
+use super::fonts::{encode_win_ansi, Font, FontFace, StandardFont};
+use super::layout::{layout_text, with_auto_height, Fragment};
+use super::Renderer;
+use crate::models::{
+    AppError, Block, BlockContent, BlockStyles, Document, ImageBlockContent, ImageFit,
+    LineBlockContent, Page, Position, Result, ShapeBlockContent, ShapeKind, StrokeDash,
+    TableBlockContent, TextAlignment, TextBlockContent, Transform,
+};
+use crate::services::assets::{asset_hash, AssetStore};
+use crate::services::font_registry::FontRegistry;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use log::{debug, info, warn};
+use pdf_writer::types::{
+    ActionType, AnnotationType, CidFontType, FontFlags, SystemInfo, UnicodeCmap,
+};
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+/// Points per canvas pixel (the editor works at 96 DPI, PDF at 72 DPI)
+pub const PT_PER_PX: f32 = 72.0 / 96.0;
+
+/// Points per millimeter
+pub const PT_PER_MM: f32 = 72.0 / 25.4;
+
+/// Line height as a multiple of the font size
+pub(crate) const LINE_HEIGHT: f32 = 1.2;
+
+/// Font size used for table cells (in pixels)
+pub(crate) const TABLE_FONT_SIZE_PX: f32 = 14.0;
+
+/// Inner padding of table cells (in pixels)
+pub(crate) const TABLE_CELL_PADDING_PX: f32 = 4.0;
+
+/// Control point distance for approximating a quarter circle with a Bézier curve
+const KAPPA: f32 = 0.552_284_8;
+
+/// Native PDF renderer
+///
+/// Draws every block at its absolute position, so no Python installation or
+/// external process is needed. Text uses the fonts of the registry, embedded
+/// as subsets, or the standard PDF fonts.
+#[derive(Debug, Default, Clone)]
+pub struct PdfRenderer {
+    assets: Option<AssetStore>,
+    fonts: FontRegistry,
+}
+
+impl PdfRenderer {
+    /// Create a new PDF renderer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renderer that resolves `asset://` image sources from a store
+    pub fn with_assets(assets: AssetStore) -> Self {
+        Self {
+            assets: Some(assets),
+            ..Self::default()
+        }
+    }
+
+    /// Use the fonts of a registry instead of only the standard fonts
+    pub fn with_fonts(mut self, fonts: FontRegistry) -> Self {
+        self.fonts = fonts;
+        self
+    }
+}
+
+impl Renderer for PdfRenderer {
+    fn render(&self, document: &Document) -> Result<Vec<u8>> {
+        if document.pages.is_empty() {
+            return Err(AppError::RenderError("Document has no pages".to_string()));
+        }
+
+        info!(
+            "Rendering document {} with {} pages and {} blocks",
+            document.id,
+            document.pages.len(),
+            document.blocks.len()
+        );
+
+        // Auto height text blocks are drawn at the height of their text
+        let document = with_auto_height(document, &self.fonts);
+        let mut builder = PdfBuilder::new(self.assets.as_ref(), &self.fonts);
+        for page in &document.pages {
+            builder.render_page(&document, page)?;
+        }
+
+        Ok(builder.finish(&document.metadata.title))
+    }
+}
+
+/// RGBA color with components in 0..=1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl Rgba {
+    pub const BLACK: Rgba = Rgba::rgb(0, 0, 0);
+
+    const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self {
+            r: r as f32 / 255.0,
+            g: g as f32 / 255.0,
+            b: b as f32 / 255.0,
+            a: 1.0,
+        }
+    }
+}
+
+/// Parse a CSS color (hex, rgb(), rgba() or one of the basic named colors)
+pub fn parse_color(color: &str) -> Option<Rgba> {
+    let color = color.trim();
+
+    if let Some(hex) = color.strip_prefix('#') {
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let expanded: String = match hex.len() {
+            3 => hex.chars().flat_map(|c| [c, c]).collect(),
+            6 | 8 => hex.to_string(),
+            _ => return None,
+        };
+        let channel = |i: usize| u8::from_str_radix(&expanded[i..i + 2], 16).ok();
+        let mut rgba = Rgba::rgb(channel(0)?, channel(2)?, channel(4)?);
+        if expanded.len() == 8 {
+            rgba.a = channel(6)? as f32 / 255.0;
+        }
+        return Some(rgba);
+    }
+
+    if let Some(args) = color
+        .strip_prefix("rgba(")
+        .or_else(|| color.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let parts: Vec<f32> = args
+            .split(',')
+            .map(|p| p.trim().parse::<f32>())
+            .collect::<std::result::Result<_, _>>()
+            .ok()?;
+        if parts.len() != 3 && parts.len() != 4 {
+            return None;
+        }
+        return Some(Rgba {
+            r: parts[0].clamp(0.0, 255.0) / 255.0,
+            g: parts[1].clamp(0.0, 255.0) / 255.0,
+            b: parts[2].clamp(0.0, 255.0) / 255.0,
+            a: parts.get(3).copied().unwrap_or(1.0).clamp(0.0, 1.0),
+        });
+    }
+
+    match color.to_lowercase().as_str() {
+        "black" => Some(Rgba::rgb(0, 0, 0)),
+        "white" => Some(Rgba::rgb(255, 255, 255)),
+        "red" => Some(Rgba::rgb(255, 0, 0)),
+        "green" => Some(Rgba::rgb(0, 128, 0)),
+        "blue" => Some(Rgba::rgb(0, 0, 255)),
+        "yellow" => Some(Rgba::rgb(255, 255, 0)),
+        "gray" => Some(Rgba::rgb(128, 128, 128)),
+        "transparent" => Some(Rgba {
+            a: 0.0,
+            ..Rgba::BLACK
+        }),
+        _ => None,
+    }
+}
+
+/// Rectangle in PDF points with the origin at the bottom-left corner
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frame {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl Frame {
+    fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn top(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// Shrink the frame by the given insets (in points)
+    fn inset(&self, top: f32, right: f32, bottom: f32, left: f32) -> Self {
+        Self {
+            x: self.x + left,
+            y: self.y + bottom,
+            width: (self.width - left - right).max(0.0),
+            height: (self.height - top - bottom).max(0.0),
+        }
+    }
+}
+
+/// A wrapped line of text
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    text: String,
+    /// Last line of a paragraph (never justified)
+    ends_paragraph: bool,
+}
+
+/// Break text into lines that fit into `max_width` points
+fn wrap_text(text: &str, font: &Font, font_size: f32, max_width: f32) -> Vec<Line> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut current = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+
+            if font.text_width(&candidate, font_size) <= max_width {
+                current = candidate;
+                continue;
+            }
+
+            if !current.is_empty() {
+                lines.push(Line {
+                    text: std::mem::take(&mut current),
+                    ends_paragraph: false,
+                });
+            }
+
+            // Words wider than the box are broken at character boundaries
+            for c in word.chars() {
+                current.push(c);
+                if font.text_width(&current, font_size) > max_width && current.chars().count() > 1 {
+                    current.pop();
+                    lines.push(Line {
+                        text: std::mem::take(&mut current),
+                        ends_paragraph: false,
+                    });
+                    current.push(c);
+                }
+            }
+        }
+
+        lines.push(Line {
+            text: current,
+            ends_paragraph: true,
+        });
+    }
+
+    lines
+}
+
+/// Font used in the PDF and, for embedded fonts, the glyphs shown
+struct FontResource {
+    id: Ref,
+    font: Font,
+    /// Glyph ids and the text they stand for, for subsetting and copy & paste
+    glyphs: BTreeMap<u16, String>,
+}
+
+/// Image embedded into the PDF as an XObject
+#[derive(Debug, Clone)]
+struct ImageResource {
+    name: String,
+    id: Ref,
+    width: u32,
+    height: u32,
+}
+
+/// Content and resources of the page being drawn
+struct PageCanvas {
+    width: f32,
+    height: f32,
+    art_box: Frame,
+    content: Content,
+    /// Resource names of the fonts used on the page
+    fonts: BTreeSet<String>,
+    x_objects: BTreeMap<String, Ref>,
+    ext_states: BTreeMap<String, Ref>,
+    /// Hyperlink areas and their targets
+    links: Vec<(Frame, String)>,
+}
+
+impl PageCanvas {
+    fn new(width: f32, height: f32) -> Self {
+        Self {
+            width,
+            height,
+            art_box: Frame::new(0.0, 0.0, width, height),
+            content: Content::new(),
+            fonts: BTreeSet::new(),
+            x_objects: BTreeMap::new(),
+            ext_states: BTreeMap::new(),
+            links: Vec::new(),
+        }
+    }
+
+    /// Convert a block's canvas position/size into a PDF frame
+    fn block_frame(&self, block: &Block) -> Frame {
+        let width = block.size.width as f32 * PT_PER_PX;
+        let height = block.size.height as f32 * PT_PER_PX;
+        let x = block.position.x as f32 * PT_PER_PX;
+        let y = self.height - block.position.y as f32 * PT_PER_PX - height;
+        Frame::new(x, y, width, height)
+    }
+
+    /// Restrict drawing to the frame until `end_clip` is called
+    fn begin_clip(&mut self, frame: Frame) {
+        self.content.save_state();
+        self.content
+            .rect(frame.x, frame.y, frame.width, frame.height)
+            .clip_nonzero()
+            .end_path();
+    }
+
+    fn end_clip(&mut self) {
+        self.content.restore_state();
+    }
+}
+
+/// Low-level PDF assembly: object ids and shared resources
+struct PdfBuilder<'a> {
+    pdf: Pdf,
+    next_id: i32,
+    catalog_id: Ref,
+    page_tree_id: Ref,
+    page_ids: Vec<Ref>,
+    fonts: BTreeMap<String, FontResource>,
+    registry: &'a FontRegistry,
+    images: HashMap<String, ImageResource>,
+    alpha_states: HashMap<u16, Ref>,
+    assets: Option<&'a AssetStore>,
+}
+
+impl<'a> PdfBuilder<'a> {
+    fn new(assets: Option<&'a AssetStore>, registry: &'a FontRegistry) -> Self {
+        Self {
+            pdf: Pdf::new(),
+            next_id: 3,
+            catalog_id: Ref::new(1),
+            page_tree_id: Ref::new(2),
+            page_ids: Vec::new(),
+            fonts: BTreeMap::new(),
+            registry,
+            images: HashMap::new(),
+            alpha_states: HashMap::new(),
+            assets,
+        }
+    }
+
+    fn alloc(&mut self) -> Ref {
+        let id = Ref::new(self.next_id);
+        self.next_id += 1;
+        id
+    }
+
+    /// Select the graphics state with the given constant alpha
+    fn set_alpha(&mut self, canvas: &mut PageCanvas, alpha: f32) {
+        let key = (alpha.clamp(0.0, 1.0) * 1000.0).round() as u16;
+        let id = match self.alpha_states.get(&key) {
+            Some(id) => *id,
+            None => {
+                let id = self.alloc();
+                let value = key as f32 / 1000.0;
+                self.pdf
+                    .ext_graphics(id)
+                    .non_stroking_alpha(value)
+                    .stroking_alpha(value);
+                self.alpha_states.insert(key, id);
+                id
+            }
+        };
+
+        let name = format!("Gs{}", key);
+        canvas.content.set_parameters(Name(name.as_bytes()));
+        canvas.ext_states.insert(name, id);
+    }
+
+    fn set_font(&mut self, canvas: &mut PageCanvas, font: &Font, size: f32) {
+        let name = font.resource_name();
+        if !self.fonts.contains_key(&name) {
+            let id = self.alloc();
+            let resource = FontResource {
+                id,
+                font: font.clone(),
+                glyphs: BTreeMap::new(),
+            };
+            self.fonts.insert(name.clone(), resource);
+        }
+        canvas.content.set_font(Name(name.as_bytes()), size);
+        canvas.fonts.insert(name);
+    }
+
+    /// Encode text for a font set before, recording the glyphs it uses
+    fn encode_text(&mut self, font: &Font, text: &str) -> Vec<u8> {
+        let Font::Embedded(face) = font else {
+            return encode_win_ansi(text);
+        };
+        let Some(resource) = self.fonts.get_mut(&font.resource_name()) else {
+            return Vec::new();
+        };
+
+        let mut encoded = Vec::with_capacity(text.len() * 2);
+        for c in text.chars() {
+            // Identity-H: two-byte glyph ids, .notdef for missing characters
+            let glyph = face.glyph(c).map_or(0, |(glyph, _)| glyph);
+            resource
+                .glyphs
+                .entry(glyph)
+                .or_insert_with(|| c.to_string());
+            encoded.extend(glyph.to_be_bytes());
+        }
+        encoded
+    }
+
+    /// Draw one document page with its own size and background
+    fn render_page(&mut self, document: &Document, page: &Page) -> Result<()> {
+        let geometry = page.geometry().scale(PT_PER_MM as f64);
+        let mut canvas = PageCanvas::new(geometry.width as f32, geometry.height as f32);
+
+        // The content box inside the margins is recorded as the page's ArtBox
+        let content_box = geometry.content_box;
+        canvas.art_box = Frame::new(
+            content_box.x as f32,
+            (geometry.height - content_box.y - content_box.height) as f32,
+            content_box.width as f32,
+            content_box.height as f32,
+        );
+
+        if let Some(background) = page.background.as_deref().and_then(parse_color) {
+            let frame = Frame::new(0.0, 0.0, canvas.width, canvas.height);
+            self.fill_rect(&mut canvas, frame, background, 1.0);
+        }
+
+        // Paint in stacking order so higher z-index blocks end up on top
+        let mut blocks: Vec<&Block> = document.blocks_on_page(&page.id).collect();
+        blocks.sort_by_key(|b| b.z_index);
+
+        for block in blocks {
+            self.draw_block(&mut canvas, block)?;
+        }
+
+        self.finish_page(canvas);
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, canvas: &mut PageCanvas, frame: Frame, color: Rgba, opacity: f32) {
+        self.set_alpha(canvas, color.a * opacity);
+        canvas
+            .content
+            .set_fill_rgb(color.r, color.g, color.b)
+            .rect(frame.x, frame.y, frame.width, frame.height)
+            .fill_nonzero();
+    }
+
+    fn draw_block(&mut self, canvas: &mut PageCanvas, block: &Block) -> Result<()> {
+        debug!("Rendering block {} ({:?})", block.id, block.block_type());
+
+        let frame = canvas.block_frame(block);
+        let styles = block.styles.as_ref();
+        let opacity = styles
+            .and_then(|s| s.opacity)
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0) as f32;
+
+        let transform = block.transform.as_ref().filter(|t| !t.is_identity());
+        if let Some(transform) = transform {
+            canvas
+                .content
+                .save_state()
+                .transform(transform_matrix(transform, frame));
+        }
+
+        if let Some(styles) = styles {
+            self.draw_background(canvas, styles, frame, opacity);
+        }
+
+        let content_frame = match styles.and_then(|s| s.padding.as_ref()) {
+            Some(p) => frame.inset(
+                p.top as f32 * PT_PER_PX,
+                p.right as f32 * PT_PER_PX,
+                p.bottom as f32 * PT_PER_PX,
+                p.left as f32 * PT_PER_PX,
+            ),
+            None => frame,
+        };
+
+        match &block.content {
+            BlockContent::Text(text) => self.draw_text(canvas, text, content_frame, opacity),
+            BlockContent::Image(image) => self.draw_image(canvas, image, content_frame, opacity)?,
+            BlockContent::Table(table) => self.draw_table(canvas, table, content_frame, opacity),
+            BlockContent::Shape(shape) => self.draw_shape(canvas, shape, frame, opacity),
+            BlockContent::Line(line) => self.draw_line(canvas, line, frame, opacity),
+            BlockContent::Spacer => {}
+        }
+
+        if let Some(styles) = styles {
+            self.draw_border(canvas, styles, frame, opacity);
+        }
+
+        if transform.is_some() {
+            canvas.content.restore_state();
+        }
+        Ok(())
+    }
+
+    /// Draw the shadow and background fill of a block
+    fn draw_background(
+        &mut self,
+        canvas: &mut PageCanvas,
+        styles: &BlockStyles,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        if let Some((dx, dy, color)) = styles.shadow.as_deref().and_then(parse_shadow) {
+            let shadow = Frame::new(
+                frame.x + dx * PT_PER_PX,
+                frame.y - dy * PT_PER_PX,
+                frame.width,
+                frame.height,
+            );
+            self.fill_rect(canvas, shadow, color, opacity);
+        }
+
+        if let Some(color) = styles.background.as_deref().and_then(parse_color) {
+            self.fill_rect(canvas, frame, color, opacity);
+        }
+    }
+
+    fn draw_border(
+        &mut self,
+        canvas: &mut PageCanvas,
+        styles: &BlockStyles,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        let Some(border) = styles.border.as_ref() else {
+            return;
+        };
+        let Some(color) = parse_color(&border.color) else {
+            return;
+        };
+        if border.width <= 0.0 || border.style == "none" {
+            return;
+        }
+
+        let width = border.width as f32 * PT_PER_PX;
+        self.set_alpha(canvas, color.a * opacity);
+
+        let content = &mut canvas.content;
+        content.save_state();
+        content
+            .set_stroke_rgb(color.r, color.g, color.b)
+            .set_line_width(width);
+        let dash = match border.style.as_str() {
+            "dashed" => StrokeDash::Dashed,
+            "dotted" => StrokeDash::Dotted,
+            _ => StrokeDash::Solid,
+        };
+        set_dash(content, dash, width);
+
+        // Keep the stroke inside the block like CSS `box-sizing: border-box`
+        let inner = frame.inset(width / 2.0, width / 2.0, width / 2.0, width / 2.0);
+        content
+            .rect(inner.x, inner.y, inner.width, inner.height)
+            .stroke()
+            .restore_state();
+    }
+
+    /// Fill and outline a shape filling the block frame
+    fn draw_shape(
+        &mut self,
+        canvas: &mut PageCanvas,
+        shape: &ShapeBlockContent,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        if let Some(fill) = shape.fill.as_deref().and_then(parse_color) {
+            self.set_alpha(canvas, fill.a * opacity);
+            canvas.content.set_fill_rgb(fill.r, fill.g, fill.b);
+            shape_path(&mut canvas.content, shape, frame);
+            canvas.content.fill_nonzero();
+        }
+
+        let Some(stroke) = shape.stroke.as_ref().filter(|s| s.width > 0.0) else {
+            return;
+        };
+        let Some(color) = parse_color(&stroke.color) else {
+            return;
+        };
+        let width = stroke.width as f32 * PT_PER_PX;
+        self.set_alpha(canvas, color.a * opacity);
+
+        let content = &mut canvas.content;
+        content.save_state();
+        content
+            .set_stroke_rgb(color.r, color.g, color.b)
+            .set_line_width(width);
+        set_dash(content, stroke.dash, width);
+        shape_path(content, shape, frame);
+        content.stroke().restore_state();
+    }
+
+    /// Stroke a line with optional arrowheads at its ends
+    fn draw_line(
+        &mut self,
+        canvas: &mut PageCanvas,
+        line: &LineBlockContent,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        let Some(color) = parse_color(&line.stroke.color) else {
+            return;
+        };
+        let width = line.stroke.width as f32 * PT_PER_PX;
+        let mut start = frame_point(frame, &line.start);
+        let mut end = frame_point(frame, &line.end);
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if width <= 0.0 || length == 0.0 {
+            return;
+        }
+        let direction = (dx / length, dy / length);
+        let arrow = width * 3.0 + 4.0;
+
+        self.set_alpha(canvas, color.a * opacity);
+        let content = &mut canvas.content;
+        content.save_state();
+        content
+            .set_fill_rgb(color.r, color.g, color.b)
+            .set_stroke_rgb(color.r, color.g, color.b)
+            .set_line_width(width);
+
+        // The shaft stops at the arrowhead so it does not poke through the tip
+        if line.start_arrow {
+            draw_arrowhead(content, start, (-direction.0, -direction.1), arrow);
+            start = (start.0 + direction.0 * arrow, start.1 + direction.1 * arrow);
+        }
+        if line.end_arrow {
+            draw_arrowhead(content, end, direction, arrow);
+            end = (end.0 - direction.0 * arrow, end.1 - direction.1 * arrow);
+        }
+
+        set_dash(content, line.stroke.dash, width);
+        content
+            .move_to(start.0, start.1)
+            .line_to(end.0, end.1)
+            .stroke()
+            .restore_state();
+    }
+
+    fn draw_text(
+        &mut self,
+        canvas: &mut PageCanvas,
+        text: &TextBlockContent,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        let layout = layout_text(text, frame.width, self.registry);
+        // Underlines and strike-throughs are drawn after the text object
+        let mut decorations = Vec::new();
+
+        canvas.begin_clip(frame);
+        canvas.content.begin_text();
+        let mut current: Option<(Font, f32)> = None;
+        let mut color = None;
+
+        for line in &layout.lines {
+            // Everything below the frame is clipped anyway
+            if line.baseline - line.height > frame.height {
+                break;
+            }
+            let baseline = frame.top() - line.baseline;
+
+            for fragment in &line.fragments {
+                let style = &fragment.style;
+                if current.as_ref() != Some(&(style.font.clone(), style.size)) {
+                    self.set_font(canvas, &style.font, style.size);
+                    current = Some((style.font.clone(), style.size));
+                }
+                if color != Some(style.color) {
+                    self.set_alpha(canvas, style.color.a * opacity);
+                    canvas
+                        .content
+                        .set_fill_rgb(style.color.r, style.color.g, style.color.b);
+                    color = Some(style.color);
+                }
+
+                let x = frame.x + fragment.x;
+                let encoded = self.encode_text(&style.font, &fragment.text);
+                canvas
+                    .content
+                    .set_rise(style.rise)
+                    .set_text_matrix([1.0, 0.0, 0.0, 1.0, x, baseline])
+                    .show(Str(&encoded));
+
+                if style.underline || style.strike {
+                    decorations.push((fragment.clone(), baseline));
+                }
+                if let Some(link) = &style.link {
+                    let bottom = baseline + style.rise - style.size * 0.25;
+                    canvas.links.push((
+                        Frame::new(x, bottom, fragment.width, style.size * 1.1),
+                        link.clone(),
+                    ));
+                }
+            }
+        }
+
+        canvas.content.set_rise(0.0).end_text();
+        for (fragment, baseline) in decorations {
+            self.draw_decorations(canvas, &fragment, frame.x, baseline, opacity);
+        }
+        canvas.end_clip();
+    }
+
+    /// Underline and strike-through of a text fragment
+    fn draw_decorations(
+        &mut self,
+        canvas: &mut PageCanvas,
+        fragment: &Fragment,
+        left: f32,
+        baseline: f32,
+        opacity: f32,
+    ) {
+        let style = &fragment.style;
+        let thickness = (style.size * 0.05).max(0.5);
+        let mut offsets = Vec::new();
+        if style.underline {
+            offsets.push(-style.size * 0.12);
+        }
+        if style.strike {
+            offsets.push(style.size * 0.28);
+        }
+
+        for offset in offsets {
+            let frame = Frame::new(
+                left + fragment.x,
+                baseline + style.rise + offset - thickness / 2.0,
+                fragment.width,
+                thickness,
+            );
+            self.fill_rect(canvas, frame, style.color, opacity);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn show_lines(
+        &mut self,
+        canvas: &mut PageCanvas,
+        lines: &[Line],
+        font: &Font,
+        size: f32,
+        color: Rgba,
+        opacity: f32,
+        alignment: &TextAlignment,
+        frame: Frame,
+    ) {
+        let leading = size * LINE_HEIGHT;
+        let mut baseline = frame.top() - (leading - size) / 2.0 - font.ascent() * size / 1000.0;
+
+        self.set_alpha(canvas, color.a * opacity);
+        canvas.content.set_fill_rgb(color.r, color.g, color.b);
+        canvas.content.begin_text();
+        self.set_font(canvas, font, size);
+
+        for line in lines {
+            // Everything below the frame is clipped anyway
+            if baseline < frame.y - leading {
+                break;
+            }
+
+            let width = font.text_width(&line.text, size);
+            let spaces = line.text.matches(' ').count();
+            let (x, word_spacing) = match alignment {
+                TextAlignment::Left => (frame.x, 0.0),
+                TextAlignment::Center => (frame.x + (frame.width - width) / 2.0, 0.0),
+                TextAlignment::Right => (frame.x + frame.width - width, 0.0),
+                TextAlignment::Justify if !line.ends_paragraph && spaces > 0 => {
+                    (frame.x, (frame.width - width) / spaces as f32)
+                }
+                TextAlignment::Justify => (frame.x, 0.0),
+            };
+
+            let encoded = self.encode_text(font, &line.text);
+            canvas
+                .content
+                .set_word_spacing(word_spacing)
+                .set_text_matrix([1.0, 0.0, 0.0, 1.0, x, baseline])
+                .show(Str(&encoded));
+            baseline -= leading;
+        }
+
+        canvas.content.end_text();
+    }
+
+    fn draw_image(
+        &mut self,
+        canvas: &mut PageCanvas,
+        image: &ImageBlockContent,
+        frame: Frame,
+        opacity: f32,
+    ) -> Result<()> {
+        // An image block without a source yet is drawn as an empty frame
+        if image.src.trim().is_empty() {
+            self.draw_image_placeholder(canvas, frame, opacity);
+            return Ok(());
+        }
+
+        let resource = self.image_resource(&image.src)?;
+        let natural_width = resource.width as f32 * PT_PER_PX;
+        let natural_height = resource.height as f32 * PT_PER_PX;
+        let fit = image.fit.as_ref().unwrap_or(&ImageFit::Contain);
+        let placed = fit_image(fit, natural_width, natural_height, frame);
+
+        canvas.begin_clip(frame);
+        self.set_alpha(canvas, opacity);
+        canvas
+            .content
+            .save_state()
+            .transform([placed.width, 0.0, 0.0, placed.height, placed.x, placed.y])
+            .x_object(Name(resource.name.as_bytes()))
+            .restore_state();
+        canvas.end_clip();
+
+        canvas.x_objects.insert(resource.name, resource.id);
+        Ok(())
+    }
+
+    /// Dashed grey outline standing in for an image without a source
+    fn draw_image_placeholder(&mut self, canvas: &mut PageCanvas, frame: Frame, opacity: f32) {
+        self.set_alpha(canvas, opacity);
+        let content = &mut canvas.content;
+        content.save_state();
+        content
+            .set_stroke_rgb(0.6, 0.6, 0.6)
+            .set_line_width(PT_PER_PX);
+        set_dash(content, StrokeDash::Dashed, PT_PER_PX);
+        let inner = frame.inset(
+            PT_PER_PX / 2.0,
+            PT_PER_PX / 2.0,
+            PT_PER_PX / 2.0,
+            PT_PER_PX / 2.0,
+        );
+        content
+            .rect(inner.x, inner.y, inner.width, inner.height)
+            .stroke()
+            .restore_state();
+    }
+
+    /// Decode and embed an image once per source, returning the cached resource
+    fn image_resource(&mut self, src: &str) -> Result<ImageResource> {
+        if let Some(resource) = self.images.get(src) {
+            return Ok(resource.clone());
+        }
+
+        let bytes = load_image_source(src, self.assets)?;
+        let decoded = image::load_from_memory(&bytes)
+            .map_err(|e| AppError::RenderError(format!("Failed to decode image: {}", e)))?;
+
+        let id = self.alloc();
+        let mask_id = decoded.color().has_alpha().then(|| self.alloc());
+        let resource = ImageResource {
+            name: format!("Im{}", self.images.len() + 1),
+            id,
+            width: decoded.width(),
+            height: decoded.height(),
+        };
+
+        let rgb = deflate(decoded.to_rgb8().as_raw());
+        let mut x_object = self.pdf.image_xobject(id, &rgb);
+        x_object
+            .width(resource.width as i32)
+            .height(resource.height as i32)
+            .bits_per_component(8)
+            .filter(Filter::FlateDecode);
+        x_object.color_space().device_rgb();
+        if let Some(mask_id) = mask_id {
+            x_object.s_mask(mask_id);
+        }
+        x_object.finish();
+
+        if let Some(mask_id) = mask_id {
+            let alpha: Vec<u8> = decoded.to_rgba8().pixels().map(|p| p[3]).collect();
+            let alpha = deflate(&alpha);
+            let mut mask = self.pdf.image_xobject(mask_id, &alpha);
+            mask.width(resource.width as i32)
+                .height(resource.height as i32)
+                .bits_per_component(8)
+                .filter(Filter::FlateDecode);
+            mask.color_space().device_gray();
+            mask.finish();
+        }
+
+        self.images.insert(src.to_string(), resource.clone());
+        Ok(resource)
+    }
+
+    fn draw_table(
+        &mut self,
+        canvas: &mut PageCanvas,
+        table: &TableBlockContent,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        let columns = table.rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+        if table.rows.is_empty() || columns == 0 {
+            return;
+        }
+
+        let widths = column_widths(table, columns, frame.width);
+        let row_height = frame.height / table.rows.len() as f32;
+        let size = TABLE_FONT_SIZE_PX * PT_PER_PX;
+        let padding = TABLE_CELL_PADDING_PX * PT_PER_PX;
+
+        for (row_index, row) in table.rows.iter().enumerate() {
+            let y = frame.top() - (row_index + 1) as f32 * row_height;
+            let mut x = frame.x;
+
+            for (cell, width) in row.cells.iter().zip(&widths) {
+                let cell_frame = Frame::new(x, y, *width, row_height);
+                let styles = cell.styles.as_ref();
+
+                if let Some(color) = styles
+                    .and_then(|s| s.background.as_deref())
+                    .and_then(parse_color)
+                {
+                    self.fill_rect(canvas, cell_frame, color, opacity);
+                }
+
+                let weight = if styles.and_then(|s| s.bold).unwrap_or(false) {
+                    700
+                } else {
+                    400
+                };
+                // A cell is shown in one font, so text the standard font
+                // cannot show switches the whole cell to a fallback
+                let mut font = Font::Standard(StandardFont::resolve("Helvetica", weight));
+                if let Some(fallback) = cell
+                    .content
+                    .chars()
+                    .find_map(|c| self.registry.fallback(&font, c, weight, false))
+                {
+                    font = fallback;
+                }
+                let color = styles
+                    .and_then(|s| s.color.as_deref())
+                    .and_then(parse_color)
+                    .unwrap_or(Rgba::BLACK);
+
+                let inner = cell_frame.inset(padding, padding, padding, padding);
+                let lines = wrap_text(&cell.content, &font, size, inner.width);
+
+                canvas.begin_clip(cell_frame);
+                self.show_lines(
+                    canvas,
+                    &lines,
+                    &font,
+                    size,
+                    color,
+                    opacity,
+                    &TextAlignment::Left,
+                    inner,
+                );
+                canvas.end_clip();
+
+                x += width;
+            }
+        }
+
+        self.draw_table_grid(canvas, &widths, table.rows.len(), frame, opacity);
+    }
+
+    fn draw_table_grid(
+        &mut self,
+        canvas: &mut PageCanvas,
+        widths: &[f32],
+        rows: usize,
+        frame: Frame,
+        opacity: f32,
+    ) {
+        self.set_alpha(canvas, opacity);
+        let content = &mut canvas.content;
+        content
+            .set_stroke_rgb(0.0, 0.0, 0.0)
+            .set_line_width(PT_PER_PX)
+            .rect(frame.x, frame.y, frame.width, frame.height);
+
+        let row_height = frame.height / rows as f32;
+        for row in 1..rows {
+            let y = frame.top() - row as f32 * row_height;
+            content
+                .move_to(frame.x, y)
+                .line_to(frame.x + frame.width, y);
+        }
+
+        let mut x = frame.x;
+        for width in &widths[..widths.len() - 1] {
+            x += width;
+            content.move_to(x, frame.y).line_to(x, frame.top());
+        }
+
+        content.stroke();
+    }
+
+    /// Write the page object for a finished canvas
+    fn finish_page(&mut self, canvas: PageCanvas) {
+        let page_id = self.alloc();
+        let content_id = self.alloc();
+        self.page_ids.push(page_id);
+
+        let stream = deflate(&canvas.content.finish());
+        self.pdf
+            .stream(content_id, &stream)
+            .filter(Filter::FlateDecode);
+
+        let fonts: Vec<(&String, Ref)> = canvas
+            .fonts
+            .iter()
+            .map(|name| (name, self.fonts[name].id))
+            .collect();
+
+        let mut page = self.pdf.page(page_id);
+        let art_box = canvas.art_box;
+        page.media_box(Rect::new(0.0, 0.0, canvas.width, canvas.height))
+            .art_box(Rect::new(
+                art_box.x,
+                art_box.y,
+                art_box.x + art_box.width,
+                art_box.top(),
+            ))
+            .parent(self.page_tree_id)
+            .contents(content_id);
+
+        let mut resources = page.resources();
+        let mut font_dict = resources.fonts();
+        for (name, id) in fonts {
+            font_dict.pair(Name(name.as_bytes()), id);
+        }
+        font_dict.finish();
+
+        let mut x_objects = resources.x_objects();
+        for (name, id) in &canvas.x_objects {
+            x_objects.pair(Name(name.as_bytes()), *id);
+        }
+        x_objects.finish();
+
+        let mut ext_states = resources.ext_g_states();
+        for (name, id) in &canvas.ext_states {
+            ext_states.pair(Name(name.as_bytes()), *id);
+        }
+        ext_states.finish();
+        resources.finish();
+
+        if !canvas.links.is_empty() {
+            let mut annotations = page.annotations();
+            for (area, uri) in &canvas.links {
+                annotations
+                    .push()
+                    .subtype(AnnotationType::Link)
+                    .rect(Rect::new(area.x, area.y, area.x + area.width, area.top()))
+                    .border(0.0, 0.0, 0.0, None)
+                    .action()
+                    .action_type(ActionType::Uri)
+                    .uri(Str(uri.as_bytes()));
+            }
+        }
+    }
+
+    /// Write a font file subset to the glyphs used as a CID font
+    ///
+    /// Text is encoded as glyph ids (Identity-H); the ToUnicode map keeps
+    /// it searchable and copyable.
+    fn write_embedded_font(&mut self, id: Ref, face: &FontFace, glyphs: &BTreeMap<u16, String>) {
+        let cid_id = self.alloc();
+        let descriptor_id = self.alloc();
+        let file_id = self.alloc();
+        let cmap_id = self.alloc();
+
+        let base_font = format!("{}+{}", subset_tag(glyphs), face.post_script_name);
+        let system_info = SystemInfo {
+            registry: Str(b"Adobe"),
+            ordering: Str(b"Identity"),
+            supplement: 0,
+        };
+
+        self.pdf
+            .type0_font(id)
+            .base_font(Name(base_font.as_bytes()))
+            .encoding_predefined(Name(b"Identity-H"))
+            .descendant_font(cid_id)
+            .to_unicode(cmap_id);
+
+        let mut cid_font = self.pdf.cid_font(cid_id);
+        cid_font
+            .subtype(if face.cff {
+                CidFontType::Type0
+            } else {
+                CidFontType::Type2
+            })
+            .base_font(Name(base_font.as_bytes()))
+            .system_info(system_info)
+            .font_descriptor(descriptor_id);
+        if !face.cff {
+            cid_font.cid_to_gid_map_predefined(Name(b"Identity"));
+        }
+        let mut widths = cid_font.widths();
+        for glyph in glyphs.keys() {
+            widths.consecutive(*glyph, [face.to_pdf_units(face.advance(*glyph) as f32)]);
+        }
+        widths.finish();
+        cid_font.finish();
+
+        let mut flags = FontFlags::SYMBOLIC;
+        flags.set(FontFlags::FIXED_PITCH, face.monospaced);
+        flags.set(FontFlags::ITALIC, face.italic_angle != 0.0);
+        let [x_min, y_min, x_max, y_max] = face.bbox.map(|v| face.to_pdf_units(v));
+        let mut descriptor = self.pdf.font_descriptor(descriptor_id);
+        descriptor
+            .name(Name(base_font.as_bytes()))
+            .flags(flags)
+            .bbox(Rect::new(x_min, y_min, x_max, y_max))
+            .italic_angle(face.italic_angle)
+            .ascent(face.to_pdf_units(face.ascender))
+            .descent(face.to_pdf_units(face.descender))
+            .cap_height(face.to_pdf_units(face.cap_height))
+            .stem_v(80.0);
+        if face.cff {
+            descriptor.font_file3(file_id);
+        } else {
+            descriptor.font_file2(file_id);
+        }
+        descriptor.finish();
+
+        let used: Vec<u16> = glyphs.keys().copied().collect();
+        let data = subsetter::subset(face.data(), face.index(), subsetter::Profile::pdf(&used))
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to subset font {}, embedding it whole: {}",
+                    face.post_script_name, e
+                );
+                face.data().to_vec()
+            });
+        let compressed = deflate(&data);
+        let mut stream = self.pdf.stream(file_id, &compressed);
+        stream.filter(Filter::FlateDecode);
+        if face.cff {
+            stream.pair(Name(b"Subtype"), Name(b"OpenType"));
+        } else {
+            stream.pair(Name(b"Length1"), data.len() as i32);
+        }
+        stream.finish();
+
+        let mut cmap = UnicodeCmap::new(Name(b"Custom"), system_info);
+        for (glyph, text) in glyphs {
+            cmap.pair_with_multiple(*glyph, text.chars());
+        }
+        self.pdf.cmap(cmap_id, &cmap.finish());
+    }
+
+    /// Write the document catalog and shared resources
+    fn finish(mut self, title: &str) -> Vec<u8> {
+        let info_id = self.alloc();
+
+        self.pdf.catalog(self.catalog_id).pages(self.page_tree_id);
+        self.pdf
+            .pages(self.page_tree_id)
+            .kids(self.page_ids.iter().copied())
+            .count(self.page_ids.len() as i32);
+
+        for resource in std::mem::take(&mut self.fonts).into_values() {
+            match &resource.font {
+                Font::Standard(font) => {
+                    self.pdf
+                        .type1_font(resource.id)
+                        .base_font(Name(font.base_font().as_bytes()))
+                        .encoding_predefined(Name(b"WinAnsiEncoding"));
+                }
+                Font::Embedded(face) => {
+                    self.write_embedded_font(resource.id, face, &resource.glyphs)
+                }
+            }
+        }
+
+        self.pdf
+            .document_info(info_id)
+            .title(TextStr(title))
+            .producer(TextStr("SimpleDoc"));
+
+        self.pdf.finish()
+    }
+}
+
+/// Six letter tag naming a font subset, derived from its glyphs
+fn subset_tag(glyphs: &BTreeMap<u16, String>) -> String {
+    let mut hasher = Sha256::new();
+    for glyph in glyphs.keys() {
+        hasher.update(glyph.to_be_bytes());
+    }
+    hasher.finalize()[..6]
+        .iter()
+        .map(|b| (b'A' + b % 26) as char)
+        .collect()
+}
+
+/// Compute table column widths, scaled to fill `total_width`
+pub(crate) fn column_widths(
+    table: &TableBlockContent,
+    columns: usize,
+    total_width: f32,
+) -> Vec<f32> {
+    let declared: f64 = table.column_widths.iter().sum();
+
+    if table.column_widths.len() == columns && declared > 0.0 {
+        table
+            .column_widths
+            .iter()
+            .map(|w| (*w / declared) as f32 * total_width)
+            .collect()
+    } else {
+        vec![total_width / columns as f32; columns]
+    }
+}
+
+/// Size of an image of the given natural size placed into a box according
+/// to `fit`
+pub(crate) fn fit_size(
+    fit: &ImageFit,
+    width: f32,
+    height: f32,
+    box_width: f32,
+    box_height: f32,
+) -> (f32, f32) {
+    if width <= 0.0 || height <= 0.0 {
+        return (box_width, box_height);
+    }
+
+    match fit {
+        ImageFit::Fill => (box_width, box_height),
+        ImageFit::Contain => {
+            let scale = (box_width / width).min(box_height / height);
+            (width * scale, height * scale)
+        }
+        ImageFit::Cover => {
+            let scale = (box_width / width).max(box_height / height);
+            (width * scale, height * scale)
+        }
+        ImageFit::None => (width, height),
+    }
+}
+
+/// Place an image of the given natural size into a frame according to `fit`
+fn fit_image(fit: &ImageFit, width: f32, height: f32, frame: Frame) -> Frame {
+    let (placed_width, placed_height) = fit_size(fit, width, height, frame.width, frame.height);
+
+    // Centered like CSS `object-position: 50% 50%`
+    Frame::new(
+        frame.x + (frame.width - placed_width) / 2.0,
+        frame.y + (frame.height - placed_height) / 2.0,
+        placed_width,
+        placed_height,
+    )
+}
+
+/// Parse a CSS box-shadow like `2px 4px 8px rgba(0, 0, 0, 0.2)`
+///
+/// Returns the offsets in pixels and the color; blur and spread are ignored.
+pub(crate) fn parse_shadow(shadow: &str) -> Option<(f32, f32, Rgba)> {
+    let color_start = shadow
+        .find("rgb")
+        .or_else(|| shadow.find('#'))
+        .unwrap_or(shadow.len());
+    let (lengths, color) = shadow.split_at(color_start);
+    let color = if color.trim().is_empty() {
+        Rgba {
+            a: 0.25,
+            ..Rgba::BLACK
+        }
+    } else {
+        parse_color(color)?
+    };
+
+    let offsets: Vec<f32> = lengths
+        .split_whitespace()
+        .filter_map(|token| token.trim_end_matches("px").parse().ok())
+        .collect();
+    match offsets.as_slice() {
+        [dx, dy, ..] => Some((*dx, *dy, color)),
+        _ => None,
+    }
+}
+
+/// Dash pattern scaled to the line width
+fn set_dash(content: &mut Content, dash: StrokeDash, width: f32) {
+    match dash {
+        StrokeDash::Solid => {}
+        StrokeDash::Dashed => {
+            content.set_dash_pattern([width * 3.0, width * 2.0], 0.0);
+        }
+        StrokeDash::Dotted => {
+            content.set_dash_pattern([width, width], 0.0);
+        }
+    }
+}
+
+/// Matrix rotating and scaling a block around its center
+///
+/// The editor rotates clockwise with y pointing down, which is
+/// counter-clockwise in PDF space.
+fn transform_matrix(transform: &Transform, frame: Frame) -> [f32; 6] {
+    let angle = -(transform.rotation as f32).to_radians();
+    let (sin, cos) = angle.sin_cos();
+    let (scale_x, scale_y) = (transform.scale_x as f32, transform.scale_y as f32);
+    let (a, b, c, d) = (cos * scale_x, sin * scale_x, -sin * scale_y, cos * scale_y);
+    let (cx, cy) = (frame.x + frame.width / 2.0, frame.y + frame.height / 2.0);
+    [a, b, c, d, cx - a * cx - c * cy, cy - b * cx - d * cy]
+}
+
+/// Point relative to a block's top-left corner (in pixels) in PDF points
+fn frame_point(frame: Frame, point: &Position) -> (f32, f32) {
+    (
+        frame.x + point.x as f32 * PT_PER_PX,
+        frame.top() - point.y as f32 * PT_PER_PX,
+    )
+}
+
+/// Append the outline of a shape to the current path
+fn shape_path(content: &mut Content, shape: &ShapeBlockContent, frame: Frame) {
+    match shape.shape {
+        ShapeKind::Rectangle => {
+            content.rect(frame.x, frame.y, frame.width, frame.height);
+        }
+        ShapeKind::RoundedRect => {
+            let radius = (shape.corner_radius.unwrap_or(0.0) as f32 * PT_PER_PX)
+                .min(frame.width / 2.0)
+                .min(frame.height / 2.0);
+            rounded_rect_path(content, frame, radius);
+        }
+        ShapeKind::Ellipse => ellipse_path(content, frame),
+        ShapeKind::Polygon => {
+            let mut points = shape.points.iter().map(|p| frame_point(frame, p));
+            if let Some((x, y)) = points.next() {
+                content.move_to(x, y);
+                for (x, y) in points {
+                    content.line_to(x, y);
+                }
+                content.close_path();
+            }
+        }
+    }
+}
+
+fn rounded_rect_path(content: &mut Content, frame: Frame, radius: f32) {
+    if radius <= 0.0 {
+        content.rect(frame.x, frame.y, frame.width, frame.height);
+        return;
+    }
+
+    let (left, bottom) = (frame.x, frame.y);
+    let (right, top) = (frame.x + frame.width, frame.top());
+    let k = radius * (1.0 - KAPPA);
+    content
+        .move_to(left + radius, bottom)
+        .line_to(right - radius, bottom)
+        .cubic_to(right - k, bottom, right, bottom + k, right, bottom + radius)
+        .line_to(right, top - radius)
+        .cubic_to(right, top - k, right - k, top, right - radius, top)
+        .line_to(left + radius, top)
+        .cubic_to(left + k, top, left, top - k, left, top - radius)
+        .line_to(left, bottom + radius)
+        .cubic_to(left, bottom + k, left + k, bottom, left + radius, bottom)
+        .close_path();
+}
+
+fn ellipse_path(content: &mut Content, frame: Frame) {
+    let (rx, ry) = (frame.width / 2.0, frame.height / 2.0);
+    let (cx, cy) = (frame.x + rx, frame.y + ry);
+    let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+    content
+        .move_to(cx + rx, cy)
+        .cubic_to(cx + rx, cy + ky, cx + kx, cy + ry, cx, cy + ry)
+        .cubic_to(cx - kx, cy + ry, cx - rx, cy + ky, cx - rx, cy)
+        .cubic_to(cx - rx, cy - ky, cx - kx, cy - ry, cx, cy - ry)
+        .cubic_to(cx + kx, cy - ry, cx + rx, cy - ky, cx + rx, cy)
+        .close_path();
+}
+
+/// Filled triangle with its tip at `tip`, pointing along `direction`
+fn draw_arrowhead(content: &mut Content, tip: (f32, f32), direction: (f32, f32), length: f32) {
+    let base = (tip.0 - direction.0 * length, tip.1 - direction.1 * length);
+    let normal = (-direction.1 * length / 2.0, direction.0 * length / 2.0);
+    content
+        .move_to(tip.0, tip.1)
+        .line_to(base.0 + normal.0, base.1 + normal.1)
+        .line_to(base.0 - normal.0, base.1 - normal.1)
+        .close_path()
+        .fill_nonzero();
+}
+
+/// Read image bytes from an `asset://` reference, a data URI, a file path
+/// or a bare base64 string
+pub(crate) fn load_image_source(src: &str, assets: Option<&AssetStore>) -> Result<Vec<u8>> {
+    if asset_hash(src).is_some() {
+        let assets = assets
+            .ok_or_else(|| AppError::RenderError(format!("No asset store to resolve {}", src)))?;
+        return assets.read(src);
+    }
+
+    if let Some(data_uri) = src.strip_prefix("data:") {
+        let (_, data) = data_uri
+            .split_once(',')
+            .ok_or_else(|| AppError::RenderError("Malformed image data URI".to_string()))?;
+        return BASE64
+            .decode(data.trim())
+            .map_err(|e| AppError::RenderError(format!("Invalid base64 image data: {}", e)));
+    }
+
+    let path = Path::new(src.strip_prefix("file://").unwrap_or(src));
+    if path.exists() {
+        return Ok(std::fs::read(path)?);
+    }
+
+    BASE64
+        .decode(src.trim())
+        .map_err(|_| AppError::RenderError(format!("Image not found: {}", src)))
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .expect("writing to an in-memory buffer cannot fail")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlockType, CellStyles, PageOrientation, Stroke, TableCell, TableRow};
+    use crate::services::font_registry::TEST_FONT;
+    use crate::services::rich_text;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#fff"), Some(Rgba::rgb(255, 255, 255)));
+        assert_eq!(parse_color("#ff0000"), Some(Rgba::rgb(255, 0, 0)));
+        assert_eq!(parse_color("rgba(0, 0, 0, 0.5)").map(|c| c.a), Some(0.5));
+        assert_eq!(parse_color("#ff000080").map(|c| c.a), Some(128.0 / 255.0));
+        assert!(parse_color("#12").is_none());
+        assert!(parse_color("invalid").is_none());
+    }
+
+    #[test]
+    fn test_wrap_text() {
+        let lines = wrap_text(
+            "one two three\nfour",
+            &Font::Standard(StandardFont::Courier),
+            10.0,
+            45.0,
+        );
+        let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        // Courier is 6pt per char at 10pt, so 7 chars fit into 45pt
+        assert_eq!(texts, vec!["one two", "three", "four"]);
+        assert!(!lines[0].ends_paragraph);
+        assert!(lines[1].ends_paragraph);
+    }
+
+    #[test]
+    fn test_wrap_breaks_long_words() {
+        let lines = wrap_text(
+            "abcdefghij",
+            &Font::Standard(StandardFont::Courier),
+            10.0,
+            30.0,
+        );
+        let texts: Vec<&str> = lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, vec!["abcde", "fghij"]);
+    }
+
+    #[test]
+    fn test_fit_image() {
+        let frame = Frame::new(0.0, 0.0, 100.0, 50.0);
+        let contained = fit_image(&ImageFit::Contain, 200.0, 200.0, frame);
+        assert_eq!(contained, Frame::new(25.0, 0.0, 50.0, 50.0));
+        let covered = fit_image(&ImageFit::Cover, 200.0, 200.0, frame);
+        assert_eq!(covered, Frame::new(0.0, -25.0, 100.0, 100.0));
+    }
+
+    #[test]
+    fn test_transform_matrix() {
+        let frame = Frame::new(0.0, 0.0, 20.0, 10.0);
+        let quarter = Transform {
+            rotation: 90.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+        };
+        let expected = [0.0, -1.0, 1.0, 0.0, 5.0, 15.0];
+        for (value, expected) in transform_matrix(&quarter, frame).iter().zip(expected) {
+            assert!((value - expected).abs() < 1e-5, "{} != {}", value, expected);
+        }
+
+        let wide = Transform {
+            rotation: 0.0,
+            scale_x: 2.0,
+            scale_y: 1.0,
+        };
+        assert_eq!(
+            transform_matrix(&wide, frame),
+            [2.0, 0.0, 0.0, 1.0, -10.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_render_all_block_types() {
+        let mut document = Document::new("Render Test".to_string());
+        document.add_block(Block::text_for_test("Hello, world"));
+
+        let mut rich = Block::for_test(BlockType::Text, 40.0, 120.0, 300.0, 100.0);
+        if let BlockContent::Text(content) = &mut rich.content {
+            content.alignment = TextAlignment::Justify;
+            content.set_paragraphs(rich_text::from_markdown(
+                "Total **120** EUR^1^, see *the* [terms](https://example.com) ~~old~~\n\n- one\n- two",
+            ));
+        }
+        document.add_block(rich);
+
+        let mut table = Block::for_test(BlockType::Table, 40.0, 200.0, 300.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![TableRow {
+                cells: vec![
+                    TableCell {
+                        content: "Item".to_string(),
+                        styles: Some(CellStyles {
+                            background: Some("#eeeeee".to_string()),
+                            color: None,
+                            bold: Some(true),
+                        }),
+                        name: None,
+                    },
+                    TableCell {
+                        content: "Price".to_string(),
+                        styles: None,
+                        name: None,
+                    },
+                ],
+            }],
+            column_widths: vec![200.0, 100.0],
+        });
+        document.add_block(table);
+
+        let mut image = Block::for_test(BlockType::Image, 40.0, 300.0, 50.0, 50.0);
+        // 1x1 transparent PNG
+        image.content = BlockContent::Image(ImageBlockContent {
+            src: "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==".to_string(),
+            alt: String::new(),
+            fit: Some(ImageFit::Cover),
+        });
+        document.add_block(image);
+
+        document.add_block(Block::for_test(BlockType::Spacer, 0.0, 400.0, 10.0, 10.0));
+
+        for kind in [
+            ShapeKind::Rectangle,
+            ShapeKind::RoundedRect,
+            ShapeKind::Ellipse,
+            ShapeKind::Polygon,
+        ] {
+            let mut shape = Block::for_test(BlockType::Shape, 40.0, 450.0, 80.0, 40.0);
+            if let BlockContent::Shape(content) = &mut shape.content {
+                content.shape = kind;
+                content.corner_radius = Some(8.0);
+                content.stroke = Some(Stroke {
+                    color: "rgba(0, 0, 255, 0.5)".to_string(),
+                    width: 2.0,
+                    dash: StrokeDash::Dashed,
+                });
+                content.points = vec![
+                    Position { x: 40.0, y: 0.0 },
+                    Position { x: 80.0, y: 40.0 },
+                    Position { x: 0.0, y: 40.0 },
+                ];
+            }
+            document.add_block(shape);
+        }
+
+        let mut line = Block::for_test(BlockType::Line, 40.0, 520.0, 200.0, 4.0);
+        if let BlockContent::Line(content) = &mut line.content {
+            content.start_arrow = true;
+            content.end_arrow = true;
+        }
+        document.add_block(line);
+
+        let bytes = PdfRenderer::new().render(&document).unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.starts_with("%PDF-"));
+        assert!(pdf.contains("/Helvetica-Bold"));
+        assert!(pdf.contains("/Helvetica-Oblique"));
+        assert!(pdf.contains("/URI (https://example.com)"));
+        assert!(pdf.contains("/SMask"));
+        assert!(pdf.contains("/Count 1"));
+    }
+
+    #[test]
+    fn test_render_one_pdf_page_per_page() {
+        let mut document = Document::new("Pages".to_string());
+        document.add_block(Block::text_for_test("First page"));
+        let first = document.pages[0].id.clone();
+        let second = document.duplicate_page(&first).unwrap();
+        document.get_page_mut(&second).unwrap().orientation = PageOrientation::Landscape;
+
+        let bytes = PdfRenderer::new().render(&document).unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.contains("/Count 2"));
+        // A4 portrait followed by A4 landscape
+        assert!(pdf.contains("/MediaBox [0 0 595.27563 841.8898]"));
+        assert!(pdf.contains("/MediaBox [0 0 841.8898 595.27563]"));
+        // 20mm default margins
+        assert!(pdf.contains("/ArtBox [56.692917 56.692917 785.1969 538.5827]"));
+    }
+
+    #[test]
+    fn test_render_landscape_custom_page() {
+        let mut document = Document::new("Custom".to_string());
+        document.pages[0] = Page::custom(297.0, 210.0);
+        document.add_block(Block::text_for_test("Wide"));
+
+        let bytes = PdfRenderer::new().render(&document).unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.contains("/MediaBox [0 0 841.8898 595.27563]"));
+    }
+
+    #[test]
+    fn test_render_embeds_fallback_font() {
+        let temp_dir = TempDir::new().unwrap();
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let mut fonts = FontRegistry::new();
+        fonts.import(TEST_FONT, &assets).unwrap();
+        let helvetica = Font::Standard(StandardFont::Helvetica);
+        assert!(fonts.fallback(&helvetica, 'Д', 400, false).is_some());
+
+        let mut document = Document::new("Cyrillic".to_string());
+        document.add_block(Block::text_for_test("Договор"));
+
+        let bytes = PdfRenderer::new()
+            .with_fonts(fonts)
+            .render(&document)
+            .unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(pdf.contains("/Subtype /Type0"));
+        assert!(pdf.contains("/Encoding /Identity-H"));
+        assert!(pdf.contains("/ToUnicode"));
+        assert!(pdf.contains("/FontFile2"));
+        // Subset fonts are named `<tag>+<PostScript name>`
+        assert!(pdf.contains("+Tuffy"));
+    }
+
+    #[test]
+    fn test_render_missing_image_fails() {
+        let mut document = Document::new("Broken".to_string());
+        let mut image = Block::for_test(BlockType::Image, 0.0, 0.0, 50.0, 50.0);
+        image.content = BlockContent::Image(ImageBlockContent {
+            src: "/nonexistent/logo.png".to_string(),
+            alt: String::new(),
+            fit: None,
+        });
+        document.add_block(image);
+
+        assert!(PdfRenderer::new().render(&document).is_err());
+    }
+
+    #[test]
+    fn test_render_image_without_source() {
+        let mut document = Document::new("Draft".to_string());
+        document.add_block(Block::for_test(BlockType::Image, 0.0, 0.0, 50.0, 50.0));
+
+        let bytes = PdfRenderer::new().render(&document).unwrap();
+        let pdf = String::from_utf8_lossy(&bytes);
+        assert!(!pdf.contains("/Subtype /Image"));
+    }
+}
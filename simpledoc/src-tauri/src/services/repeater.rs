@@ -0,0 +1,532 @@
+use crate::models::{Block, BlockContent, Document, Page, RepeatBinding, TableRow};
+use crate::services::formula;
+use crate::services::template_engine::{lookup, Resolution, TemplateEngine};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Name of the current element inside a repeater
+pub const ITEM_VARIABLE: &str = "item";
+
+/// Name of the 1-based element position inside a repeater
+pub const INDEX_VARIABLE: &str = "index";
+
+/// Whether a placeholder path refers to the repeater scope
+pub fn is_scoped_path(path: &str) -> bool {
+    let root = path.split(['.', '[']).next().unwrap_or_default();
+    root == ITEM_VARIABLE || root == INDEX_VARIABLE
+}
+
+/// Data context for one element: the root data plus `item` and `index`
+fn item_scope(data: &Value, item: &Value, index: usize) -> Value {
+    let mut scope = match data {
+        Value::Object(map) => map.clone(),
+        _ => Map::new(),
+    };
+    scope.insert(ITEM_VARIABLE.to_string(), item.clone());
+    scope.insert(INDEX_VARIABLE.to_string(), Value::from(index));
+    Value::Object(scope)
+}
+
+/// Layout cursor for blocks that follow a repeater
+///
+/// `page` counts continuation pages created for the original page and
+/// `shift` is added to the original y position of the block.
+#[derive(Debug, Clone, Copy, Default)]
+struct Flow {
+    page: usize,
+    shift: f64,
+}
+
+impl Flow {
+    fn is_moved(&self) -> bool {
+        self.page > 0 || self.shift != 0.0
+    }
+}
+
+/// Vertical limits of the content box of a page (in pixels)
+#[derive(Debug, Clone, Copy)]
+struct Column {
+    top: f64,
+    bottom: f64,
+}
+
+impl Column {
+    fn of(page: &Page) -> Self {
+        let content = page.geometry().to_px().content_box;
+        Self {
+            top: content.y,
+            bottom: content.y + content.height,
+        }
+    }
+
+    /// Whether a block of this height should move to the next page
+    fn overflows(&self, y: f64, height: f64) -> bool {
+        y + height > self.bottom && y > self.top && height <= self.bottom - self.top
+    }
+}
+
+/// Expand repeater blocks against the data context
+///
+/// Repeated content pushes the blocks below it down and continues on new
+/// pages (copies of the original page settings) when it reaches the bottom
+/// margin. Returns the ids of the generated blocks, which are already
+/// resolved with their element scope.
+pub(crate) fn expand_repeaters(
+    document: &mut Document,
+    data: &Value,
+    resolution: &mut Resolution,
+) -> HashSet<String> {
+    let mut expanded = HashSet::new();
+    if document.blocks.iter().all(|b| b.repeat.is_none()) {
+        return expanded;
+    }
+
+    let blocks = std::mem::take(&mut document.blocks);
+    let mut pages = Vec::new();
+    let mut output = Vec::new();
+
+    for page in std::mem::take(&mut document.pages) {
+        let column = Column::of(&page);
+        let mut page_blocks: Vec<Block> = blocks
+            .iter()
+            .filter(|b| b.page_id == page.id)
+            .cloned()
+            .collect();
+        page_blocks.sort_by(|a, b| a.position.y.total_cmp(&b.position.y));
+
+        // Continuation pages are created on demand
+        let mut page_ids = vec![page.id.clone()];
+        let mut page_id = |index: usize, pages_out: &mut Vec<Page>| {
+            while page_ids.len() <= index {
+                let mut continuation = page.clone();
+                continuation.id = Uuid::new_v4().to_string();
+                page_ids.push(continuation.id.clone());
+                pages_out.push(continuation);
+            }
+            page_ids[index].clone()
+        };
+
+        let mut continuations = Vec::new();
+        let mut flow = Flow::default();
+        let mut beside = Flow::default();
+        let mut threshold = f64::NEG_INFINITY;
+
+        for mut block in page_blocks {
+            // Blocks next to a repeater keep the layout the repeater started with
+            let in_flow = block.position.y >= threshold;
+            let start = if in_flow { flow } else { beside };
+
+            let Some(repeat) = block.repeat.take() else {
+                let mut placed = start;
+                let y = block.position.y + start.shift;
+                if in_flow && start.is_moved() && column.overflows(y, block.size.height) {
+                    placed = Flow {
+                        page: start.page + 1,
+                        shift: column.top - block.position.y,
+                    };
+                    flow = placed;
+                }
+                block.position.y += placed.shift;
+                block.page_id = page_id(placed.page, &mut continuations);
+                output.push(block);
+                continue;
+            };
+
+            let items = match lookup(data, &repeat.path) {
+                Some(Value::Array(items)) => items.clone(),
+                Some(Value::Null) => Vec::new(),
+                None if resolution.without_data => Vec::new(),
+                Some(_) => {
+                    resolution.template_errors.push(format!(
+                        "Repeater path {} is not an array (block {})",
+                        repeat.path, block.id
+                    ));
+                    continue;
+                }
+                None => {
+                    resolution.template_errors.push(format!(
+                        "Unresolved variable: {} (block {})",
+                        repeat.path, block.id
+                    ));
+                    continue;
+                }
+            };
+
+            let original_bottom = block.position.y + block.size.height;
+            let cursor = Cursor {
+                page: start.page,
+                y: block.position.y + start.shift,
+            };
+            let (copies, end) = if matches!(block.content, BlockContent::Table(_)) {
+                expand_table(&block, &repeat, &items, data, cursor, column, resolution)
+            } else {
+                expand_copies(&block, &repeat, &items, data, cursor, column, resolution)
+            };
+
+            for (index, mut copy) in copies {
+                copy.page_id = page_id(index, &mut continuations);
+                expanded.insert(copy.id.clone());
+                output.push(copy);
+            }
+
+            beside = start;
+            if in_flow {
+                flow = Flow {
+                    page: end.page,
+                    shift: end.y - original_bottom,
+                };
+                threshold = threshold.max(original_bottom);
+            }
+        }
+
+        pages.push(page.clone());
+        pages.extend(continuations);
+    }
+
+    document.pages = pages;
+    document.blocks = output;
+    expanded
+}
+
+/// Position where the next repeated content starts
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+    page: usize,
+    y: f64,
+}
+
+/// Repeat a block once per element, stacked vertically
+fn expand_copies(
+    block: &Block,
+    repeat: &RepeatBinding,
+    items: &[Value],
+    data: &Value,
+    mut cursor: Cursor,
+    column: Column,
+    resolution: &mut Resolution,
+) -> (Vec<(usize, Block)>, Cursor) {
+    let height = block.size.height;
+    let mut copies = Vec::new();
+    let mut end = cursor;
+
+    for (i, item) in items.iter().enumerate() {
+        if column.overflows(cursor.y, height) {
+            cursor = Cursor {
+                page: cursor.page + 1,
+                y: column.top,
+            };
+        }
+
+        let mut copy = block.clone();
+        if i > 0 {
+            copy.id = Uuid::new_v4().to_string();
+        }
+        copy.position.y = cursor.y;
+        TemplateEngine::resolve_block(&mut copy, &item_scope(data, item, i + 1), resolution);
+        copies.push((cursor.page, copy));
+
+        end = Cursor {
+            page: cursor.page,
+            y: cursor.y + height,
+        };
+        cursor.y = end.y + repeat.gap;
+    }
+
+    (copies, end)
+}
+
+/// Add the template rows of a table once per element
+///
+/// Rows keep the height they have in the editor. When the table reaches the
+/// bottom margin it continues on the next page with the header rows repeated.
+fn expand_table(
+    block: &Block,
+    repeat: &RepeatBinding,
+    items: &[Value],
+    data: &Value,
+    mut cursor: Cursor,
+    column: Column,
+    resolution: &mut Resolution,
+) -> (Vec<(usize, Block)>, Cursor) {
+    let BlockContent::Table(table) = &block.content else {
+        return (Vec::new(), cursor);
+    };
+
+    let header_count = repeat.header_rows.min(table.rows.len());
+    let footer_count = repeat.footer_rows.min(table.rows.len() - header_count);
+    let template_end = table.rows.len() - footer_count;
+    let row_height = block.size.height / table.rows.len().max(1) as f64;
+
+    let mut rows = table.rows[..header_count].to_vec();
+    TemplateEngine::resolve_rows(&mut rows, data, &block.id, resolution);
+
+    for (i, item) in items.iter().enumerate() {
+        let mut item_rows = table.rows[header_count..template_end].to_vec();
+        let scope = item_scope(data, item, i + 1);
+        TemplateEngine::resolve_rows(&mut item_rows, &scope, &block.id, resolution);
+        rows.extend(item_rows);
+    }
+
+    let mut footer = table.rows[template_end..].to_vec();
+    TemplateEngine::resolve_rows(&mut footer, data, &block.id, resolution);
+    rows.extend(footer);
+
+    // Formulas see the whole table, so totals cover the rows on every page
+    if let Err(e) = formula::compute_rows(&mut rows, data, resolution.locale) {
+        resolution
+            .formula_errors
+            .push(format!("{} (block {})", e, block.id));
+    }
+
+    let body = rows.split_off(header_count);
+    let header = rows;
+
+    let mut copies = Vec::new();
+    let mut remaining: &[TableRow] = &body;
+    let page_rows = ((column.bottom - column.top) / row_height).floor() as usize;
+
+    loop {
+        let capacity = ((column.bottom - cursor.y) / row_height + 1e-6).floor() as usize;
+        let fits = capacity.saturating_sub(header_count);
+
+        // Start on a fresh page rather than leaving a header without rows
+        if fits == 0 && !remaining.is_empty() && cursor.y > column.top && page_rows > header_count {
+            cursor = Cursor {
+                page: cursor.page + 1,
+                y: column.top,
+            };
+            continue;
+        }
+
+        let take = fits.max(1).min(remaining.len());
+        let mut rows = header.clone();
+        rows.extend_from_slice(&remaining[..take]);
+        remaining = &remaining[take..];
+
+        let mut chunk = block.clone();
+        if !copies.is_empty() {
+            chunk.id = Uuid::new_v4().to_string();
+        }
+        chunk.position.y = cursor.y;
+        chunk.size.height = rows.len() as f64 * row_height;
+        if let BlockContent::Table(content) = &mut chunk.content {
+            content.rows = rows;
+        }
+
+        let end = Cursor {
+            page: cursor.page,
+            y: cursor.y + chunk.size.height,
+        };
+        copies.push((cursor.page, chunk));
+
+        if remaining.is_empty() {
+            return (copies, end);
+        }
+        cursor = Cursor {
+            page: cursor.page + 1,
+            y: column.top,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BlockType, TableBlockContent, TableCell};
+    use serde_json::json;
+
+    fn block(block_type: BlockType, y: f64, height: f64) -> Block {
+        Block::for_test(block_type, 80.0, y, 300.0, height)
+    }
+
+    fn text(content: &str, y: f64, height: f64) -> Block {
+        block(BlockType::Text, y, height).with_text(content)
+    }
+
+    fn row(cells: &[&str]) -> TableRow {
+        TableRow {
+            cells: cells
+                .iter()
+                .map(|c| TableCell {
+                    content: c.to_string(),
+                    styles: None,
+                    name: None,
+                })
+                .collect(),
+        }
+    }
+
+    fn items(count: usize) -> Value {
+        let items: Vec<Value> = (1..=count)
+            .map(|i| json!({ "name": format!("Item {}", i) }))
+            .collect();
+        json!({ "items": items })
+    }
+
+    fn repeat(path: &str) -> Option<RepeatBinding> {
+        Some(RepeatBinding {
+            path: path.to_string(),
+            gap: 0.0,
+            header_rows: 1,
+            footer_rows: 0,
+        })
+    }
+
+    fn cell_text(block: &Block, row: usize) -> &str {
+        match &block.content {
+            BlockContent::Table(table) => &table.rows[row].cells[0].content,
+            _ => panic!("expected table block"),
+        }
+    }
+
+    #[test]
+    fn test_repeated_blocks_push_following_blocks_down() {
+        let mut document = Document::new("Offer".to_string());
+        let mut line = text("{{ index }}. {{ item.name }}", 100.0, 20.0);
+        line.repeat = repeat("items");
+        document.add_block(line);
+        document.add_block(text("Total", 130.0, 20.0));
+
+        let resolved = TemplateEngine::apply(&document, &items(3)).unwrap();
+        let texts: Vec<(String, f64)> = resolved
+            .blocks
+            .iter()
+            .map(|b| match &b.content {
+                BlockContent::Text(t) => (t.text.clone(), b.position.y),
+                _ => panic!("expected text block"),
+            })
+            .collect();
+
+        assert_eq!(
+            texts,
+            vec![
+                ("1. Item 1".to_string(), 100.0),
+                ("2. Item 2".to_string(), 120.0),
+                ("3. Item 3".to_string(), 140.0),
+                ("Total".to_string(), 170.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_table_rows_continue_on_new_pages() {
+        let mut document = Document::new("Invoice".to_string());
+        let mut table = block(BlockType::Table, 100.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![row(&["Name"]), row(&["{{ item.name }}"])],
+            column_widths: vec![],
+        });
+        table.repeat = repeat("items");
+        document.add_block(table);
+        document.add_block(text("Signature", 200.0, 40.0));
+
+        let resolved = TemplateEngine::apply(&document, &items(50)).unwrap();
+        assert_eq!(resolved.pages.len(), 2);
+        assert!(resolved.validate().is_ok());
+
+        let tables: Vec<&Block> = resolved
+            .blocks
+            .iter()
+            .filter(|b| matches!(b.content, BlockContent::Table(_)))
+            .collect();
+        assert_eq!(tables.len(), resolved.pages.len());
+        assert_eq!(cell_text(tables[1], 0), "Name");
+        assert_eq!(cell_text(tables[0], 1), "Item 1");
+
+        // Every chunk stays inside the content box of its page
+        for table in &tables {
+            let page = resolved.get_page(&table.page_id).unwrap();
+            let content = page.geometry().to_px().content_box;
+            assert!(table.position.y + table.size.height <= content.y + content.height + 1e-6);
+        }
+
+        // The signature follows the last chunk
+        let last = tables.last().unwrap();
+        let signature = resolved.blocks.last().unwrap();
+        assert_eq!(signature.page_id, last.page_id);
+        assert!(signature.position.y >= last.position.y + last.size.height);
+    }
+
+    #[test]
+    fn test_footer_totals_cover_all_rows() {
+        let mut document = Document::new("Invoice".to_string());
+        let mut table = block(BlockType::Table, 100.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![
+                row(&["Item", "Price"]),
+                row(&["{{ item.name }}", "{{ item.price }}"]),
+                row(&["Total", "=SUM(col(2))"]),
+            ],
+            column_widths: vec![],
+        });
+        table.repeat = Some(RepeatBinding {
+            footer_rows: 1,
+            ..repeat("items").unwrap()
+        });
+        document.add_block(table);
+
+        let data = json!({ "items": [
+            { "name": "Setup", "price": "100.10" },
+            { "name": "Support", "price": 49.9 },
+        ] });
+        let resolved = TemplateEngine::apply(&document, &data).unwrap();
+        let table = &resolved.blocks[0];
+        match &table.content {
+            BlockContent::Table(content) => {
+                assert_eq!(content.rows.len(), 4);
+                assert_eq!(content.rows[3].cells[1].content, "150.00");
+            }
+            _ => panic!("expected table block"),
+        }
+    }
+
+    #[test]
+    fn test_repeater_errors() {
+        let mut document = Document::new("Offer".to_string());
+        let mut line = text("{{ item.name }}", 100.0, 20.0);
+        line.repeat = repeat("items");
+        document.add_block(line);
+
+        assert!(TemplateEngine::apply(&document, &json!({ "items": 5 })).is_err());
+        assert!(TemplateEngine::apply(&document, &json!({})).is_err());
+        let resolved = TemplateEngine::apply(&document, &json!({ "items": null })).unwrap();
+        assert!(resolved.blocks.is_empty());
+
+        let paths: Vec<String> = TemplateEngine::list_variables(&document)
+            .into_iter()
+            .map(|v| v.path)
+            .collect();
+        assert_eq!(paths, vec!["items"]);
+    }
+
+    #[test]
+    fn test_repeaters_without_data_are_empty() {
+        let mut document = Document::new("Invoice".to_string());
+        let mut line = text("{{ item.name }}", 100.0, 20.0);
+        line.repeat = repeat("items");
+        document.add_block(line);
+        let mut table = block(BlockType::Table, 140.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![row(&["Name {{ title }}"]), row(&["{{ item.name }}"])],
+            column_widths: vec![],
+        });
+        table.repeat = repeat("items");
+        document.add_block(table);
+        document.add_block(text("Signature {{ name }}", 220.0, 20.0));
+
+        let resolved = TemplateEngine::apply_without_data(&document).unwrap();
+        assert_eq!(resolved.blocks.len(), 2);
+        assert_eq!(resolved.pages.len(), 1);
+        // Only the header row is left, placeholders outside the scope are kept
+        let BlockContent::Table(table) = &resolved.blocks[0].content else {
+            panic!("expected table block");
+        };
+        assert_eq!(table.rows.len(), 1);
+        assert_eq!(cell_text(&resolved.blocks[0], 0), "Name {{ title }}");
+        let BlockContent::Text(signature) = &resolved.blocks[1].content else {
+            panic!("expected text block");
+        };
+        assert_eq!(signature.text, "Signature {{ name }}");
+    }
+}
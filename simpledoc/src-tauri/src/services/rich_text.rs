@@ -0,0 +1,608 @@
+use crate::models::{ListKind, Paragraph, TextAlignment, TextRun, VerticalAlign};
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+
+/// Heading font sizes for levels 1 to 6 (in pixels)
+const HEADING_SIZES_PX: [f64; 6] = [32.0, 24.0, 20.0, 18.0, 16.0, 14.0];
+
+/// Space below paragraphs and headings (in pixels)
+const PARAGRAPH_SPACING_PX: f64 = 8.0;
+
+/// Markup that can be imported into a text block
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RichTextFormat {
+    Markdown,
+    Html,
+}
+
+/// Convert markup into text block paragraphs
+pub fn import(source: &str, format: RichTextFormat) -> Vec<Paragraph> {
+    match format {
+        RichTextFormat::Markdown => from_markdown(source),
+        RichTextFormat::Html => from_html(source),
+    }
+}
+
+/// Collects styled text into paragraphs
+struct Builder {
+    paragraphs: Vec<Paragraph>,
+    current: Option<Paragraph>,
+    lists: Vec<ListKind>,
+    /// Style of new text; the top of the stack applies
+    styles: Vec<TextRun>,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            paragraphs: Vec::new(),
+            current: None,
+            lists: Vec::new(),
+            styles: vec![TextRun::default()],
+        }
+    }
+
+    fn push_style(&mut self, change: impl FnOnce(&mut TextRun)) {
+        let mut style = self.styles.last().cloned().unwrap_or_default();
+        change(&mut style);
+        self.styles.push(style);
+    }
+
+    fn pop_style(&mut self) {
+        if self.styles.len() > 1 {
+            self.styles.pop();
+        }
+    }
+
+    fn start_paragraph(&mut self) -> &mut Paragraph {
+        self.end_paragraph();
+        self.current.insert(Paragraph::default())
+    }
+
+    /// Start a list item of the innermost list
+    fn start_item(&mut self) -> &mut Paragraph {
+        let list = self.lists.last().copied().unwrap_or(ListKind::Bullet);
+        let level = self.lists.len().saturating_sub(1) as u8;
+        let paragraph = self.start_paragraph();
+        paragraph.list = Some(list);
+        paragraph.level = level;
+        paragraph
+    }
+
+    /// Finish the open paragraph, dropping it if it has no text
+    fn end_paragraph(&mut self) {
+        let Some(mut paragraph) = self.current.take() else {
+            return;
+        };
+        while let Some(run) = paragraph.runs.last_mut() {
+            let trimmed = run.text.trim_end_matches([' ', '\n']).len();
+            run.text.truncate(trimmed);
+            if !run.text.is_empty() {
+                break;
+            }
+            paragraph.runs.pop();
+        }
+        if !paragraph.runs.is_empty() || paragraph.list.is_some() {
+            self.paragraphs.push(paragraph);
+        }
+    }
+
+    /// Append text in the current style, starting a paragraph if needed
+    fn text(&mut self, text: &str) {
+        let style = self.styles.last().cloned().unwrap_or_default();
+        let paragraph = self.current.get_or_insert_with(Paragraph::default);
+
+        // Whitespace at the start of a paragraph or after a space is dropped
+        let at_space = match paragraph.runs.last() {
+            Some(run) => run.text.is_empty() || run.text.ends_with([' ', '\n']),
+            None => true,
+        };
+        let text = if at_space {
+            text.trim_start_matches(' ')
+        } else {
+            text
+        };
+        if text.is_empty() {
+            return;
+        }
+
+        match paragraph.runs.last_mut() {
+            Some(run) if same_style(run, &style) => run.text.push_str(text),
+            _ => paragraph.runs.push(TextRun {
+                text: text.to_string(),
+                ..style
+            }),
+        }
+    }
+
+    fn line_break(&mut self) {
+        let paragraph = self.current.get_or_insert_with(Paragraph::default);
+        match paragraph.runs.last_mut() {
+            Some(run) => {
+                let trimmed = run.text.trim_end_matches(' ').len();
+                run.text.truncate(trimmed);
+                run.text.push('\n');
+            }
+            None => paragraph.runs.push(TextRun::plain("\n".to_string())),
+        }
+    }
+
+    fn finish(mut self) -> Vec<Paragraph> {
+        self.end_paragraph();
+        self.paragraphs
+    }
+}
+
+fn same_style(run: &TextRun, style: &TextRun) -> bool {
+    let mut run = run.clone();
+    run.text.clear();
+    run == *style
+}
+
+/// Convert CommonMark with strike-through, superscript and subscript
+pub fn from_markdown(source: &str) -> Vec<Paragraph> {
+    let options =
+        Options::ENABLE_STRIKETHROUGH | Options::ENABLE_SUPERSCRIPT | Options::ENABLE_SUBSCRIPT;
+    let mut builder = Builder::new();
+    // Paragraphs of a list item after the first one continue the item
+    let mut item_started = false;
+
+    for event in Parser::new_ext(source, options) {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                if !item_started {
+                    builder.start_paragraph().space_after = PARAGRAPH_SPACING_PX;
+                }
+                item_started = false;
+            }
+            Event::Start(Tag::Heading { level, .. }) => {
+                let size = HEADING_SIZES_PX[heading_index(level)];
+                builder.start_paragraph().space_after = PARAGRAPH_SPACING_PX;
+                builder.push_style(|style| {
+                    style.bold = true;
+                    style.font_size = Some(size);
+                });
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                builder.start_paragraph().space_after = PARAGRAPH_SPACING_PX;
+            }
+            Event::Start(Tag::List(first)) => {
+                builder.end_paragraph();
+                builder.lists.push(match first {
+                    Some(_) => ListKind::Numbered,
+                    None => ListKind::Bullet,
+                });
+            }
+            Event::Start(Tag::Item) => {
+                builder.start_item();
+                item_started = true;
+            }
+            Event::Start(Tag::Emphasis) => builder.push_style(|style| style.italic = true),
+            Event::Start(Tag::Strong) => builder.push_style(|style| style.bold = true),
+            Event::Start(Tag::Strikethrough) => builder.push_style(|style| style.strike = true),
+            Event::Start(Tag::Superscript) => builder.push_style(|style| {
+                style.vertical_align = Some(VerticalAlign::Superscript);
+            }),
+            Event::Start(Tag::Subscript) => builder.push_style(|style| {
+                style.vertical_align = Some(VerticalAlign::Subscript);
+            }),
+            Event::Start(Tag::Link { dest_url, .. }) => builder.push_style(|style| {
+                style.link = Some(dest_url.to_string());
+                style.underline = true;
+            }),
+            Event::End(TagEnd::Paragraph | TagEnd::CodeBlock | TagEnd::Item) => {
+                builder.end_paragraph();
+                item_started = false;
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                builder.pop_style();
+                builder.end_paragraph();
+            }
+            Event::End(TagEnd::List(_)) => {
+                builder.end_paragraph();
+                builder.lists.pop();
+            }
+            Event::End(
+                TagEnd::Emphasis
+                | TagEnd::Strong
+                | TagEnd::Strikethrough
+                | TagEnd::Superscript
+                | TagEnd::Subscript
+                | TagEnd::Link,
+            ) => builder.pop_style(),
+            Event::Text(text) | Event::Code(text) => {
+                builder.text(text.trim_end_matches('\n'));
+            }
+            Event::SoftBreak => builder.text(" "),
+            Event::HardBreak => builder.line_break(),
+            _ => {}
+        }
+    }
+
+    builder.finish()
+}
+
+fn heading_index(level: HeadingLevel) -> usize {
+    match level {
+        HeadingLevel::H1 => 0,
+        HeadingLevel::H2 => 1,
+        HeadingLevel::H3 => 2,
+        HeadingLevel::H4 => 3,
+        HeadingLevel::H5 => 4,
+        HeadingLevel::H6 => 5,
+    }
+}
+
+/// Convert a basic HTML subset
+///
+/// Supports `p`, `div`, `h1`-`h6`, `br`, `ul`, `ol`, `li`, `b`, `strong`,
+/// `i`, `em`, `u`, `s`, `del`, `sup`, `sub`, `a` and `span`, plus inline
+/// `style` colors, sizes, weights and decorations. Other tags are ignored
+/// and keep their text.
+pub fn from_html(source: &str) -> Vec<Paragraph> {
+    let mut builder = Builder::new();
+    // Open inline tags, so closing tags pop the right style
+    let mut open: Vec<String> = Vec::new();
+    let mut rest = source;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            builder.text(&collapse_whitespace(&decode_entities(rest)));
+            break;
+        };
+        if start > 0 {
+            builder.text(&collapse_whitespace(&decode_entities(&rest[..start])));
+        }
+        let Some(end) = rest[start..].find('>') else {
+            builder.text(&collapse_whitespace(&decode_entities(&rest[start..])));
+            break;
+        };
+        let tag = &rest[start + 1..start + end];
+        rest = &rest[start + end + 1..];
+
+        if tag.starts_with('!') || tag.starts_with('?') {
+            continue;
+        }
+        if let Some(name) = tag.strip_prefix('/') {
+            close_tag(&mut builder, &mut open, &name.trim().to_lowercase());
+            continue;
+        }
+
+        let (name, attributes) = parse_tag(tag);
+        if matches!(name.as_str(), "script" | "style") {
+            // Skip the contents entirely
+            let closing = format!("</{}", name);
+            rest = match rest.to_ascii_lowercase().find(&closing) {
+                Some(index) => rest[index..]
+                    .find('>')
+                    .map_or("", |i| &rest[index + i + 1..]),
+                None => "",
+            };
+            continue;
+        }
+        open_tag(&mut builder, &mut open, &name, &attributes);
+    }
+
+    builder.finish()
+}
+
+fn open_tag(
+    builder: &mut Builder,
+    open: &mut Vec<String>,
+    name: &str,
+    attributes: &[(String, String)],
+) {
+    let attribute = |key: &str| {
+        attributes
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    let style = attribute("style").unwrap_or_default();
+
+    match name {
+        "br" => return builder.line_break(),
+        "img" | "hr" | "meta" | "link" | "input" | "col" => return,
+        "p" | "div" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+            // Blocks inside a list item (as pasted from Word) stay list items
+            let in_item = open.iter().any(|tag| tag == "li");
+            let fresh_item = builder
+                .current
+                .as_ref()
+                .is_some_and(|p| p.list.is_some() && p.runs.is_empty());
+            let paragraph = if in_item && fresh_item {
+                builder.current.get_or_insert_with(Paragraph::default)
+            } else if in_item {
+                builder.start_item()
+            } else {
+                let paragraph = builder.start_paragraph();
+                paragraph.space_after = PARAGRAPH_SPACING_PX;
+                paragraph
+            };
+            paragraph.alignment = css_property(style, "text-align").and_then(|align| match align {
+                "left" => Some(TextAlignment::Left),
+                "center" => Some(TextAlignment::Center),
+                "right" => Some(TextAlignment::Right),
+                "justify" => Some(TextAlignment::Justify),
+                _ => None,
+            });
+        }
+        "ul" | "ol" => {
+            builder.end_paragraph();
+            builder.lists.push(if name == "ol" {
+                ListKind::Numbered
+            } else {
+                ListKind::Bullet
+            });
+            return;
+        }
+        "li" => {
+            builder.start_item();
+        }
+        _ => {}
+    }
+
+    let heading = name
+        .strip_prefix('h')
+        .and_then(|level| level.parse::<usize>().ok())
+        .filter(|level| (1..=6).contains(level));
+    let link = attribute("href").map(str::to_string);
+
+    builder.push_style(|run| {
+        match name {
+            "b" | "strong" => run.bold = true,
+            "i" | "em" => run.italic = true,
+            "u" | "ins" => run.underline = true,
+            "s" | "strike" | "del" => run.strike = true,
+            "sup" => run.vertical_align = Some(VerticalAlign::Superscript),
+            "sub" => run.vertical_align = Some(VerticalAlign::Subscript),
+            "a" if link.is_some() => {
+                run.link = link;
+                run.underline = true;
+            }
+            _ => {}
+        }
+        if let Some(level) = heading {
+            run.bold = true;
+            run.font_size = Some(HEADING_SIZES_PX[level - 1]);
+        }
+        apply_css(run, style);
+    });
+    open.push(name.to_string());
+}
+
+fn close_tag(builder: &mut Builder, open: &mut Vec<String>, name: &str) {
+    if matches!(name, "ul" | "ol") {
+        builder.end_paragraph();
+        builder.lists.pop();
+        return;
+    }
+    // Tolerate unclosed inner tags by popping up to the matching one
+    if let Some(index) = open.iter().rposition(|tag| tag == name) {
+        for _ in index..open.len() {
+            builder.pop_style();
+        }
+        open.truncate(index);
+    }
+    if matches!(
+        name,
+        "p" | "div" | "li" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6"
+    ) {
+        builder.end_paragraph();
+    }
+}
+
+/// Lowercase tag name and attributes of a tag's source
+fn parse_tag(tag: &str) -> (String, Vec<(String, String)>) {
+    let tag = tag.trim().trim_end_matches('/');
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = tag[..name_end].to_lowercase();
+    let mut attributes = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+
+    while !rest.is_empty() {
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = rest[..key_end].to_lowercase();
+        rest = rest[key_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (raw, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(quote).unwrap_or(body.len());
+                    (&body[..end], body.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = decode_entities(raw);
+            rest = remaining.trim_start();
+        }
+        if !key.is_empty() {
+            attributes.push((key, value));
+        }
+    }
+
+    (name, attributes)
+}
+
+/// Value of a property in an inline `style` attribute
+fn css_property<'a>(style: &'a str, name: &str) -> Option<&'a str> {
+    style.split(';').find_map(|declaration| {
+        let (key, value) = declaration.split_once(':')?;
+        (key.trim().eq_ignore_ascii_case(name)).then(|| value.trim())
+    })
+}
+
+fn apply_css(run: &mut TextRun, style: &str) {
+    if let Some(color) = css_property(style, "color") {
+        run.color = Some(color.to_string());
+    }
+    if let Some(size) = css_property(style, "font-size") {
+        let px = size
+            .strip_suffix("px")
+            .and_then(|px| px.trim().parse::<f64>().ok())
+            .or_else(|| {
+                size.strip_suffix("pt")
+                    .and_then(|pt| pt.trim().parse::<f64>().ok())
+                    .map(|pt| pt * 96.0 / 72.0)
+            });
+        if px.is_some() {
+            run.font_size = px;
+        }
+    }
+    if let Some(weight) = css_property(style, "font-weight") {
+        run.bold = weight == "bold" || weight.parse::<u16>().is_ok_and(|w| w >= 600);
+    }
+    if let Some(font_style) = css_property(style, "font-style") {
+        run.italic = font_style == "italic" || font_style == "oblique";
+    }
+    if let Some(decoration) = css_property(style, "text-decoration") {
+        run.underline = decoration.contains("underline");
+        run.strike = decoration.contains("line-through");
+    }
+}
+
+/// HTML whitespace (including newlines) collapses to single spaces
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !space {
+                collapsed.push(' ');
+            }
+            space = true;
+        } else {
+            collapsed.push(c);
+            space = false;
+        }
+    }
+    collapsed
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|end| *end <= 10)
+            .map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            "ndash" => Some('–'),
+            "mdash" => Some('—'),
+            "hellip" => Some('…'),
+            "laquo" => Some('«'),
+            "raquo" => Some('»'),
+            "euro" => Some('€'),
+            "copy" => Some('©'),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        });
+
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::plain_text;
+
+    #[test]
+    fn test_markdown_runs_and_lists() {
+        let paragraphs = from_markdown(
+            "# Offer\n\nTotal **120 EUR** with *free* [support](https://example.com).\n\n1. Setup\n2. Training\n\n- Extra",
+        );
+
+        assert_eq!(
+            plain_text(&paragraphs),
+            "Offer\nTotal 120 EUR with free support.\nSetup\nTraining\nExtra"
+        );
+        assert_eq!(paragraphs[0].runs[0].font_size, Some(32.0));
+        let total = &paragraphs[1].runs;
+        assert!(total[1].bold && total[1].text == "120 EUR");
+        assert!(total[3].italic);
+        assert_eq!(total[5].link.as_deref(), Some("https://example.com"));
+        assert_eq!(paragraphs[2].list, Some(ListKind::Numbered));
+        assert_eq!(paragraphs[4].list, Some(ListKind::Bullet));
+    }
+
+    #[test]
+    fn test_html_subset() {
+        let paragraphs = from_html(
+            "<p style=\"text-align: right\">Price:\n  <b>99&nbsp;&euro;</b><sup>1</sup><br>net</p>\
+             <ul><li>One <span style=\"color: #ff0000\">red</span></li><li><s>Two</s></li></ul>\
+             <script>ignored()</script><p>A &amp; B</p>",
+        );
+
+        assert_eq!(paragraphs.len(), 4);
+        assert_eq!(paragraphs[0].alignment, Some(TextAlignment::Right));
+        assert_eq!(paragraphs[0].text(), "Price: 99\u{a0}€1\nnet");
+        assert!(paragraphs[0].runs[1].bold);
+        assert_eq!(
+            paragraphs[0].runs[2].vertical_align,
+            Some(VerticalAlign::Superscript)
+        );
+        assert_eq!(paragraphs[1].runs[1].color.as_deref(), Some("#ff0000"));
+        assert_eq!(paragraphs[2].list, Some(ListKind::Bullet));
+        assert!(paragraphs[2].runs[0].strike);
+        assert_eq!(paragraphs[3].text(), "A & B");
+    }
+
+    #[test]
+    fn test_html_paragraphs_in_list_items() {
+        let paragraphs = from_html(
+            "<ul>\n  <li><p>One</p></li>\n  <li><p>Two</p><p>more</p>\
+             <ol><li><p style=\"text-align: center\">Nested</p></li></ol></li>\n</ul>",
+        );
+
+        let items: Vec<(String, Option<ListKind>, u8)> = paragraphs
+            .iter()
+            .map(|p| (p.text(), p.list, p.level))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("One".to_string(), Some(ListKind::Bullet), 0),
+                ("Two".to_string(), Some(ListKind::Bullet), 0),
+                ("more".to_string(), Some(ListKind::Bullet), 0),
+                ("Nested".to_string(), Some(ListKind::Numbered), 1),
+            ]
+        );
+        assert_eq!(paragraphs[3].alignment, Some(TextAlignment::Center));
+        assert_eq!(paragraphs[0].space_after, 0.0);
+    }
+}
@@ -0,0 +1,285 @@
+use crate::models::{BlockContent, Document};
+use serde::{Deserialize, Serialize};
+
+/// Marks placed around matched words in snippets
+pub const MATCH_START: &str = "**";
+pub const MATCH_END: &str = "**";
+
+/// Words of context kept on each side of the first match
+const SNIPPET_CONTEXT: usize = 6;
+
+/// Part of a document a search hit comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Title,
+    Description,
+    Tags,
+    /// Text block content or table cells
+    Block,
+}
+
+impl SearchField {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchField::Title => "title",
+            SearchField::Description => "description",
+            SearchField::Tags => "tags",
+            SearchField::Block => "block",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "title" => Some(SearchField::Title),
+            "description" => Some(SearchField::Description),
+            "tags" => Some(SearchField::Tags),
+            "block" => Some(SearchField::Block),
+            _ => None,
+        }
+    }
+
+    /// Ranking multiplier, so title matches outrank body matches
+    pub fn weight(&self) -> f64 {
+        match self {
+            SearchField::Title => 4.0,
+            SearchField::Tags => 3.0,
+            SearchField::Description => 2.0,
+            SearchField::Block => 1.0,
+        }
+    }
+}
+
+/// One ranked match
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    #[serde(rename = "documentId")]
+    pub document_id: String,
+    pub title: String,
+    pub field: SearchField,
+    /// Set when the match is in a block
+    #[serde(rename = "blockId", skip_serializing_if = "Option::is_none")]
+    pub block_id: Option<String>,
+    /// Excerpt with matched words wrapped in `**`
+    pub snippet: String,
+    /// Higher is better
+    pub score: f64,
+}
+
+/// Searchable text of one field or block
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub field: SearchField,
+    pub block_id: Option<String>,
+    pub content: String,
+}
+
+/// Everything of a document that search looks at
+pub fn index_entries(document: &Document) -> Vec<IndexEntry> {
+    let metadata = &document.metadata;
+    let mut entries = vec![IndexEntry {
+        field: SearchField::Title,
+        block_id: None,
+        content: metadata.title.clone(),
+    }];
+
+    if let Some(description) = metadata.description.as_ref().filter(|d| !d.is_empty()) {
+        entries.push(IndexEntry {
+            field: SearchField::Description,
+            block_id: None,
+            content: description.clone(),
+        });
+    }
+    if let Some(tags) = metadata.tags.as_ref().filter(|t| !t.is_empty()) {
+        entries.push(IndexEntry {
+            field: SearchField::Tags,
+            block_id: None,
+            content: tags.join(" "),
+        });
+    }
+
+    for block in &document.blocks {
+        let content = match &block.content {
+            BlockContent::Text(text) => text.text.clone(),
+            BlockContent::Table(table) => table
+                .rows
+                .iter()
+                .flat_map(|row| row.cells.iter())
+                .map(|cell| cell.content.as_str())
+                .filter(|content| !content.is_empty())
+                .collect::<Vec<_>>()
+                .join(" "),
+            BlockContent::Image(_)
+            | BlockContent::Shape(_)
+            | BlockContent::Line(_)
+            | BlockContent::Spacer => continue,
+        };
+        if !content.trim().is_empty() {
+            entries.push(IndexEntry {
+                field: SearchField::Block,
+                block_id: Some(block.id.clone()),
+                content,
+            });
+        }
+    }
+
+    entries
+}
+
+/// Lowercase words of a query; punctuation and operators are ignored
+pub fn query_terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// FTS5 query matching every term as a word prefix
+pub fn fts_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Rank documents without an index, matching like `fts_query`
+///
+/// Used by stores that keep no search index.
+pub fn scan(documents: &[Document], query: &str, limit: usize) -> Vec<SearchHit> {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+        return Vec::new();
+    }
+
+    let mut hits = Vec::new();
+    for document in documents {
+        for entry in index_entries(document) {
+            let words: Vec<String> = query_terms(&entry.content);
+            let counts: Vec<usize> = terms
+                .iter()
+                .map(|term| words.iter().filter(|w| w.starts_with(term)).count())
+                .collect();
+            if counts.contains(&0) {
+                continue;
+            }
+
+            // Frequency damped by length, like BM25 without corpus statistics
+            let frequency = counts.iter().sum::<usize>() as f64;
+            let length = 1.0 + (words.len() as f64 / 50.0);
+            hits.push(SearchHit {
+                document_id: document.id.clone(),
+                title: document.metadata.title.clone(),
+                field: entry.field,
+                block_id: entry.block_id,
+                snippet: snippet(&entry.content, &terms),
+                score: entry.field.weight() * frequency / length,
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit);
+    hits
+}
+
+/// Excerpt around the first matched word, with matches marked
+pub fn snippet(content: &str, terms: &[String]) -> String {
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let matches = |word: &str| {
+        query_terms(word)
+            .iter()
+            .any(|w| terms.iter().any(|term| w.starts_with(term)))
+    };
+
+    let first = words.iter().position(|w| matches(w)).unwrap_or(0);
+    let start = first.saturating_sub(SNIPPET_CONTEXT);
+    let end = (first + SNIPPET_CONTEXT + 1).min(words.len());
+
+    let mut parts = Vec::new();
+    if start > 0 {
+        parts.push("…".to_string());
+    }
+    for word in &words[start..end] {
+        if matches(word) {
+            parts.push(mark(word));
+        } else {
+            parts.push(word.to_string());
+        }
+    }
+    if end < words.len() {
+        parts.push("…".to_string());
+    }
+    parts.join(" ")
+}
+
+/// Wrap a word in match marks, leaving surrounding punctuation outside
+fn mark(word: &str) -> String {
+    let start = word.find(char::is_alphanumeric).unwrap_or(0);
+    let end = word
+        .rfind(char::is_alphanumeric)
+        .map(|i| i + word[i..].chars().next().map_or(1, char::len_utf8))
+        .unwrap_or(word.len());
+    format!(
+        "{}{}{}{}{}",
+        &word[..start],
+        MATCH_START,
+        &word[start..end],
+        MATCH_END,
+        &word[end..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Block;
+
+    fn document(title: &str, body: &str) -> Document {
+        let mut document = Document::new(title.to_string());
+        document.add_block(Block::text_for_test(body));
+        document
+    }
+
+    #[test]
+    fn test_query_terms() {
+        assert_eq!(
+            query_terms("Kubernetes, \"k8s\" OR-helm"),
+            vec!["kubernetes", "k8s", "or", "helm"]
+        );
+        assert_eq!(
+            fts_query(&query_terms("Cloud migration")),
+            "\"cloud\"* \"migration\"*"
+        );
+        assert!(query_terms(" ,. ").is_empty());
+    }
+
+    #[test]
+    fn test_snippet() {
+        let content = "one two three four five six seven Kubernetes cluster eight nine ten eleven twelve thirteen";
+        assert_eq!(
+            snippet(content, &query_terms("kube")),
+            "… two three four five six seven **Kubernetes** cluster eight nine ten eleven twelve …"
+        );
+    }
+
+    #[test]
+    fn test_scan_ranks_title_matches_first() {
+        let documents = vec![
+            document("Hosting offer", "We run your services on Kubernetes."),
+            document("Kubernetes migration", "Moving workloads."),
+            document("Website", "Static pages only."),
+        ];
+
+        let hits = scan(&documents, "kubernetes", 10);
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].field, SearchField::Title);
+        assert_eq!(hits[0].document_id, documents[1].id);
+        assert_eq!(hits[1].block_id.as_ref(), Some(&documents[0].blocks[0].id));
+        assert!(hits[1].snippet.contains("**Kubernetes**."));
+
+        assert!(scan(&documents, "kubernetes website", 10).is_empty());
+        assert!(scan(&documents, "", 10).is_empty());
+    }
+}
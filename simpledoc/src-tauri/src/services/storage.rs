@@ -1,24 +1,101 @@
-use crate::models::{AppError, Document, DocumentListItem, Result};
-use log::{debug, error, info};
+use crate::models::{
+    AppError, Document, DocumentListItem, Result, Template, TemplateListItem, TrashItem,
+};
+use crate::services::assets::{self, AssetStore};
+use crate::services::bundle::{self, BUNDLE_EXTENSION};
+use crate::services::font_registry::{FontInfo, FontRegistry};
+use crate::services::migration;
+use crate::services::history::{RevisionInfo, RevisionStore};
+use crate::services::search::SearchHit;
+use crate::services::store::{DocumentStore, JsonStore, SqliteStore};
+use crate::services::template_library::{self, TemplateLibrary, TemplateOptions};
+use crate::services::Validator;
+use chrono::{Duration, Utc};
+use log::{error, info, warn};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+/// File name of the document database inside the storage directory
+const DATABASE_FILE: &str = "simpledoc.db";
+
+/// File name of the revision history inside the storage directory
+const HISTORY_FILE: &str = "history.db";
+
+/// Directory of the template library inside the storage directory
+const TEMPLATES_DIR: &str = "templates";
+
+/// Directory of the asset store inside the storage directory
+const ASSETS_DIR: &str = "assets";
+
+/// Per-user fonts folder inside the storage directory
+const FONTS_DIR: &str = "fonts";
+
+/// How long a new asset survives garbage collection without being referenced
+const ASSET_GRACE_PERIOD: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Days a deleted document stays in the trash unless configured otherwise
+const DEFAULT_TRASH_DAYS: u32 = 30;
+
 /// Service for document storage operations
 pub struct StorageService {
-    storage_dir: PathBuf,
+    /// Shared with blocking tasks, see `blocking`
+    store: Arc<dyn DocumentStore>,
+    history: Arc<RevisionStore>,
+    templates: TemplateLibrary,
+    /// Images referenced as `asset://<hash>`
+    assets: AssetStore,
+    fonts: FontRegistry,
+    /// Days before trashed documents are purged; `None` keeps them
+    trash_days: Option<u32>,
 }
 
 impl StorageService {
-    /// Create a new storage service with the given directory
+    /// Create a storage service keeping one JSON file per document in the directory
     pub fn new(storage_dir: PathBuf) -> Result<Self> {
-        // Ensure storage directory exists
+        Self::ensure_dir(&storage_dir)?;
+        let store = JsonStore::new(storage_dir.clone());
+        Self::with_store(&storage_dir, Box::new(store))
+    }
+
+    /// Create a storage service backed by `simpledoc.db` in the directory
+    ///
+    /// JSON documents already in the directory are imported on first open.
+    pub fn open_database(storage_dir: PathBuf) -> Result<Self> {
+        Self::ensure_dir(&storage_dir)?;
+        let store = SqliteStore::open(&storage_dir.join(DATABASE_FILE))?;
+        store.import_json_dir(&storage_dir)?;
+        Self::with_store(&storage_dir, Box::new(store))
+    }
+
+    /// Create a storage service on top of any store, keeping history,
+    /// templates and assets in the storage directory
+    pub fn with_store(storage_dir: &Path, store: Box<dyn DocumentStore>) -> Result<Self> {
+        let history = RevisionStore::open(&storage_dir.join(HISTORY_FILE), Default::default())?;
+        let assets = AssetStore::open(storage_dir.join(ASSETS_DIR))?;
+        Ok(Self {
+            store: Arc::from(store),
+            history: Arc::new(history),
+            templates: TemplateLibrary::open(storage_dir.join(TEMPLATES_DIR))?,
+            fonts: FontRegistry::open(storage_dir.join(FONTS_DIR), &assets)?,
+            assets,
+            trash_days: Some(DEFAULT_TRASH_DAYS),
+        })
+    }
+
+    /// Set how many days deleted documents stay in the trash (`None` keeps them)
+    pub fn set_trash_retention(&mut self, days: Option<u32>) {
+        self.trash_days = days;
+    }
+
+    fn ensure_dir(storage_dir: &Path) -> Result<()> {
         if !storage_dir.exists() {
-            fs::create_dir_all(&storage_dir)?;
+            fs::create_dir_all(storage_dir)?;
             info!("Created storage directory: {:?}", storage_dir);
         }
-
-        Ok(Self { storage_dir })
+        Ok(())
     }
 
     /// Get the default storage directory (in user's documents folder)
@@ -27,119 +104,341 @@ impl StorageService {
         home.join("Documents").join("SimpleDoc")
     }
 
-    /// Get the path to a document file
-    fn get_document_path(&self, document_id: &str) -> PathBuf {
-        self.storage_dir.join(format!("{}.json", document_id))
+    /// Run document store and history work on the blocking thread pool
+    ///
+    /// Both are synchronous (SQLite and `std::fs`), so calling them directly
+    /// would stall the async runtime while the storage lock is held.
+    async fn blocking<T, F>(&self, work: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&dyn DocumentStore, &RevisionStore) -> Result<T> + Send + 'static,
+    {
+        let store = Arc::clone(&self.store);
+        let history = Arc::clone(&self.history);
+        tokio::task::spawn_blocking(move || work(store.as_ref(), &history))
+            .await
+            .map_err(|e| AppError::Unknown(format!("Storage task failed: {}", e)))?
     }
 
-    /// Save a document to disk (atomic write: temp file + rename)
+    /// Validate and save a document, keeping a revision
     pub async fn save_document(&self, document: &Document) -> Result<()> {
+        self.save_document_with_message(document, None).await
+    }
+
+    /// Validate and save a document, keeping a revision with a message
+    pub async fn save_document_with_message(
+        &self,
+        document: &Document,
+        message: Option<&str>,
+    ) -> Result<()> {
         document.validate().map_err(|e| AppError::ValidationError(e))?;
+        Validator::validate_blocks(document).map_err(AppError::ValidationError)?;
+        for warning in Validator::validate_visibility_variables(document) {
+            warn!("Document {}: {}", document.id, warning);
+        }
 
-        let path = self.get_document_path(&document.id);
-        let temp_path = path.with_extension("json.tmp");
+        let author = revision_author(document);
+        let message = message.map(str::to_string);
+        let saved = document.clone();
+        self.blocking(move |store, history| {
+            // A revision without a saved document is harmless; a saved
+            // document without its revision would be missing from the history
+            history.record(&saved, author.as_deref(), message.as_deref())?;
+            store.save(&saved)
+        })
+        .await?;
 
-        debug!("Saving document {} to {:?}", document.id, path);
+        info!("Document {} saved successfully", document.id);
+        Ok(())
+    }
 
-        // Serialize to JSON
-        let json = serde_json::to_string_pretty(&document)?;
+    /// Load a document
+    pub async fn load_document(&self, document_id: &str) -> Result<Document> {
+        let id = document_id.to_string();
+        let document = self.blocking(move |store, _| store.load(&id)).await?;
 
-        // Write to temporary file
-        let mut file = tokio::fs::File::create(&temp_path).await?;
-        file.write_all(json.as_bytes()).await?;
-        file.sync_all().await?;
-        drop(file);
+        info!("Document {} loaded successfully", document_id);
+        Ok(document)
+    }
 
-        // Atomic rename
-        tokio::fs::rename(&temp_path, &path).await?;
+    /// List all documents (returns lightweight list items, newest first)
+    pub async fn list_documents(&self) -> Result<Vec<DocumentListItem>> {
+        let documents = self.blocking(|store, _| store.list()).await?;
 
-        info!("Document {} saved successfully", document.id);
+        info!("Found {} documents", documents.len());
+        Ok(documents)
+    }
+
+    /// Move a document to the trash
+    pub async fn delete_document(&self, document_id: &str) -> Result<()> {
+        let id = document_id.to_string();
+        let days = self.trash_days;
+        self.blocking(move |store, history| {
+            store.trash(&id)?;
+            purge_expired(store, history, days)
+        })
+        .await?;
+
+        info!("Document {} moved to the trash", document_id);
         Ok(())
     }
 
-    /// Load a document from disk
-    pub async fn load_document(&self, document_id: &str) -> Result<Document> {
-        let path = self.get_document_path(document_id);
+    /// List the trashed documents, most recently deleted first
+    pub async fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        let days = self.trash_days;
+        let items = self
+            .blocking(move |store, history| {
+                purge_expired(store, history, days)?;
+                store.list_trash()
+            })
+            .await?;
+
+        info!("Found {} documents in the trash", items.len());
+        Ok(items)
+    }
 
-        if !path.exists() {
-            return Err(AppError::DocumentNotFound(document_id.to_string()));
-        }
+    /// Move a document out of the trash
+    pub async fn restore_document(&self, document_id: &str) -> Result<Document> {
+        let id = document_id.to_string();
+        let document = self.blocking(move |store, _| store.restore(&id)).await?;
 
-        debug!("Loading document {} from {:?}", document_id, path);
+        info!("Document {} restored from the trash", document_id);
+        Ok(document)
+    }
 
-        let mut file = tokio::fs::File::open(&path).await?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).await?;
+    /// Delete a trashed document and its revisions for good
+    pub async fn purge_document(&self, document_id: &str) -> Result<()> {
+        let id = document_id.to_string();
+        self.blocking(move |store, history| {
+            store.purge(&id)?;
+            history.forget(&id).map(|_| ())
+        })
+        .await?;
+
+        info!("Document {} purged", document_id);
+        Ok(())
+    }
 
-        let document: Document = serde_json::from_str(&contents)?;
+    /// Purge documents that have been in the trash longer than the retention
+    ///
+    /// Returns the number of purged documents.
+    pub fn purge_expired_trash(&self) -> Result<usize> {
+        purge_expired(self.store.as_ref(), &self.history, self.trash_days)
+    }
 
-        info!("Document {} loaded successfully", document_id);
-        Ok(document)
+    /// Asset store used to resolve `asset://` references
+    pub fn assets(&self) -> &AssetStore {
+        &self.assets
     }
 
-    /// List all documents (returns lightweight list items)
-    pub async fn list_documents(&self) -> Result<Vec<DocumentListItem>> {
-        debug!("Listing documents in {:?}", self.storage_dir);
-
-        let mut documents = Vec::new();
-
-        let mut entries = tokio::fs::read_dir(&self.storage_dir).await?;
-        
-        while let Some(entry) = entries.next_entry().await? {
-            let path = entry.path();
-            
-            // Only process .json files (not .tmp)
-            if path.extension().and_then(|s| s.to_str()) == Some("json") {
-                match self.load_document_metadata(&path).await {
-                    Ok(item) => documents.push(item),
-                    Err(e) => {
-                        error!("Failed to load document metadata from {:?}: {}", path, e);
-                        continue;
-                    }
+    /// Fonts of the fonts folder and the asset store, plus system fonts once
+    /// loaded
+    pub fn fonts(&self) -> &FontRegistry {
+        &self.fonts
+    }
+
+    /// Make the fonts installed on the system available
+    pub fn load_system_fonts(&mut self) {
+        self.fonts.load_system_fonts();
+    }
+
+    /// Import a TTF/OTF file into the asset store, returning its faces
+    pub async fn import_font(&mut self, path: &Path) -> Result<Vec<FontInfo>> {
+        let data = tokio::fs::read(path).await?;
+        self.fonts.import(&data, &self.assets)
+    }
+
+    /// Add an image (file path, data URI or base64) to the asset store,
+    /// returning its `asset://` reference
+    pub async fn add_asset(&self, src: &str) -> Result<String> {
+        let uri = self.assets.add_image(src)?;
+
+        info!("Asset added as {}", uri);
+        Ok(uri)
+    }
+
+    /// Delete assets no document, trashed document, template or revision refers to
+    ///
+    /// Returns the number of deleted assets.
+    pub async fn collect_asset_garbage(&self) -> Result<usize> {
+        let mut referenced = self
+            .blocking(|store, history| {
+                let mut referenced = HashSet::new();
+                for item in store.list()? {
+                    let document = store.load(&item.id)?;
+                    assets::collect_references(
+                        &serde_json::to_string(&document)?,
+                        &mut referenced,
+                    );
+                }
+                for item in store.list_trash()? {
+                    let document = store.load_trashed(&item.document.id)?;
+                    assets::collect_references(
+                        &serde_json::to_string(&document)?,
+                        &mut referenced,
+                    );
                 }
-            }
+                history.collect_asset_references(&mut referenced)?;
+                Ok(referenced)
+            })
+            .await?;
+        for item in self.templates.list()? {
+            let template = self.templates.load(&item.id)?;
+            assets::collect_references(&serde_json::to_string(&template)?, &mut referenced);
         }
+        // Imported fonts belong to the font list, not to a document
+        referenced.extend(
+            self.fonts
+                .list()
+                .into_iter()
+                .filter_map(|font| font.asset)
+                .filter_map(|uri| assets::asset_hash(&uri).map(str::to_string)),
+        );
+
+        let deleted = self.assets.collect_garbage(&referenced, ASSET_GRACE_PERIOD)?;
+        Ok(deleted.len())
+    }
 
-        // Sort by updated_at (newest first)
-        documents.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+    /// Check if a document exists
+    pub async fn document_exists(&self, document_id: &str) -> bool {
+        let id = document_id.to_string();
+        self.blocking(move |store, _| store.exists(&id))
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to check document {}: {}", document_id, e);
+                false
+            })
+    }
 
-        info!("Found {} documents", documents.len());
-        Ok(documents)
+    /// Revisions of a document, newest first
+    pub async fn list_revisions(&self, document_id: &str) -> Result<Vec<RevisionInfo>> {
+        let id = document_id.to_string();
+        self.blocking(move |_, history| history.list(&id)).await
     }
 
-    /// Load just the metadata of a document (faster than loading full document)
-    async fn load_document_metadata(&self, path: &Path) -> Result<DocumentListItem> {
-        let mut file = tokio::fs::File::open(path).await?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).await?;
+    /// Load a document as it was at a revision
+    pub async fn load_revision(&self, document_id: &str, revision_id: i64) -> Result<Document> {
+        let id = document_id.to_string();
+        self.blocking(move |_, history| history.load(&id, revision_id)).await
+    }
 
-        let document: Document = serde_json::from_str(&contents)?;
-        Ok(DocumentListItem::from(&document))
+    /// Load a revision, or the current document when `revision_id` is `None`
+    pub async fn load_version(
+        &self,
+        document_id: &str,
+        revision_id: Option<i64>,
+    ) -> Result<Document> {
+        match revision_id {
+            Some(revision_id) => self.load_revision(document_id, revision_id).await,
+            None => self.load_document(document_id).await,
+        }
     }
 
-    /// Delete a document
-    pub async fn delete_document(&self, document_id: &str) -> Result<()> {
-        let path = self.get_document_path(document_id);
+    /// Make a revision the current version, recorded as a new revision
+    pub async fn restore_revision(&self, document_id: &str, revision_id: i64) -> Result<Document> {
+        let mut document = self.load_revision(document_id, revision_id).await?;
+        document.touch();
 
-        if !path.exists() {
-            return Err(AppError::DocumentNotFound(document_id.to_string()));
-        }
+        let message = format!("Restored revision {}", revision_id);
+        self.save_document_with_message(&document, Some(&message)).await?;
 
-        debug!("Deleting document {} at {:?}", document_id, path);
-        tokio::fs::remove_file(&path).await?;
+        info!("Document {} restored to revision {}", document_id, revision_id);
+        Ok(document)
+    }
+
+    /// Full-text search over titles, descriptions, tags and block content
+    pub async fn search_documents(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let text = query.to_string();
+        let hits = self
+            .blocking(move |store, _| store.search(&text, limit))
+            .await?;
+
+        info!("Search for {:?} found {} hits", query, hits.len());
+        Ok(hits)
+    }
+
+    /// Rebuild the search index from the stored documents
+    pub async fn rebuild_search_index(&self) -> Result<usize> {
+        self.blocking(|store, _| store.rebuild_search_index()).await
+    }
+
+    /// Save a copy of a document in the template library
+    pub async fn save_as_template(
+        &self,
+        document_id: &str,
+        options: TemplateOptions,
+    ) -> Result<Template> {
+        let document = self.load_document(document_id).await?;
+        let template = TemplateLibrary::create(&document, options);
+        self.templates.save(&template)?;
 
-        info!("Document {} deleted successfully", document_id);
+        info!("Document {} saved as template {}", document_id, template.id);
+        Ok(template)
+    }
+
+    /// List the templates by category and name
+    pub async fn list_templates(&self) -> Result<Vec<TemplateListItem>> {
+        let templates = self.templates.list()?;
+
+        info!("Found {} templates", templates.len());
+        Ok(templates)
+    }
+
+    /// Load a template with its document
+    pub async fn load_template(&self, template_id: &str) -> Result<Template> {
+        self.templates.load(template_id)
+    }
+
+    /// Delete a template
+    pub async fn delete_template(&self, template_id: &str) -> Result<()> {
+        self.templates.delete(template_id)?;
+
+        info!("Template {} deleted successfully", template_id);
         Ok(())
     }
 
-    /// Check if a document exists
-    pub fn document_exists(&self, document_id: &str) -> bool {
-        self.get_document_path(document_id).exists()
+    /// Create and save a new document from a template
+    pub async fn create_document_from_template(
+        &self,
+        template_id: &str,
+        title: Option<String>,
+    ) -> Result<Document> {
+        let template = self.templates.load(template_id)?;
+        let document = template_library::instantiate(&template, title);
+        let message = format!("Created from template {}", template.name);
+        self.save_document_with_message(&document, Some(&message)).await?;
+
+        info!("Document {} created from template {}", document.id, template_id);
+        Ok(document)
+    }
+
+    /// Export a template to a bundle file with the assets it references
+    pub async fn export_template(&self, template_id: &str, export_path: &Path) -> Result<()> {
+        self.templates.export(template_id, export_path, &self.assets, &self.fonts)
+    }
+
+    /// Import a template bundle under a new id
+    ///
+    /// Bundled assets are added to the asset store and bundled fonts become
+    /// available for rendering.
+    pub async fn import_template(&mut self, import_path: &Path) -> Result<Template> {
+        let template = self.templates.import(import_path, &self.assets)?;
+        self.fonts.load_assets(&self.assets)?;
+        Ok(template)
     }
 
     /// Export a document to a specific path
+    ///
+    /// A `.sdoc` path gets a bundle with the referenced images embedded,
+    /// anything else bare JSON.
     pub async fn export_document(&self, document_id: &str, export_path: &Path) -> Result<()> {
         let document = self.load_document(document_id).await?;
+        if export_path.extension().and_then(|e| e.to_str()) == Some(BUNDLE_EXTENSION) {
+            bundle::write_bundle(&document, export_path, &self.assets, &self.fonts)?;
+            info!("Document {} exported to bundle {:?}", document_id, export_path);
+            return Ok(());
+        }
+
         let json = serde_json::to_string_pretty(&document)?;
 
         let mut file = tokio::fs::File::create(export_path).await?;
@@ -150,14 +449,23 @@ impl StorageService {
         Ok(())
     }
 
-    /// Import a document from a file
-    pub async fn import_document(&self, import_path: &Path) -> Result<Document> {
-        let mut file = tokio::fs::File::open(import_path).await?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).await?;
+    /// Import a document from a JSON file or a bundle
+    ///
+    /// Bundled assets are added to the asset store and relinked; bundled
+    /// fonts become available for rendering.
+    pub async fn import_document(&mut self, import_path: &Path) -> Result<Document> {
+        let mut document = if bundle::is_bundle(import_path)? {
+            let document = bundle::read_bundle(import_path, &self.assets)?;
+            self.fonts.load_assets(&self.assets)?;
+            document
+        } else {
+            let mut file = tokio::fs::File::open(import_path).await?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).await?;
+
+            migration::parse_document(&contents)?
+        };
 
-        let mut document: Document = serde_json::from_str(&contents)?;
-        
         // Generate new ID to avoid conflicts
         document.id = uuid::Uuid::new_v4().to_string();
         document.touch();
@@ -170,9 +478,43 @@ impl StorageService {
     }
 }
 
+/// Purge documents trashed more than `days` ago together with their revisions
+fn purge_expired(
+    store: &dyn DocumentStore,
+    history: &RevisionStore,
+    days: Option<u32>,
+) -> Result<usize> {
+    let Some(days) = days else {
+        return Ok(0);
+    };
+
+    let cutoff = Utc::now() - Duration::days(days as i64);
+    let purged = store.purge_trashed_before(cutoff)?;
+    for document_id in &purged {
+        history.forget(document_id)?;
+    }
+
+    if !purged.is_empty() {
+        info!("Purged {} documents from the trash", purged.len());
+    }
+    Ok(purged.len())
+}
+
+/// Author recorded with a revision: the document author or the OS user
+fn revision_author(document: &Document) -> Option<String> {
+    document
+        .metadata
+        .author
+        .clone()
+        .filter(|author| !author.is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::{Block, BlockContent, BlockType};
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -185,7 +527,7 @@ mod tests {
 
         // Save
         storage.save_document(&doc).await.unwrap();
-        assert!(storage.document_exists(&doc_id));
+        assert!(storage.document_exists(&doc_id).await);
 
         // Load
         let loaded = storage.load_document(&doc_id).await.unwrap();
@@ -193,6 +535,59 @@ mod tests {
         assert_eq!(loaded.metadata.title, "Test Document");
     }
 
+    #[tokio::test]
+    async fn test_save_rejects_invalid_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = StorageService::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut doc = Document::new("Shapes".to_string());
+        let mut line = Block::for_test(BlockType::Line, 0.0, 0.0, 100.0, 10.0);
+        if let BlockContent::Line(content) = &mut line.content {
+            content.end = content.start.clone();
+        }
+        doc.add_block(line);
+
+        assert!(matches!(
+            storage.save_document(&doc).await,
+            Err(AppError::ValidationError(_))
+        ));
+        assert!(!storage.document_exists(&doc.id).await);
+
+        doc.blocks.clear();
+        doc.add_block(Block::for_test(BlockType::Image, 0.0, 0.0, 50.0, 50.0));
+        storage.save_document(&doc).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_upgrades_old_schema_with_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = StorageService::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let doc = Document::new("Legacy".to_string());
+        let mut legacy = serde_json::to_value(&doc).unwrap();
+        legacy.as_object_mut().unwrap().remove("schemaVersion");
+        legacy["version"] = serde_json::json!("1.0.0");
+        let path = temp_dir.path().join(format!("{}.json", doc.id));
+        fs::write(&path, legacy.to_string()).unwrap();
+
+        let loaded = storage.load_document(&doc.id).await.unwrap();
+        assert_eq!(loaded.schema_version, crate::models::SCHEMA_VERSION);
+        assert!(fs::read_to_string(&path).unwrap().contains("schemaVersion"));
+        let backup = temp_dir
+            .path()
+            .join("backups")
+            .join(format!("{}.v1.json", doc.id));
+        assert!(fs::read_to_string(backup).unwrap().contains("1.0.0"));
+
+        let mut newer = serde_json::to_value(&doc).unwrap();
+        newer["schemaVersion"] = serde_json::json!(crate::models::SCHEMA_VERSION + 1);
+        fs::write(&path, newer.to_string()).unwrap();
+        assert!(matches!(
+            storage.load_document(&doc.id).await,
+            Err(AppError::UnsupportedSchemaVersion(_))
+        ));
+    }
+
     #[tokio::test]
     async fn test_delete_document() {
         let temp_dir = TempDir::new().unwrap();
@@ -202,10 +597,135 @@ mod tests {
         let doc_id = doc.id.clone();
 
         storage.save_document(&doc).await.unwrap();
-        assert!(storage.document_exists(&doc_id));
+        assert!(storage.document_exists(&doc_id).await);
 
         storage.delete_document(&doc_id).await.unwrap();
-        assert!(!storage.document_exists(&doc_id));
+        assert!(!storage.document_exists(&doc_id).await);
+
+        let trash = storage.list_trash().await.unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].document.title, "Test Document");
+
+        let restored = storage.restore_document(&doc_id).await.unwrap();
+        assert_eq!(restored.metadata.title, "Test Document");
+        assert!(storage.document_exists(&doc_id).await);
+        assert!(storage.list_trash().await.unwrap().is_empty());
+
+        storage.delete_document(&doc_id).await.unwrap();
+        storage.purge_document(&doc_id).await.unwrap();
+        assert!(storage.list_trash().await.unwrap().is_empty());
+        assert!(storage.list_revisions(&doc_id).await.unwrap().is_empty());
+        assert!(storage.restore_document(&doc_id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_expired_trash_is_purged() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = StorageService::open_database(temp_dir.path().to_path_buf()).unwrap();
+
+        let doc = Document::new("Test Document".to_string());
+        storage.save_document(&doc).await.unwrap();
+        storage.delete_document(&doc.id).await.unwrap();
+        assert_eq!(storage.purge_expired_trash().unwrap(), 0);
+
+        storage.set_trash_retention(None);
+        assert_eq!(storage.purge_expired_trash().unwrap(), 0);
+        assert_eq!(storage.list_trash().await.unwrap().len(), 1);
+
+        storage.set_trash_retention(Some(0));
+        assert_eq!(storage.purge_expired_trash().unwrap(), 1);
+        assert!(storage.list_trash().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_restore_revision() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = StorageService::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut doc = Document::new("First Title".to_string());
+        doc.metadata.author = Some("Anna".to_string());
+        storage.save_document(&doc).await.unwrap();
+        doc.metadata.title = "Second Title".to_string();
+        storage
+            .save_document_with_message(&doc, Some("Renamed"))
+            .await
+            .unwrap();
+
+        let revisions = storage.list_revisions(&doc.id).await.unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].message.as_deref(), Some("Renamed"));
+        assert_eq!(revisions[1].author.as_deref(), Some("Anna"));
+
+        let restored = storage
+            .restore_revision(&doc.id, revisions[1].id)
+            .await
+            .unwrap();
+        assert_eq!(restored.metadata.title, "First Title");
+        let current = storage.load_document(&doc.id).await.unwrap();
+        assert_eq!(current.metadata.title, "First Title");
+        assert_eq!(storage.list_revisions(&doc.id).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_create_document_from_template() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = StorageService::open_database(temp_dir.path().to_path_buf()).unwrap();
+        let doc = Document::new("Offer".to_string());
+        storage.save_document(&doc).await.unwrap();
+
+        let options = TemplateOptions {
+            name: "Offer template".to_string(),
+            ..Default::default()
+        };
+        let template = storage.save_as_template(&doc.id, options).await.unwrap();
+        assert_eq!(storage.list_templates().await.unwrap().len(), 1);
+        // Templates are not documents
+        assert_eq!(storage.list_documents().await.unwrap().len(), 1);
+
+        let created = storage
+            .create_document_from_template(&template.id, None)
+            .await
+            .unwrap();
+        assert_ne!(created.id, doc.id);
+        assert_eq!(created.metadata.title, "Offer template");
+        assert_eq!(storage.list_documents().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = StorageService::new(temp_dir.path().join("library")).unwrap();
+        let doc = Document::new("Proposal".to_string());
+        storage.save_document(&doc).await.unwrap();
+
+        let bundle_path = temp_dir.path().join("proposal.sdoc");
+        storage.export_document(&doc.id, &bundle_path).await.unwrap();
+        let json_path = temp_dir.path().join("proposal.json");
+        storage.export_document(&doc.id, &json_path).await.unwrap();
+
+        for path in [bundle_path, json_path] {
+            let imported = storage.import_document(&path).await.unwrap();
+            assert_ne!(imported.id, doc.id);
+            assert_eq!(imported.metadata.title, "Proposal");
+        }
+        assert_eq!(storage.list_documents().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_open_database_imports_json_documents() {
+        let temp_dir = TempDir::new().unwrap();
+        let doc = Document::new("Legacy Document".to_string());
+        StorageService::new(temp_dir.path().to_path_buf())
+            .unwrap()
+            .save_document(&doc)
+            .await
+            .unwrap();
+
+        let storage = StorageService::open_database(temp_dir.path().to_path_buf()).unwrap();
+        let items = storage.list_documents().await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].title, "Legacy Document");
+        assert!(temp_dir.path().join(DATABASE_FILE).exists());
     }
 }
 
@@ -0,0 +1,277 @@
+use super::DocumentStore;
+use crate::models::{AppError, Document, DocumentListItem, Result, TrashItem, SCHEMA_VERSION};
+use crate::services::migration;
+use crate::services::search::{self, SearchHit};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Subdirectory holding trashed documents
+const TRASH_DIR: &str = "trash";
+
+/// Subdirectory keeping the original files of migrated documents
+const BACKUP_DIR: &str = "backups";
+
+/// Stores every document as `<id>.json` in a directory
+///
+/// Trashed documents move to `trash/<id>.json`, wrapped with their deletion
+/// time. Files of an older schema are upgraded when loaded, keeping the
+/// original as `backups/<id>.v<version>.json`.
+pub struct JsonStore {
+    dir: PathBuf,
+}
+
+/// File format of a trashed document
+#[derive(Serialize, Deserialize)]
+struct TrashedDocument {
+    #[serde(rename = "deletedAt")]
+    deleted_at: DateTime<Utc>,
+    document: Document,
+}
+
+impl JsonStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Get the path to a document file
+    fn document_path(&self, document_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", document_id))
+    }
+
+    fn trash_path(&self, document_id: &str) -> PathBuf {
+        self.dir
+            .join(TRASH_DIR)
+            .join(format!("{}.json", document_id))
+    }
+
+    /// Atomic write: temp file + rename
+    fn write_file(path: &Path, json: &str) -> Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    /// Read a document file of any supported schema version
+    pub fn read_file(path: &Path) -> Result<Document> {
+        let contents = fs::read_to_string(path)?;
+        migration::parse_document(&contents)
+    }
+
+    /// Read a document file, rewriting it in the current schema if it is older
+    fn read_and_upgrade(&self, document_id: &str, path: &Path) -> Result<Document> {
+        let contents = fs::read_to_string(path)?;
+        let mut value: Value = serde_json::from_str(&contents)?;
+        let version = migration::migrate(&mut value)?;
+        let document = migration::document_from_value(value)?;
+
+        if version < SCHEMA_VERSION {
+            let backup_dir = self.dir.join(BACKUP_DIR);
+            fs::create_dir_all(&backup_dir)?;
+            let backup = backup_dir.join(format!("{}.v{}.json", document_id, version));
+            if !backup.exists() {
+                fs::write(&backup, &contents)?;
+            }
+            Self::write_file(path, &serde_json::to_string_pretty(&document)?)?;
+            info!(
+                "Upgraded document {} from schema {}, backup at {:?}",
+                document_id, version, backup
+            );
+        }
+        Ok(document)
+    }
+
+    /// All readable documents; broken files are logged and skipped
+    fn read_all(&self) -> Result<Vec<Document>> {
+        let mut documents = Vec::new();
+        for path in Self::document_files(&self.dir)? {
+            match Self::read_file(&path) {
+                Ok(document) => documents.push(document),
+                Err(e) => error!("Failed to load document from {:?}: {}", path, e),
+            }
+        }
+        Ok(documents)
+    }
+
+    fn read_trash_file(path: &Path) -> Result<TrashedDocument> {
+        let contents = fs::read_to_string(path)?;
+        let mut value: Value = serde_json::from_str(&contents)?;
+        if let Some(document) = value.get_mut("document") {
+            migration::migrate(document)?;
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// All readable trashed documents; broken files are logged and skipped
+    fn read_trash(&self) -> Result<Vec<TrashedDocument>> {
+        let dir = self.dir.join(TRASH_DIR);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut trashed = Vec::new();
+        for path in Self::document_files(&dir)? {
+            match Self::read_trash_file(&path) {
+                Ok(document) => trashed.push(document),
+                Err(e) => error!("Failed to load trashed document from {:?}: {}", path, e),
+            }
+        }
+        Ok(trashed)
+    }
+
+    /// Paths of all document files in the directory (not `.tmp` files)
+    pub fn document_files(dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+impl DocumentStore for JsonStore {
+    fn save(&self, document: &Document) -> Result<()> {
+        let path = self.document_path(&document.id);
+
+        debug!("Saving document {} to {:?}", document.id, path);
+
+        let json = serde_json::to_string_pretty(document)?;
+        Self::write_file(&path, &json)
+    }
+
+    fn load(&self, document_id: &str) -> Result<Document> {
+        let path = self.document_path(document_id);
+        if !path.exists() {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+
+        debug!("Loading document {} from {:?}", document_id, path);
+        self.read_and_upgrade(document_id, &path)
+    }
+
+    /// Opens and deserializes every file; use `SqliteStore` for large libraries
+    fn list(&self) -> Result<Vec<DocumentListItem>> {
+        debug!("Listing documents in {:?}", self.dir);
+
+        let mut documents: Vec<DocumentListItem> = self
+            .read_all()?
+            .iter()
+            .map(DocumentListItem::from)
+            .collect();
+
+        documents.sort_by_key(|d| std::cmp::Reverse(d.updated_at));
+        Ok(documents)
+    }
+
+    fn trash(&self, document_id: &str) -> Result<()> {
+        let path = self.document_path(document_id);
+        if !path.exists() {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+
+        debug!("Moving document {} at {:?} to the trash", document_id, path);
+        let trashed = TrashedDocument {
+            deleted_at: Utc::now(),
+            document: Self::read_file(&path)?,
+        };
+        fs::create_dir_all(self.dir.join(TRASH_DIR))?;
+        let json = serde_json::to_string_pretty(&trashed)?;
+        Self::write_file(&self.trash_path(document_id), &json)?;
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        let mut items: Vec<TrashItem> = self
+            .read_trash()?
+            .iter()
+            .map(|trashed| TrashItem {
+                document: DocumentListItem::from(&trashed.document),
+                deleted_at: trashed.deleted_at,
+            })
+            .collect();
+
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+        Ok(items)
+    }
+
+    fn load_trashed(&self, document_id: &str) -> Result<Document> {
+        let trash_path = self.trash_path(document_id);
+        if !trash_path.exists() {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+
+        let mut document = Self::read_trash_file(&trash_path)?.document;
+        document.assign_orphan_blocks();
+        Ok(document)
+    }
+
+    fn restore(&self, document_id: &str) -> Result<Document> {
+        let trash_path = self.trash_path(document_id);
+        if !trash_path.exists() {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+        if self.exists(document_id)? {
+            return Err(AppError::InvalidData(format!(
+                "Document {} already exists",
+                document_id
+            )));
+        }
+
+        debug!("Restoring document {} from the trash", document_id);
+        let mut document = Self::read_trash_file(&trash_path)?.document;
+        document.assign_orphan_blocks();
+        self.save(&document)?;
+        fs::remove_file(&trash_path)?;
+        Ok(document)
+    }
+
+    fn purge(&self, document_id: &str) -> Result<()> {
+        let path = self.trash_path(document_id);
+        if !path.exists() {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+
+        debug!("Purging document {} at {:?}", document_id, path);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    fn purge_trashed_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+        let mut purged = Vec::new();
+        for trashed in self.read_trash()? {
+            if trashed.deleted_at < cutoff {
+                self.purge(&trashed.document.id)?;
+                purged.push(trashed.document.id);
+            }
+        }
+        Ok(purged)
+    }
+
+    fn exists(&self, document_id: &str) -> Result<bool> {
+        Ok(self.document_path(document_id).exists())
+    }
+
+    /// Scans every document, there is no index
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        Ok(search::scan(&self.read_all()?, query, limit))
+    }
+
+    /// Nothing to rebuild; returns the number of readable documents
+    fn rebuild_search_index(&self) -> Result<usize> {
+        Ok(self.read_all()?.len())
+    }
+}
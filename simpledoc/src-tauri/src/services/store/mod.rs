@@ -0,0 +1,57 @@
+pub mod json;
+pub mod sqlite;
+
+pub use json::JsonStore;
+pub use sqlite::SqliteStore;
+
+use crate::models::{Document, DocumentListItem, Result, TrashItem};
+use crate::services::search::SearchHit;
+use chrono::{DateTime, Utc};
+
+/// Persistence backend for documents
+///
+/// Implementations only store and index documents; validation and
+/// import/export stay in `StorageService`.
+pub trait DocumentStore: Send + Sync {
+    /// Insert or replace a document
+    fn save(&self, document: &Document) -> Result<()>;
+
+    /// Load a document, failing with `DocumentNotFound` if it is missing
+    fn load(&self, document_id: &str) -> Result<Document>;
+
+    /// List all documents, newest first
+    fn list(&self) -> Result<Vec<DocumentListItem>>;
+
+    /// Move a document to the trash, failing with `DocumentNotFound` if it is missing
+    fn trash(&self, document_id: &str) -> Result<()>;
+
+    /// Documents in the trash, most recently deleted first
+    fn list_trash(&self) -> Result<Vec<TrashItem>>;
+
+    /// Load a document in the trash without restoring it
+    ///
+    /// Fails with `DocumentNotFound` if it is not in the trash.
+    fn load_trashed(&self, document_id: &str) -> Result<Document>;
+
+    /// Move a document out of the trash
+    ///
+    /// Fails with `DocumentNotFound` if it is not in the trash.
+    fn restore(&self, document_id: &str) -> Result<Document>;
+
+    /// Delete a document in the trash for good
+    fn purge(&self, document_id: &str) -> Result<()>;
+
+    /// Delete everything trashed before `cutoff` for good, returning the ids
+    fn purge_trashed_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>>;
+
+    /// Check if a document exists
+    fn exists(&self, document_id: &str) -> Result<bool>;
+
+    /// Full-text search over metadata and block content, best match first
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>>;
+
+    /// Rebuild the search index from the stored documents
+    ///
+    /// Returns the number of indexed documents.
+    fn rebuild_search_index(&self) -> Result<usize>;
+}
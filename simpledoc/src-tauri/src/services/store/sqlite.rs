@@ -0,0 +1,655 @@
+use super::json::JsonStore;
+use super::DocumentStore;
+use crate::models::{AppError, Document, DocumentListItem, Result, TrashItem, SCHEMA_VERSION};
+use crate::services::migration;
+use crate::services::search::{self, SearchField, SearchHit, MATCH_END, MATCH_START};
+use chrono::{DateTime, Utc};
+use log::{debug, error, info};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// Bumped whenever `SCHEMA` changes
+const DATABASE_VERSION: i32 = 4;
+
+/// First schema version with `search_index`
+const SEARCH_INDEX_VERSION: i32 = 2;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS documents (
+        id TEXT PRIMARY KEY,
+        title TEXT NOT NULL,
+        description TEXT,
+        author TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL,
+        block_count INTEGER NOT NULL,
+        page_count INTEGER NOT NULL,
+        content TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_documents_updated_at ON documents (updated_at DESC);
+    CREATE INDEX IF NOT EXISTS idx_documents_title ON documents (title COLLATE NOCASE);
+    CREATE INDEX IF NOT EXISTS idx_documents_author ON documents (author);
+
+    CREATE TABLE IF NOT EXISTS document_tags (
+        document_id TEXT NOT NULL REFERENCES documents (id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (document_id, tag)
+    );
+    CREATE INDEX IF NOT EXISTS idx_document_tags_tag ON document_tags (tag);
+
+    CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5 (
+        document_id UNINDEXED,
+        block_id UNINDEXED,
+        field UNINDEXED,
+        content,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+
+    CREATE TABLE IF NOT EXISTS trash (
+        id TEXT PRIMARY KEY,
+        deleted_at TEXT NOT NULL,
+        content TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_trash_deleted_at ON trash (deleted_at DESC);
+
+    CREATE TABLE IF NOT EXISTS document_backups (
+        id TEXT NOT NULL,
+        schema_version INTEGER NOT NULL,
+        backed_up_at TEXT NOT NULL,
+        content TEXT NOT NULL,
+        PRIMARY KEY (id, schema_version)
+    );
+
+    CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// Settings key marking the one-shot import of `*.json` documents as done
+const JSON_IMPORT_KEY: &str = "json_import_done";
+
+/// Stores documents in a SQLite database with an index of their metadata
+///
+/// Listing reads only the indexed columns, never the document content.
+/// Trashed documents move to the `trash` table and out of the search index.
+/// Documents of an older schema are upgraded when loaded, keeping the
+/// original in `document_backups`.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open or create the database at `path`
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > DATABASE_VERSION {
+            return Err(AppError::InvalidData(format!(
+                "Database schema version {} is newer than supported version {}",
+                version, DATABASE_VERSION
+            )));
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", DATABASE_VERSION)?;
+
+        let store = Self {
+            conn: Mutex::new(conn),
+        };
+        if version > 0 && version < SEARCH_INDEX_VERSION {
+            store.rebuild_search_index()?;
+        }
+        Ok(store)
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        // A panic mid-query cannot leave SQLite itself inconsistent
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Import the `*.json` documents of a directory, once per database
+    ///
+    /// Documents that are already stored are kept and unreadable files are
+    /// skipped. The files themselves are left in place as a backup. Returns
+    /// the number of imported documents.
+    pub fn import_json_dir(&self, dir: &Path) -> Result<usize> {
+        let done = self
+            .conn()
+            .query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                [JSON_IMPORT_KEY],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?
+            .is_some();
+        if done {
+            return Ok(0);
+        }
+
+        let mut imported = 0;
+        if dir.exists() {
+            for path in JsonStore::document_files(dir)? {
+                let document = match JsonStore::read_file(&path) {
+                    Ok(document) => document,
+                    Err(e) => {
+                        error!("Skipping {:?} during import: {}", path, e);
+                        continue;
+                    }
+                };
+                if !self.exists(&document.id)? {
+                    self.save(&document)?;
+                    imported += 1;
+                }
+            }
+        }
+
+        self.conn().execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![JSON_IMPORT_KEY, chrono::Utc::now().to_rfc3339()],
+        )?;
+
+        info!("Imported {} JSON documents from {:?}", imported, dir);
+        Ok(imported)
+    }
+}
+
+impl DocumentStore for SqliteStore {
+    fn save(&self, document: &Document) -> Result<()> {
+        debug!("Saving document {} to database", document.id);
+
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        write_document(&tx, document)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn load(&self, document_id: &str) -> Result<Document> {
+        debug!("Loading document {} from database", document_id);
+
+        let content: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT content FROM documents WHERE id = ?1",
+                [document_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let content = content.ok_or_else(|| AppError::DocumentNotFound(document_id.to_string()))?;
+
+        let mut value: Value = serde_json::from_str(&content)?;
+        let version = migration::migrate(&mut value)?;
+        let document = migration::document_from_value(value)?;
+
+        if version < SCHEMA_VERSION {
+            let conn = self.conn();
+            let tx = conn.unchecked_transaction()?;
+            tx.execute(
+                "INSERT OR IGNORE INTO document_backups (id, schema_version, backed_up_at, content)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![document_id, version, Utc::now(), content],
+            )?;
+            write_document(&tx, &document)?;
+            tx.commit()?;
+            info!(
+                "Upgraded document {} from schema {}, original kept in document_backups",
+                document_id, version
+            );
+        }
+        Ok(document)
+    }
+
+    fn list(&self) -> Result<Vec<DocumentListItem>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT d.id, d.title, d.description, d.author, d.created_at, d.updated_at,
+                    d.block_count, d.page_count,
+                    (SELECT json_group_array(tag) FROM document_tags WHERE document_id = d.id)
+             FROM documents d
+             ORDER BY d.updated_at DESC",
+        )?;
+
+        let rows = statement.query_map([], |row| {
+            let tags: String = row.get(8)?;
+            Ok(DocumentListItem {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                description: row.get(2)?,
+                author: row.get(3)?,
+                tags: serde_json::from_str(&tags).unwrap_or_default(),
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+                block_count: row.get::<_, i64>(6)? as usize,
+                page_count: row.get::<_, i64>(7)? as usize,
+            })
+        })?;
+
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn trash(&self, document_id: &str) -> Result<()> {
+        debug!("Moving document {} to the trash", document_id);
+
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        let trashed = tx.execute(
+            "INSERT OR REPLACE INTO trash (id, deleted_at, content)
+             SELECT id, ?2, content FROM documents WHERE id = ?1",
+            params![document_id, Utc::now()],
+        )?;
+        if trashed == 0 {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+        tx.execute("DELETE FROM documents WHERE id = ?1", [document_id])?;
+        tx.execute(
+            "DELETE FROM search_index WHERE document_id = ?1",
+            [document_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Reads the content of every trashed document
+    fn list_trash(&self) -> Result<Vec<TrashItem>> {
+        let conn = self.conn();
+        let mut statement =
+            conn.prepare("SELECT deleted_at, content FROM trash ORDER BY deleted_at DESC")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, DateTime<Utc>>(0)?, row.get::<_, String>(1)?))
+        })?;
+
+        let mut items = Vec::new();
+        for row in rows {
+            let (deleted_at, content) = row?;
+            let document = migration::parse_document(&content)?;
+            items.push(TrashItem {
+                document: DocumentListItem::from(&document),
+                deleted_at,
+            });
+        }
+        Ok(items)
+    }
+
+    fn load_trashed(&self, document_id: &str) -> Result<Document> {
+        let content: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT content FROM trash WHERE id = ?1",
+                [document_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let content = content.ok_or_else(|| AppError::DocumentNotFound(document_id.to_string()))?;
+
+        let document = migration::parse_document(&content)?;
+        Ok(document)
+    }
+
+    fn restore(&self, document_id: &str) -> Result<Document> {
+        debug!("Restoring document {} from the trash", document_id);
+
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        let content: Option<String> = tx
+            .query_row(
+                "SELECT content FROM trash WHERE id = ?1",
+                [document_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let content = content.ok_or_else(|| AppError::DocumentNotFound(document_id.to_string()))?;
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM documents WHERE id = ?1)",
+            [document_id],
+            |row| row.get(0),
+        )?;
+        if exists {
+            return Err(AppError::InvalidData(format!(
+                "Document {} already exists",
+                document_id
+            )));
+        }
+
+        let document = migration::parse_document(&content)?;
+        write_document(&tx, &document)?;
+        tx.execute("DELETE FROM trash WHERE id = ?1", [document_id])?;
+        tx.commit()?;
+        Ok(document)
+    }
+
+    fn purge(&self, document_id: &str) -> Result<()> {
+        debug!("Purging document {} from the trash", document_id);
+
+        let purged = self
+            .conn()
+            .execute("DELETE FROM trash WHERE id = ?1", [document_id])?;
+        if purged == 0 {
+            return Err(AppError::DocumentNotFound(document_id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn purge_trashed_before(&self, cutoff: DateTime<Utc>) -> Result<Vec<String>> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        let ids = {
+            let mut statement = tx.prepare("SELECT id FROM trash WHERE deleted_at < ?1")?;
+            let rows = statement.query_map([cutoff], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<Vec<String>>>()?
+        };
+        tx.execute("DELETE FROM trash WHERE deleted_at < ?1", [cutoff])?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    fn exists(&self, document_id: &str) -> Result<bool> {
+        let exists = self.conn().query_row(
+            "SELECT EXISTS (SELECT 1 FROM documents WHERE id = ?1)",
+            [document_id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Ranked with BM25, scaled by `SearchField::weight`
+    fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        let terms = search::query_terms(query);
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let weights = [
+            SearchField::Title,
+            SearchField::Description,
+            SearchField::Tags,
+            SearchField::Block,
+        ]
+        .iter()
+        .map(|field| format!("WHEN '{}' THEN {:.1}", field.as_str(), field.weight()))
+        .collect::<Vec<_>>()
+        .join(" ");
+        let sql = format!(
+            "SELECT search_index.document_id, d.title, search_index.field, search_index.block_id,
+                    snippet(search_index, 3, ?2, ?3, '…', 12),
+                    bm25(search_index) * (CASE search_index.field {} ELSE 1.0 END) AS rank
+             FROM search_index
+             JOIN documents d ON d.id = search_index.document_id
+             WHERE search_index MATCH ?1
+             ORDER BY rank
+             LIMIT ?4",
+            weights
+        );
+
+        let conn = self.conn();
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement.query_map(
+            params![
+                search::fts_query(&terms),
+                MATCH_START,
+                MATCH_END,
+                limit as i64
+            ],
+            |row| {
+                let field: String = row.get(2)?;
+                let rank: f64 = row.get(5)?;
+                Ok(SearchHit {
+                    document_id: row.get(0)?,
+                    title: row.get(1)?,
+                    field: SearchField::parse(&field).unwrap_or(SearchField::Block),
+                    block_id: row.get(3)?,
+                    snippet: row.get(4)?,
+                    // BM25 is negative, lower is better
+                    score: -rank,
+                })
+            },
+        )?;
+
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    fn rebuild_search_index(&self) -> Result<usize> {
+        let conn = self.conn();
+        let tx = conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM search_index", [])?;
+
+        let mut indexed = 0;
+        {
+            let mut statement = tx.prepare("SELECT id, content FROM documents")?;
+            let mut rows = statement.query([])?;
+            while let Some(row) = rows.next()? {
+                let id: String = row.get(0)?;
+                let content: String = row.get(1)?;
+                match migration::parse_document(&content) {
+                    Ok(document) => {
+                        index_document(&tx, &document)?;
+                        indexed += 1;
+                    }
+                    Err(e) => error!("Skipping document {} in search index: {}", id, e),
+                }
+            }
+        }
+
+        tx.commit()?;
+        info!("Rebuilt search index for {} documents", indexed);
+        Ok(indexed)
+    }
+}
+
+/// Insert or replace a document with its tags and search index rows
+fn write_document(conn: &Connection, document: &Document) -> Result<()> {
+    let content = serde_json::to_string(document)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO documents
+            (id, title, description, author, created_at, updated_at,
+             block_count, page_count, content)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            document.id,
+            document.metadata.title,
+            document.metadata.description,
+            document.metadata.author,
+            document.metadata.created_at,
+            document.metadata.updated_at,
+            document.blocks.len() as i64,
+            document.pages.len() as i64,
+            content,
+        ],
+    )?;
+
+    conn.execute(
+        "DELETE FROM document_tags WHERE document_id = ?1",
+        [&document.id],
+    )?;
+    for tag in document.metadata.tags.iter().flatten() {
+        conn.execute(
+            "INSERT OR IGNORE INTO document_tags (document_id, tag) VALUES (?1, ?2)",
+            params![document.id, tag],
+        )?;
+    }
+    index_document(conn, document)
+}
+
+/// Replace the search index rows of a document
+fn index_document(conn: &Connection, document: &Document) -> Result<()> {
+    conn.execute(
+        "DELETE FROM search_index WHERE document_id = ?1",
+        [&document.id],
+    )?;
+    for entry in search::index_entries(document) {
+        conn.execute(
+            "INSERT INTO search_index (document_id, block_id, field, content)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                document.id,
+                entry.block_id,
+                entry.field.as_str(),
+                entry.content
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Block;
+    use chrono::{Duration, Utc};
+    use tempfile::TempDir;
+
+    fn open(temp_dir: &TempDir) -> SqliteStore {
+        SqliteStore::open(&temp_dir.path().join("simpledoc.db")).unwrap()
+    }
+
+    fn document(title: &str, tags: &[&str]) -> Document {
+        let mut document = Document::new(title.to_string());
+        document.metadata.author = Some("Anna".to_string());
+        document.metadata.tags = Some(tags.iter().map(|t| t.to_string()).collect());
+        document
+    }
+
+    #[test]
+    fn test_save_load_and_list() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = open(&temp_dir);
+
+        let mut older = document("Older", &["offer"]);
+        older.metadata.updated_at = Utc::now() - Duration::days(1);
+        let newer = document("Newer", &["offer", "2024"]);
+        store.save(&older).unwrap();
+        store.save(&newer).unwrap();
+
+        let loaded = store.load(&newer.id).unwrap();
+        assert_eq!(loaded.metadata.title, "Newer");
+
+        let items = store.list().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Newer");
+        assert_eq!(items[0].author.as_deref(), Some("Anna"));
+        assert_eq!(items[0].tags, vec!["2024".to_string(), "offer".to_string()]);
+        assert_eq!(items[0].page_count, 1);
+
+        // Saving again replaces the row and its tags
+        let mut renamed = loaded;
+        renamed.metadata.title = "Renamed".to_string();
+        renamed.metadata.tags = None;
+        store.save(&renamed).unwrap();
+        let items = store.list().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].title, "Renamed");
+        assert!(items[0].tags.is_empty());
+    }
+
+    #[test]
+    fn test_trash_restore_and_purge() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = open(&temp_dir);
+        let doc = document("Kubernetes offer", &["offer"]);
+        store.save(&doc).unwrap();
+
+        store.trash(&doc.id).unwrap();
+        assert!(!store.exists(&doc.id).unwrap());
+        assert!(matches!(
+            store.load(&doc.id),
+            Err(AppError::DocumentNotFound(_))
+        ));
+        assert!(matches!(
+            store.trash(&doc.id),
+            Err(AppError::DocumentNotFound(_))
+        ));
+        assert!(store.search("kubernetes", 10).unwrap().is_empty());
+
+        let trash = store.list_trash().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].document.title, "Kubernetes offer");
+        assert_eq!(trash[0].document.tags, vec!["offer".to_string()]);
+
+        let restored = store.restore(&doc.id).unwrap();
+        assert_eq!(restored.metadata.title, "Kubernetes offer");
+        assert!(store.list_trash().unwrap().is_empty());
+        assert_eq!(store.list().unwrap()[0].tags, vec!["offer".to_string()]);
+        assert_eq!(store.search("kubernetes", 10).unwrap().len(), 1);
+
+        store.trash(&doc.id).unwrap();
+        let purged = store
+            .purge_trashed_before(Utc::now() - Duration::days(1))
+            .unwrap();
+        assert!(purged.is_empty());
+        let purged = store
+            .purge_trashed_before(Utc::now() + Duration::seconds(1))
+            .unwrap();
+        assert_eq!(purged, vec![doc.id.clone()]);
+        assert!(matches!(
+            store.purge(&doc.id),
+            Err(AppError::DocumentNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = open(&temp_dir);
+
+        let mut hosting = document("Hosting offer", &[]);
+        hosting.metadata.description = Some("Managed cloud".to_string());
+        let block = Block::text_for_test("Your services run on a managed Kubernetes cluster.");
+        let block_id = block.id.clone();
+        hosting.add_block(block);
+        let migration = document("Kubernetes migration", &["devops"]);
+        store.save(&hosting).unwrap();
+        store.save(&migration).unwrap();
+        store.save(&document("Website", &[])).unwrap();
+
+        let hits = store.search("kubernetes", 10).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].document_id, migration.id);
+        assert_eq!(hits[0].field, SearchField::Title);
+        assert_eq!(hits[1].block_id.as_deref(), Some(block_id.as_str()));
+        assert!(hits[1].snippet.contains("**Kubernetes**"));
+
+        // Prefix and multi-term queries
+        assert_eq!(store.search("kube clus", 10).unwrap().len(), 1);
+        assert!(store.search("\"", 10).unwrap().is_empty());
+
+        store.trash(&migration.id).unwrap();
+        assert_eq!(store.search("kubernetes", 10).unwrap().len(), 1);
+
+        store
+            .conn()
+            .execute("DELETE FROM search_index", [])
+            .unwrap();
+        assert!(store.search("kubernetes", 10).unwrap().is_empty());
+        assert_eq!(store.rebuild_search_index().unwrap(), 2);
+        assert_eq!(
+            store.search("cloud", 10).unwrap()[0].field,
+            SearchField::Description
+        );
+    }
+
+    #[test]
+    fn test_import_json_dir_once() {
+        let temp_dir = TempDir::new().unwrap();
+        let json = JsonStore::new(temp_dir.path().to_path_buf());
+        let first = document("First", &[]);
+        json.save(&first).unwrap();
+        json.save(&document("Second", &[])).unwrap();
+        std::fs::write(temp_dir.path().join("broken.json"), "{").unwrap();
+
+        let store = open(&temp_dir);
+        assert_eq!(store.import_json_dir(temp_dir.path()).unwrap(), 2);
+        assert_eq!(store.load(&first.id).unwrap().metadata.title, "First");
+
+        // Later runs leave the database alone
+        json.save(&document("Third", &[])).unwrap();
+        assert_eq!(store.import_json_dir(temp_dir.path()).unwrap(), 0);
+        assert_eq!(store.list().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,806 @@
+use crate::models::{
+    plain_text, AppError, Block, BlockContent, Document, Locale, Result, TableRow,
+};
+use crate::services::expression::Expression;
+use crate::services::formatter::{DateStyle, Formatter};
+use crate::services::formula;
+use crate::services::repeater;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// A filter applied to a placeholder value, e.g. `date:"%d.%m.%Y"`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterCall {
+    pub name: String,
+    pub argument: Option<String>,
+}
+
+/// A parsed `{{ path.to.field | filter:arg }}` placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placeholder {
+    pub path: String,
+    pub filters: Vec<FilterCall>,
+}
+
+/// Piece of a template string
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+}
+
+/// Where a variable is used in a document
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VariableUsage {
+    pub path: String,
+    #[serde(rename = "blockIds")]
+    pub block_ids: Vec<String>,
+}
+
+/// Locale and problems collected while resolving a document
+#[derive(Debug, Default)]
+pub(crate) struct Resolution {
+    pub locale: Locale,
+    /// Unresolved variables and malformed placeholders
+    pub template_errors: Vec<String>,
+    /// Formula evaluation failures
+    pub formula_errors: Vec<String>,
+    /// Rendering without generation data: unresolved variables are kept
+    /// verbatim and repeaters without a source produce no items
+    pub without_data: bool,
+}
+
+/// Resolves `{{ placeholders }}` in block content against a JSON data context
+pub struct TemplateEngine;
+
+impl TemplateEngine {
+    /// Split a template string into literal text and placeholders
+    pub fn parse(template: &str) -> std::result::Result<Vec<Segment>, String> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                segments.push(Segment::Text(rest[..start].to_string()));
+            }
+
+            let after = &rest[start + 2..];
+            let end = after.find("}}").ok_or_else(|| {
+                format!(
+                    "Unclosed placeholder: {{{{{}",
+                    after.chars().take(20).collect::<String>()
+                )
+            })?;
+
+            segments.push(Segment::Placeholder(Self::parse_placeholder(
+                &after[..end],
+            )?));
+            rest = &after[end + 2..];
+        }
+
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+
+        Ok(segments)
+    }
+
+    /// Parse the inside of `{{ ... }}`
+    fn parse_placeholder(inner: &str) -> std::result::Result<Placeholder, String> {
+        let mut parts = split_outside_quotes(inner, '|').into_iter();
+        let path = parts.next().unwrap_or_default().trim().to_string();
+
+        if path.is_empty() {
+            return Err("Empty placeholder".to_string());
+        }
+        if !is_valid_path(&path) {
+            return Err(format!("Invalid variable name: {}", path));
+        }
+
+        let filters = parts
+            .map(|part| {
+                let (name, argument) = match part.split_once(':') {
+                    Some((name, argument)) => (name, Some(unquote(argument.trim()))),
+                    None => (part.as_str(), None),
+                };
+                let name = name.trim().to_string();
+                if name.is_empty() {
+                    return Err(format!("Empty filter in placeholder: {}", inner.trim()));
+                }
+                Ok(FilterCall { name, argument })
+            })
+            .collect::<std::result::Result<_, String>>()?;
+
+        Ok(Placeholder { path, filters })
+    }
+
+    /// Render a template string against the data context
+    ///
+    /// Every variable that cannot be resolved is returned as an error.
+    pub fn render(
+        template: &str,
+        data: &Value,
+        locale: Locale,
+    ) -> std::result::Result<String, Vec<String>> {
+        let formatter = Formatter::new(locale);
+        let segments = Self::parse(template).map_err(|e| vec![e])?;
+        let mut output = String::new();
+        let mut errors = Vec::new();
+
+        for segment in segments {
+            match segment {
+                Segment::Text(text) => output.push_str(&text),
+                Segment::Placeholder(placeholder) => {
+                    match Self::evaluate(&placeholder, data, &formatter) {
+                        Ok(value) => output.push_str(&value),
+                        Err(e) => errors.push(e),
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(output)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Resolve a single placeholder and apply its filters
+    fn evaluate(
+        placeholder: &Placeholder,
+        data: &Value,
+        formatter: &Formatter,
+    ) -> std::result::Result<String, String> {
+        let mut value = lookup(data, &placeholder.path).cloned();
+
+        for filter in &placeholder.filters {
+            value = apply_filter(value, filter, formatter)
+                .map_err(|e| format!("{} (in {{{{{}}}}})", e, placeholder.path))?;
+        }
+
+        match value {
+            Some(value) => Ok(value_to_string(&value)),
+            None => Err(format!("Unresolved variable: {}", placeholder.path)),
+        }
+    }
+
+    /// Return a copy of the document with all placeholders resolved
+    ///
+    /// Blocks whose `visible_if` is false are dropped before resolving, so
+    /// their variables may be missing. Fails with a `TemplateError` listing
+    /// every unresolved variable and the block it belongs to, or with a
+    /// `ValidationError` when a table formula cannot be computed.
+    pub fn apply(document: &Document, data: &Value) -> Result<Document> {
+        Self::resolve(document, Some(data))
+    }
+
+    /// Return a copy of the document as rendered without generation data
+    ///
+    /// Visibility rules see an empty data context, repeaters collapse to no
+    /// items and placeholders are kept verbatim; only formulas that do not
+    /// depend on data are computed.
+    pub fn apply_without_data(document: &Document) -> Result<Document> {
+        Self::resolve(document, None)
+    }
+
+    fn resolve(document: &Document, data: Option<&Value>) -> Result<Document> {
+        let empty = Value::Object(Map::new());
+        let with_data = data.is_some();
+        let data = data.unwrap_or(&empty);
+        let mut resolved = document.clone();
+        let mut resolution = Resolution {
+            locale: document.metadata.locale,
+            without_data: !with_data,
+            ..Default::default()
+        };
+
+        resolved.blocks.retain(|block| match &block.visible_if {
+            Some(source) => match Expression::parse(source) {
+                Ok(expression) => expression.is_true(data),
+                Err(e) => {
+                    resolution.template_errors.push(format!(
+                        "Invalid visibility rule: {} (block {})",
+                        e, block.id
+                    ));
+                    false
+                }
+            },
+            None => true,
+        });
+
+        // Repeated blocks are resolved per element while expanding
+        let expanded = repeater::expand_repeaters(&mut resolved, data, &mut resolution);
+
+        if with_data {
+            for block in &mut resolved.blocks {
+                if !expanded.contains(&block.id) {
+                    Self::resolve_block(block, data, &mut resolution);
+                }
+            }
+        }
+
+        if !resolution.template_errors.is_empty() {
+            return Err(AppError::TemplateError(
+                resolution.template_errors.join("; "),
+            ));
+        }
+
+        if let Err(e) = formula::compute_document(&mut resolved, with_data.then_some(data)) {
+            resolution.formula_errors.push(e);
+        }
+        if !resolution.formula_errors.is_empty() {
+            return Err(AppError::ValidationError(
+                resolution.formula_errors.join("; "),
+            ));
+        }
+
+        Ok(resolved)
+    }
+
+    /// Resolve the placeholders of a block in place, collecting errors
+    pub(crate) fn resolve_block(block: &mut Block, data: &Value, resolution: &mut Resolution) {
+        let block_id = block.id.clone();
+        match &mut block.content {
+            BlockContent::Text(text) if !text.paragraphs.is_empty() => {
+                // Placeholders resolve inside each run, keeping its style
+                for run in text.paragraphs.iter_mut().flat_map(|p| p.runs.iter_mut()) {
+                    Self::resolve_text(&mut run.text, data, &block_id, resolution);
+                }
+                text.text = plain_text(&text.paragraphs);
+            }
+            BlockContent::Text(text) => {
+                Self::resolve_text(&mut text.text, data, &block_id, resolution)
+            }
+            BlockContent::Table(table) => {
+                Self::resolve_rows(&mut table.rows, data, &block_id, resolution)
+            }
+            BlockContent::Image(_)
+            | BlockContent::Shape(_)
+            | BlockContent::Line(_)
+            | BlockContent::Spacer => {}
+        }
+    }
+
+    /// Resolve the placeholders of table rows in place, collecting errors
+    pub(crate) fn resolve_rows(
+        rows: &mut [TableRow],
+        data: &Value,
+        block_id: &str,
+        resolution: &mut Resolution,
+    ) {
+        for cell in rows.iter_mut().flat_map(|r| r.cells.iter_mut()) {
+            Self::resolve_text(&mut cell.content, data, block_id, resolution);
+        }
+    }
+
+    fn resolve_text(text: &mut String, data: &Value, block_id: &str, resolution: &mut Resolution) {
+        match Self::render(text, data, resolution.locale) {
+            Ok(rendered) => *text = rendered,
+            Err(_) if resolution.without_data => {}
+            Err(text_errors) => resolution.template_errors.extend(
+                text_errors
+                    .into_iter()
+                    .map(|e| format!("{} (block {})", e, block_id)),
+            ),
+        }
+    }
+
+    /// Collect every variable used in a document, sorted by path
+    pub fn list_variables(document: &Document) -> Vec<VariableUsage> {
+        let mut usages: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for block in &document.blocks {
+            let mut add_usage = |path: String| {
+                let blocks = usages.entry(path).or_default();
+                if !blocks.contains(&block.id) {
+                    blocks.push(block.id.clone());
+                }
+            };
+
+            if let Some(Ok(expression)) = block.visible_if.as_deref().map(Expression::parse) {
+                expression.variables().into_iter().for_each(&mut add_usage);
+            }
+            Self::content_variables(block)
+                .into_iter()
+                .for_each(&mut add_usage);
+        }
+
+        usages
+            .into_iter()
+            .map(|(path, block_ids)| VariableUsage { path, block_ids })
+            .collect()
+    }
+
+    /// Variables a block fills in from the data context: its repeater
+    /// source and its placeholders outside the element scope
+    pub(crate) fn content_variables(block: &Block) -> Vec<String> {
+        let mut variables = Vec::new();
+        if let Some(repeat) = &block.repeat {
+            variables.push(repeat.path.clone());
+        }
+
+        let texts: Vec<&str> = match &block.content {
+            BlockContent::Text(text) => vec![text.text.as_str()],
+            BlockContent::Table(table) => table
+                .rows
+                .iter()
+                .flat_map(|r| r.cells.iter().map(|c| c.content.as_str()))
+                .collect(),
+            BlockContent::Image(_)
+            | BlockContent::Shape(_)
+            | BlockContent::Line(_)
+            | BlockContent::Spacer => vec![],
+        };
+
+        for text in texts {
+            // Malformed templates are reported at generation time
+            let Ok(segments) = Self::parse(text) else {
+                continue;
+            };
+            for segment in segments {
+                if let Segment::Placeholder(placeholder) = segment {
+                    // Element variables are not part of the data context
+                    if block.repeat.is_some() && repeater::is_scoped_path(&placeholder.path) {
+                        continue;
+                    }
+                    variables.push(placeholder.path);
+                }
+            }
+        }
+
+        variables
+    }
+}
+
+/// Check that a variable path has no empty segments or stray characters
+pub fn is_valid_path(path: &str) -> bool {
+    path.replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .all(|key| {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-'))
+        })
+}
+
+/// Look up a dotted path such as `client.address.city` or `items[0].name`
+pub fn lookup<'a>(data: &'a Value, path: &str) -> Option<&'a Value> {
+    let normalized = path.replace('[', ".").replace(']', "");
+
+    normalized
+        .split('.')
+        .filter(|key| !key.is_empty())
+        .try_fold(data, |value, key| match value {
+            Value::Object(map) => map.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+/// Convert a JSON value to the text shown in the document
+pub fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        Value::Number(n) => n.to_string(),
+        Value::Bool(b) => b.to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Apply a filter; `None` means the variable is missing
+fn apply_filter(
+    value: Option<Value>,
+    filter: &FilterCall,
+    formatter: &Formatter,
+) -> std::result::Result<Option<Value>, String> {
+    if filter.name == "default" {
+        let is_empty = matches!(&value, None | Some(Value::Null))
+            || matches!(&value, Some(Value::String(s)) if s.is_empty());
+        return Ok(if is_empty {
+            Some(Value::String(filter.argument.clone().unwrap_or_default()))
+        } else {
+            value
+        });
+    }
+
+    let Some(value) = value else {
+        return Ok(None);
+    };
+
+    let argument = filter.argument.as_deref();
+    let result = match filter.name.as_str() {
+        "upper" | "uppercase" => value_to_string(&value).to_uppercase(),
+        "lower" | "lowercase" => value_to_string(&value).to_lowercase(),
+        "capitalize" => {
+            let text = value_to_string(&value);
+            let mut chars = text.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => text,
+            }
+        }
+        "trim" => value_to_string(&value).trim().to_string(),
+        "date" => format_date(&value, argument, formatter)?,
+        "number" => formatter.format_number(
+            as_number(&value, formatter)?,
+            Some(parse_decimals(argument, 2)?),
+        ),
+        "percent" => {
+            formatter.format_percent(as_number(&value, formatter)?, parse_decimals(argument, 0)?)
+        }
+        "currency" => match argument {
+            Some(code) if !code.is_empty() => {
+                formatter.format_currency(as_number(&value, formatter)?, code)
+            }
+            _ => formatter.format_number(as_number(&value, formatter)?, Some(2)),
+        },
+        other => return Err(format!("Unknown filter: {}", other)),
+    };
+
+    Ok(Some(Value::String(result)))
+}
+
+fn as_number(value: &Value, formatter: &Formatter) -> std::result::Result<Decimal, String> {
+    match value {
+        Value::Number(n) => {
+            let text = n.to_string();
+            Decimal::from_str(&text)
+                .or_else(|_| Decimal::from_scientific(&text))
+                .map_err(|_| format!("Invalid number: {}", n))
+        }
+        Value::String(s) => formatter
+            .parse_number(s)
+            .ok_or_else(|| format!("Not a number: {}", s)),
+        other => Err(format!("Not a number: {}", other)),
+    }
+}
+
+fn parse_decimals(argument: Option<&str>, default: u32) -> std::result::Result<u32, String> {
+    match argument {
+        Some(a) => a
+            .parse()
+            .ok()
+            .filter(|decimals| *decimals <= 28)
+            .ok_or_else(|| format!("Invalid decimal places: {}", a)),
+        None => Ok(default),
+    }
+}
+
+/// Format a date given as ISO 8601 text or a Unix timestamp
+///
+/// The format is `short` (default), `long` or a chrono `strftime` pattern.
+fn format_date(
+    value: &Value,
+    format: Option<&str>,
+    formatter: &Formatter,
+) -> std::result::Result<String, String> {
+    let datetime = match value {
+        Value::Number(n) => n
+            .as_i64()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .map(|dt| dt.naive_utc()),
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .map(|dt| dt.naive_local())
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S"))
+            .or_else(|_| {
+                NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|d| d.and_time(Default::default()))
+            })
+            .ok(),
+        _ => None,
+    }
+    .ok_or_else(|| format!("Not a date: {}", value_to_string(value)))?;
+
+    Ok(match format.unwrap_or("short") {
+        "short" => formatter.format_date(datetime.date(), DateStyle::Short),
+        "long" => formatter.format_date(datetime.date(), DateStyle::Long),
+        pattern => {
+            // User-supplied patterns may be invalid, which `to_string` would panic on
+            let mut formatted = String::new();
+            write!(formatted, "{}", datetime.format(pattern))
+                .map_err(|_| format!("Invalid date format: {}", pattern))?;
+            formatted
+        }
+    })
+}
+
+/// Split on a separator, ignoring separators inside single or double quotes
+fn split_outside_quotes(input: &str, separator: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+
+    for c in input.chars() {
+        match (quote, c) {
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (Some(q), _) if q == c => {
+                quote = None;
+                current.push(c);
+            }
+            (None, _) if c == separator => parts.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+
+    parts.push(current);
+    parts
+}
+
+fn unquote(value: &str) -> String {
+    let quoted = value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')));
+    if quoted {
+        value[1..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{
+        Block, BlockType, Paragraph, TableBlockContent, TableCell, TableRow, TextRun,
+    };
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_placeholders() {
+        let segments = TemplateEngine::parse("Hello {{ client.name | upper }}!").unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                Segment::Text("Hello ".to_string()),
+                Segment::Placeholder(Placeholder {
+                    path: "client.name".to_string(),
+                    filters: vec![FilterCall {
+                        name: "upper".to_string(),
+                        argument: None,
+                    }],
+                }),
+                Segment::Text("!".to_string()),
+            ]
+        );
+        assert!(TemplateEngine::parse("{{ unclosed").is_err());
+        assert!(TemplateEngine::parse("{{ }}").is_err());
+    }
+
+    #[test]
+    fn test_lookup_paths() {
+        let data = json!({ "client": { "name": "ACME" }, "items": [{ "name": "Setup" }] });
+        assert_eq!(lookup(&data, "client.name"), Some(&json!("ACME")));
+        assert_eq!(lookup(&data, "items[0].name"), Some(&json!("Setup")));
+        assert_eq!(lookup(&data, "items.0.name"), Some(&json!("Setup")));
+        assert_eq!(lookup(&data, "client.phone"), None);
+    }
+
+    #[test]
+    fn test_filters() {
+        let data = json!({
+            "name": "acme",
+            "date": "2024-03-05",
+            "amount": 1234567.891,
+        });
+        let render = |t: &str| TemplateEngine::render(t, &data, Locale::EnUs).unwrap();
+
+        assert_eq!(render("{{ name | uppercase }}"), "ACME");
+        assert_eq!(render("{{ name | capitalize }}"), "Acme");
+        assert_eq!(render("{{ date | date }}"), "03/05/2024");
+        assert_eq!(render("{{ date | date:\"%Y/%m\" }}"), "2024/03");
+        assert_eq!(render("{{ amount | currency:\"EUR\" }}"), "€1,234,567.89");
+        assert_eq!(render("{{ amount | number:0 }}"), "1,234,568");
+        assert_eq!(render("{{ missing | default:'n/a' }}"), "n/a");
+    }
+
+    #[test]
+    fn test_invalid_date_pattern() {
+        let data = json!({ "date": "2024-03-05" });
+        let result = TemplateEngine::render("{{ date | date:\"%Q\" }}", &data, Locale::EnUs);
+        assert_eq!(
+            result,
+            Err(vec!["Invalid date format: %Q (in {{date}})".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_filters_follow_locale() {
+        let data = json!({ "date": "2024-03-05", "amount": "1234.5", "rate": 0.2 });
+        let cases = [
+            (
+                Locale::Ru,
+                "05.03.2024",
+                "5 марта 2024 г.",
+                "1\u{a0}234,50\u{a0}₽",
+                "20\u{a0}%",
+            ),
+            (
+                Locale::EnUs,
+                "03/05/2024",
+                "March 5, 2024",
+                "₽1,234.50",
+                "20%",
+            ),
+            (
+                Locale::De,
+                "05.03.2024",
+                "5. März 2024",
+                "1.234,50\u{a0}₽",
+                "20\u{a0}%",
+            ),
+            (
+                Locale::Fr,
+                "05/03/2024",
+                "5 mars 2024",
+                "1\u{a0}234,50\u{a0}₽",
+                "20\u{a0}%",
+            ),
+        ];
+
+        for (locale, short, long, currency, percent) in cases {
+            let render = |t: &str| TemplateEngine::render(t, &data, locale).unwrap();
+            assert_eq!(render("{{ date | date }}"), short);
+            assert_eq!(render("{{ date | date:long }}"), long);
+            assert_eq!(render("{{ amount | currency:RUB }}"), currency);
+            assert_eq!(render("{{ rate | percent }}"), percent);
+        }
+    }
+
+    #[test]
+    fn test_unresolved_variables_are_errors() {
+        let errors =
+            TemplateEngine::render("{{ a }} and {{ b }}", &json!({ "a": 1 }), Locale::EnUs)
+                .unwrap_err();
+        assert_eq!(errors, vec!["Unresolved variable: b".to_string()]);
+
+        let errors = TemplateEngine::render("{{ a | shout }}", &json!({ "a": 1 }), Locale::EnUs)
+            .unwrap_err();
+        assert!(errors[0].contains("Unknown filter: shout"));
+    }
+
+    #[test]
+    fn test_apply_to_document() {
+        let mut document = Document::new("Offer".to_string());
+        document.add_block(Block::text_for_test("Dear {{ client.name }}"));
+
+        let resolved =
+            TemplateEngine::apply(&document, &json!({ "client": { "name": "ACME" } })).unwrap();
+        match &resolved.blocks[0].content {
+            BlockContent::Text(text) => assert_eq!(text.text, "Dear ACME"),
+            _ => panic!("expected text block"),
+        }
+
+        let error = TemplateEngine::apply(&document, &json!({})).unwrap_err();
+        assert!(error.to_string().contains("client.name"));
+    }
+
+    #[test]
+    fn test_apply_keeps_run_styles() {
+        let mut document = Document::new("Offer".to_string());
+        let mut block = Block::text_for_test("");
+        if let BlockContent::Text(content) = &mut block.content {
+            content.set_paragraphs(vec![Paragraph {
+                runs: vec![
+                    TextRun::plain("Total: ".to_string()),
+                    TextRun {
+                        bold: true,
+                        ..TextRun::plain("{{ amount }} EUR".to_string())
+                    },
+                ],
+                ..Default::default()
+            }]);
+        }
+        document.add_block(block);
+
+        let resolved = TemplateEngine::apply(&document, &json!({ "amount": 120 })).unwrap();
+        let BlockContent::Text(text) = &resolved.blocks[0].content else {
+            panic!("expected text block");
+        };
+        assert_eq!(text.text, "Total: 120 EUR");
+        assert!(text.paragraphs[0].runs[1].bold);
+    }
+
+    #[test]
+    fn test_hidden_blocks_are_dropped() {
+        let mut document = Document::new("Offer".to_string());
+        let mut discount = Block::text_for_test("Discount: {{ discount }}%");
+        discount.visible_if = Some("amount > 1000".to_string());
+        document.add_block(discount);
+        document.add_block(Block::text_for_test("Total: {{ amount }}"));
+
+        let resolved = TemplateEngine::apply(&document, &json!({ "amount": 500 })).unwrap();
+        assert_eq!(resolved.blocks.len(), 1);
+
+        let resolved =
+            TemplateEngine::apply(&document, &json!({ "amount": 1500, "discount": 5 })).unwrap();
+        assert_eq!(resolved.blocks.len(), 2);
+
+        let paths: Vec<String> = TemplateEngine::list_variables(&document)
+            .into_iter()
+            .map(|v| v.path)
+            .collect();
+        assert_eq!(paths, vec!["amount", "discount"]);
+    }
+
+    #[test]
+    fn test_visibility_without_data() {
+        let mut document = Document::new("Offer".to_string());
+        let mut discount = Block::text_for_test("Discount: {{ discount }}%");
+        discount.visible_if = Some("amount > 1000".to_string());
+        document.add_block(discount);
+        let mut fallback = Block::text_for_test("Total: {{ amount }}");
+        fallback.visible_if = Some("not exists(amount)".to_string());
+        document.add_block(fallback);
+
+        let resolved = TemplateEngine::apply_without_data(&document).unwrap();
+        assert_eq!(resolved.blocks.len(), 1);
+        let BlockContent::Text(text) = &resolved.blocks[0].content else {
+            panic!("expected text block");
+        };
+        assert_eq!(text.text, "Total: {{ amount }}");
+    }
+
+    #[test]
+    fn test_formulas_without_data() {
+        let cell = |content: &str| TableCell {
+            content: content.to_string(),
+            styles: None,
+            name: None,
+        };
+        let mut document = Document::new("Offer".to_string());
+        let mut table = Block::for_test(BlockType::Table, 0.0, 0.0, 200.0, 60.0);
+        table.content = BlockContent::Table(TableBlockContent {
+            rows: vec![
+                TableRow {
+                    cells: vec![cell("=ROUND(price * qty, 2)"), cell("=2 * 3")],
+                },
+                TableRow {
+                    cells: vec![cell("{{ price }}"), cell("=SUM(col(2))")],
+                },
+            ],
+            column_widths: vec![100.0, 100.0],
+        });
+        document.add_block(table);
+
+        let resolved = TemplateEngine::apply_without_data(&document).unwrap();
+        let BlockContent::Table(table) = &resolved.blocks[0].content else {
+            panic!("expected table block");
+        };
+        let contents: Vec<Vec<&str>> = table
+            .rows
+            .iter()
+            .map(|row| row.cells.iter().map(|c| c.content.as_str()).collect())
+            .collect();
+        assert_eq!(
+            contents,
+            vec![
+                vec!["=ROUND(price * qty, 2)", "6"],
+                vec!["{{ price }}", "6"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_variables() {
+        let mut document = Document::new("Offer".to_string());
+        document.add_block(Block::text_for_test("{{ b }} {{ a | upper }} {{ b }}"));
+        document.add_block(Block::text_for_test("{{ a }}"));
+
+        let variables = TemplateEngine::list_variables(&document);
+        let paths: Vec<&str> = variables.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec!["a", "b"]);
+        assert_eq!(variables[0].block_ids.len(), 2);
+        assert_eq!(variables[1].block_ids.len(), 1);
+    }
+}
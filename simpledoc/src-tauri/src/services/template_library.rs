@@ -0,0 +1,368 @@
+use crate::models::{AppError, Document, Result, Template, TemplateListItem, TemplateVariable};
+use crate::services::assets::AssetStore;
+use crate::services::bundle;
+use crate::services::font_registry::FontRegistry;
+use crate::services::{migration, TemplateEngine};
+use chrono::Utc;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Marks a JSON file as a template export of earlier versions
+const BUNDLE_FORMAT: &str = "simpledoc-template";
+
+/// Latest layout of JSON template exports
+const BUNDLE_VERSION: u32 = 1;
+
+/// What to record when saving a document as a template
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TemplateOptions {
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(rename = "defaultData", default)]
+    pub default_data: Option<Value>,
+}
+
+/// Templates stored as `<id>.json` in their own directory
+pub struct TemplateLibrary {
+    dir: PathBuf,
+}
+
+impl TemplateLibrary {
+    /// Open the library, creating its directory
+    pub fn open(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn template_path(&self, template_id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", template_id))
+    }
+
+    /// Build a template from a copy of a document
+    ///
+    /// Variables are taken from the placeholders the document uses.
+    pub fn create(document: &Document, options: TemplateOptions) -> Template {
+        let variables = TemplateEngine::list_variables(document)
+            .into_iter()
+            .map(|usage| TemplateVariable {
+                path: usage.path,
+                label: None,
+            })
+            .collect();
+        let now = Utc::now();
+
+        Template {
+            id: Uuid::new_v4().to_string(),
+            name: options.name,
+            category: options.category.filter(|c| !c.is_empty()),
+            description: options.description.filter(|d| !d.is_empty()),
+            thumbnail: options.thumbnail,
+            variables,
+            default_data: options
+                .default_data
+                .unwrap_or_else(|| Value::Object(Default::default())),
+            created_at: now,
+            updated_at: now,
+            document: document.clone(),
+        }
+    }
+
+    /// Insert or replace a template (atomic write: temp file + rename)
+    pub fn save(&self, template: &Template) -> Result<()> {
+        if template.name.trim().is_empty() {
+            return Err(AppError::ValidationError(
+                "Template name must not be empty".to_string(),
+            ));
+        }
+        template
+            .document
+            .validate()
+            .map_err(AppError::ValidationError)?;
+
+        let path = self.template_path(&template.id);
+        let temp_path = path.with_extension("json.tmp");
+        debug!("Saving template {} to {:?}", template.id, path);
+
+        let json = serde_json::to_string_pretty(template)?;
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&temp_path, &path)?;
+        Ok(())
+    }
+
+    pub fn load(&self, template_id: &str) -> Result<Template> {
+        let path = self.template_path(template_id);
+        if !path.exists() {
+            return Err(AppError::TemplateNotFound(template_id.to_string()));
+        }
+
+        parse_template(serde_json::from_str(&fs::read_to_string(&path)?)?)
+    }
+
+    /// All readable templates by category, then name; broken files are skipped
+    pub fn list(&self) -> Result<Vec<TemplateListItem>> {
+        let mut items = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            let template = fs::read_to_string(&path)
+                .map_err(AppError::from)
+                .and_then(|contents| parse_template(serde_json::from_str(&contents)?));
+            match template {
+                Ok(template) => items.push(TemplateListItem::from(&template)),
+                Err(e) => error!("Failed to load template from {:?}: {}", path, e),
+            }
+        }
+
+        items.sort_by(|a, b| {
+            (&a.category, a.name.to_lowercase()).cmp(&(&b.category, b.name.to_lowercase()))
+        });
+        Ok(items)
+    }
+
+    pub fn delete(&self, template_id: &str) -> Result<()> {
+        let path = self.template_path(template_id);
+        if !path.exists() {
+            return Err(AppError::TemplateNotFound(template_id.to_string()));
+        }
+
+        debug!("Deleting template {} at {:?}", template_id, path);
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    /// Write a template to a single bundle file
+    ///
+    /// Images and fonts the template uses travel with it, as in document
+    /// bundles.
+    pub fn export(
+        &self,
+        template_id: &str,
+        export_path: &Path,
+        assets: &AssetStore,
+        fonts: &FontRegistry,
+    ) -> Result<()> {
+        let template = self.load(template_id)?;
+        bundle::write_template_bundle(&template, export_path, assets, fonts)?;
+
+        info!("Template {} exported to {:?}", template_id, export_path);
+        Ok(())
+    }
+
+    /// Add the template of a bundle file to the library under a new id
+    ///
+    /// Bundled assets are added to `assets` and relinked. JSON exports of
+    /// earlier versions are accepted too.
+    pub fn import(&self, import_path: &Path, assets: &AssetStore) -> Result<Template> {
+        let value = if bundle::is_bundle(import_path)? {
+            bundle::read_template_bundle(import_path, assets)?
+        } else {
+            read_json_export(import_path)?
+        };
+
+        let mut template = parse_template(value)?;
+        template.id = Uuid::new_v4().to_string();
+        template.updated_at = Utc::now();
+        self.save(&template)?;
+
+        info!(
+            "Template imported from {:?} with new ID {}",
+            import_path, template.id
+        );
+        Ok(template)
+    }
+}
+
+/// Template of a JSON export written by earlier versions
+fn read_json_export(import_path: &Path) -> Result<Value> {
+    let contents = fs::read_to_string(import_path)?;
+    let mut bundle: Value = serde_json::from_str(&contents)?;
+    if bundle.get("format").and_then(Value::as_str) != Some(BUNDLE_FORMAT) {
+        return Err(AppError::InvalidData(format!(
+            "{:?} is not a template bundle",
+            import_path
+        )));
+    }
+    let version = bundle.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > BUNDLE_VERSION as u64 {
+        return Err(AppError::InvalidData(format!(
+            "Template bundle version {} is newer than supported version {}",
+            version, BUNDLE_VERSION
+        )));
+    }
+
+    Ok(bundle["template"].take())
+}
+
+/// Deserialize a template, migrating its document to the current schema
+fn parse_template(mut value: Value) -> Result<Template> {
+    if let Some(document) = value.get_mut("document") {
+        migration::migrate(document)?;
+    }
+    let mut template: Template = serde_json::from_value(value)?;
+    template.document.assign_orphan_blocks();
+    Ok(template)
+}
+
+/// New document from a template, with fresh ids for it, its pages and blocks
+pub fn instantiate(template: &Template, title: Option<String>) -> Document {
+    let mut document = template.document.duplicate();
+    document.metadata.title = title
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| template.name.clone());
+    document.metadata.created_at = Utc::now();
+    document.touch();
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Block, BlockContent, BlockType};
+    use base64::engine::general_purpose::STANDARD as BASE64;
+    use base64::Engine;
+    use tempfile::TempDir;
+
+    const PNG: &str = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNkYPhfDwAChwGA60e6kgAAAABJRU5ErkJggg==";
+
+    fn document() -> Document {
+        let mut document = Document::new("Offer".to_string());
+        document.add_block(Block::text_for_test(
+            "Dear {{ client.name }}, total {{ total }}",
+        ));
+        document
+    }
+
+    fn options(name: &str, category: Option<&str>) -> TemplateOptions {
+        TemplateOptions {
+            name: name.to_string(),
+            category: category.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_list_and_delete() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = TemplateLibrary::open(temp_dir.path().join("templates")).unwrap();
+
+        let offer = TemplateLibrary::create(&document(), options("Offer", Some("Sales")));
+        let invoice = TemplateLibrary::create(&document(), options("invoice", Some("Finance")));
+        library.save(&offer).unwrap();
+        library.save(&invoice).unwrap();
+        assert!(library
+            .save(&TemplateLibrary::create(&document(), options(" ", None)))
+            .is_err());
+
+        let paths: Vec<&str> = offer.variables.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec!["client.name", "total"]);
+        assert!(offer.default_data.is_object());
+
+        let items = library.list().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].name, "invoice");
+        assert_eq!(items[1].variable_count, 2);
+
+        library.delete(&offer.id).unwrap();
+        assert!(matches!(
+            library.load(&offer.id),
+            Err(AppError::TemplateNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_instantiate_uses_fresh_ids() {
+        let template = TemplateLibrary::create(&document(), options("Offer", None));
+
+        let first = instantiate(&template, None);
+        let second = instantiate(&template, Some("Offer for ACME".to_string()));
+
+        assert_eq!(first.metadata.title, "Offer");
+        assert_eq!(second.metadata.title, "Offer for ACME");
+        assert_ne!(first.id, template.document.id);
+        assert_ne!(first.blocks[0].id, template.document.blocks[0].id);
+        assert_ne!(first.blocks[0].id, second.blocks[0].id);
+        assert_eq!(first.blocks[0].page_id, first.pages[0].id);
+    }
+
+    #[test]
+    fn test_export_and_import_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = TemplateLibrary::open(temp_dir.path().join("templates")).unwrap();
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+
+        let mut document = document();
+        let mut logo = Block::for_test(BlockType::Image, 0.0, 40.0, 50.0, 50.0);
+        if let BlockContent::Image(image) = &mut logo.content {
+            image.src = assets.add(&BASE64.decode(PNG).unwrap()).unwrap();
+        }
+        document.add_block(logo);
+        let template = TemplateLibrary::create(&document, options("Offer", Some("Sales")));
+        library.save(&template).unwrap();
+
+        let bundle_path = temp_dir.path().join("offer.sdtemplate");
+        library
+            .export(&template.id, &bundle_path, &assets, &FontRegistry::new())
+            .unwrap();
+
+        // Import on another machine, with an empty asset store
+        let other_assets = AssetStore::open(temp_dir.path().join("other")).unwrap();
+        let imported = library.import(&bundle_path, &other_assets).unwrap();
+
+        assert_ne!(imported.id, template.id);
+        assert_eq!(imported.name, "Offer");
+        assert_eq!(imported.category.as_deref(), Some("Sales"));
+        assert_eq!(imported.variables, template.variables);
+        assert_eq!(library.list().unwrap().len(), 2);
+        let BlockContent::Image(image) = &imported.document.blocks[1].content else {
+            panic!("expected image block");
+        };
+        assert_eq!(
+            other_assets.read(&image.src).unwrap(),
+            BASE64.decode(PNG).unwrap()
+        );
+
+        // Document bundles are not templates
+        let document_path = temp_dir.path().join("offer.sdoc");
+        bundle::write_bundle(&document, &document_path, &assets, &FontRegistry::new()).unwrap();
+        assert!(library.import(&document_path, &assets).is_err());
+    }
+
+    #[test]
+    fn test_import_json_export() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = TemplateLibrary::open(temp_dir.path().join("templates")).unwrap();
+        let assets = AssetStore::open(temp_dir.path().join("assets")).unwrap();
+        let template = TemplateLibrary::create(&document(), options("Offer", None));
+
+        let export = |format: &str| {
+            let path = temp_dir.path().join(format!("{}.json", format));
+            let json = serde_json::json!({
+                "format": format,
+                "version": BUNDLE_VERSION,
+                "template": template,
+            });
+            fs::write(&path, json.to_string()).unwrap();
+            path
+        };
+
+        let imported = library.import(&export(BUNDLE_FORMAT), &assets).unwrap();
+        assert_eq!(imported.name, "Offer");
+        assert!(library.import(&export("other"), &assets).is_err());
+    }
+}
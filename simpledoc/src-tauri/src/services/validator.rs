@@ -1,4 +1,9 @@
-use crate::models::{Block, BlockContent, BlockType, Document};
+use crate::models::{Block, BlockContent, Document, Page, Position, ShapeKind, Size, Stroke, TextRun};
+use crate::services::expression::Expression;
+use crate::services::font_registry::FontRegistry;
+use crate::services::formula;
+use crate::services::renderer::layout::{is_auto_height, measure_block};
+use crate::services::template_engine::{is_valid_path, TemplateEngine};
 
 /// Service for validating data structures
 pub struct Validator;
@@ -21,16 +26,109 @@ impl Validator {
             return Err("Block z-index must be non-negative".to_string());
         }
 
-        // Validate content matches type
-        Self::validate_block_content(&block.block_type, &block.content)?;
+        // Validate content
+        Self::validate_block_content(&block.content, &block.size)
+            .map_err(|e| format!("Block {}: {}", block.id, e))?;
+
+        Self::validate_visibility_rule(block)?;
+        Self::validate_repeat(block)?;
+        Self::validate_formulas(block)?;
+
+        Ok(())
+    }
+
+    /// Validate the data binding of a repeater block
+    pub fn validate_repeat(block: &Block) -> Result<(), String> {
+        let Some(repeat) = &block.repeat else {
+            return Ok(());
+        };
+
+        if !is_valid_path(&repeat.path) {
+            return Err(format!("Block {} has an invalid repeat path: {}", block.id, repeat.path));
+        }
+        if repeat.gap < 0.0 {
+            return Err("Repeat gap must be non-negative".to_string());
+        }
+        if let BlockContent::Table(table) = &block.content {
+            if table.rows.len() <= repeat.header_rows + repeat.footer_rows {
+                return Err(format!(
+                    "Repeating table {} needs at least one row besides its header and footer rows",
+                    block.id
+                ));
+            }
+        }
 
         Ok(())
     }
 
-    /// Validate block content matches the block type
-    fn validate_block_content(block_type: &BlockType, content: &BlockContent) -> Result<(), String> {
-        match (block_type, content) {
-            (BlockType::Text, BlockContent::Text(text_content)) => {
+    /// Validate the syntax and variable names of a block's `visible_if`
+    pub fn validate_visibility_rule(block: &Block) -> Result<(), String> {
+        match &block.visible_if {
+            Some(source) => Expression::parse(source)
+                .map(|_| ())
+                .map_err(|e| format!("Block {} has an invalid visibility rule: {}", block.id, e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that visibility rules only use variables the document fills in
+    ///
+    /// A variable is known when a placeholder or repeater of any block refers
+    /// to it, to one of its fields or to another field of the same object.
+    /// Returns one warning per unknown variable, since a typo silently hides
+    /// the block.
+    pub fn validate_visibility_variables(document: &Document) -> Vec<String> {
+        let known: Vec<String> = document
+            .blocks
+            .iter()
+            .flat_map(TemplateEngine::content_variables)
+            .collect();
+        let related = |a: &str, b: &str| {
+            a.strip_prefix(b)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+        };
+
+        let mut warnings = Vec::new();
+        for block in &document.blocks {
+            let Some(Ok(expression)) = block.visible_if.as_deref().map(Expression::parse) else {
+                continue;
+            };
+            for variable in expression.variables() {
+                let parent = variable.rfind(['.', '[']).map(|i| &variable[..i]);
+                let is_known = known.iter().any(|path| {
+                    related(path, &variable)
+                        || related(&variable, path)
+                        || parent.is_some_and(|parent| related(path, parent))
+                });
+                if !is_known {
+                    warnings.push(format!(
+                        "Block {} has a visibility rule with an unknown variable: {}",
+                        block.id, variable
+                    ));
+                }
+            }
+        }
+        warnings
+    }
+
+    /// Validate formula syntax and circular references in a table block
+    pub fn validate_formulas(block: &Block) -> Result<(), String> {
+        match &block.content {
+            BlockContent::Table(table) => formula::check_rows(&table.rows)
+                .map_err(|e| format!("Block {} has an invalid formula: {}", block.id, e)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Validate the content, expressions and data bindings of all blocks (run before saving)
+    pub fn validate_blocks(document: &Document) -> Result<(), String> {
+        document.blocks.iter().try_for_each(Self::validate_block)
+    }
+
+    /// Validate block content
+    fn validate_block_content(content: &BlockContent, size: &Size) -> Result<(), String> {
+        match content {
+            BlockContent::Text(text_content) => {
                 if text_content.font_size <= 0.0 {
                     return Err("Font size must be positive".to_string());
                 }
@@ -40,13 +138,17 @@ impl Validator {
                 if !Self::is_valid_color(&text_content.color) {
                     return Err(format!("Invalid color: {}", text_content.color));
                 }
-            }
-            (BlockType::Image, BlockContent::Image(image_content)) => {
-                if image_content.src.is_empty() {
-                    return Err("Image source cannot be empty".to_string());
+                if text_content.line_height.is_some_and(|height| height <= 0.0) {
+                    return Err("Line height must be positive".to_string());
+                }
+                for run in text_content.paragraphs.iter().flat_map(|p| p.runs.iter()) {
+                    Self::validate_run(run)?;
                 }
             }
-            (BlockType::Table, BlockContent::Table(table_content)) => {
+            BlockContent::Image(_) => {
+                // An empty source is an image still to be chosen in the editor
+            }
+            BlockContent::Table(table_content) => {
                 if table_content.rows.is_empty() {
                     return Err("Table must have at least one row".to_string());
                 }
@@ -71,20 +173,81 @@ impl Validator {
                     return Err("Column widths count must match cell count".to_string());
                 }
             }
-            (BlockType::Spacer, BlockContent::Spacer) => {
+            BlockContent::Spacer => {
                 // Spacer is always valid
             }
-            _ => {
-                return Err(format!(
-                    "Block type {:?} does not match content type",
-                    block_type
-                ));
+            BlockContent::Shape(shape) => {
+                if let Some(fill) = &shape.fill {
+                    if !Self::is_valid_color(fill) {
+                        return Err(format!("Invalid fill color: {}", fill));
+                    }
+                }
+                if let Some(stroke) = &shape.stroke {
+                    Self::validate_stroke(stroke)?;
+                }
+                if shape.corner_radius.is_some_and(|r| r < 0.0) {
+                    return Err("Corner radius must be non-negative".to_string());
+                }
+                if shape.shape == ShapeKind::Polygon {
+                    if shape.points.len() < 3 {
+                        return Err("Polygon must have at least 3 points".to_string());
+                    }
+                    if !shape.points.iter().all(|p| Self::is_inside(p, size)) {
+                        return Err("Polygon points must lie inside the block".to_string());
+                    }
+                }
             }
+            BlockContent::Line(line) => {
+                Self::validate_stroke(&line.stroke)?;
+                if line.stroke.width <= 0.0 {
+                    return Err("Line width must be positive".to_string());
+                }
+                if line.start == line.end {
+                    return Err("Line start and end must differ".to_string());
+                }
+                if !Self::is_inside(&line.start, size) || !Self::is_inside(&line.end, size) {
+                    return Err("Line end points must lie inside the block".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the style of a rich text run
+    fn validate_run(run: &TextRun) -> Result<(), String> {
+        if run.font_size.is_some_and(|size| size <= 0.0) {
+            return Err("Font size must be positive".to_string());
         }
+        if let Some(color) = &run.color {
+            if !Self::is_valid_color(color) {
+                return Err(format!("Invalid color: {}", color));
+            }
+        }
+        if let Some(link) = &run.link {
+            let allowed = ["http://", "https://", "mailto:", "tel:"];
+            if !allowed.iter().any(|scheme| link.starts_with(scheme)) {
+                return Err(format!("Unsupported link: {}", link));
+            }
+        }
+        Ok(())
+    }
 
+    fn validate_stroke(stroke: &Stroke) -> Result<(), String> {
+        if !Self::is_valid_color(&stroke.color) {
+            return Err(format!("Invalid stroke color: {}", stroke.color));
+        }
+        if stroke.width < 0.0 {
+            return Err("Stroke width must be non-negative".to_string());
+        }
         Ok(())
     }
 
+    /// Check a point relative to a block's top-left corner against its size
+    fn is_inside(point: &Position, size: &Size) -> bool {
+        (0.0..=size.width).contains(&point.x) && (0.0..=size.height).contains(&point.y)
+    }
+
     /// Validate a color string (supports hex, rgb, rgba)
     fn is_valid_color(color: &str) -> bool {
         if color.starts_with('#') {
@@ -113,6 +276,13 @@ impl Validator {
         // Validate all blocks
         for block in &document.blocks {
             Self::validate_block(block)?;
+
+            if document.get_page(&block.page_id).is_none() {
+                return Err(format!(
+                    "Block {} is not placed on an existing page",
+                    block.id
+                ));
+            }
         }
 
         // Validate metadata
@@ -123,37 +293,86 @@ impl Validator {
         Ok(())
     }
 
-    /// Validate page bounds (check if block fits within page)
-    pub fn validate_block_in_page_bounds(
-        block: &Block,
-        page_width: f64,
-        page_height: f64,
-    ) -> Result<(), String> {
-        let right = block.position.x + block.size.width;
-        let bottom = block.position.y + block.size.height;
+    /// Validate page bounds (check if block fits within the page content box)
+    ///
+    /// Uses the same geometry as generation, so orientation and margins are
+    /// taken into account.
+    pub fn validate_block_in_page_bounds(block: &Block, page: &Page) -> Result<(), String> {
+        let content_box = page.geometry().to_px().content_box;
+        let bounds = block.bounds();
 
-        if right > page_width {
+        if bounds.x < content_box.x || bounds.y < content_box.y {
             return Err(format!(
-                "Block extends beyond page width ({} > {})",
-                right, page_width
+                "Block {} starts inside the page margins ({}, {})",
+                block.id, bounds.x, bounds.y
             ));
         }
 
-        if bottom > page_height {
+        let right = bounds.x + bounds.width;
+        if right > content_box.x + content_box.width {
             return Err(format!(
-                "Block extends beyond page height ({} > {})",
-                bottom, page_height
+                "Block {} extends beyond the right margin ({} > {})",
+                block.id,
+                right,
+                content_box.x + content_box.width
+            ));
+        }
+
+        let bottom = bounds.y + bounds.height;
+        if bottom > content_box.y + content_box.height {
+            return Err(format!(
+                "Block {} extends beyond the bottom margin ({} > {})",
+                block.id,
+                bottom,
+                content_box.y + content_box.height
             ));
         }
 
         Ok(())
     }
+
+    /// Check that the text of a block fits its height
+    ///
+    /// Auto height blocks always fit, since they are resized before rendering.
+    pub fn validate_text_fit(block: &Block, fonts: &FontRegistry) -> Result<(), String> {
+        if is_auto_height(block) {
+            return Ok(());
+        }
+        match measure_block(block, fonts) {
+            Some(measure) if measure.overflow => Err(format!(
+                "Text of block {} is clipped: needs {:.0}px, has {:.0}px",
+                block.id, measure.required_height, block.size.height
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check every block against the content box of its page
+    ///
+    /// Returns one warning per block that leaves the printable area and per
+    /// text block whose text is clipped with the given fonts.
+    pub fn validate_layout(document: &Document, fonts: &FontRegistry) -> Vec<String> {
+        document
+            .pages
+            .iter()
+            .flat_map(|page| {
+                document.blocks_on_page(&page.id).flat_map(move |block| {
+                    [
+                        Self::validate_block_in_page_bounds(block, page).err(),
+                        Self::validate_text_fit(block, fonts).err(),
+                    ]
+                    .into_iter()
+                    .flatten()
+                })
+            })
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{Position, Size, TextAlignment, TextBlockContent};
+    use crate::models::{BlockType, PageOrientation, PageSize, Position, Size};
 
     #[test]
     fn test_valid_colors() {
@@ -199,5 +418,103 @@ mod tests {
 
         assert!(Validator::validate_block(&block).is_err());
     }
-}
 
+    #[test]
+    fn test_validate_visibility_rule() {
+        let mut block = Block::for_test(BlockType::Spacer, 0.0, 0.0, 100.0, 50.0);
+
+        block.visible_if = Some("amount > 1000 && exists(discount)".to_string());
+        assert!(Validator::validate_block(&block).is_ok());
+
+        block.visible_if = Some("amount >".to_string());
+        assert!(Validator::validate_block(&block).is_err());
+    }
+
+    #[test]
+    fn test_validate_visibility_variables() {
+        let mut document = Document::new("Offer".to_string());
+        document.add_block(Block::text_for_test("{{ amount }} for {{ client.name }}"));
+
+        let mut discount = Block::for_test(BlockType::Spacer, 0.0, 0.0, 100.0, 50.0);
+        discount.visible_if = Some("amount > 1000 && exists(client) && !client.vip".to_string());
+        let discount_id = discount.id.clone();
+        document.add_block(discount);
+        assert!(Validator::validate_visibility_variables(&document).is_empty());
+
+        document.blocks[1].visible_if = Some("ammount > 1000".to_string());
+        assert_eq!(
+            Validator::validate_visibility_variables(&document),
+            vec![format!(
+                "Block {} has a visibility rule with an unknown variable: ammount",
+                discount_id
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_shape_and_line() {
+        let mut shape = Block::for_test(BlockType::Shape, 0.0, 0.0, 100.0, 50.0);
+        assert!(Validator::validate_block(&shape).is_ok());
+
+        if let BlockContent::Shape(content) = &mut shape.content {
+            content.shape = ShapeKind::Polygon;
+            content.points = vec![
+                Position { x: 0.0, y: 50.0 },
+                Position { x: 50.0, y: 0.0 },
+            ];
+        }
+        assert!(Validator::validate_block(&shape).is_err());
+        if let BlockContent::Shape(content) = &mut shape.content {
+            content.points.push(Position { x: 120.0, y: 50.0 });
+        }
+        assert!(Validator::validate_block(&shape).is_err());
+
+        let mut line = Block::for_test(BlockType::Line, 0.0, 0.0, 100.0, 50.0);
+        assert!(Validator::validate_block(&line).is_ok());
+        if let BlockContent::Line(content) = &mut line.content {
+            content.end = content.start.clone();
+        }
+        assert!(Validator::validate_block(&line).is_err());
+    }
+
+    #[test]
+    fn test_block_in_page_bounds_respects_margins() {
+        let page = Page::new(PageSize::A4, PageOrientation::Portrait);
+        let mut block = Block::for_test(BlockType::Spacer, 0.0, 100.0, 100.0, 50.0);
+
+        // 20mm margin is ~75.6px
+        assert!(Validator::validate_block_in_page_bounds(&block, &page).is_err());
+        block.position.x = 80.0;
+        assert!(Validator::validate_block_in_page_bounds(&block, &page).is_ok());
+    }
+
+    #[test]
+    fn test_block_in_page_bounds_uses_orientation() {
+        let mut page = Page::new(PageSize::A4, PageOrientation::Portrait);
+        let block = Block::for_test(BlockType::Spacer, 100.0, 100.0, 900.0, 100.0);
+
+        assert!(Validator::validate_block_in_page_bounds(&block, &page).is_err());
+        page.orientation = PageOrientation::Landscape;
+        assert!(Validator::validate_block_in_page_bounds(&block, &page).is_ok());
+    }
+
+    #[test]
+    fn test_validate_text_fit() {
+        let fonts = FontRegistry::new();
+        let mut block = Block::for_test(BlockType::Text, 0.0, 0.0, 100.0, 30.0);
+        assert!(Validator::validate_text_fit(&block, &fonts).is_ok());
+
+        let BlockContent::Text(text) = &mut block.content else {
+            unreachable!();
+        };
+        text.text = "A sentence that needs several lines in a narrow block".to_string();
+        assert!(Validator::validate_text_fit(&block, &fonts)
+            .unwrap_err()
+            .contains("is clipped"));
+
+        if let BlockContent::Text(text) = &mut block.content {
+            text.auto_height = true;
+        }
+        assert!(Validator::validate_text_fit(&block, &fonts).is_ok());
+    }
+}
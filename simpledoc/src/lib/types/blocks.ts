@@ -76,6 +76,30 @@ export interface TextBlockContent {
   alignment: 'left' | 'center' | 'right' | 'justify';
   textDecoration?: 'none' | 'underline' | 'line-through';
   isEditable: boolean;   // можно ли редактировать inline
+  paragraphs?: Paragraph[]; // форматированный текст; text = его plain-текст
+  hyphenate?: boolean;   // переносить длинные слова по слогам
+  autoHeight?: boolean;  // высота блока подстраивается под текст при генерации
+}
+
+export interface Paragraph {
+  runs: TextRun[];
+  list?: 'bullet' | 'numbered';
+  level?: number;        // уровень вложенности списка, с 0
+  alignment?: 'left' | 'center' | 'right' | 'justify';
+  spaceBefore?: number;  // в px
+  spaceAfter?: number;   // в px
+}
+
+export interface TextRun {
+  text: string;
+  bold?: boolean;
+  italic?: boolean;
+  underline?: boolean;
+  strike?: boolean;
+  color?: string;
+  fontSize?: number;     // в px
+  link?: string;
+  verticalAlign?: 'superscript' | 'subscript';
 }
 
 export interface ImageBlockContent {
@@ -117,6 +141,28 @@ export interface SpacerBlockContent {
   // Пустой блок для отступов (только размер важен)
 }
 
+export interface Stroke {
+  color: string;
+  width: number;         // в px
+  dash?: 'solid' | 'dashed' | 'dotted';
+}
+
+export interface ShapeBlockContent {
+  shape: 'rectangle' | 'ellipse' | 'roundedRect' | 'polygon';
+  fill?: string;
+  stroke?: Stroke;
+  cornerRadius?: number; // в px, для roundedRect
+  points?: Position[];   // вершины polygon относительно блока
+}
+
+export interface LineBlockContent {
+  start: Position;       // относительно левого верхнего угла блока
+  end: Position;
+  stroke: Stroke;
+  startArrow?: boolean;
+  endArrow?: boolean;
+}
+
 // ============================================================================
 // Главная структура блока
 // ============================================================================
@@ -133,7 +179,13 @@ export interface Block {
   name?: string;                 // опциональное имя для поиска
   
   // Контент (зависит от типа)
-  content: TextBlockContent | ImageBlockContent | TableBlockContent | SpacerBlockContent;
+  content:
+    | TextBlockContent
+    | ImageBlockContent
+    | TableBlockContent
+    | SpacerBlockContent
+    | ShapeBlockContent
+    | LineBlockContent;
   
   // Стили
   styles: BlockStyles;
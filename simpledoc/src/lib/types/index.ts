@@ -12,10 +12,15 @@ export type {
   Transform,
   BlockStyles,
   TextBlockContent,
+  Paragraph,
+  TextRun,
   ImageBlockContent,
   TableBlockContent,
   TableCell,
   SpacerBlockContent,
+  Stroke,
+  ShapeBlockContent,
+  LineBlockContent,
   BlockSelection,
   BlockDragState,
   BlockResizeState,